@@ -0,0 +1,188 @@
+//! Command-line arguments for opening the app on a specific session or search.
+
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Maximum accepted length of a `--search` query.
+const MAX_SEARCH_LEN: usize = 500;
+
+/// Where the frontend should navigate once it has loaded.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LaunchTarget {
+    pub session_id: Option<String>,
+    pub search: Option<String>,
+}
+
+/// Check that a session ID is safe to look up (optionally `codex:`- or
+/// `gemini:`-prefixed).
+fn is_valid_session_id(id: &str) -> bool {
+    let id = ["codex:", "gemini:"]
+        .iter()
+        .find_map(|prefix| id.strip_prefix(prefix))
+        .unwrap_or(id);
+    !id.is_empty() && id.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Options parsed from the command line.
+#[derive(Debug, Default, PartialEq)]
+pub struct CliArgs {
+    pub launch_target: Option<LaunchTarget>,
+    /// Open the database without allowing writes, and skip syncing.
+    pub read_only: bool,
+    /// Database file to use instead of the default.
+    pub db_path: Option<PathBuf>,
+    /// Override for the largest limit list commands will honor.
+    pub max_limit: Option<i32>,
+    /// Override for the SQLite page cache size, in KiB.
+    pub cache_size_kb: Option<i64>,
+    /// Override for the SQLite memory-map size, in MiB.
+    pub mmap_size_mb: Option<i64>,
+}
+
+/// Parse `--session <id>`, `--search <query>`, `--db <path>`, `--max-limit <n>`,
+/// `--cache-size-kb <n>`, `--mmap-size-mb <n>` and `--readonly` (flags taking a
+/// value also accept `--flag=value`).
+///
+/// Invalid or missing values are ignored so the app falls back to a normal startup.
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> CliArgs {
+    let mut cli = CliArgs::default();
+    let mut target = LaunchTarget::default();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (arg, None),
+        };
+
+        match flag.as_str() {
+            "--session" => {
+                let Some(value) = inline.or_else(|| args.next()) else { break };
+                if is_valid_session_id(&value) {
+                    target.session_id = Some(value);
+                } else {
+                    eprintln!("Ignoring invalid --session value: {:?}", value);
+                }
+            }
+            "--search" => {
+                let Some(value) = inline.or_else(|| args.next()) else { break };
+                let query = value.trim();
+                if !query.is_empty() && query.len() <= MAX_SEARCH_LEN {
+                    target.search = Some(query.to_string());
+                } else {
+                    eprintln!("Ignoring invalid --search value");
+                }
+            }
+            "--db" => {
+                let Some(value) = inline.or_else(|| args.next()) else { break };
+                cli.db_path = Some(PathBuf::from(value));
+            }
+            "--max-limit" => {
+                let Some(value) = inline.or_else(|| args.next()) else { break };
+                match value.parse::<i32>() {
+                    Ok(n) if n > 0 => cli.max_limit = Some(n),
+                    _ => eprintln!("Ignoring invalid --max-limit value: {:?}", value),
+                }
+            }
+            "--cache-size-kb" | "--mmap-size-mb" => {
+                let Some(value) = inline.or_else(|| args.next()) else { break };
+                let Ok(n) = value.parse::<i64>() else {
+                    eprintln!("Ignoring invalid {} value: {:?}", flag, value);
+                    continue;
+                };
+                // Ranges are checked when the settings are applied
+                if flag == "--cache-size-kb" {
+                    cli.cache_size_kb = Some(n);
+                } else {
+                    cli.mmap_size_mb = Some(n);
+                }
+            }
+            "--readonly" => cli.read_only = true,
+            _ => {}
+        }
+    }
+
+    if target != LaunchTarget::default() {
+        cli.launch_target = Some(target);
+    }
+    cli
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_no_arguments() {
+        assert_eq!(parse_args(args(&[])).launch_target, None);
+        assert_eq!(parse_args(args(&["--unrelated", "x"])).launch_target, None);
+    }
+
+    #[test]
+    fn test_session_argument() {
+        let target = parse_args(args(&["--session", "abc-123"])).launch_target.unwrap();
+        assert_eq!(target.session_id.as_deref(), Some("abc-123"));
+        assert_eq!(target.search, None);
+
+        let target = parse_args(args(&["--session=codex:019b9da7-1f41"])).launch_target.unwrap();
+        assert_eq!(target.session_id.as_deref(), Some("codex:019b9da7-1f41"));
+
+        let target = parse_args(args(&["--session", "gemini:5f2c-a1"])).launch_target.unwrap();
+        assert_eq!(target.session_id.as_deref(), Some("gemini:5f2c-a1"));
+    }
+
+    #[test]
+    fn test_search_argument() {
+        let target = parse_args(args(&["--search", "  jwt tokens "])).launch_target.unwrap();
+        assert_eq!(target.search.as_deref(), Some("jwt tokens"));
+    }
+
+    #[test]
+    fn test_invalid_values_fall_back() {
+        assert_eq!(parse_args(args(&["--session", "../etc/passwd"])).launch_target, None);
+        assert_eq!(parse_args(args(&["--session", "codex:"])).launch_target, None);
+        assert_eq!(parse_args(args(&["--session"])).launch_target, None);
+        assert_eq!(parse_args(args(&["--search", "   "])).launch_target, None);
+
+        let long = "x".repeat(MAX_SEARCH_LEN + 1);
+        assert_eq!(parse_args(args(&["--search", &long])).launch_target, None);
+
+        // A bad value doesn't discard a good one
+        let target =
+            parse_args(args(&["--session", "bad/id", "--search", "auth"])).launch_target.unwrap();
+        assert_eq!(target.session_id, None);
+        assert_eq!(target.search.as_deref(), Some("auth"));
+    }
+
+    #[test]
+    fn test_read_only_database_arguments() {
+        let cli = parse_args(args(&["--readonly", "--db", "/tmp/teammate.db"]));
+        assert!(cli.read_only);
+        assert_eq!(cli.db_path, Some(PathBuf::from("/tmp/teammate.db")));
+        assert_eq!(cli.launch_target, None);
+
+        let cli = parse_args(args(&["--session", "abc"]));
+        assert!(!cli.read_only);
+        assert_eq!(cli.db_path, None);
+    }
+
+    #[test]
+    fn test_cache_arguments() {
+        let cli = parse_args(args(&["--cache-size-kb", "65536", "--mmap-size-mb=0"]));
+        assert_eq!(cli.cache_size_kb, Some(65536));
+        assert_eq!(cli.mmap_size_mb, Some(0));
+        assert_eq!(parse_args(args(&["--cache-size-kb", "big"])).cache_size_kb, None);
+    }
+
+    #[test]
+    fn test_max_limit_argument() {
+        assert_eq!(parse_args(args(&["--max-limit", "2000"])).max_limit, Some(2000));
+        assert_eq!(parse_args(args(&["--max-limit=0"])).max_limit, None);
+        assert_eq!(parse_args(args(&["--max-limit", "-5"])).max_limit, None);
+        assert_eq!(parse_args(args(&["--max-limit", "lots"])).max_limit, None);
+    }
+}
@@ -1,47 +1,507 @@
 //! Tauri commands - the API layer between frontend and backend.
 
-use crate::db::{Database, Message, SearchResult, Session};
-use crate::sync::{self, SyncStats};
-use std::sync::Arc;
-use tauri::State;
+use crate::cli::LaunchTarget;
+use crate::db::{
+    self, ActivityEntry, Database, DbConfig, ExportBatch, GrowthEstimate, LastPrompt,
+    MergedSearchResult, Message, NormalizedMessage, RankedHit, ResponseLatency,
+    SearchIntegrityReport, SearchMode, SearchOptions, SearchPage, SearchResult, Session,
+    SessionFilter, SessionIndexEntry, SessionNode, SessionSort, SnippetStyle, Stats, TermScope,
+    TimeBucket, TimelineEntry, TokenOutliers, ToolCall,
+};
+use crate::diff::{self, SessionDiff};
+use crate::export;
+use crate::parser::ParseOptions;
+use crate::sync::{self, ParseMetrics, SyncOptions, SyncResult};
+use crate::watch;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tauri::{AppHandle, Emitter, State};
+
+/// Default cap on the number of items any list command returns.
+pub const DEFAULT_MAX_LIMIT: i32 = 5000;
 
 /// Application state containing the database.
 pub struct AppState {
     pub db: Arc<Database>,
+    /// Session or search requested on the command line.
+    pub launch_target: Option<LaunchTarget>,
+    /// When the app was previously opened, for flagging new sessions.
+    pub last_open_at: Option<String>,
+    /// Largest limit a list command will honor.
+    pub max_limit: i32,
+    /// Live subscriptions to open sessions, by session id.
+    pub session_tails: Mutex<HashMap<String, watch::SessionTail>>,
+    /// Set while a background sync started by `trigger_sync` is running.
+    pub sync_running: Arc<AtomicBool>,
+    /// Asks the running background sync to stop after its current file.
+    pub sync_cancel: Arc<AtomicBool>,
+}
+
+/// Clears the running flag when a background sync ends, even by panic.
+struct SyncRunning(Arc<AtomicBool>);
+
+impl Drop for SyncRunning {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// A list response along with the limit actually applied.
+#[derive(Debug, Serialize)]
+pub struct Limited<T> {
+    pub items: Vec<T>,
+    pub limit: i32,
+    /// The requested limit exceeded the server maximum and was reduced,
+    /// or an unlimited request did not return every item.
+    pub capped: bool,
+}
+
+/// A search page along with whether its requested size was capped.
+#[derive(Debug, Serialize)]
+pub struct LimitedPage {
+    #[serde(flatten)]
+    pub page: SearchPage,
+    /// The requested page size exceeded the maximum and was reduced.
+    pub capped: bool,
+}
+
+/// Resolve a requested limit against a default and the server maximum,
+/// returning the limit to apply and whether it was capped.
+fn clamp_limit(requested: Option<i32>, default: i32, max: i32) -> Result<(i32, bool), String> {
+    let requested = requested.unwrap_or(default);
+    if requested < 0 {
+        return Err(format!("limit must be non-negative, got {}", requested));
+    }
+    Ok((requested.min(max), requested > max))
 }
 
-/// Get all sessions.
+/// Get all sessions, or those matching `filter`, whose fields may be left out.
 #[tauri::command]
 pub fn get_sessions(
     state: State<AppState>,
-    project: Option<String>,
+    filter: Option<SessionFilter>,
     limit: Option<i32>,
-) -> Result<Vec<Session>, String> {
-    let limit = limit.unwrap_or(500);
+    sort: Option<SessionSort>,
+) -> Result<Limited<Session>, String> {
+    let (limit, capped) = clamp_limit(limit, 500, state.max_limit)?;
+    let mut sessions = state
+        .db
+        .get_sessions_filtered(&filter.unwrap_or_default(), limit, sort.unwrap_or_default())
+        .map_err(|e| e.to_string())?;
+    if let Some(since) = &state.last_open_at {
+        db::flag_new_since(&mut sessions, since);
+    }
+    Ok(Limited { items: sessions, limit, capped })
+}
+
+/// Get one session, or None if it doesn't exist.
+#[tauri::command]
+pub fn get_session(state: State<AppState>, session_id: String) -> Result<Option<Session>, String> {
+    let mut session = state.db.get_session(&session_id).map_err(|e| e.to_string())?;
+    if let (Some(session), Some(since)) = (&mut session, &state.last_open_at) {
+        db::flag_new_since(std::slice::from_mut(session), since);
+    }
+    Ok(session)
+}
+
+/// Get a minimal index of all sessions for client-side search.
+#[tauri::command]
+pub fn get_session_index(state: State<AppState>) -> Result<Vec<SessionIndexEntry>, String> {
+    state.db.get_session_index().map_err(|e| e.to_string())
+}
+
+/// Get a project's sessions grouped by git branch.
+#[tauri::command]
+pub fn get_sessions_by_branch(
+    state: State<AppState>,
+    project: String,
+) -> Result<Vec<(String, Vec<Session>)>, String> {
+    state
+        .db
+        .get_sessions_by_branch(&project)
+        .map_err(|e| e.to_string())
+}
+
+/// Get the tree of sessions forked from a root session.
+#[tauri::command]
+pub fn get_session_tree(
+    state: State<AppState>,
+    root_session_id: String,
+) -> Result<Option<SessionNode>, String> {
     state
         .db
-        .get_sessions(project.as_deref(), limit)
+        .get_session_tree(&root_session_id)
         .map_err(|e| e.to_string())
 }
 
+/// Get the chain of sessions a session continues, oldest first.
+#[tauri::command]
+pub fn get_thread(state: State<AppState>, session_id: String) -> Result<Vec<Session>, String> {
+    state
+        .db
+        .get_session_thread(&session_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Get a random sample of sessions for rediscovering old work.
+#[tauri::command]
+pub fn get_random_sessions(
+    state: State<AppState>,
+    limit: Option<i32>,
+    project: Option<String>,
+) -> Result<Limited<Session>, String> {
+    let (limit, capped) = clamp_limit(limit, 10, state.max_limit)?;
+    let sessions = state
+        .db
+        .get_random_sessions(limit, project.as_deref())
+        .map_err(|e| e.to_string())?;
+    Ok(Limited { items: sessions, limit, capped })
+}
+
+/// Find which sessions contain a message id.
+#[tauri::command]
+pub fn find_session_for_message(
+    state: State<AppState>,
+    msg_id: String,
+) -> Result<Vec<String>, String> {
+    state.db.find_sessions_for_message(&msg_id).map_err(|e| e.to_string())
+}
+
+/// Get messages for a session with roles mapped to a canonical set.
+#[tauri::command]
+pub fn get_normalized_messages(
+    state: State<AppState>,
+    session_id: String,
+) -> Result<Vec<NormalizedMessage>, String> {
+    state.db.get_normalized_messages(&session_id).map_err(|e| e.to_string())
+}
+
 /// Get messages for a session.
+///
+/// With `structured`, each message also carries its parsed tool calls.
+/// Messages start after the first `offset`; without a `limit`, all
+/// remaining messages are returned up to the server maximum, and
+/// `capped` reports whether any were left out.
+#[tauri::command]
+pub fn get_messages(
+    state: State<AppState>,
+    session_id: String,
+    structured: Option<bool>,
+    offset: Option<i32>,
+    limit: Option<i32>,
+) -> Result<Limited<Message>, String> {
+    if offset.is_some_and(|o| o < 0) {
+        return Err("offset must be non-negative".to_string());
+    }
+    let unbounded = limit.is_none();
+    let (limit, mut capped) = clamp_limit(limit, state.max_limit, state.max_limit)?;
+    let messages = if structured.unwrap_or(false) {
+        state.db.get_messages_with_tools(&session_id, offset, Some(limit))
+    } else {
+        state.db.get_messages(&session_id, offset, Some(limit))
+    }
+    .map_err(|e| e.to_string())?;
+    if unbounded && messages.len() == limit as usize {
+        let total = state.db.count_messages(&session_id).map_err(|e| e.to_string())?;
+        capped = total - i64::from(offset.unwrap_or(0)) > i64::from(limit);
+    }
+    Ok(Limited { items: messages, limit, capped })
+}
+
+/// Count a session's messages, for showing how many are loaded.
+#[tauri::command]
+pub fn count_messages(state: State<AppState>, session_id: String) -> Result<i64, String> {
+    state
+        .db
+        .count_messages(&session_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Stream messages appended to a session's file as
+/// `session-message-appended` events until `unwatch_session` is called.
+#[tauri::command]
+pub fn watch_session(
+    app: AppHandle,
+    state: State<AppState>,
+    session_id: String,
+) -> Result<(), String> {
+    let options = sync::saved_parse_options(&state.db).map_err(|e| e.to_string())?;
+    // Sessions not stored yet will be synced as this machine's
+    let machine = state
+        .db
+        .get_session(&session_id)
+        .map_err(|e| e.to_string())?
+        .map_or_else(|| "local".to_string(), |session| session.machine);
+    let tail = watch::tail_session(&state.db, &session_id, &machine, options, move |appended| {
+        if let Err(e) = app.emit("session-message-appended", appended) {
+            eprintln!("Failed to emit session-message-appended: {}", e);
+        }
+    })
+    .map_err(|e| e.to_string())?;
+    state.session_tails.lock().unwrap().insert(session_id, tail);
+    Ok(())
+}
+
+/// Stop streaming a session's appended messages. Returns whether it was
+/// being watched.
+#[tauri::command]
+pub fn unwatch_session(state: State<AppState>, session_id: String) -> Result<bool, String> {
+    Ok(state.session_tails.lock().unwrap().remove(&session_id).is_some())
+}
+
+/// Get a single tool call with its full input JSON.
+#[tauri::command]
+pub fn get_tool_call_detail(
+    state: State<AppState>,
+    session_id: String,
+    seq: i32,
+) -> Result<Option<ToolCall>, String> {
+    state
+        .db
+        .get_tool_call_detail(&session_id, seq)
+        .map_err(|e| e.to_string())
+}
+
+/// Get a session's timeline, with runs of tool-only turns collapsed.
+#[tauri::command]
+pub fn get_session_timeline(
+    state: State<AppState>,
+    session_id: String,
+) -> Result<Vec<TimelineEntry>, String> {
+    state
+        .db
+        .get_session_timeline(&session_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Get a session's tool calls as a concise execution log.
+#[tauri::command]
+pub fn get_activity_feed(
+    state: State<AppState>,
+    session_id: String,
+) -> Result<Vec<ActivityEntry>, String> {
+    state
+        .db
+        .get_activity_feed(&session_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Get the per-exchange delay between each user message and the assistant's reply.
+#[tauri::command]
+pub fn get_response_latencies(
+    state: State<AppState>,
+    session_id: String,
+) -> Result<Vec<ResponseLatency>, String> {
+    state
+        .db
+        .get_response_latencies(&session_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Get (user, assistant, other) message counts for a session.
 #[tauri::command]
-pub fn get_messages(state: State<AppState>, session_id: String) -> Result<Vec<Message>, String> {
+pub fn get_message_role_counts(
+    state: State<AppState>,
+    session_id: String,
+) -> Result<(i64, i64, i64), String> {
     state
         .db
-        .get_messages(&session_id)
+        .get_message_role_counts(&session_id)
         .map_err(|e| e.to_string())
 }
 
-/// Search messages.
+/// Get the most frequent terms in a session or project, for a word cloud.
+#[tauri::command]
+pub fn get_term_frequencies(
+    state: State<AppState>,
+    scope: TermScope,
+    limit: Option<i32>,
+) -> Result<Limited<(String, i64)>, String> {
+    let (limit, capped) = clamp_limit(limit, 50, state.max_limit)?;
+    let terms = state
+        .db
+        .get_term_frequencies(&scope, limit as usize)
+        .map_err(|e| e.to_string())?;
+    Ok(Limited { items: terms, limit, capped })
+}
+
+/// Count messages matching a term per day, week or month.
+#[tauri::command]
+pub fn term_over_time(
+    state: State<AppState>,
+    term: String,
+    bucket: TimeBucket,
+) -> Result<Vec<(String, i64)>, String> {
+    state.db.term_over_time(&term, bucket).map_err(|e| e.to_string())
+}
+
+/// Diff the message content of two sessions, e.g. a retried run against the original.
+#[tauri::command]
+pub fn diff_sessions(
+    state: State<AppState>,
+    session_a: String,
+    session_b: String,
+) -> Result<SessionDiff, String> {
+    let a = state.db.get_messages(&session_a, None, None).map_err(|e| e.to_string())?;
+    let b = state.db.get_messages(&session_b, None, None).map_err(|e| e.to_string())?;
+    Ok(diff::diff_sessions(&session_a, &a, &session_b, &b))
+}
+
+/// Search messages, optionally only those timestamped within `after..=before`.
+///
+/// `mode` selects exact token matching (the default) or prefix matching.
+/// `role` restricts matches to `user` or `assistant` messages.
 #[tauri::command]
 pub fn search(
     state: State<AppState>,
     query: String,
     limit: Option<i32>,
-) -> Result<Vec<SearchResult>, String> {
-    let limit = limit.unwrap_or(100);
-    state.db.search(&query, limit).map_err(|e| e.to_string())
+    after: Option<String>,
+    before: Option<String>,
+    mode: Option<SearchMode>,
+    role: Option<String>,
+) -> Result<Limited<SearchResult>, String> {
+    if let Some(role) = role.as_deref().filter(|r| !matches!(*r, "user" | "assistant")) {
+        return Err(format!("Unknown role: {}", role));
+    }
+    let max = state.max_limit.min(db::MAX_SEARCH_LIMIT);
+    let (limit, capped) = clamp_limit(limit, 100, max)?;
+    let options = SearchOptions {
+        after,
+        before,
+        prefix: mode.unwrap_or_default() == SearchMode::Prefix,
+        role,
+        ..Default::default()
+    };
+    let results = state
+        .db
+        .search(&query, limit, &options)
+        .map_err(|e| e.to_string())?;
+    Ok(Limited { items: results, limit, capped })
+}
+
+/// Search the messages of one session, e.g. to jump to a term in it.
+#[tauri::command]
+pub fn search_in_session(
+    state: State<AppState>,
+    session_id: String,
+    query: String,
+    limit: Option<i32>,
+) -> Result<Limited<SearchResult>, String> {
+    let max = state.max_limit.min(db::MAX_SEARCH_LIMIT);
+    let (limit, capped) = clamp_limit(limit, 100, max)?;
+    let results = state
+        .db
+        .search_in_session(&session_id, &query, limit)
+        .map_err(|e| e.to_string())?;
+    Ok(Limited { items: results, limit, capped })
+}
+
+/// Search session titles and message bodies in one ranked list.
+///
+/// `title_weight` scales title match scores; it defaults to
+/// `db::DEFAULT_TITLE_WEIGHT`.
+#[tauri::command]
+pub fn search_all(
+    state: State<AppState>,
+    query: String,
+    limit: Option<i32>,
+    title_weight: Option<f64>,
+) -> Result<Limited<RankedHit>, String> {
+    let max = state.max_limit.min(db::MAX_SEARCH_LIMIT);
+    let (limit, capped) = clamp_limit(limit, 100, max)?;
+    let title_weight = title_weight.unwrap_or(db::DEFAULT_TITLE_WEIGHT);
+    if !title_weight.is_finite() || title_weight < 0.0 {
+        return Err("title_weight must be a non-negative number".to_string());
+    }
+    let hits = state
+        .db
+        .search_all(&query, limit, title_weight)
+        .map_err(|e| e.to_string())?;
+    Ok(Limited { items: hits, limit, capped })
+}
+
+/// Rank sessions by overall relevance to a query.
+#[tauri::command]
+pub fn rank_sessions(
+    state: State<AppState>,
+    query: String,
+    limit: Option<i32>,
+) -> Result<Limited<Session>, String> {
+    let (limit, capped) = clamp_limit(limit, 50, state.max_limit)?;
+    let sessions = state.db.rank_sessions(&query, limit).map_err(|e| e.to_string())?;
+    Ok(Limited { items: sessions, limit, capped })
+}
+
+/// Search messages, returning hits expanded to their merged turns.
+#[tauri::command]
+pub fn search_merged(
+    state: State<AppState>,
+    query: String,
+    limit: Option<i32>,
+) -> Result<Limited<MergedSearchResult>, String> {
+    let max = state.max_limit.min(db::MAX_SEARCH_LIMIT);
+    let (limit, capped) = clamp_limit(limit, 100, max)?;
+    let results = state.db.search_merged(&query, limit).map_err(|e| e.to_string())?;
+    Ok(Limited { items: results, limit, capped })
+}
+
+/// Search messages one page at a time, continuing from the `after` cursor.
+/// A `limit` above `db::MAX_SEARCH_LIMIT` or the server maximum is reduced;
+/// the page reports the size applied and whether it was capped.
+///
+/// `snippet` selects sentence-expanded snippets instead of the FTS token window;
+/// `snippet_column` picks the indexed column the snippet is taken from.
+#[tauri::command]
+pub fn search_page(
+    state: State<AppState>,
+    query: String,
+    limit: Option<i32>,
+    after: Option<String>,
+    snippet: Option<SnippetStyle>,
+    snippet_column: Option<usize>,
+) -> Result<LimitedPage, String> {
+    let max = state.max_limit.min(db::MAX_SEARCH_LIMIT);
+    let (limit, capped) = clamp_limit(limit, 100, max)?;
+    let options = SearchOptions {
+        snippet: snippet.unwrap_or_default(),
+        snippet_column: snippet_column.unwrap_or(db::FTS_CONTENT_COLUMN),
+        ..Default::default()
+    };
+    let page = state
+        .db
+        .search_page(&query, limit, after.as_deref(), &options)
+        .map_err(|e| e.to_string())?;
+    Ok(LimitedPage { page, capped })
+}
+
+/// Check the search index against stored messages, rebuilding it if `repair` is set.
+#[tauri::command]
+pub fn verify_search_integrity(
+    state: State<AppState>,
+    repair: Option<bool>,
+) -> Result<SearchIntegrityReport, String> {
+    state
+        .db
+        .verify_search_integrity(repair.unwrap_or(false))
+        .map_err(|e| e.to_string())
+}
+
+/// Rebuild the search index from stored messages, then compact it if
+/// `optimize` is set.
+#[tauri::command]
+pub fn rebuild_index(state: State<AppState>, optimize: Option<bool>) -> Result<(), String> {
+    state.db.rebuild_fts().map_err(|e| e.to_string())?;
+    if optimize.unwrap_or(false) {
+        state.db.optimize_fts().map_err(|e| e.to_string())?;
+    }
+    Ok(())
 }
 
 /// Get list of projects.
@@ -50,10 +510,340 @@ pub fn get_projects(state: State<AppState>) -> Result<Vec<String>, String> {
     state.db.get_projects().map_err(|e| e.to_string())
 }
 
-/// Trigger a sync operation.
+/// Get aggregate session, message, and project counts.
 #[tauri::command]
-pub fn trigger_sync(state: State<AppState>) -> Result<SyncStats, String> {
-    Ok(sync::sync_all(&state.db, "local"))
+pub fn get_stats(state: State<AppState>) -> Result<Stats, String> {
+    state.db.get_stats().map_err(|e| e.to_string())
+}
+
+/// Export one batch of sessions with their messages, continuing after
+/// `after_session_id`. Clients write each session as an NDJSON line.
+#[tauri::command]
+pub fn export_batch(
+    state: State<AppState>,
+    after_session_id: Option<String>,
+    batch_size: Option<i32>,
+) -> Result<ExportBatch, String> {
+    state
+        .db
+        .export_batch(after_session_id.as_deref(), batch_size.unwrap_or(100))
+        .map_err(|e| e.to_string())
+}
+
+/// Check an export destination: an absolute `.html` file in an existing directory.
+fn validate_export_path(path: &str) -> Result<PathBuf, String> {
+    let path = Path::new(path);
+    if !path.is_absolute() {
+        return Err("Export path must be absolute".to_string());
+    }
+    let is_html = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("html") || e.eq_ignore_ascii_case("htm"));
+    if !is_html {
+        return Err("Export path must end in .html".to_string());
+    }
+    if path.is_dir() {
+        return Err(format!("Export path is a directory: {}", path.display()));
+    }
+    if !path.parent().is_some_and(|dir| dir.is_dir()) {
+        return Err(format!("Export directory does not exist: {}", path.display()));
+    }
+    Ok(path.to_path_buf())
+}
+
+/// Write every session of a project to one self-contained HTML file at
+/// `path`, returning how many sessions it holds.
+#[tauri::command]
+pub fn export_project(
+    state: State<AppState>,
+    project: String,
+    path: String,
+) -> Result<usize, String> {
+    let path = validate_export_path(&path)?;
+    let sessions = state.db.export_project(&project).map_err(|e| e.to_string())?;
+    if sessions.is_empty() {
+        return Err(format!("Project not found: {}", project));
+    }
+    fs::write(&path, export::project_to_html(&project, &sessions)).map_err(|e| e.to_string())?;
+    Ok(sessions.len())
+}
+
+/// Export a session with its tags, star and messages as normalized JSON for
+/// other tools.
+#[tauri::command]
+pub fn export_session_json(
+    state: State<AppState>,
+    session_id: String,
+) -> Result<serde_json::Value, String> {
+    export::export_session(&state.db, &session_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Session not found: {}", session_id))
+}
+
+/// Import sessions from a JSON export file holding one session or an array.
+///
+/// Sessions whose id already exists are skipped unless `overwrite` is set.
+#[tauri::command]
+pub fn import_sessions(
+    state: State<AppState>,
+    path: String,
+    overwrite: Option<bool>,
+) -> Result<export::ImportStats, String> {
+    export::import_sessions_file(&state.db, Path::new(&path), overwrite.unwrap_or(false))
+        .map_err(|e| e.to_string())
+}
+
+/// Get each project's most recent user prompt, for resuming recent work.
+#[tauri::command]
+pub fn get_last_prompts_by_project(
+    state: State<AppState>,
+    limit: Option<i32>,
+) -> Result<Limited<LastPrompt>, String> {
+    let (limit, capped) = clamp_limit(limit, 20, state.max_limit)?;
+    let prompts = state
+        .db
+        .get_last_prompts_by_project(limit)
+        .map_err(|e| e.to_string())?;
+    Ok(Limited { items: prompts, limit, capped })
+}
+
+/// Tag a session.
+#[tauri::command]
+pub fn add_session_tag(
+    state: State<AppState>,
+    session_id: String,
+    tag: String,
+) -> Result<(), String> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return Err("Tag must not be empty".to_string());
+    }
+    if tag.contains(char::is_control) {
+        return Err("Tag must not contain control characters".to_string());
+    }
+    state
+        .db
+        .add_session_tag(&session_id, tag)
+        .map_err(|e| e.to_string())
+}
+
+/// Remove a tag from a session.
+#[tauri::command]
+pub fn remove_session_tag(
+    state: State<AppState>,
+    session_id: String,
+    tag: String,
+) -> Result<bool, String> {
+    state
+        .db
+        .remove_session_tag(&session_id, tag.trim())
+        .map_err(|e| e.to_string())
+}
+
+/// Get a session's tags.
+#[tauri::command]
+pub fn get_session_tags(
+    state: State<AppState>,
+    session_id: String,
+) -> Result<Vec<String>, String> {
+    state
+        .db
+        .get_session_tags(&session_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Star or unstar a session.
+#[tauri::command]
+pub fn set_starred(
+    state: State<AppState>,
+    session_id: String,
+    starred: bool,
+) -> Result<(), String> {
+    if state
+        .db
+        .set_session_starred(&session_id, starred)
+        .map_err(|e| e.to_string())?
+    {
+        Ok(())
+    } else {
+        Err(format!("Session not found: {}", session_id))
+    }
+}
+
+/// Get all tags with how many sessions carry each, most used first.
+#[tauri::command]
+pub fn get_tags_with_counts(state: State<AppState>) -> Result<Vec<(String, i64)>, String> {
+    state.db.get_tags_with_counts().map_err(|e| e.to_string())
+}
+
+/// Merge another exported viewer database into this one.
+#[tauri::command]
+pub fn merge_database(state: State<AppState>, other_path: String) -> Result<usize, String> {
+    state
+        .db
+        .merge_database(std::path::Path::new(&other_path))
+        .map_err(|e| e.to_string())
+}
+
+/// Mark a session as read.
+#[tauri::command]
+pub fn mark_session_read(state: State<AppState>, session_id: String) -> Result<(), String> {
+    state
+        .db
+        .mark_session_read(&session_id, &chrono::Utc::now().to_rfc3339())
+        .map_err(|e| e.to_string())
+}
+
+/// Flag a session's messages that are far larger than its median.
+#[tauri::command]
+pub fn get_token_outliers(
+    state: State<AppState>,
+    session_id: String,
+) -> Result<TokenOutliers, String> {
+    state
+        .db
+        .get_token_outliers(&session_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Get the original JSON of a message, if it was stored during sync.
+#[tauri::command]
+pub fn get_raw_message(
+    state: State<AppState>,
+    session_id: String,
+    msg_id: String,
+) -> Result<Option<String>, String> {
+    state
+        .db
+        .get_raw_message(&session_id, &msg_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Delete a session along with its messages and search index entries.
+#[tauri::command]
+pub fn delete_session(state: State<AppState>, session_id: String) -> Result<(), String> {
+    if state.db.delete_session(&session_id).map_err(|e| e.to_string())? {
+        Ok(())
+    } else {
+        Err(format!("Session not found: {}", session_id))
+    }
+}
+
+/// Get unread sessions with no activity in the last `days` days.
+#[tauri::command]
+pub fn get_stale_unread(state: State<AppState>, days: i64) -> Result<Vec<Session>, String> {
+    if days < 0 {
+        return Err("days must be non-negative".to_string());
+    }
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(days);
+    state
+        .db
+        .get_stale_unread(&cutoff.to_rfc3339())
+        .map_err(|e| e.to_string())
+}
+
+/// Start a sync on a background thread.
+///
+/// Its stats are emitted in a `sync-complete` event when it finishes or is
+/// cancelled with `cancel_sync`; starting a sync while one is running fails.
+/// When `verbose` is set, the stats include a per-file decision log.
+/// When `prune` is set, this machine's sessions whose source files are gone
+/// are deleted; it is off by default so databases shared across machines
+/// keep sessions synced elsewhere.
+/// `parse` replaces the saved parser settings, which apply to files parsed
+/// by this and every later sync, watcher update and import; without it the
+/// saved settings are used. Its `store_raw` keeps each Claude message's
+/// original JSON for `get_raw_message`.
+#[tauri::command]
+pub fn trigger_sync(
+    app: AppHandle,
+    state: State<AppState>,
+    verbose: Option<bool>,
+    prune: Option<bool>,
+    parse: Option<ParseOptions>,
+) -> Result<(), String> {
+    if state.db.is_read_only() {
+        return Err("Database is open read-only; sync is disabled".to_string());
+    }
+    if state.sync_running.swap(true, Ordering::SeqCst) {
+        return Err("A sync is already running".to_string());
+    }
+    let running = SyncRunning(Arc::clone(&state.sync_running));
+    let parse = match parse {
+        Some(parse) => {
+            sync::save_parse_options(&state.db, &parse).map_err(|e| e.to_string())?;
+            parse
+        }
+        None => sync::saved_parse_options(&state.db).map_err(|e| e.to_string())?,
+    };
+    state.sync_cancel.store(false, Ordering::SeqCst);
+    let options = SyncOptions {
+        verbose: verbose.unwrap_or(false),
+        prune: prune.unwrap_or(false),
+        parse,
+        cancel: Some(Arc::clone(&state.sync_cancel)),
+    };
+    let db = Arc::clone(&state.db);
+    thread::spawn(move || {
+        let stats = sync::sync_all_with(&db, "local", &options);
+        drop(running);
+        if let Err(e) = app.emit("sync-complete", &stats) {
+            eprintln!("Failed to emit sync-complete: {}", e);
+        }
+    });
+    Ok(())
+}
+
+/// Ask the running background sync to stop after its current file.
+/// Returns false when no sync is running.
+#[tauri::command]
+pub fn cancel_sync(state: State<AppState>) -> Result<bool, String> {
+    if !state.sync_running.load(Ordering::SeqCst) {
+        return Ok(false);
+    }
+    state.sync_cancel.store(true, Ordering::SeqCst);
+    Ok(true)
+}
+
+/// Get parse throughput counters accumulated since the app started.
+#[tauri::command]
+pub fn get_parse_metrics() -> Result<ParseMetrics, String> {
+    Ok(sync::parse_metrics())
+}
+
+/// Project daily growth from sessions synced within the last day.
+#[tauri::command]
+pub fn estimate_growth(state: State<AppState>) -> Result<GrowthEstimate, String> {
+    state
+        .db
+        .estimate_growth(chrono::Utc::now(), chrono::Duration::hours(24))
+        .map_err(|e| e.to_string())
+}
+
+/// Re-derive every session's project name from its source file.
+///
+/// Returns how many sessions changed project.
+#[tauri::command]
+pub fn reproject_all(state: State<AppState>) -> Result<usize, String> {
+    sync::reproject_all(&state.db).map_err(|e| e.to_string())
+}
+
+/// Move every session of project `from` into `into`, e.g. after a repo
+/// rename reported by sync. Returns how many sessions moved.
+#[tauri::command]
+pub fn merge_projects(state: State<AppState>, from: String, into: String) -> Result<usize, String> {
+    state
+        .db
+        .merge_projects(&from, &into)
+        .map_err(|e| e.to_string())
+}
+
+/// Get the directories holding a project's session files.
+#[tauri::command]
+pub fn get_project_paths(state: State<AppState>, project: String) -> Result<Vec<String>, String> {
+    let paths = sync::get_project_paths(&state.db, &project).map_err(|e| e.to_string())?;
+    Ok(paths.iter().map(|p| p.to_string_lossy().into_owned()).collect())
 }
 
 /// Check if a session's source file has been modified.
@@ -68,16 +858,21 @@ pub fn check_session_update(
         None => return Ok(false),
     };
 
-    // Get current file size
-    let source_size = std::fs::metadata(&source_path)
-        .map(|m| m.len() as i64)
-        .map_err(|e| e.to_string())?;
+    // Get current file size and mtime
+    let meta = std::fs::metadata(&source_path).map_err(|e| e.to_string())?;
+    let source_size = meta.len() as i64;
+    let source_mtime = sync::file_mtime(&meta);
 
     // Check against stored info
-    if let Ok(Some((stored_size, stored_hash))) = state.db.get_session_file_info(&session_id) {
+    if let Ok(Some((stored_size, stored_hash, stored_mtime))) =
+        state.db.get_session_file_info(&session_id)
+    {
         if stored_size != source_size {
             return Ok(true); // Size changed
         }
+        if source_mtime.is_some() && source_mtime == stored_mtime {
+            return Ok(false); // Untouched since last sync
+        }
         // Check hash
         if let Some(source_hash) = sync::compute_file_hash(&source_path) {
             if source_hash != stored_hash {
@@ -104,24 +899,129 @@ pub fn sync_session(
         None => return Ok(None),
     };
 
-    // Determine if it's Claude or Codex
-    if session_id.starts_with("codex:") {
-        sync::sync_codex_session(&state.db, &source_path, "local", true);
-    } else {
-        // Get project name from path
-        let project_name = source_path
-            .parent()
-            .and_then(|p| p.file_name())
-            .and_then(|n| n.to_str())
-            .unwrap_or("unknown");
-        sync::sync_claude_session(&state.db, &source_path, project_name, "local", true);
+    if !state.db.is_read_only() {
+        let _ = sync::sync_path(&state.db, &source_path, "local");
     }
 
     // Return updated session
-    let sessions = state
+    state.db.get_session(&session_id).map_err(|e| e.to_string())
+}
+
+/// Open a session's source file in the editor named by
+/// `AGENT_SESSION_VIEWER_EDITOR`, or else the system's default application.
+#[tauri::command]
+pub fn open_source_file(session_id: String) -> Result<(), String> {
+    let path = sync::find_verified_source_file(&session_id)
+        .ok_or_else(|| format!("Source file not found for session: {}", session_id))?;
+    open_path(&path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))
+}
+
+/// Launch the configured editor or the platform opener on a path, without
+/// waiting for it to exit.
+fn open_path(path: &Path) -> std::io::Result<()> {
+    let mut command = match std::env::var_os("AGENT_SESSION_VIEWER_EDITOR") {
+        Some(editor) if !editor.is_empty() => Command::new(editor),
+        _ if cfg!(target_os = "macos") => Command::new("open"),
+        // Unlike `cmd /C start`, explorer doesn't reinterpret `&` or `^` in the path
+        _ if cfg!(target_os = "windows") => Command::new("explorer.exe"),
+        _ => Command::new("xdg-open"),
+    };
+    command.arg(path).spawn()?;
+    Ok(())
+}
+
+/// Import a session file from outside the standard session directories.
+#[tauri::command]
+pub fn import_session_file(
+    state: State<AppState>,
+    path: String,
+    agent_hint: Option<String>,
+    machine: Option<String>,
+) -> Result<SyncResult, String> {
+    if state.db.is_read_only() {
+        return Err("Database is open read-only; import is disabled".to_string());
+    }
+    sync::import_session_file(
+        &state.db,
+        std::path::Path::new(&path),
+        agent_hint.as_deref(),
+        machine.as_deref().unwrap_or("local"),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Delete message bodies of sessions that ended before `before_date`
+/// (`YYYY-MM-DD` or RFC 3339), keeping their metadata.
+#[tauri::command]
+pub fn purge_old_messages(state: State<AppState>, before_date: String) -> Result<usize, String> {
+    let valid = chrono::NaiveDate::parse_from_str(&before_date, "%Y-%m-%d").is_ok()
+        || chrono::DateTime::parse_from_rfc3339(&before_date).is_ok();
+    if !valid {
+        return Err(format!("Invalid date: {}", before_date));
+    }
+    state
         .db
-        .get_sessions(None, 1000)
-        .map_err(|e| e.to_string())?;
+        .purge_old_messages(&before_date)
+        .map_err(|e| e.to_string())
+}
+
+/// Get the session or search requested on the command line, if any.
+#[tauri::command]
+pub fn get_launch_target(state: State<AppState>) -> Result<Option<LaunchTarget>, String> {
+    Ok(state.launch_target.clone())
+}
+
+/// Get the SQLite cache settings in effect.
+#[tauri::command]
+pub fn get_db_config(state: State<AppState>) -> Result<DbConfig, String> {
+    state.db.config().map_err(|e| e.to_string())
+}
 
-    Ok(sessions.into_iter().find(|s| s.session_id == session_id))
+/// Change the SQLite cache settings for this run, returning those applied.
+///
+/// Out-of-range values fall back to the defaults.
+#[tauri::command]
+pub fn tune_database(
+    state: State<AppState>,
+    cache_size_kb: Option<i64>,
+    mmap_size_mb: Option<i64>,
+) -> Result<DbConfig, String> {
+    let current = state.db.config().map_err(|e| e.to_string())?;
+    let config = DbConfig {
+        cache_size_kb: cache_size_kb.unwrap_or(current.cache_size_kb),
+        mmap_size_mb: mmap_size_mb.unwrap_or(current.mmap_size_mb),
+    };
+    state.db.apply_config(&config).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clamp_limit() {
+        assert_eq!(clamp_limit(None, 500, 1000), Ok((500, false)));
+        assert_eq!(clamp_limit(Some(20), 500, 1000), Ok((20, false)));
+        assert_eq!(clamp_limit(Some(1000), 500, 1000), Ok((1000, false)));
+        assert_eq!(clamp_limit(Some(i32::MAX), 500, 1000), Ok((1000, true)));
+        assert_eq!(clamp_limit(None, 5000, 1000), Ok((1000, true)));
+        assert!(clamp_limit(Some(-1), 500, 1000).is_err());
+    }
+
+    #[test]
+    fn test_validate_export_path() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("project.html");
+        let path = |p: &Path| p.to_string_lossy().into_owned();
+
+        assert_eq!(validate_export_path(&path(&file)), Ok(file.clone()));
+        assert!(validate_export_path(&path(&tmp.path().join("Project.HTM"))).is_ok());
+        assert!(validate_export_path("project.html").is_err());
+        assert!(validate_export_path(&path(&tmp.path().join("project.db"))).is_err());
+        assert!(validate_export_path(&path(&tmp.path().join("missing").join("a.html"))).is_err());
+
+        let dir = tmp.path().join("dir.html");
+        fs::create_dir(&dir).unwrap();
+        assert!(validate_export_path(&path(&dir)).is_err());
+    }
 }
@@ -1,9 +1,12 @@
 //! SQLite database with FTS5 full-text search.
 
-use rusqlite::{params, Connection, Result};
+use rusqlite::{params, Connection, DatabaseName, OpenFlags, OptionalExtension, Result};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::sync::Mutex;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
 
 /// Session metadata stored in the database.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,22 +15,415 @@ pub struct Session {
     pub project: String,
     pub machine: String,
     pub first_message: Option<String>,
+    /// Topic summary the agent recorded, preferred over `first_message` as a title.
+    #[serde(default)]
+    pub summary: Option<String>,
     pub started_at: Option<String>,
     pub ended_at: Option<String>,
+    /// User and assistant messages carrying prose, per the parser's
+    /// `MessageCountPolicy`; thinking- and tool-only turns are not counted.
     pub message_count: i32,
     pub file_size: Option<i64>,
     pub file_hash: Option<String>,
+    /// Source file modification time, in nanoseconds since the Unix epoch.
+    #[serde(default)]
+    pub file_mtime: Option<i64>,
+    /// Path the session was last synced from.
+    #[serde(default)]
+    pub source_path: Option<String>,
     pub agent: String,
+    /// Git branch checked out when the session started, if recorded.
+    #[serde(default)]
+    pub git_branch: Option<String>,
+    /// Session this one was forked from, if any.
+    #[serde(default)]
+    pub parent_session_id: Option<String>,
+    /// Message bodies were purged by retention; metadata is kept.
+    #[serde(default)]
+    pub content_purged: bool,
+    /// Reasoning effort or thinking level the session ran with, if recorded.
+    #[serde(default)]
+    pub reasoning_effort: Option<String>,
+    /// Absolute working directory the session ran in, if recorded.
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Model the session mostly ran on, if recorded.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Input tokens summed over assistant turns, cache reads and writes
+    /// included; None if the source never reported usage.
+    #[serde(default)]
+    pub total_input_tokens: Option<i64>,
+    /// Output tokens summed over assistant turns, if reported.
+    #[serde(default)]
+    pub total_output_tokens: Option<i64>,
+    /// Bytes of the source file consumed by the last parse, where a parse of
+    /// appended lines resumes. None for compressed files.
+    #[serde(default)]
+    pub parsed_offset: Option<i64>,
+    /// Pinned by the user. Upserts leave it untouched, so resyncs keep it.
+    #[serde(default)]
+    pub starred: bool,
+    /// Tags in name order.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Started after the previous app launch; computed, not stored.
+    #[serde(default)]
+    pub is_new_since_last_open: bool,
 }
 
 /// Message stored in the database.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Message {
     pub msg_id: String,
     pub session_id: String,
     pub role: String,
     pub content: String,
     pub timestamp: String,
+    /// Whether this came from a sidechain (subagent) rather than the main thread.
+    #[serde(default)]
+    pub is_sidechain: bool,
+    /// Number of identical consecutive copies folded into this message when
+    /// the parser collapses retries; 0 when nothing was folded.
+    #[serde(default)]
+    pub repeat_count: u32,
+    /// Claude entry uuid, if recorded.
+    #[serde(default)]
+    pub uuid: Option<String>,
+    /// uuid of the nearest earlier message in this conversation thread.
+    #[serde(default)]
+    pub parent_uuid: Option<String>,
+    /// API response id of a Claude assistant message. A response split
+    /// across several entries repeats its id, and its usage counts once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_id: Option<String>,
+    /// Whether the message is made of tool calls with no prose, as the
+    /// parser saw it. Such turns collapse into runs on the timeline.
+    #[serde(default)]
+    pub is_tool_only: bool,
+    /// Structured tool invocations; only populated when explicitly requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+}
+
+/// Message with its role mapped to the canonical set the frontend renders.
+#[derive(Debug, Clone, Serialize)]
+pub struct NormalizedMessage {
+    /// The message, with `role` one of `user`, `assistant`, `thinking`,
+    /// `tool` or `system`.
+    #[serde(flatten)]
+    pub message: Message,
+    /// Role as recorded by the agent.
+    pub raw_role: String,
+}
+
+/// Lowercased role strings agents record, each with its canonical role.
+const ROLE_ALIASES: &[(&str, &str)] = &[
+    ("user", "user"),
+    ("human", "user"),
+    ("assistant", "assistant"),
+    ("model", "assistant"),
+    ("ai", "assistant"),
+    ("thinking", "thinking"),
+    ("reasoning", "thinking"),
+    ("tool", "tool"),
+    ("tool_result", "tool"),
+    ("tool_use", "tool"),
+    ("function", "tool"),
+    ("function_call", "tool"),
+    ("function_call_output", "tool"),
+];
+
+/// Map an agent's role string to its canonical role.
+///
+/// Unrecognized roles are treated as `system` so they never render as
+/// part of the conversation.
+pub fn normalize_role(raw: &str) -> &'static str {
+    let raw = raw.to_ascii_lowercase();
+    ROLE_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == raw)
+        .map_or("system", |(_, role)| role)
+}
+
+/// `ROLE_ALIASES` as a JSON object, for normalizing roles inside a query.
+fn role_aliases_json() -> String {
+    let aliases: serde_json::Map<String, serde_json::Value> = ROLE_ALIASES
+        .iter()
+        .map(|(alias, role)| (alias.to_string(), (*role).into()))
+        .collect();
+    serde_json::Value::Object(aliases).to_string()
+}
+
+impl From<Message> for NormalizedMessage {
+    fn from(mut message: Message) -> Self {
+        let role = normalize_role(&message.role).to_string();
+        let raw_role = std::mem::replace(&mut message.role, role);
+        NormalizedMessage { message, raw_role }
+    }
+}
+
+/// Structured tool invocation extracted from a message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub session_id: String,
+    pub msg_id: String,
+    /// Position of the call within its session.
+    pub seq: i32,
+    pub tool_name: String,
+    pub summary: String,
+    /// Raw tool input JSON; only populated by `get_tool_call_detail`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_input_json: Option<String>,
+}
+
+/// One tool invocation in a session's activity feed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityEntry {
+    pub seq: i32,
+    pub msg_id: String,
+    pub timestamp: String,
+    pub tool_name: String,
+    /// What the tool acted on: a file path, command, or search pattern.
+    pub target: Option<String>,
+}
+
+/// One point on a session's timeline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelineEntry {
+    pub msg_id: String,
+    /// Message role, or `tool` for a collapsed run of tool-only turns.
+    pub role: String,
+    pub timestamp: String,
+    /// Tool calls folded into this entry, for showing "+N tool calls".
+    pub collapsed_tool_calls: usize,
+}
+
+/// Order messages given in timestamp order, as (uuid, parent_uuid) pairs,
+/// so that each one follows its parent, keeping timestamp order otherwise.
+/// Timestamps can collide or run backwards within a burst, but the parent
+/// links can't. Returns the positions of the messages in display order.
+fn parent_link_order(links: &[(Option<&str>, Option<&str>)]) -> Vec<usize> {
+    let index: HashMap<&str, usize> = links
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (uuid, _))| uuid.map(|uuid| (uuid, i)))
+        .collect();
+
+    // Repeatedly take the earliest message whose parent is already placed;
+    // one without a parent in the list is placed as soon as its turn comes
+    let mut children = vec![Vec::new(); links.len()];
+    let mut ready = BinaryHeap::new();
+    for (i, (_, parent)) in links.iter().enumerate() {
+        match parent.and_then(|p| index.get(p)) {
+            Some(&parent) if parent != i => children[parent].push(i),
+            _ => ready.push(Reverse(i)),
+        }
+    }
+    let mut order = Vec::with_capacity(links.len());
+    let mut placed = vec![false; links.len()];
+    while let Some(Reverse(i)) = ready.pop() {
+        order.push(i);
+        placed[i] = true;
+        ready.extend(children[i].iter().map(|&child| Reverse(child)));
+    }
+    // Messages in a link cycle never become ready; keep them at the end
+    order.extend((0..links.len()).filter(|&i| !placed[i]));
+    order
+}
+
+/// Build a timeline from messages with tool calls attached, collapsing each
+/// run of consecutive tool-only turns into a single entry.
+fn build_timeline(messages: &[Message]) -> Vec<TimelineEntry> {
+    let mut timeline: Vec<TimelineEntry> = Vec::new();
+    let mut in_run = false;
+
+    for message in messages {
+        if !message.is_tool_only {
+            in_run = false;
+            timeline.push(TimelineEntry {
+                msg_id: message.msg_id.clone(),
+                role: message.role.clone(),
+                timestamp: message.timestamp.clone(),
+                collapsed_tool_calls: 0,
+            });
+            continue;
+        }
+
+        let calls = message.tool_calls.as_ref().map_or(0, |c| c.len());
+        match timeline.last_mut() {
+            Some(run) if in_run => run.collapsed_tool_calls += calls,
+            _ => timeline.push(TimelineEntry {
+                msg_id: message.msg_id.clone(),
+                role: "tool".to_string(),
+                timestamp: message.timestamp.clone(),
+                collapsed_tool_calls: calls,
+            }),
+        }
+        in_run = true;
+    }
+    timeline
+}
+
+/// Time between a user message and the first assistant reply to it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseLatency {
+    pub user_msg_id: String,
+    pub assistant_msg_id: String,
+    pub seconds: f64,
+}
+
+/// The most recent user prompt in a project.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LastPrompt {
+    pub project: String,
+    pub session_id: String,
+    pub msg_id: String,
+    /// Prompt text, cut to `MAX_LAST_PROMPT` characters.
+    pub content: String,
+    pub timestamp: String,
+}
+
+/// Maximum length of a prompt returned by `get_last_prompts_by_project`.
+const MAX_LAST_PROMPT: usize = 200;
+
+/// Projected growth of synced session data, from recent size samples.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrowthEstimate {
+    /// Sessions sampled within the activity window.
+    pub active_sessions: usize,
+    /// Combined growth rate of active session files, in bytes per day.
+    pub bytes_per_day: f64,
+}
+
+/// A message's size, as counted by `get_token_outliers`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageSize {
+    pub msg_id: String,
+    pub role: String,
+    pub timestamp: String,
+    /// Content length in characters, standing in for a token count.
+    pub size: i64,
+}
+
+/// Messages far larger than is typical for their session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenOutliers {
+    pub median: f64,
+    /// Sizes above this are flagged: `OUTLIER_FACTOR` times the median.
+    pub threshold: f64,
+    pub outliers: Vec<MessageSize>,
+}
+
+/// How many times the median size a message must exceed to be an outlier.
+pub const OUTLIER_FACTOR: f64 = 5.0;
+
+/// Size samples kept per session for growth estimates.
+const MAX_SIZE_SAMPLES: i64 = 5;
+
+/// Maximum length of an activity feed target.
+const MAX_ACTIVITY_TARGET: usize = 200;
+
+/// Pick the most descriptive input field of a tool call as its target.
+fn tool_target(input_json: Option<&str>) -> Option<String> {
+    const TARGET_KEYS: &[&str] = &[
+        "file_path",
+        "notebook_path",
+        "command",
+        "pattern",
+        "url",
+        "path",
+        "description",
+    ];
+
+    let input: serde_json::Value = serde_json::from_str(input_json?).ok()?;
+    let target = TARGET_KEYS
+        .iter()
+        .find_map(|key| input.get(key).and_then(|v| v.as_str()))?;
+    if target.chars().count() > MAX_ACTIVITY_TARGET {
+        let cut: String = target.chars().take(MAX_ACTIVITY_TARGET).collect();
+        Some(format!("{}...", cut))
+    } else {
+        Some(target.to_string())
+    }
+}
+
+/// Optional restrictions on a session list.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionFilter {
+    pub project: Option<String>,
+    /// Only sessions with at least one call of this tool.
+    pub uses_tool: Option<String>,
+    /// Only sessions whose model name contains this, ignoring case.
+    pub model: Option<String>,
+    /// Only starred sessions.
+    pub starred_only: bool,
+    /// Only sessions carrying this tag.
+    pub tag: Option<String>,
+    /// Only sessions last on this git branch.
+    pub git_branch: Option<String>,
+}
+
+/// Aggregate counts over non-empty sessions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Stats {
+    pub session_count: i64,
+    /// Sum of the sessions' message counts, so purged sessions still count.
+    pub message_count: i64,
+    pub project_count: i64,
+    /// Session count per agent, most sessions first.
+    pub by_agent: Vec<(String, i64)>,
+    /// Session count per project, most sessions first.
+    pub by_project: Vec<(String, i64)>,
+    pub earliest_started_at: Option<String>,
+    pub latest_started_at: Option<String>,
+}
+
+/// Ordering for session lists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SessionSort {
+    /// Most recently started first.
+    #[default]
+    #[serde(rename = "started_desc")]
+    Newest,
+    /// Earliest started first.
+    #[serde(rename = "started_asc")]
+    Oldest,
+    /// Most recent activity first; sessions without an end time sort last.
+    #[serde(rename = "ended_desc")]
+    RecentActivity,
+    /// Most input plus output tokens first.
+    #[serde(rename = "tokens_desc")]
+    MostTokens,
+    /// Starred sessions first, each group most recently started first.
+    #[serde(rename = "starred_first")]
+    StarredFirst,
+    /// Most messages first.
+    #[serde(rename = "messages_desc")]
+    MostMessages,
+    /// Longest from start to end first; sessions missing either time sort last.
+    #[serde(rename = "duration_desc")]
+    Longest,
+}
+
+impl SessionSort {
+    fn order_by(self) -> &'static str {
+        match self {
+            SessionSort::Newest => "started_at DESC",
+            SessionSort::Oldest => "started_at ASC",
+            SessionSort::RecentActivity => "ended_at IS NULL, ended_at DESC",
+            SessionSort::MostTokens => {
+                "COALESCE(total_input_tokens, 0) + COALESCE(total_output_tokens, 0) DESC"
+            }
+            SessionSort::StarredFirst => "COALESCE(starred, 0) DESC, started_at DESC",
+            SessionSort::MostMessages => "COALESCE(message_count, 0) DESC, started_at DESC",
+            SessionSort::Longest => {
+                "julianday(ended_at) - julianday(started_at) IS NULL,
+                 julianday(ended_at) - julianday(started_at) DESC"
+            }
+        }
+    }
 }
 
 /// Search result from FTS query.
@@ -39,446 +435,5180 @@ pub struct SearchResult {
     pub content: String,
     pub timestamp: String,
     pub project: String,
+    /// HTML-escaped excerpt with matches wrapped in `<mark>`.
     pub snippet: String,
+    /// Up to `MAX_SNIPPETS` highlighted fragments, one per cluster of matches.
+    #[serde(default)]
+    pub snippets: Vec<String>,
 }
 
-/// Thread-safe database handle.
-pub struct Database {
-    conn: Mutex<Connection>,
+/// A search hit expanded to the run of consecutive same-role messages around it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergedSearchResult {
+    pub session_id: String,
+    pub project: String,
+    pub role: String,
+    /// Messages merged into this turn, in display order.
+    pub msg_ids: Vec<String>,
+    /// The best-ranked matching message within the turn.
+    pub match_msg_id: String,
+    /// Merged content, HTML-escaped, with every match in the turn wrapped in `<mark>`.
+    pub content: String,
+    /// Timestamp of the first message in the turn.
+    pub timestamp: String,
 }
 
-impl Database {
-    /// Open or create the database at the given path.
-    pub fn open(path: &PathBuf) -> Result<Self> {
-        let conn = Connection::open(path)?;
-        let db = Self {
-            conn: Mutex::new(conn),
+/// One page of search results and the cursor for the next page, if any.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchPage {
+    pub results: Vec<SearchResult>,
+    pub next: Option<String>,
+    /// Page size applied, after capping the requested one at `MAX_SEARCH_LIMIT`.
+    pub limit: i32,
+}
+
+/// Where a unified search hit matched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HitKind {
+    /// The session's first message, used as its title.
+    Title,
+    /// A message body.
+    Message,
+}
+
+/// A hit in the unified session-title and message search.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RankedHit {
+    pub kind: HitKind,
+    pub session_id: String,
+    /// The matching message; None for title hits.
+    pub msg_id: Option<String>,
+    pub project: String,
+    /// HTML-escaped excerpt with matches wrapped in `<mark>`.
+    pub snippet: String,
+    /// Relevance, higher is better; title hits are scaled by the title weight.
+    pub score: f64,
+}
+
+/// Default multiplier applied to title match scores in `search_all`.
+pub const DEFAULT_TITLE_WEIGHT: f64 = 3.0;
+
+/// How search result snippets are built.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnippetStyle {
+    /// FTS5's fixed token window around the match.
+    #[default]
+    Fts,
+    /// The sentence containing the first match, falling back to `Fts`.
+    Sentence,
+}
+
+/// How search terms are matched against indexed tokens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchMode {
+    /// Whole tokens only, unless a term ends in `*`.
+    #[default]
+    Exact,
+    /// Every term matches as a prefix.
+    Prefix,
+}
+
+/// Columns of `messages_fts`, in index order.
+const FTS_COLUMNS: &[&str] = &["content", "msg_id", "session_id"];
+
+/// Turn free-form user input into a valid FTS5 query.
+///
+/// Every term is quoted, so punctuation such as apostrophes, `-` or `(` can
+/// never cause a syntax error. Adjacent terms are ANDed. What survives:
+///
+/// - `"quoted phrases"`, matched as phrases (an unclosed quote runs to the end)
+/// - `AND`, `OR` and `NOT` in capitals between two terms
+/// - `-term`, excluding the term (`NOT term`) unless it comes first
+/// - a trailing `*` for prefix matching, as in `auth*`; with `prefix` set,
+///   every term outside quotes matches as a prefix
+/// - `column:term` for the columns of `messages_fts`, as in `msg_id:abc`
+///
+/// Other syntax, including `NEAR(...)` and parentheses, is matched literally.
+/// Terms without any letters or digits are dropped; the result is empty when
+/// nothing searchable is left.
+pub fn prepare_fts_query(raw: &str, prefix: bool) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    let mut pending_op: Option<&str> = None;
+    let mut chars = raw.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let (term, op) = if c == '"' {
+            chars.next();
+            let phrase: String = chars.by_ref().take_while(|&c| c != '"').collect();
+            if !phrase.chars().any(char::is_alphanumeric) {
+                continue;
+            }
+            (format!("\"{}\"", phrase.trim()), pending_op.take())
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '"' {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            if let Some(op) = ["AND", "OR", "NOT"].into_iter().find(|op| *op == word) {
+                // An operator needs a term on its left
+                if !parts.is_empty() {
+                    pending_op = Some(op);
+                }
+                continue;
+            }
+
+            let (word, negated) = match word.strip_prefix('-') {
+                Some(rest) => (rest, true),
+                None => (word.as_str(), false),
+            };
+            let (word, is_prefix) = match word.strip_suffix('*') {
+                Some(rest) => (rest, true),
+                None => (word, prefix),
+            };
+            let (column, word) = match word.split_once(':') {
+                Some((col, rest)) if FTS_COLUMNS.contains(&col) => (Some(col), rest),
+                _ => (None, word),
+            };
+            // NOT is binary, so a leading exclusion has nothing to exclude from
+            if !word.chars().any(char::is_alphanumeric) || (negated && parts.is_empty()) {
+                continue;
+            }
+
+            let mut term = format!("\"{}\"", word);
+            if is_prefix {
+                term.push('*');
+            }
+            if let Some(col) = column {
+                term = format!("{}:{}", col, term);
+            }
+            let op = pending_op.take();
+            (term, if negated { Some("NOT") } else { op })
         };
-        db.init_schema()?;
-        Ok(db)
+
+        if !parts.is_empty() {
+            parts.push(op.unwrap_or("AND").to_string());
+        }
+        parts.push(term);
     }
+    parts.join(" ")
+}
 
-    /// Initialize the database schema.
-    fn init_schema(&self) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+/// Index of the `messages_fts` content column.
+pub const FTS_CONTENT_COLUMN: usize = 0;
 
-        conn.execute_batch(
-            r#"
-            CREATE TABLE IF NOT EXISTS sessions (
-                session_id TEXT PRIMARY KEY,
-                project TEXT NOT NULL,
-                machine TEXT DEFAULT 'local',
-                first_message TEXT,
-                started_at TEXT,
-                ended_at TEXT,
-                message_count INTEGER DEFAULT 0,
-                file_size INTEGER,
-                file_hash TEXT,
-                agent TEXT DEFAULT 'claude'
-            );
-
-            CREATE INDEX IF NOT EXISTS idx_sessions_project ON sessions(project);
-            CREATE INDEX IF NOT EXISTS idx_sessions_started ON sessions(started_at);
-
-            CREATE TABLE IF NOT EXISTS messages (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                session_id TEXT NOT NULL,
-                msg_id TEXT NOT NULL,
-                role TEXT NOT NULL,
-                content TEXT,
-                timestamp TEXT,
-                FOREIGN KEY (session_id) REFERENCES sessions(session_id)
-            );
-
-            CREATE INDEX IF NOT EXISTS idx_messages_session ON messages(session_id);
-
-            CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
-                content,
-                msg_id,
-                session_id,
-                content='messages',
-                content_rowid='id'
-            );
-
-            CREATE TRIGGER IF NOT EXISTS messages_ai AFTER INSERT ON messages BEGIN
-                INSERT INTO messages_fts(rowid, content, msg_id, session_id)
-                VALUES (NEW.id, NEW.content, NEW.msg_id, NEW.session_id);
-            END;
-
-            CREATE TRIGGER IF NOT EXISTS messages_ad AFTER DELETE ON messages BEGIN
-                INSERT INTO messages_fts(messages_fts, rowid, content, msg_id, session_id)
-                VALUES ('delete', OLD.id, OLD.content, OLD.msg_id, OLD.session_id);
-            END;
-
-            CREATE TRIGGER IF NOT EXISTS messages_au AFTER UPDATE ON messages BEGIN
-                INSERT INTO messages_fts(messages_fts, rowid, content, msg_id, session_id)
-                VALUES ('delete', OLD.id, OLD.content, OLD.msg_id, OLD.session_id);
-                INSERT INTO messages_fts(rowid, content, msg_id, session_id)
-                VALUES (NEW.id, NEW.content, NEW.msg_id, NEW.session_id);
-            END;
-            "#,
-        )?;
+/// Options controlling which search results are returned and how they are rendered.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchOptions {
+    pub snippet: SnippetStyle,
+    /// `messages_fts` column the snippet is taken from. Sentence snippets
+    /// only apply to the content column.
+    pub snippet_column: usize,
+    /// Only match messages timestamped at or after this ISO timestamp.
+    pub after: Option<String>,
+    /// Only match messages timestamped at or before this ISO timestamp.
+    pub before: Option<String>,
+    /// Match every loose term as a prefix, so `parse_cla` finds
+    /// `parse_claude_session`.
+    pub prefix: bool,
+    /// Only match messages in this session.
+    pub session_id: Option<String>,
+    /// Only match messages with this canonical role, e.g. `user` or
+    /// `assistant`. Aliases such as `human` match their canonical role.
+    pub role: Option<String>,
+}
 
-        Ok(())
+/// Maximum length of a sentence snippet, in bytes, before it is cut.
+const MAX_SENTENCE_SNIPPET: usize = 300;
+
+/// Plain search words from an FTS query, lowercased, with operators dropped.
+fn query_terms(query: &str) -> impl Iterator<Item = String> + '_ {
+    query
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|t| !t.is_empty() && !matches!(*t, "AND" | "OR" | "NOT" | "NEAR"))
+        .map(|t| t.to_ascii_lowercase())
+}
+
+/// Maximum number of fragments returned in `SearchResult::snippets`.
+const MAX_SNIPPETS: usize = 3;
+
+/// Bytes of context kept on each side of a match in a fragment.
+const SNIPPET_CONTEXT: usize = 40;
+
+/// Match markers passed to `highlight()`, chosen so they never collide with
+/// text that merely looks like markup.
+const HIGHLIGHT_OPEN: char = '\u{1}';
+const HIGHLIGHT_CLOSE: char = '\u{2}';
+
+/// Escape text for use in HTML content and quoted attribute values.
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
     }
+    escaped
+}
 
-    /// Get all sessions, optionally filtered by project.
-    pub fn get_sessions(&self, project: Option<&str>, limit: i32) -> Result<Vec<Session>> {
-        let conn = self.conn.lock().unwrap();
+/// Render `snippet()` or `highlight()` output that used the highlight markers
+/// as HTML, escaping the text and wrapping matches in `<mark>`.
+fn marked_html(marked: &str) -> String {
+    let mut out = String::with_capacity(marked.len());
+    let mut text = String::new();
+    for c in marked.chars() {
+        match c {
+            HIGHLIGHT_OPEN | HIGHLIGHT_CLOSE => {
+                out.push_str(&escape_html(&text));
+                text.clear();
+                out.push_str(if c == HIGHLIGHT_OPEN { "<mark>" } else { "</mark>" });
+            }
+            _ => text.push(c),
+        }
+    }
+    out.push_str(&escape_html(&text));
+    out
+}
 
-        fn row_to_session(row: &rusqlite::Row) -> rusqlite::Result<Session> {
-            Ok(Session {
-                session_id: row.get(0)?,
-                project: row.get(1)?,
-                machine: row.get(2)?,
-                first_message: row.get(3)?,
-                started_at: row.get(4)?,
-                ended_at: row.get(5)?,
-                message_count: row.get(6)?,
-                file_size: row.get(7)?,
-                file_hash: row.get(8)?,
-                agent: row.get::<_, Option<String>>(9)?.unwrap_or_else(|| "claude".to_string()),
-            })
+/// Split `highlight()` output into up to `MAX_SNIPPETS` fragments.
+///
+/// Each fragment keeps `SNIPPET_CONTEXT` bytes around its matches, and
+/// matches close enough to share context are merged into one fragment.
+/// Text is HTML-escaped, matches are wrapped in `<mark>` and cut sides are
+/// marked with `...`.
+fn highlight_fragments(highlighted: &str) -> Vec<String> {
+    let mut text = String::with_capacity(highlighted.len());
+    let mut marks = Vec::new();
+    let mut open = None;
+    for c in highlighted.chars() {
+        match c {
+            HIGHLIGHT_OPEN => open = Some(text.len()),
+            HIGHLIGHT_CLOSE => {
+                if let Some(start) = open.take() {
+                    marks.push((start, text.len()));
+                }
+            }
+            _ => text.push(c),
         }
+    }
 
-        if let Some(p) = project {
-            let mut stmt = conn.prepare(
-                "SELECT session_id, project, machine, first_message, started_at, ended_at,
-                        COALESCE(message_count, 0), file_size, file_hash, agent
-                 FROM sessions
-                 WHERE project = ?1 AND COALESCE(message_count, 0) > 0
-                 ORDER BY started_at DESC
-                 LIMIT ?2"
-            )?;
-            let result: Vec<_> = stmt.query_map(params![p, limit], row_to_session)?.collect();
-            result.into_iter().collect()
+    let floor = |mut i: usize| {
+        while !text.is_char_boundary(i) {
+            i -= 1;
+        }
+        i
+    };
+    let ceil = |mut i: usize| {
+        while !text.is_char_boundary(i) {
+            i += 1;
+        }
+        i
+    };
+
+    let mut fragments: Vec<(usize, usize, Vec<_>)> = Vec::new();
+    for (start, end) in marks {
+        let from = ceil(start.saturating_sub(SNIPPET_CONTEXT));
+        let to = floor((end + SNIPPET_CONTEXT).min(text.len()));
+        if let Some(fragment) = fragments.last_mut().filter(|f| from <= f.1) {
+            fragment.1 = fragment.1.max(to);
+            fragment.2.push((start, end));
+        } else if fragments.len() == MAX_SNIPPETS {
+            break;
         } else {
-            let mut stmt = conn.prepare(
-                "SELECT session_id, project, machine, first_message, started_at, ended_at,
-                        COALESCE(message_count, 0), file_size, file_hash, agent
-                 FROM sessions
-                 WHERE COALESCE(message_count, 0) > 0
-                 ORDER BY started_at DESC
-                 LIMIT ?1"
-            )?;
-            let result: Vec<_> = stmt.query_map(params![limit], row_to_session)?.collect();
-            result.into_iter().collect()
+            fragments.push((from, to, vec![(start, end)]));
         }
     }
 
-    /// Get messages for a session.
-    pub fn get_messages(&self, session_id: &str) -> Result<Vec<Message>> {
-        let conn = self.conn.lock().unwrap();
+    fragments
+        .into_iter()
+        .map(|(from, to, marks)| {
+            let mut out = String::new();
+            if from > 0 {
+                out.push_str("...");
+            }
+            let mut pos = from;
+            for (start, end) in marks {
+                out.push_str(&escape_html(&text[pos..start]));
+                out.push_str("<mark>");
+                out.push_str(&escape_html(&text[start..end]));
+                out.push_str("</mark>");
+                pos = end;
+            }
+            out.push_str(&escape_html(&text[pos..to]));
+            if to < text.len() {
+                out.push_str("...");
+            }
+            out
+        })
+        .collect()
+}
 
-        let mut stmt = conn.prepare(
-            "SELECT msg_id, session_id, role, content, timestamp
-             FROM messages
-             WHERE session_id = ?1
-             ORDER BY timestamp ASC",
-        )?;
+/// Expand the first match of a query term to its enclosing sentence.
+///
+/// The snippet extends to the nearest `.`, `!`, `?` or newline on each side,
+/// cut with `...` where a side would exceed its share of
+/// `MAX_SENTENCE_SNIPPET`. The text is HTML-escaped and the match is wrapped
+/// in `<mark>`.
+fn sentence_snippet(content: &str, query: &str) -> Option<String> {
+    let is_boundary = |c: char| matches!(c, '.' | '!' | '?' | '\n');
+    let floor = |mut i: usize| {
+        while !content.is_char_boundary(i) {
+            i -= 1;
+        }
+        i
+    };
+    let ceil = |mut i: usize| {
+        while !content.is_char_boundary(i) {
+            i += 1;
+        }
+        i
+    };
 
-        let rows = stmt.query_map(params![session_id], |row| {
-            Ok(Message {
-                msg_id: row.get(0)?,
-                session_id: row.get(1)?,
-                role: row.get(2)?,
-                content: row.get(3)?,
-                timestamp: row.get(4)?,
-            })
-        })?;
+    // ASCII lowercasing keeps byte offsets aligned with `content`
+    let lower = content.to_ascii_lowercase();
+    let (start, end) = query_terms(query)
+        .filter_map(|term| lower.find(&term).map(|i| (i, i + term.len())))
+        .min()?;
+    let budget = MAX_SENTENCE_SNIPPET.saturating_sub(end - start) / 2;
 
-        rows.collect()
+    let mut from = content[..start].rfind(is_boundary).map_or(0, |i| i + 1);
+    let cut_left = start - from > budget;
+    if cut_left {
+        from = ceil(start - budget);
+    }
+    let mut to = content[end..].find(is_boundary).map_or(content.len(), |i| end + i + 1);
+    let cut_right = to - end > budget;
+    if cut_right {
+        to = floor(end + budget);
     }
 
-    /// Search messages using FTS5.
-    pub fn search(&self, query: &str, limit: i32) -> Result<Vec<SearchResult>> {
-        let conn = self.conn.lock().unwrap();
+    Some(format!(
+        "{}{}<mark>{}</mark>{}{}",
+        if cut_left { "..." } else { "" },
+        escape_html(content[from..start].trim_start()),
+        escape_html(&content[start..end]),
+        escape_html(content[end..to].trim_end()),
+        if cut_right { "..." } else { "" },
+    ))
+}
 
-        let mut stmt = conn.prepare(
-            r#"
-            SELECT m.session_id, m.msg_id, m.role, m.content, m.timestamp, s.project,
-                   snippet(messages_fts, 0, '<mark>', '</mark>', '...', 32) as snippet
-            FROM messages_fts
-            JOIN messages m ON messages_fts.rowid = m.id
-            JOIN sessions s ON m.session_id = s.session_id
-            WHERE messages_fts MATCH ?1
-            ORDER BY rank
-            LIMIT ?2
-            "#,
-        )?;
+/// A session with its messages, as written to one NDJSON export line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportSession {
+    pub session: Session,
+    pub messages: Vec<Message>,
+}
 
-        let rows = stmt.query_map(params![query, limit], |row| {
-            Ok(SearchResult {
-                session_id: row.get(0)?,
-                msg_id: row.get(1)?,
-                role: row.get(2)?,
-                content: row.get(3)?,
-                timestamp: row.get(4)?,
-                project: row.get(5)?,
-                snippet: row.get(6)?,
-            })
-        })?;
+/// One batch of an incremental export and the cursor for the next, if any.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportBatch {
+    pub sessions: Vec<ExportSession>,
+    pub next: Option<String>,
+}
 
-        rows.collect()
-    }
+/// Hard cap on sessions returned by a single export batch.
+pub const MAX_EXPORT_BATCH: i32 = 1000;
 
-    /// Insert or update a session.
-    pub fn upsert_session(&self, session: &Session) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+/// Hard cap on results returned by a single search call.
+pub const MAX_SEARCH_LIMIT: i32 = 500;
 
-        conn.execute(
-            r#"
-            INSERT INTO sessions (session_id, project, machine, first_message, started_at,
-                                  ended_at, message_count, file_size, file_hash, agent)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
-            ON CONFLICT(session_id) DO UPDATE SET
-                project = excluded.project,
-                machine = excluded.machine,
-                first_message = excluded.first_message,
-                started_at = excluded.started_at,
-                ended_at = excluded.ended_at,
-                message_count = excluded.message_count,
-                file_size = excluded.file_size,
-                file_hash = excluded.file_hash,
-                agent = excluded.agent
-            "#,
-            params![
-                session.session_id,
-                session.project,
-                session.machine,
-                session.first_message,
-                session.started_at,
-                session.ended_at,
-                session.message_count,
-                session.file_size,
-                session.file_hash,
-                session.agent,
-            ],
-        )?;
+/// Encode the (rank, rowid) of the last result as an opaque cursor.
+fn encode_search_cursor(rank: f64, rowid: i64) -> String {
+    format!("{:016x}-{:x}", rank.to_bits(), rowid)
+}
 
-        Ok(())
-    }
+fn decode_search_cursor(token: &str) -> Result<(f64, i64)> {
+    let invalid = || rusqlite::Error::ToSqlConversionFailure("invalid search cursor".into());
+    let (rank, rowid) = token.split_once('-').ok_or_else(invalid)?;
+    let rank = u64::from_str_radix(rank, 16).map_err(|_| invalid())?;
+    let rowid = i64::from_str_radix(rowid, 16).map_err(|_| invalid())?;
+    Ok((f64::from_bits(rank), rowid))
+}
 
-    /// Delete messages for a session (before re-indexing).
-    pub fn delete_session_messages(&self, session_id: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute("DELETE FROM messages WHERE session_id = ?1", params![session_id])?;
-        Ok(())
+/// Which messages a term-frequency query covers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TermScope {
+    Session(String),
+    Project(String),
+}
+
+/// Time bucket for grouping matches over time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeBucket {
+    Day,
+    Week,
+    Month,
+}
+
+impl TimeBucket {
+    /// strftime format naming a timestamp's bucket; weeks start on Monday.
+    fn format(self) -> &'static str {
+        match self {
+            TimeBucket::Day => "%Y-%m-%d",
+            TimeBucket::Week => "%Y-W%W",
+            TimeBucket::Month => "%Y-%m",
+        }
     }
+}
 
-    /// Insert messages in batch.
-    pub fn insert_messages(&self, messages: &[Message]) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+/// Common words excluded from term frequencies.
+const STOPWORDS: &[&str] = &[
+    "about", "after", "all", "also", "and", "any", "are", "because", "been", "before", "but",
+    "can", "could", "did", "does", "doesn", "don", "each", "for", "from", "get", "got", "had",
+    "has", "have", "here", "how", "into", "its", "just", "let", "like", "make", "more", "most",
+    "need", "not", "now", "only", "other", "our", "out", "should", "some", "than", "that",
+    "the", "their", "them", "then", "there", "these", "they", "this", "those", "through",
+    "use", "using", "was", "way", "were", "what", "when", "where", "which", "while", "who",
+    "why", "will", "with", "would", "you", "your",
+];
 
-        let mut stmt = conn.prepare(
-            "INSERT INTO messages (session_id, msg_id, role, content, timestamp)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
-        )?;
+/// Split message content into lowercase terms, skipping tool marker lines and stopwords.
+fn tokenize_terms(content: &str) -> impl Iterator<Item = String> + '_ {
+    content
+        .lines()
+        .filter(|line| {
+            let line = line.trim_start();
+            !line.starts_with('[') && !line.starts_with("$ ")
+        })
+        .flat_map(|line| line.split(|c: char| !c.is_alphanumeric() && c != '_'))
+        .filter(|word| word.chars().count() >= 3 && !word.chars().all(|c| c.is_ascii_digit()))
+        .map(|word| word.to_lowercase())
+        .filter(|word| !STOPWORDS.contains(&word.as_str()))
+}
 
-        for msg in messages {
-            stmt.execute(params![
-                msg.session_id,
-                msg.msg_id,
-                msg.role,
-                msg.content,
-                msg.timestamp,
-            ])?;
-        }
+/// Result of comparing the FTS index against the messages table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchIntegrityReport {
+    pub message_count: i64,
+    pub indexed_count: i64,
+    pub sampled: i64,
+    pub missing_from_index: i64,
+    pub drift: bool,
+    pub repaired: bool,
+}
 
-        Ok(())
+/// Schema migrations in order. `PRAGMA user_version` records how many have
+/// been applied, so each runs once per database.
+const MIGRATIONS: &[fn(&Connection) -> Result<()>] = &[
+    migrate_v1, migrate_v2, migrate_v3, migrate_v4, migrate_v5, migrate_v6, migrate_v7, migrate_v8,
+    migrate_v9, migrate_v10,
+];
+
+/// Bring the schema up to date by applying every migration past `user_version`.
+///
+/// Each migration runs in its own transaction together with its version bump,
+/// so a failed upgrade leaves the database at the last good version.
+fn migrate(conn: &Connection) -> Result<()> {
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version.max(0) as usize) {
+        let tx = conn.unchecked_transaction()?;
+        migration(&tx)?;
+        tx.pragma_update(None, "user_version", index as i64 + 1)?;
+        tx.commit()?;
     }
+    Ok(())
+}
 
-    /// Get file info for incremental sync check.
-    pub fn get_session_file_info(&self, session_id: &str) -> Result<Option<(i64, String)>> {
-        let conn = self.conn.lock().unwrap();
+/// Migration 1: the schema as it stood before versioning.
+///
+/// Databases created before `user_version` was tracked may hold any earlier
+/// layout, so every statement here is idempotent and missing columns are
+/// added in place.
+fn migrate_v1(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS sessions (
+            session_id TEXT PRIMARY KEY,
+            project TEXT NOT NULL,
+            machine TEXT DEFAULT 'local',
+            first_message TEXT,
+            started_at TEXT,
+            ended_at TEXT,
+            message_count INTEGER DEFAULT 0,
+            file_size INTEGER,
+            file_hash TEXT,
+            file_mtime INTEGER,
+            source_path TEXT,
+            agent TEXT DEFAULT 'claude',
+            git_branch TEXT,
+            parent_session_id TEXT,
+            content_purged INTEGER DEFAULT 0,
+            reasoning_effort TEXT,
+            cwd TEXT,
+            model TEXT,
+            total_input_tokens INTEGER,
+            total_output_tokens INTEGER
+        );
 
-        let mut stmt = conn.prepare(
-            "SELECT file_size, file_hash FROM sessions WHERE session_id = ?1",
-        )?;
+        CREATE INDEX IF NOT EXISTS idx_sessions_project ON sessions(project);
+        CREATE INDEX IF NOT EXISTS idx_sessions_started ON sessions(started_at);
 
-        let result = stmt.query_row(params![session_id], |row| {
-            let size: Option<i64> = row.get(0)?;
-            let hash: Option<String> = row.get(1)?;
-            Ok(size.zip(hash))
-        });
+        CREATE TABLE IF NOT EXISTS messages (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT NOT NULL,
+            msg_id TEXT NOT NULL,
+            role TEXT NOT NULL,
+            content TEXT,
+            timestamp TEXT,
+            is_sidechain INTEGER DEFAULT 0,
+            repeat_count INTEGER DEFAULT 0,
+            uuid TEXT,
+            parent_uuid TEXT,
+            FOREIGN KEY (session_id) REFERENCES sessions(session_id)
+        );
 
-        match result {
-            Ok(Some((size, hash))) => Ok(Some((size, hash))),
-            Ok(None) => Ok(None),
-            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-            Err(e) => Err(e),
-        }
-    }
+        CREATE INDEX IF NOT EXISTS idx_messages_session ON messages(session_id);
+
+        CREATE TABLE IF NOT EXISTS messages_raw (
+            session_id TEXT NOT NULL,
+            msg_id TEXT NOT NULL,
+            raw_json TEXT NOT NULL,
+            PRIMARY KEY (session_id, msg_id)
+        );
+
+        CREATE TABLE IF NOT EXISTS tool_calls (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT NOT NULL,
+            msg_id TEXT NOT NULL,
+            seq INTEGER NOT NULL,
+            tool_name TEXT NOT NULL,
+            summary TEXT,
+            tool_input_json TEXT,
+            FOREIGN KEY (session_id) REFERENCES sessions(session_id)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_tool_calls_session ON tool_calls(session_id, seq);
+
+        CREATE TABLE IF NOT EXISTS size_samples (
+            session_id TEXT NOT NULL,
+            file_size INTEGER NOT NULL,
+            sampled_at TEXT NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_size_samples_session
+            ON size_samples(session_id, sampled_at);
+
+        CREATE TABLE IF NOT EXISTS session_tags (
+            session_id TEXT NOT NULL,
+            tag TEXT NOT NULL,
+            PRIMARY KEY (session_id, tag),
+            FOREIGN KEY (session_id) REFERENCES sessions(session_id)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_session_tags_tag ON session_tags(tag);
+
+        CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
+            content,
+            msg_id,
+            session_id,
+            content='messages',
+            content_rowid='id'
+        );
+
+        CREATE TABLE IF NOT EXISTS meta (
+            key TEXT PRIMARY KEY,
+            value TEXT
+        );
+
+        CREATE TRIGGER IF NOT EXISTS messages_ai AFTER INSERT ON messages BEGIN
+            INSERT INTO messages_fts(rowid, content, msg_id, session_id)
+            VALUES (NEW.id, NEW.content, NEW.msg_id, NEW.session_id);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS messages_ad AFTER DELETE ON messages BEGIN
+            INSERT INTO messages_fts(messages_fts, rowid, content, msg_id, session_id)
+            VALUES ('delete', OLD.id, OLD.content, OLD.msg_id, OLD.session_id);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS messages_au AFTER UPDATE ON messages BEGIN
+            INSERT INTO messages_fts(messages_fts, rowid, content, msg_id, session_id)
+            VALUES ('delete', OLD.id, OLD.content, OLD.msg_id, OLD.session_id);
+            INSERT INTO messages_fts(rowid, content, msg_id, session_id)
+            VALUES (NEW.id, NEW.content, NEW.msg_id, NEW.session_id);
+        END;
+        "#,
+    )?;
+
+    // Columns added after their table was first created
+    add_column_if_missing(conn, "tool_calls", "tool_input_json", "TEXT")?;
+    add_column_if_missing(conn, "messages", "is_sidechain", "INTEGER DEFAULT 0")?;
+    add_column_if_missing(conn, "messages", "repeat_count", "INTEGER DEFAULT 0")?;
+    add_column_if_missing(conn, "messages", "uuid", "TEXT")?;
+    add_column_if_missing(conn, "messages", "parent_uuid", "TEXT")?;
+    add_column_if_missing(conn, "sessions", "git_branch", "TEXT")?;
+    add_column_if_missing(conn, "sessions", "parent_session_id", "TEXT")?;
+    add_column_if_missing(conn, "sessions", "content_purged", "INTEGER DEFAULT 0")?;
+    add_column_if_missing(conn, "sessions", "reasoning_effort", "TEXT")?;
+    add_column_if_missing(conn, "sessions", "last_viewed_at", "TEXT")?;
+    add_column_if_missing(conn, "sessions", "cwd", "TEXT")?;
+    add_column_if_missing(conn, "sessions", "file_mtime", "INTEGER")?;
+    add_column_if_missing(conn, "sessions", "source_path", "TEXT")?;
+    add_column_if_missing(conn, "sessions", "model", "TEXT")?;
+    add_column_if_missing(conn, "sessions", "total_input_tokens", "INTEGER")?;
+    add_column_if_missing(conn, "sessions", "total_output_tokens", "INTEGER")?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_sessions_parent ON sessions(parent_session_id)",
+        [],
+    )?;
+
+    // Session titles are indexed separately; backfill when the index is first created
+    let has_title_index: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = 'sessions_fts')",
+        [],
+        |row| row.get(0),
+    )?;
+    conn.execute_batch(
+        r#"
+        CREATE VIRTUAL TABLE IF NOT EXISTS sessions_fts USING fts5(
+            first_message,
+            content='sessions'
+        );
+
+        CREATE TRIGGER IF NOT EXISTS sessions_ai AFTER INSERT ON sessions BEGIN
+            INSERT INTO sessions_fts(rowid, first_message)
+            VALUES (NEW.rowid, NEW.first_message);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS sessions_ad AFTER DELETE ON sessions BEGIN
+            INSERT INTO sessions_fts(sessions_fts, rowid, first_message)
+            VALUES ('delete', OLD.rowid, OLD.first_message);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS sessions_au AFTER UPDATE OF first_message ON sessions BEGIN
+            INSERT INTO sessions_fts(sessions_fts, rowid, first_message)
+            VALUES ('delete', OLD.rowid, OLD.first_message);
+            INSERT INTO sessions_fts(rowid, first_message)
+            VALUES (NEW.rowid, NEW.first_message);
+        END;
+        "#,
+    )?;
+    if !has_title_index {
+        conn.execute("INSERT INTO sessions_fts(sessions_fts) VALUES ('rebuild')", [])?;
+    }
+
+    Ok(())
+}
+
+/// Migration 2: sessions can be starred.
+fn migrate_v2(conn: &Connection) -> Result<()> {
+    conn.execute("ALTER TABLE sessions ADD COLUMN starred BOOLEAN DEFAULT 0", [])?;
+    Ok(())
+}
+
+/// Migration 3: sessions keep the agent's topic summary.
+fn migrate_v3(conn: &Connection) -> Result<()> {
+    conn.execute("ALTER TABLE sessions ADD COLUMN summary TEXT", [])?;
+    Ok(())
+}
+
+/// Migration 4: sessions record how far their source file was parsed.
+fn migrate_v4(conn: &Connection) -> Result<()> {
+    conn.execute("ALTER TABLE sessions ADD COLUMN parsed_offset INTEGER", [])?;
+    Ok(())
+}
+
+/// Migration 5: messages record the API response they came from. Sessions
+/// parsed before can't say which responses their usage already counted, so
+/// their next sync parses them in full.
+fn migrate_v5(conn: &Connection) -> Result<()> {
+    conn.execute("ALTER TABLE messages ADD COLUMN response_id TEXT", [])?;
+    conn.execute("UPDATE sessions SET parsed_offset = NULL", [])?;
+    Ok(())
+}
+
+/// Migration 6: Claude sessions synced before token totals and summaries
+/// were recorded are parsed again at their next sync to fill them in. A
+/// purged session only has its metadata refreshed.
+fn migrate_v6(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "UPDATE sessions SET file_hash = NULL, parsed_offset = NULL WHERE agent = 'claude'",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Migration 7: messages store their display position, so sessions can be
+/// paged in SQL. Existing messages are placed as their timestamps and
+/// parent links order them. The search index is only refreshed when indexed
+/// columns change, so placing them doesn't rewrite it.
+fn migrate_v7(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"
+        ALTER TABLE messages ADD COLUMN ordinal INTEGER;
+
+        DROP TRIGGER IF EXISTS messages_au;
+        CREATE TRIGGER messages_au AFTER UPDATE OF content, msg_id, session_id ON messages BEGIN
+            INSERT INTO messages_fts(messages_fts, rowid, content, msg_id, session_id)
+            VALUES ('delete', OLD.id, OLD.content, OLD.msg_id, OLD.session_id);
+            INSERT INTO messages_fts(rowid, content, msg_id, session_id)
+            VALUES (NEW.id, NEW.content, NEW.msg_id, NEW.session_id);
+        END;
+        "#,
+    )?;
+    let rows: Vec<(i64, String, Option<String>, Option<String>)> = {
+        let mut stmt = conn.prepare(
+            "SELECT id, session_id, uuid, parent_uuid FROM messages
+             ORDER BY session_id, timestamp, id",
+        )?;
+        let rows =
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?;
+        rows.collect::<Result<_>>()?
+    };
+    let mut update = conn.prepare("UPDATE messages SET ordinal = ?2 WHERE id = ?1")?;
+    for session in rows.chunk_by(|a, b| a.1 == b.1) {
+        let links: Vec<_> = session.iter().map(|m| (m.2.as_deref(), m.3.as_deref())).collect();
+        for (ordinal, i) in parent_link_order(&links).into_iter().enumerate() {
+            update.execute(params![session[i].0, ordinal as i64])?;
+        }
+    }
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_messages_ordinal ON messages(session_id, ordinal)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Migration 8: messages record whether they are tool calls with no prose.
+/// That can't be told from stored content, so every session is parsed again
+/// at its next sync to fill it in.
+fn migrate_v8(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "ALTER TABLE messages ADD COLUMN is_tool_only BOOLEAN NOT NULL DEFAULT 0",
+        [],
+    )?;
+    conn.execute("UPDATE sessions SET file_hash = NULL, parsed_offset = NULL", [])?;
+    Ok(())
+}
+
+/// Migration 9: messages can be looked up by id across sessions.
+fn migrate_v9(conn: &Connection) -> Result<()> {
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_messages_msg_id ON messages(msg_id)", [])?;
+    Ok(())
+}
+
+/// Migration 10: the title index keys rows by session_id.
+///
+/// It was an external-content index over the implicit rowid of `sessions`,
+/// which VACUUM may renumber. It now keeps its own copy of each title and
+/// the session_id it belongs to, and is rebuilt from `sessions`.
+fn migrate_v10(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"
+        DROP TRIGGER IF EXISTS sessions_ai;
+        DROP TRIGGER IF EXISTS sessions_ad;
+        DROP TRIGGER IF EXISTS sessions_au;
+        DROP TABLE IF EXISTS sessions_fts;
+
+        CREATE VIRTUAL TABLE sessions_fts USING fts5(
+            first_message,
+            session_id UNINDEXED
+        );
+
+        INSERT INTO sessions_fts(first_message, session_id)
+        SELECT first_message, session_id FROM sessions;
+
+        CREATE TRIGGER sessions_ai AFTER INSERT ON sessions BEGIN
+            INSERT INTO sessions_fts(first_message, session_id)
+            VALUES (NEW.first_message, NEW.session_id);
+        END;
+
+        CREATE TRIGGER sessions_ad AFTER DELETE ON sessions BEGIN
+            DELETE FROM sessions_fts WHERE session_id = OLD.session_id;
+        END;
+
+        CREATE TRIGGER sessions_au AFTER UPDATE OF first_message, session_id ON sessions BEGIN
+            DELETE FROM sessions_fts WHERE session_id = OLD.session_id;
+            INSERT INTO sessions_fts(first_message, session_id)
+            VALUES (NEW.first_message, NEW.session_id);
+        END;
+        "#,
+    )?;
+    Ok(())
+}
+
+/// Add a column to an existing table unless it is already present.
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, decl: &str) -> Result<()> {
+    let exists = conn
+        .prepare(&format!("PRAGMA table_info({})", table))?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>>>()?
+        .iter()
+        .any(|name| name == column);
+
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl), [])?;
+    }
+    Ok(())
+}
+
+/// Separates the tags `SESSION_COLUMNS` concatenates into one column.
+const TAG_SEPARATOR: char = '\u{1f}';
+
+/// Columns selected for a `Session` from `sessions`, in the order
+/// `row_to_session` expects. The session's tags come along as one
+/// `TAG_SEPARATOR`-joined column, so listing sessions takes one query.
+const SESSION_COLUMNS: &str = "session_id, project, machine, first_message, started_at, ended_at,
+    COALESCE(message_count, 0), file_size, file_hash, agent, git_branch,
+    parent_session_id, COALESCE(content_purged, 0), reasoning_effort, cwd, file_mtime,
+    source_path, model, total_input_tokens, total_output_tokens,
+    COALESCE(starred, 0), summary, parsed_offset,
+    (SELECT GROUP_CONCAT(session_tags.tag, char(31)) FROM session_tags
+     WHERE session_tags.session_id = sessions.session_id)";
+
+fn row_to_session(row: &rusqlite::Row) -> rusqlite::Result<Session> {
+    Ok(Session {
+        session_id: row.get(0)?,
+        project: row.get(1)?,
+        machine: row.get(2)?,
+        first_message: row.get(3)?,
+        started_at: row.get(4)?,
+        ended_at: row.get(5)?,
+        message_count: row.get(6)?,
+        file_size: row.get(7)?,
+        file_hash: row.get(8)?,
+        agent: row.get::<_, Option<String>>(9)?.unwrap_or_else(|| "claude".to_string()),
+        git_branch: row.get(10)?,
+        parent_session_id: row.get(11)?,
+        content_purged: row.get(12)?,
+        reasoning_effort: row.get(13)?,
+        cwd: row.get(14)?,
+        file_mtime: row.get(15)?,
+        source_path: row.get(16)?,
+        model: row.get(17)?,
+        total_input_tokens: row.get(18)?,
+        total_output_tokens: row.get(19)?,
+        starred: row.get(20)?,
+        summary: row.get(21)?,
+        parsed_offset: row.get(22)?,
+        tags: split_tags(row.get(23)?),
+        is_new_since_last_open: false,
+    })
+}
+
+/// Split a `TAG_SEPARATOR`-joined tag column into tags in name order.
+fn split_tags(joined: Option<String>) -> Vec<String> {
+    let mut tags: Vec<String> =
+        joined.iter().flat_map(|list| list.split(TAG_SEPARATOR)).map(String::from).collect();
+    tags.sort();
+    tags
+}
+
+/// The few session fields a client-side finder needs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionIndexEntry {
+    pub session_id: String,
+    pub project: String,
+    pub first_message: Option<String>,
+    pub started_at: Option<String>,
+}
+
+/// A session and the sessions forked from it.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionNode {
+    pub session: Session,
+    pub children: Vec<SessionNode>,
+}
+
+/// Flag sessions that started after `since`, an RFC 3339 timestamp.
+pub fn flag_new_since(sessions: &mut [Session], since: &str) {
+    let Ok(since) = chrono::DateTime::parse_from_rfc3339(since) else {
+        return;
+    };
+    for session in sessions {
+        session.is_new_since_last_open = session
+            .started_at
+            .as_deref()
+            .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+            .is_some_and(|started| started > since);
+    }
+}
+
+/// Default SQLite page cache size, in KiB.
+pub const DEFAULT_CACHE_SIZE_KB: i64 = 32 * 1024;
+
+/// Default SQLite memory-map size, in MiB.
+pub const DEFAULT_MMAP_SIZE_MB: i64 = 256;
+
+/// Largest accepted page cache size, in KiB.
+const MAX_CACHE_SIZE_KB: i64 = 4 * 1024 * 1024;
+
+/// Largest accepted memory-map size, in MiB.
+const MAX_MMAP_SIZE_MB: i64 = 16 * 1024;
+
+/// SQLite cache and memory-map settings applied to a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DbConfig {
+    pub cache_size_kb: i64,
+    /// Zero disables memory-mapped I/O.
+    pub mmap_size_mb: i64,
+}
+
+impl Default for DbConfig {
+    fn default() -> Self {
+        Self {
+            cache_size_kb: DEFAULT_CACHE_SIZE_KB,
+            mmap_size_mb: DEFAULT_MMAP_SIZE_MB,
+        }
+    }
+}
+
+impl DbConfig {
+    /// Replace out-of-range values with their defaults, warning about each.
+    pub fn validated(self) -> Self {
+        let mut config = self;
+        if !(1..=MAX_CACHE_SIZE_KB).contains(&config.cache_size_kb) {
+            eprintln!("Ignoring out-of-range cache_size_kb: {}", config.cache_size_kb);
+            config.cache_size_kb = DEFAULT_CACHE_SIZE_KB;
+        }
+        if !(0..=MAX_MMAP_SIZE_MB).contains(&config.mmap_size_mb) {
+            eprintln!("Ignoring out-of-range mmap_size_mb: {}", config.mmap_size_mb);
+            config.mmap_size_mb = DEFAULT_MMAP_SIZE_MB;
+        }
+        config
+    }
+}
+
+/// Insert or update a session row. Its star and tags are left untouched.
+fn upsert_session_row(conn: &Connection, session: &Session) -> Result<()> {
+    conn.execute(
+        r#"
+        INSERT INTO sessions (session_id, project, machine, first_message, started_at,
+                              ended_at, message_count, file_size, file_hash, agent,
+                              git_branch, parent_session_id, content_purged,
+                              reasoning_effort, cwd, file_mtime, source_path, model,
+                              total_input_tokens, total_output_tokens, summary,
+                              parsed_offset)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16,
+                ?17, ?18, ?19, ?20, ?21, ?22)
+        ON CONFLICT(session_id) DO UPDATE SET
+            project = excluded.project,
+            machine = excluded.machine,
+            first_message = excluded.first_message,
+            started_at = excluded.started_at,
+            ended_at = excluded.ended_at,
+            message_count = excluded.message_count,
+            file_size = excluded.file_size,
+            file_hash = excluded.file_hash,
+            agent = excluded.agent,
+            git_branch = excluded.git_branch,
+            parent_session_id = excluded.parent_session_id,
+            content_purged = excluded.content_purged,
+            reasoning_effort = excluded.reasoning_effort,
+            cwd = excluded.cwd,
+            file_mtime = excluded.file_mtime,
+            source_path = excluded.source_path,
+            model = excluded.model,
+            total_input_tokens = excluded.total_input_tokens,
+            total_output_tokens = excluded.total_output_tokens,
+            summary = excluded.summary,
+            parsed_offset = excluded.parsed_offset
+        "#,
+        params![
+            session.session_id,
+            session.project,
+            session.machine,
+            session.first_message,
+            session.started_at,
+            session.ended_at,
+            session.message_count,
+            session.file_size,
+            session.file_hash,
+            session.agent,
+            session.git_branch,
+            session.parent_session_id,
+            session.content_purged,
+            session.reasoning_effort,
+            session.cwd,
+            session.file_mtime,
+            session.source_path,
+            session.model,
+            session.total_input_tokens,
+            session.total_output_tokens,
+            session.summary,
+            session.parsed_offset,
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// Delete a session's messages, tool calls and raw JSON.
+fn delete_message_rows(conn: &Connection, session_id: &str) -> Result<()> {
+    conn.execute("DELETE FROM messages WHERE session_id = ?1", params![session_id])?;
+    conn.execute("DELETE FROM tool_calls WHERE session_id = ?1", params![session_id])?;
+    conn.execute("DELETE FROM messages_raw WHERE session_id = ?1", params![session_id])?;
+    Ok(())
+}
+
+/// Insert messages along with any attached tool calls.
+fn insert_message_rows(conn: &Connection, messages: &[Message]) -> Result<()> {
+    let ordinals = message_ordinals(conn, messages)?;
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO messages (session_id, msg_id, role, content, timestamp, is_sidechain,
+                               repeat_count, uuid, parent_uuid, response_id, ordinal,
+                               is_tool_only)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+    )?;
+    let mut tool_stmt = conn.prepare_cached(
+        "INSERT INTO tool_calls (session_id, msg_id, seq, tool_name, summary, tool_input_json)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?;
+
+    for (msg, ordinal) in messages.iter().zip(ordinals) {
+        stmt.execute(params![
+            msg.session_id,
+            msg.msg_id,
+            msg.role,
+            msg.content,
+            msg.timestamp,
+            msg.is_sidechain,
+            msg.repeat_count,
+            msg.uuid,
+            msg.parent_uuid,
+            msg.response_id,
+            ordinal,
+            msg.is_tool_only,
+        ])?;
+
+        for call in msg.tool_calls.iter().flatten() {
+            tool_stmt.execute(params![
+                call.session_id,
+                call.msg_id,
+                call.seq,
+                call.tool_name,
+                call.summary,
+                call.tool_input_json,
+            ])?;
+        }
+    }
+    Ok(())
+}
+
+/// Display positions for messages about to be stored: each session's new
+/// messages follow its stored ones, in timestamp and parent link order.
+fn message_ordinals(conn: &Connection, messages: &[Message]) -> Result<Vec<i64>> {
+    let mut sessions: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, msg) in messages.iter().enumerate() {
+        sessions.entry(msg.session_id.as_str()).or_default().push(i);
+    }
+    let mut ordinals = vec![0; messages.len()];
+    for (session_id, mut indices) in sessions {
+        let next: i64 = conn.query_row(
+            "SELECT COALESCE(MAX(ordinal) + 1, 0) FROM messages WHERE session_id = ?1",
+            params![session_id],
+            |row| row.get(0),
+        )?;
+        indices.sort_by(|&a, &b| messages[a].timestamp.cmp(&messages[b].timestamp));
+        let links: Vec<_> = indices
+            .iter()
+            .map(|&i| (messages[i].uuid.as_deref(), messages[i].parent_uuid.as_deref()))
+            .collect();
+        for (position, k) in parent_link_order(&links).into_iter().enumerate() {
+            ordinals[indices[k]] = next + position as i64;
+        }
+    }
+    Ok(ordinals)
+}
+
+/// Store the original JSON of a session's messages as (msg_id, json) pairs.
+fn insert_raw_rows(conn: &Connection, session_id: &str, raw: &[(String, String)]) -> Result<()> {
+    let mut stmt = conn.prepare_cached(
+        "INSERT OR REPLACE INTO messages_raw (session_id, msg_id, raw_json)
+         VALUES (?1, ?2, ?3)",
+    )?;
+    for (msg_id, json) in raw {
+        stmt.execute(params![session_id, msg_id, json])?;
+    }
+    Ok(())
+}
+
+/// Record a session file's size at `sampled_at` (RFC 3339), keeping only
+/// the most recent `MAX_SIZE_SAMPLES` per session.
+fn insert_size_sample(
+    conn: &Connection,
+    session_id: &str,
+    size: i64,
+    sampled_at: &str,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO size_samples (session_id, file_size, sampled_at) VALUES (?1, ?2, ?3)",
+        params![session_id, size, sampled_at],
+    )?;
+    conn.execute(
+        "DELETE FROM size_samples
+         WHERE session_id = ?1 AND rowid NOT IN (
+             SELECT rowid FROM size_samples
+             WHERE session_id = ?1
+             ORDER BY sampled_at DESC
+             LIMIT ?2)",
+        params![session_id, MAX_SIZE_SAMPLES],
+    )?;
+    Ok(())
+}
+
+/// Store a synced session's original message JSON and sample its file size,
+/// as part of the transaction that writes the session.
+fn write_sync_extras(conn: &Connection, session: &Session, raw: &[(String, String)]) -> Result<()> {
+    insert_raw_rows(conn, &session.session_id, raw)?;
+    if let Some(size) = session.file_size {
+        let sampled_at = chrono::Utc::now().to_rfc3339();
+        insert_size_sample(conn, &session.session_id, size, &sampled_at)?;
+    }
+    Ok(())
+}
+
+/// Whether a session's messages were purged, leaving only its metadata.
+fn session_purged(conn: &Connection, session_id: &str) -> Result<bool> {
+    let purged: Option<bool> = conn
+        .query_row(
+            "SELECT COALESCE(content_purged, 0) FROM sessions WHERE session_id = ?1",
+            params![session_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(purged.unwrap_or(false))
+}
+
+/// Whether a session row exists.
+fn session_row_exists(conn: &Connection, session_id: &str) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sessions WHERE session_id = ?1)",
+        params![session_id],
+        |row| row.get(0),
+    )
+}
+
+/// Number of locks that syncs of different sessions are spread over.
+const SESSION_LOCK_STRIPES: usize = 64;
+
+/// Thread-safe database handle.
+pub struct Database {
+    conn: Mutex<Connection>,
+    read_only: bool,
+    /// Locks serializing syncs of one session, shared by hash of its id.
+    session_locks: Vec<Mutex<()>>,
+}
+
+impl Database {
+    /// Open or create the database at the given path with cache settings.
+    pub fn open_with(path: &PathBuf, config: &DbConfig) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        let db = Self {
+            conn: Mutex::new(conn),
+            read_only: false,
+            session_locks: (0..SESSION_LOCK_STRIPES).map(|_| Mutex::default()).collect(),
+        };
+        db.apply_config(config)?;
+        db.init_schema()?;
+        Ok(db)
+    }
+
+    /// Open an existing database (e.g. a teammate's export) without allowing
+    /// writes, with cache settings.
+    pub fn open_read_only_with(path: &PathBuf, config: &DbConfig) -> Result<Self> {
+        let conn = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        let db = Self {
+            conn: Mutex::new(conn),
+            read_only: true,
+            session_locks: (0..SESSION_LOCK_STRIPES).map(|_| Mutex::default()).collect(),
+        };
+        db.apply_config(config)?;
+        Ok(db)
+    }
+
+    /// Apply cache settings to the open connection, after validating them,
+    /// and return the settings now in effect.
+    pub fn apply_config(&self, config: &DbConfig) -> Result<DbConfig> {
+        let config = config.validated();
+        {
+            let conn = self.conn.lock().unwrap();
+            // A negative cache_size is in KiB rather than pages
+            conn.pragma_update(None, "cache_size", -config.cache_size_kb)?;
+            conn.pragma_update(None, "mmap_size", config.mmap_size_mb * 1024 * 1024)?;
+        }
+        self.config()
+    }
+
+    /// Read the cache settings in effect on the connection.
+    pub fn config(&self) -> Result<DbConfig> {
+        let conn = self.conn.lock().unwrap();
+        let cache_size: i64 = conn.pragma_query_value(None, "cache_size", |row| row.get(0))?;
+        let mmap_size: i64 = conn.pragma_query_value(None, "mmap_size", |row| row.get(0))?;
+        let cache_size_kb = if cache_size < 0 {
+            -cache_size
+        } else {
+            // A positive value is a page count
+            let page_size: i64 = conn.pragma_query_value(None, "page_size", |row| row.get(0))?;
+            cache_size * page_size / 1024
+        };
+        Ok(DbConfig {
+            cache_size_kb,
+            mmap_size_mb: mmap_size / (1024 * 1024),
+        })
+    }
+
+    /// Whether this handle was opened with `open_read_only`.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Lock the connection for a write, failing cleanly in read-only mode.
+    fn write_conn(&self) -> Result<MutexGuard<'_, Connection>> {
+        if self.read_only {
+            return Err(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_READONLY),
+                Some("database is open in read-only mode".to_string()),
+            ));
+        }
+        Ok(self.conn.lock().unwrap())
+    }
+
+    /// Lock a session against other syncs of it, so that reading its stored
+    /// state, parsing its file and writing the result can't interleave with
+    /// another sync. Every write is a single transaction either way; the lock
+    /// keeps a slower sync from overwriting a newer result. Sessions share a
+    /// fixed set of locks, so an unrelated session may occasionally wait.
+    pub fn lock_session(&self, session_id: &str) -> MutexGuard<'_, ()> {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        session_id.hash(&mut hasher);
+        let stripe = hasher.finish() as usize % self.session_locks.len();
+        self.session_locks[stripe].lock().unwrap()
+    }
+
+    /// Initialize the database schema, applying any pending migrations.
+    fn init_schema(&self) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        migrate(&conn)
+    }
+
+    /// Get all sessions matching a filter, in the given order, with their tags.
+    pub fn get_sessions_filtered(
+        &self,
+        filter: &SessionFilter,
+        limit: i32,
+        sort: SessionSort,
+    ) -> Result<Vec<Session>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SESSION_COLUMNS}
+             FROM sessions
+             WHERE COALESCE(message_count, 0) > 0 AND (?1 IS NULL OR project = ?1)
+               AND (?3 IS NULL OR EXISTS (
+                   SELECT 1 FROM tool_calls t
+                   WHERE t.session_id = sessions.session_id AND t.tool_name = ?3))
+               AND (?4 IS NULL OR instr(lower(model), lower(?4)) > 0)
+               AND (NOT ?5 OR COALESCE(starred, 0))
+               AND (?6 IS NULL OR EXISTS (
+                   SELECT 1 FROM session_tags g
+                   WHERE g.session_id = sessions.session_id AND g.tag = ?6))
+               AND (?7 IS NULL OR git_branch = ?7)
+             ORDER BY {}
+             LIMIT ?2",
+            sort.order_by()
+        ))?;
+        let rows = stmt.query_map(
+            params![
+                filter.project,
+                limit,
+                filter.uses_tool,
+                filter.model,
+                filter.starred_only,
+                filter.tag,
+                filter.git_branch,
+            ],
+            row_to_session,
+        )?;
+        rows.collect()
+    }
+
+    /// Get every non-empty session as a lightweight index entry, in the
+    /// default session order.
+    pub fn get_session_index(&self) -> Result<Vec<SessionIndexEntry>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT session_id, project, first_message, started_at
+             FROM sessions
+             WHERE COALESCE(message_count, 0) > 0
+             ORDER BY {}",
+            SessionSort::default().order_by()
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok(SessionIndexEntry {
+                session_id: row.get(0)?,
+                project: row.get(1)?,
+                first_message: row.get(2)?,
+                started_at: row.get(3)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Get a random sample of non-empty sessions, optionally filtered by project.
+    pub fn get_random_sessions(&self, limit: i32, project: Option<&str>) -> Result<Vec<Session>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SESSION_COLUMNS}
+             FROM sessions
+             WHERE COALESCE(message_count, 0) > 0 AND (?1 IS NULL OR project = ?1)
+             ORDER BY RANDOM()
+             LIMIT ?2"
+        ))?;
+        let rows = stmt.query_map(params![project, limit], row_to_session)?;
+        rows.collect()
+    }
+
+    /// Get a project's non-empty sessions grouped by git branch, newest first
+    /// within each group. Sessions without a recorded branch group under "unknown".
+    pub fn get_sessions_by_branch(&self, project: &str) -> Result<Vec<(String, Vec<Session>)>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SESSION_COLUMNS}
+             FROM sessions
+             WHERE COALESCE(message_count, 0) > 0 AND project = ?1
+             ORDER BY COALESCE(git_branch, 'unknown'), started_at DESC"
+        ))?;
+        let rows = stmt.query_map(params![project], row_to_session)?;
+
+        let mut groups: Vec<(String, Vec<Session>)> = Vec::new();
+        for session in rows {
+            let session = session?;
+            let branch = session.git_branch.clone().unwrap_or_else(|| "unknown".to_string());
+            match groups.last_mut() {
+                Some((last, sessions)) if *last == branch => sessions.push(session),
+                _ => groups.push((branch, vec![session])),
+            }
+        }
+        Ok(groups)
+    }
+
+    /// Get messages for a session in display order, skipping the first
+    /// `offset` and returning at most `limit`. Without either, every message
+    /// is returned.
+    pub fn get_messages(
+        &self,
+        session_id: &str,
+        offset: Option<i32>,
+        limit: Option<i32>,
+    ) -> Result<Vec<Message>> {
+        let conn = self.conn.lock().unwrap();
+
+        // SQLite treats a negative LIMIT as no limit
+        let limit = limit.map_or(-1, |l| l.max(0));
+        let offset = offset.unwrap_or(0).max(0);
+
+        let mut stmt = conn.prepare(
+            "SELECT msg_id, session_id, role, content, timestamp, COALESCE(is_sidechain, 0),
+                    COALESCE(repeat_count, 0), uuid, parent_uuid, response_id, is_tool_only
+             FROM messages
+             WHERE session_id = ?1
+             ORDER BY ordinal ASC, id ASC
+             LIMIT ?2 OFFSET ?3",
+        )?;
+        let rows = stmt.query_map(params![session_id, limit, offset], |row| {
+            Ok(Message {
+                msg_id: row.get(0)?,
+                session_id: row.get(1)?,
+                role: row.get(2)?,
+                content: row.get(3)?,
+                timestamp: row.get(4)?,
+                is_sidechain: row.get(5)?,
+                repeat_count: row.get(6)?,
+                uuid: row.get(7)?,
+                parent_uuid: row.get(8)?,
+                response_id: row.get(9)?,
+                is_tool_only: row.get(10)?,
+                tool_calls: None,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Find a session's messages that are far above its median size.
+    ///
+    /// Token counts aren't recorded, so content length is used as a proxy.
+    pub fn get_token_outliers(&self, session_id: &str) -> Result<TokenOutliers> {
+        let sizes: Vec<MessageSize> = {
+            let conn = self.conn.lock().unwrap();
+            let mut stmt = conn.prepare(
+                "SELECT msg_id, role, timestamp, length(COALESCE(content, ''))
+                 FROM messages
+                 WHERE session_id = ?1
+                 ORDER BY timestamp ASC",
+            )?;
+            let rows = stmt.query_map(params![session_id], |row| {
+                Ok(MessageSize {
+                    msg_id: row.get(0)?,
+                    role: row.get(1)?,
+                    timestamp: row.get(2)?,
+                    size: row.get(3)?,
+                })
+            })?;
+            rows.collect::<Result<_>>()?
+        };
+
+        let mut sorted: Vec<i64> = sizes.iter().map(|m| m.size).collect();
+        sorted.sort_unstable();
+        let mid = sorted.len() / 2;
+        let median = match sorted.len() {
+            0 => 0.0,
+            n if n % 2 == 0 => (sorted[mid - 1] + sorted[mid]) as f64 / 2.0,
+            _ => sorted[mid] as f64,
+        };
+        let threshold = median * OUTLIER_FACTOR;
+        let outliers = sizes
+            .into_iter()
+            .filter(|m| m.size as f64 > threshold)
+            .collect();
+        Ok(TokenOutliers {
+            median,
+            threshold,
+            outliers,
+        })
+    }
+
+    /// Count a session's messages.
+    pub fn count_messages(&self, session_id: &str) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT COUNT(*) FROM messages WHERE session_id = ?1",
+            params![session_id],
+            |row| row.get(0),
+        )
+    }
+
+    /// Get the sessions containing a message id. Ids combine the timestamp,
+    /// position and a hash of the content, so a message copied into a forked
+    /// or resumed session keeps its id there too.
+    pub fn find_sessions_for_message(&self, msg_id: &str) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT session_id FROM messages WHERE msg_id = ?1 ORDER BY session_id",
+        )?;
+        let rows = stmt.query_map(params![msg_id], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    /// Get all messages for a session with roles normalized.
+    pub fn get_normalized_messages(&self, session_id: &str) -> Result<Vec<NormalizedMessage>> {
+        Ok(self.get_messages(session_id, None, None)?.into_iter().map(Into::into).collect())
+    }
+
+    /// Get messages for a session with their structured tool calls attached,
+    /// windowed as in `get_messages`.
+    pub fn get_messages_with_tools(
+        &self,
+        session_id: &str,
+        offset: Option<i32>,
+        limit: Option<i32>,
+    ) -> Result<Vec<Message>> {
+        let mut messages = self.get_messages(session_id, offset, limit)?;
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT session_id, msg_id, seq, tool_name, COALESCE(summary, '')
+             FROM tool_calls
+             WHERE session_id = ?1
+             ORDER BY seq ASC",
+        )?;
+
+        let mut by_msg: std::collections::HashMap<String, Vec<ToolCall>> =
+            std::collections::HashMap::new();
+        let rows = stmt.query_map(params![session_id], |row| {
+            Ok(ToolCall {
+                session_id: row.get(0)?,
+                msg_id: row.get(1)?,
+                seq: row.get(2)?,
+                tool_name: row.get(3)?,
+                summary: row.get(4)?,
+                tool_input_json: None,
+            })
+        })?;
+        for call in rows {
+            let call = call?;
+            by_msg.entry(call.msg_id.clone()).or_default().push(call);
+        }
+
+        for msg in &mut messages {
+            msg.tool_calls = Some(by_msg.remove(&msg.msg_id).unwrap_or_default());
+        }
+
+        Ok(messages)
+    }
+
+    /// Get a single tool call including its full input JSON.
+    pub fn get_tool_call_detail(&self, session_id: &str, seq: i32) -> Result<Option<ToolCall>> {
+        let conn = self.conn.lock().unwrap();
+
+        let result = conn.query_row(
+            "SELECT session_id, msg_id, seq, tool_name, COALESCE(summary, ''), tool_input_json
+             FROM tool_calls
+             WHERE session_id = ?1 AND seq = ?2",
+            params![session_id, seq],
+            |row| {
+                Ok(ToolCall {
+                    session_id: row.get(0)?,
+                    msg_id: row.get(1)?,
+                    seq: row.get(2)?,
+                    tool_name: row.get(3)?,
+                    summary: row.get(4)?,
+                    tool_input_json: row.get(5)?,
+                })
+            },
+        );
+
+        match result {
+            Ok(call) => Ok(Some(call)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get a session's turns for the timeline, with runs of tool-only turns collapsed.
+    pub fn get_session_timeline(&self, session_id: &str) -> Result<Vec<TimelineEntry>> {
+        Ok(build_timeline(&self.get_messages_with_tools(session_id, None, None)?))
+    }
+
+    /// Get a session's tool calls in order, without any prose, as an execution log.
+    pub fn get_activity_feed(&self, session_id: &str) -> Result<Vec<ActivityEntry>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT t.seq, t.msg_id, COALESCE(m.timestamp, ''), t.tool_name, t.tool_input_json
+             FROM tool_calls t
+             LEFT JOIN messages m ON m.session_id = t.session_id AND m.msg_id = t.msg_id
+             WHERE t.session_id = ?1
+             ORDER BY t.seq ASC",
+        )?;
+        let rows = stmt.query_map(params![session_id], |row| {
+            Ok(ActivityEntry {
+                seq: row.get(0)?,
+                msg_id: row.get(1)?,
+                timestamp: row.get(2)?,
+                tool_name: row.get(3)?,
+                target: tool_target(row.get::<_, Option<String>>(4)?.as_deref()),
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Get the gap between each user message and the assistant's first reply.
+    ///
+    /// Exchanges where either timestamp is missing or unparseable are skipped.
+    pub fn get_response_latencies(&self, session_id: &str) -> Result<Vec<ResponseLatency>> {
+        let parse = |ts: &str| chrono::DateTime::parse_from_rfc3339(ts).ok();
+
+        let mut latencies = Vec::new();
+        let mut pending: Option<&Message> = None;
+        let messages = self.get_messages(session_id, None, None)?;
+        for msg in &messages {
+            match msg.role.as_str() {
+                "user" => pending = Some(msg),
+                "assistant" => {
+                    let Some(user) = pending.take() else {
+                        continue;
+                    };
+                    if let (Some(asked), Some(replied)) =
+                        (parse(&user.timestamp), parse(&msg.timestamp))
+                    {
+                        latencies.push(ResponseLatency {
+                            user_msg_id: user.msg_id.clone(),
+                            assistant_msg_id: msg.msg_id.clone(),
+                            seconds: (replied - asked).num_milliseconds() as f64 / 1000.0,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(latencies)
+    }
+
+    /// Count messages in a session by role, returned as (user, assistant, other).
+    pub fn get_message_role_counts(&self, session_id: &str) -> Result<(i64, i64, i64)> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.query_row(
+            "SELECT COALESCE(SUM(role = 'user'), 0),
+                    COALESCE(SUM(role = 'assistant'), 0),
+                    COALESCE(SUM(role NOT IN ('user', 'assistant')), 0)
+             FROM messages
+             WHERE session_id = ?1",
+            params![session_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+    }
+
+    /// Get the most frequent terms across a session's or project's messages.
+    pub fn get_term_frequencies(&self, scope: &TermScope, limit: usize) -> Result<Vec<(String, i64)>> {
+        let conn = self.conn.lock().unwrap();
+
+        let (sql, key) = match scope {
+            TermScope::Session(id) => ("SELECT content FROM messages WHERE session_id = ?1", id),
+            TermScope::Project(project) => (
+                "SELECT m.content FROM messages m
+                 JOIN sessions s ON m.session_id = s.session_id
+                 WHERE s.project = ?1",
+                project,
+            ),
+        };
+
+        let mut counts: std::collections::HashMap<String, i64> = std::collections::HashMap::new();
+        let mut stmt = conn.prepare(sql)?;
+        let rows = stmt.query_map(params![key], |row| row.get::<_, Option<String>>(0))?;
+        for content in rows {
+            for term in tokenize_terms(&content?.unwrap_or_default()) {
+                *counts.entry(term).or_insert(0) += 1;
+            }
+        }
+
+        let mut terms: Vec<(String, i64)> = counts.into_iter().collect();
+        terms.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        terms.truncate(limit);
+        Ok(terms)
+    }
+
+    /// Count messages matching an FTS query per time bucket, oldest first.
+    /// Messages without a parseable timestamp are left out.
+    pub fn term_over_time(&self, term: &str, bucket: TimeBucket) -> Result<Vec<(String, i64)>> {
+        let term = prepare_fts_query(term, false);
+        if term.is_empty() {
+            return Ok(Vec::new());
+        }
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT strftime(?2, m.timestamp) AS bucket, COUNT(*)
+             FROM messages_fts
+             JOIN messages m ON messages_fts.rowid = m.id
+             WHERE messages_fts MATCH ?1 AND bucket IS NOT NULL
+             GROUP BY bucket
+             ORDER BY bucket",
+        )?;
+        let rows = stmt.query_map(params![term, bucket.format()], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+        rows.collect()
+    }
+
+    /// Search messages using FTS5, returning the first page of results.
+    pub fn search(
+        &self,
+        query: &str,
+        limit: i32,
+        options: &SearchOptions,
+    ) -> Result<Vec<SearchResult>> {
+        Ok(self.search_page(query, limit, None, options)?.results)
+    }
+
+    /// Full-text search within a single session.
+    pub fn search_in_session(
+        &self,
+        session_id: &str,
+        query: &str,
+        limit: i32,
+    ) -> Result<Vec<SearchResult>> {
+        let options = SearchOptions {
+            session_id: Some(session_id.to_string()),
+            ..Default::default()
+        };
+        self.search(query, limit, &options)
+    }
+
+    /// Rank sessions by how strongly they match a query as a whole.
+    ///
+    /// A session's score is the sum of its matching messages' BM25 scores, so
+    /// many strong matches outweigh a single weak one.
+    pub fn rank_sessions(&self, query: &str, limit: i32) -> Result<Vec<Session>> {
+        let limit = limit.clamp(0, MAX_SEARCH_LIMIT);
+        let query = prepare_fts_query(query, false);
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SESSION_COLUMNS}
+             FROM sessions
+             JOIN (
+                 SELECT m.session_id AS hit_session_id, SUM(-messages_fts.rank) AS score
+                 FROM messages_fts
+                 JOIN messages m ON messages_fts.rowid = m.id
+                 WHERE messages_fts MATCH ?1
+                 GROUP BY m.session_id
+             ) hits ON hits.hit_session_id = sessions.session_id
+             ORDER BY hits.score DESC, sessions.session_id
+             LIMIT ?2"
+        ))?;
+        let rows = stmt.query_map(params![query, limit], row_to_session)?;
+        rows.collect()
+    }
+
+    /// Search session titles and message bodies in one ranked list.
+    ///
+    /// Title matches have their BM25 score multiplied by `title_weight`, so with
+    /// a weight above 1 a session whose first message matches ranks above the
+    /// same term buried in a message body.
+    pub fn search_all(&self, query: &str, limit: i32, title_weight: f64) -> Result<Vec<RankedHit>> {
+        let limit = limit.clamp(0, MAX_SEARCH_LIMIT);
+        let query = prepare_fts_query(query, false);
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT 'title', s.session_id, NULL, s.project,
+                    snippet(sessions_fts, 0, char(1), char(2), '...', 32),
+                    -sessions_fts.rank * ?3 AS score
+             FROM sessions_fts
+             JOIN sessions s ON s.session_id = sessions_fts.session_id
+             WHERE sessions_fts MATCH ?1
+             UNION ALL
+             SELECT 'message', m.session_id, m.msg_id, s.project,
+                    snippet(messages_fts, 0, char(1), char(2), '...', 32),
+                    -messages_fts.rank AS score
+             FROM messages_fts
+             JOIN messages m ON messages_fts.rowid = m.id
+             JOIN sessions s ON m.session_id = s.session_id
+             WHERE messages_fts MATCH ?1
+             ORDER BY score DESC
+             LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![query, limit, title_weight], |row| {
+            let kind = match row.get::<_, String>(0)?.as_str() {
+                "title" => HitKind::Title,
+                _ => HitKind::Message,
+            };
+            Ok(RankedHit {
+                kind,
+                session_id: row.get(1)?,
+                msg_id: row.get(2)?,
+                project: row.get(3)?,
+                snippet: marked_html(&row.get::<_, String>(4)?),
+                score: row.get(5)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Search messages, returning each hit as the merged turn that contains it.
+    ///
+    /// Consecutive messages with the same role are merged, joined by a blank
+    /// line. Several hits in one turn collapse into a single result.
+    pub fn search_merged(&self, query: &str, limit: i32) -> Result<Vec<MergedSearchResult>> {
+        let limit = limit.clamp(0, MAX_SEARCH_LIMIT);
+        let query = prepare_fts_query(query, false);
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT m.session_id, m.msg_id, s.project, m.role, m.ordinal, m.id
+            FROM messages_fts
+            JOIN messages m ON messages_fts.rowid = m.id
+            JOIN sessions s ON m.session_id = s.session_id
+            WHERE messages_fts MATCH ?1
+            ORDER BY messages_fts.rank, m.id
+            LIMIT ?2
+            "#,
+        )?;
+        let hits = stmt
+            .query_map(params![query, limit], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, i64>(4)?,
+                    row.get::<_, i64>(5)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>>>()?;
+
+        // The turn is bounded by the nearest messages with another role
+        let mut before_stmt = conn.prepare(
+            "SELECT ordinal, id FROM messages
+             WHERE session_id = ?1 AND role != ?2 AND (ordinal, id) < (?3, ?4)
+             ORDER BY ordinal DESC, id DESC
+             LIMIT 1",
+        )?;
+        let mut after_stmt = conn.prepare(
+            "SELECT ordinal, id FROM messages
+             WHERE session_id = ?1 AND role != ?2 AND (ordinal, id) > (?3, ?4)
+             ORDER BY ordinal ASC, id ASC
+             LIMIT 1",
+        )?;
+        let mut turn_stmt = conn.prepare(
+            "SELECT msg_id, content, timestamp, id
+             FROM messages
+             WHERE session_id = ?1
+               AND (?2 IS NULL OR (ordinal, id) > (?2, ?3))
+               AND (?4 IS NULL OR (ordinal, id) < (?4, ?5))
+             ORDER BY ordinal ASC, id ASC",
+        )?;
+        let mut highlight_stmt = conn.prepare(
+            r#"
+            SELECT m.id, highlight(messages_fts, 0, char(1), char(2))
+            FROM messages_fts
+            JOIN messages m ON messages_fts.rowid = m.id
+            WHERE messages_fts MATCH ?1 AND m.session_id = ?2
+              AND (?3 IS NULL OR (m.ordinal, m.id) > (?3, ?4))
+              AND (?5 IS NULL OR (m.ordinal, m.id) < (?5, ?6))
+            "#,
+        )?;
+
+        let mut seen_turns = HashSet::new();
+        let mut results = Vec::new();
+
+        for (session_id, msg_id, project, role, ordinal, id) in hits {
+            let bound = |stmt: &mut rusqlite::Statement| {
+                stmt.query_row(params![session_id, role, ordinal, id], |row| {
+                    Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+                })
+                .optional()
+            };
+            let (before_ordinal, before_id) = bound(&mut before_stmt)?.unzip();
+            let (after_ordinal, after_id) = bound(&mut after_stmt)?.unzip();
+
+            let turn = turn_stmt
+                .query_map(
+                    params![session_id, before_ordinal, before_id, after_ordinal, after_id],
+                    |row| {
+                        Ok((
+                            row.get::<_, String>(0)?,
+                            row.get::<_, String>(1)?,
+                            row.get::<_, String>(2)?,
+                            row.get::<_, i64>(3)?,
+                        ))
+                    },
+                )?
+                .collect::<Result<Vec<_>>>()?;
+            let Some(first) = turn.first() else {
+                continue;
+            };
+            if !seen_turns.insert(first.3) {
+                continue;
+            }
+
+            let highlights = highlight_stmt
+                .query_map(
+                    params![query, session_id, before_ordinal, before_id, after_ordinal, after_id],
+                    |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+                )?
+                .collect::<Result<HashMap<_, _>>>()?;
+            let content = turn
+                .iter()
+                .map(|(_, content, _, id)| match highlights.get(id) {
+                    Some(marked) => marked_html(marked),
+                    None => escape_html(content),
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n");
+
+            results.push(MergedSearchResult {
+                session_id,
+                project,
+                role,
+                timestamp: first.2.clone(),
+                msg_ids: turn.into_iter().map(|m| m.0).collect(),
+                match_msg_id: msg_id,
+                content,
+            });
+        }
+
+        Ok(results)
+    }
+
+    /// Search messages using FTS5, resuming after an opaque cursor from a previous page.
+    ///
+    /// Results are ordered by (rank, rowid) and the cursor resumes after the last
+    /// row returned, so while the index is unchanged pages neither repeat nor skip
+    /// rows. Messages synced between pages shift BM25 ranks, after which a later
+    /// page may repeat or miss some matches. `limit` is capped at
+    /// `MAX_SEARCH_LIMIT`; the page reports the size it applied.
+    pub fn search_page(
+        &self,
+        query: &str,
+        limit: i32,
+        after: Option<&str>,
+        options: &SearchOptions,
+    ) -> Result<SearchPage> {
+        let limit = limit.clamp(0, MAX_SEARCH_LIMIT);
+        let cursor = after.map(decode_search_cursor).transpose()?;
+        if options.snippet_column >= FTS_COLUMNS.len() {
+            return Err(rusqlite::Error::InvalidColumnIndex(options.snippet_column));
+        }
+        let fts_query = prepare_fts_query(query, options.prefix);
+        if fts_query.is_empty() {
+            return Ok(SearchPage {
+                results: Vec::new(),
+                next: None,
+                limit,
+            });
+        }
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT m.session_id, m.msg_id, m.role, m.content, m.timestamp, s.project,
+                   snippet(messages_fts, ?5, char(1), char(2), '...', 32) as snippet,
+                   messages_fts.rank, m.id,
+                   highlight(messages_fts, ?5, char(1), char(2)) as highlighted
+            FROM messages_fts
+            JOIN messages m ON messages_fts.rowid = m.id
+            JOIN sessions s ON m.session_id = s.session_id
+            WHERE messages_fts MATCH ?1
+              AND (?3 IS NULL OR messages_fts.rank > ?3
+                   OR (messages_fts.rank = ?3 AND m.id > ?4))
+              AND (?6 IS NULL OR julianday(m.timestamp) >= julianday(?6))
+              AND (?7 IS NULL OR julianday(m.timestamp) <= julianday(?7))
+              AND (?8 IS NULL OR m.session_id = ?8)
+              AND (?9 IS NULL OR ?9 = COALESCE(
+                  (SELECT value FROM json_each(?10) WHERE key = lower(m.role)), 'system'))
+            ORDER BY messages_fts.rank, m.id
+            LIMIT ?2
+            "#,
+        )?;
+
+        let (after_rank, after_id) = cursor.unzip();
+        // Fetch one extra row to learn whether another page exists
+        let rows = stmt.query_map(
+            params![
+                fts_query,
+                limit + 1,
+                after_rank,
+                after_id,
+                options.snippet_column,
+                options.after,
+                options.before,
+                options.session_id,
+                options.role.as_deref().map(normalize_role),
+                role_aliases_json(),
+            ],
+            |row| {
+                Ok((
+                    SearchResult {
+                        session_id: row.get(0)?,
+                        msg_id: row.get(1)?,
+                        role: row.get(2)?,
+                        content: row.get(3)?,
+                        timestamp: row.get(4)?,
+                        project: row.get(5)?,
+                        snippet: marked_html(&row.get::<_, String>(6)?),
+                        snippets: highlight_fragments(
+                            &row.get::<_, Option<String>>(9)?.unwrap_or_default(),
+                        ),
+                    },
+                    row.get::<_, f64>(7)?,
+                    row.get::<_, i64>(8)?,
+                ))
+            },
+        )?;
+
+        let mut rows = rows.collect::<Result<Vec<_>>>()?;
+        let has_more = rows.len() > limit as usize;
+        rows.truncate(limit as usize);
+
+        if options.snippet == SnippetStyle::Sentence
+            && options.snippet_column == FTS_CONTENT_COLUMN
+        {
+            for (result, _, _) in &mut rows {
+                if let Some(snippet) = sentence_snippet(&result.content, query) {
+                    result.snippet = snippet;
+                }
+            }
+        }
+
+        let next = if has_more {
+            rows.last().map(|(_, rank, id)| encode_search_cursor(*rank, *id))
+        } else {
+            None
+        };
+
+        Ok(SearchPage {
+            results: rows.into_iter().map(|(result, _, _)| result).collect(),
+            next,
+            limit,
+        })
+    }
+
+    /// Compare the FTS index with the messages table, optionally rebuilding it on drift.
+    pub fn verify_search_integrity(&self, repair: bool) -> Result<SearchIntegrityReport> {
+        const SAMPLE_SIZE: i64 = 100;
+
+        let mut report = {
+            let conn = self.conn.lock().unwrap();
+
+            let message_count: i64 =
+                conn.query_row("SELECT COUNT(*) FROM messages", [], |row| row.get(0))?;
+            let indexed_count: i64 =
+                conn.query_row("SELECT COUNT(*) FROM messages_fts_docsize", [], |row| row.get(0))?;
+
+            // Spot-check a random sample of messages for an index entry
+            let missing_from_index: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM (SELECT id FROM messages ORDER BY RANDOM() LIMIT ?1) s
+                 WHERE NOT EXISTS (SELECT 1 FROM messages_fts_docsize d WHERE d.id = s.id)",
+                params![SAMPLE_SIZE],
+                |row| row.get(0),
+            )?;
+
+            // Compares index contents against the external content table; this is
+            // issued as an INSERT, so it can't run on a read-only handle
+            let check_failed = !self.read_only
+                && conn
+                    .execute(
+                        "INSERT INTO messages_fts(messages_fts, rank) VALUES('integrity-check', 1)",
+                        [],
+                    )
+                    .is_err();
+
+            SearchIntegrityReport {
+                message_count,
+                indexed_count,
+                sampled: message_count.min(SAMPLE_SIZE),
+                missing_from_index,
+                drift: message_count != indexed_count || missing_from_index > 0 || check_failed,
+                repaired: false,
+            }
+        };
+
+        if report.drift && repair {
+            self.rebuild_fts()?;
+            let conn = self.conn.lock().unwrap();
+            report.indexed_count =
+                conn.query_row("SELECT COUNT(*) FROM messages_fts_docsize", [], |row| row.get(0))?;
+            report.repaired = true;
+        }
+
+        Ok(report)
+    }
+
+    /// Rebuild the FTS index from the messages table.
+    pub fn rebuild_fts(&self) -> Result<()> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        tx.execute("INSERT INTO messages_fts(messages_fts) VALUES('rebuild')", [])?;
+        tx.commit()
+    }
+
+    /// Merge the FTS index's segments to compact it and speed up queries.
+    pub fn optimize_fts(&self) -> Result<()> {
+        let conn = self.write_conn()?;
+        conn.execute("INSERT INTO messages_fts(messages_fts) VALUES('optimize')", [])?;
+        Ok(())
+    }
+
+    /// Get the fork tree rooted at a session, or None if it doesn't exist.
+    ///
+    /// Sessions already placed in the tree are not revisited, so a cycle in
+    /// `parent_session_id` links can't recurse forever.
+    pub fn get_session_tree(&self, root_session_id: &str) -> Result<Option<SessionNode>> {
+        fn build(
+            conn: &Connection,
+            session: Session,
+            visited: &mut HashSet<String>,
+        ) -> Result<SessionNode> {
+            let mut stmt = conn.prepare_cached(&format!(
+                "SELECT {SESSION_COLUMNS}
+                 FROM sessions
+                 WHERE parent_session_id = ?1
+                 ORDER BY started_at ASC"
+            ))?;
+            let forks: Vec<Session> = stmt
+                .query_map(params![session.session_id], row_to_session)?
+                .collect::<Result<_>>()?;
+
+            let mut children = Vec::new();
+            for fork in forks {
+                if visited.insert(fork.session_id.clone()) {
+                    children.push(build(conn, fork, visited)?);
+                }
+            }
+            Ok(SessionNode { session, children })
+        }
+
+        let conn = self.conn.lock().unwrap();
+        let root = conn
+            .query_row(
+                &format!("SELECT {SESSION_COLUMNS} FROM sessions WHERE session_id = ?1"),
+                params![root_session_id],
+                row_to_session,
+            )
+            .optional()?;
+
+        let Some(root) = root else {
+            return Ok(None);
+        };
+        let mut visited = HashSet::from([root.session_id.clone()]);
+        build(&conn, root, &mut visited).map(Some)
+    }
+
+    /// Get the conversation a session continues, oldest first and ending with
+    /// the session itself, by following `parent_session_id` links.
+    ///
+    /// The walk stops at a parent that isn't in the database or was already
+    /// visited, so a cycle can't loop forever. Empty if the session doesn't exist.
+    pub fn get_session_thread(&self, session_id: &str) -> Result<Vec<Session>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {SESSION_COLUMNS} FROM sessions WHERE session_id = ?1"
+        ))?;
+
+        let mut thread = Vec::new();
+        let mut visited = HashSet::new();
+        let mut next = Some(session_id.to_string());
+        while let Some(id) = next.filter(|id| visited.insert(id.clone())) {
+            let Some(session) = stmt.query_row(params![id], row_to_session).optional()? else {
+                break;
+            };
+            next = session.parent_session_id.clone();
+            thread.push(session);
+        }
+        thread.reverse();
+        Ok(thread)
+    }
+
+    /// Get a session's metadata, or None if it doesn't exist.
+    pub fn get_session(&self, session_id: &str) -> Result<Option<Session>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            &format!("SELECT {SESSION_COLUMNS} FROM sessions WHERE session_id = ?1"),
+            params![session_id],
+            row_to_session,
+        )
+        .optional()
+    }
+
+    /// Get a value from the meta table.
+    pub fn get_meta(&self, key: &str) -> Result<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT value FROM meta WHERE key = ?1", params![key], |row| {
+            row.get(0)
+        })
+        .optional()
+    }
+
+    /// Set a value in the meta table.
+    pub fn set_meta(&self, key: &str, value: &str) -> Result<()> {
+        let conn = self.write_conn()?;
+        conn.execute(
+            "INSERT INTO meta (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    /// Record `now` as the latest app launch, returning the previous launch time.
+    pub fn record_open(&self, now: &str) -> Result<Option<String>> {
+        let previous = self.get_meta("last_open_at")?;
+        self.set_meta("last_open_at", now)?;
+        Ok(previous)
+    }
+
+    /// Get the IDs of every session.
+    pub fn get_all_session_ids(&self) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT session_id FROM sessions ORDER BY session_id")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    /// Get the (session_id, cwd) of every session of an agent that recorded a cwd.
+    pub fn get_session_cwds(&self, agent: &str) -> Result<Vec<(String, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT session_id, cwd FROM sessions
+             WHERE agent = ?1 AND cwd IS NOT NULL
+             ORDER BY session_id",
+        )?;
+        let rows = stmt.query_map(params![agent], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    }
+
+    /// Get the ids of every session in a project.
+    pub fn get_project_session_ids(&self, project: &str) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT session_id FROM sessions WHERE project = ?1 ORDER BY session_id")?;
+        let rows = stmt.query_map(params![project], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    /// Get the sessions last synced from a source file.
+    pub fn get_sessions_by_source(&self, source_path: &str) -> Result<Vec<Session>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SESSION_COLUMNS}
+             FROM sessions
+             WHERE source_path = ?1
+             ORDER BY session_id"
+        ))?;
+        let rows = stmt.query_map(params![source_path], row_to_session)?;
+        rows.collect()
+    }
+
+    /// Get a session's project and the path it was last synced from.
+    pub fn get_session_source(&self, session_id: &str) -> Result<Option<(String, Option<String>)>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT project, source_path FROM sessions WHERE session_id = ?1",
+            params![session_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+    }
+
+    /// Record that a session's source file moved, along with its new project.
+    pub fn move_session(&self, session_id: &str, source_path: &str, project: &str) -> Result<()> {
+        let conn = self.write_conn()?;
+        conn.execute(
+            "UPDATE sessions SET source_path = ?2, project = ?3 WHERE session_id = ?1",
+            params![session_id, source_path, project],
+        )?;
+        Ok(())
+    }
+
+    /// Move every session of project `from` into project `into`, returning
+    /// how many sessions moved.
+    pub fn merge_projects(&self, from: &str, into: &str) -> Result<usize> {
+        let conn = self.write_conn()?;
+        conn.execute(
+            "UPDATE sessions SET project = ?2 WHERE project = ?1",
+            params![from, into],
+        )
+    }
+
+    /// Set the project of each (session_id, project) pair in one transaction,
+    /// returning how many sessions actually changed.
+    pub fn update_projects(&self, projects: &[(String, String)]) -> Result<usize> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        let mut changed = 0;
+        {
+            let mut stmt = tx.prepare(
+                "UPDATE sessions SET project = ?2 WHERE session_id = ?1 AND project != ?2",
+            )?;
+            for (session_id, project) in projects {
+                changed += stmt.execute(params![session_id, project])?;
+            }
+        }
+        tx.commit()?;
+        Ok(changed)
+    }
+
+    /// Get the IDs of all sessions synced from a machine.
+    pub fn get_session_ids(&self, machine: &str) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT session_id FROM sessions WHERE machine = ?1")?;
+        let rows = stmt.query_map(params![machine], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    /// Delete a session along with its messages and tool calls.
+    ///
+    /// Returns whether the session existed.
+    pub fn delete_session(&self, session_id: &str) -> Result<bool> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        delete_message_rows(&tx, session_id)?;
+        tx.execute("DELETE FROM session_tags WHERE session_id = ?1", params![session_id])?;
+        tx.execute("DELETE FROM size_samples WHERE session_id = ?1", params![session_id])?;
+        let deleted = tx.execute("DELETE FROM sessions WHERE session_id = ?1", params![session_id])?;
+        tx.commit()?;
+        Ok(deleted > 0)
+    }
+
+    /// Replace a session's metadata and messages, returning whether it is new.
+    /// `raw` holds the original JSON of messages as (msg_id, json) pairs, and
+    /// the session's file size, if known, is recorded as a size sample.
+    ///
+    /// Everything is written in one transaction, so e.g. a watcher sync and
+    /// a manual sync can't interleave their delete and insert, and a failed
+    /// insert leaves the previous messages in place. A session whose
+    /// messages were purged only has its metadata updated.
+    pub fn replace_session(
+        &self,
+        session: &Session,
+        messages: &[Message],
+        raw: &[(String, String)],
+    ) -> Result<bool> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        let is_new = !session_row_exists(&tx, &session.session_id)?;
+        delete_message_rows(&tx, &session.session_id)?;
+        if session_purged(&tx, &session.session_id)? {
+            upsert_session_row(&tx, &Session { content_purged: true, ..session.clone() })?;
+            write_sync_extras(&tx, session, &[])?;
+        } else {
+            upsert_session_row(&tx, session)?;
+            insert_message_rows(&tx, messages)?;
+            write_sync_extras(&tx, session, raw)?;
+        }
+        tx.commit()?;
+        Ok(is_new)
+    }
+
+    /// Update a session's metadata and add messages parsed from lines appended
+    /// to its source file from `from_offset` on, keeping the messages already
+    /// stored. Like `replace_session`, this stores `raw`, samples the file
+    /// size and leaves a purged session's messages out, all in one
+    /// transaction.
+    ///
+    /// Returns false, writing nothing, if the session's stored offset is no
+    /// longer `from_offset` because another sync got there first.
+    pub fn append_session(
+        &self,
+        session: &Session,
+        messages: &[Message],
+        raw: &[(String, String)],
+        from_offset: i64,
+    ) -> Result<bool> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        let stored_offset: Option<i64> = tx
+            .query_row(
+                "SELECT parsed_offset FROM sessions WHERE session_id = ?1",
+                params![session.session_id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        if stored_offset != Some(from_offset) {
+            return Ok(false);
+        }
+        if session_purged(&tx, &session.session_id)? {
+            upsert_session_row(&tx, &Session { content_purged: true, ..session.clone() })?;
+            write_sync_extras(&tx, session, &[])?;
+        } else {
+            upsert_session_row(&tx, session)?;
+            insert_message_rows(&tx, messages)?;
+            write_sync_extras(&tx, session, raw)?;
+        }
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// Get what a parse of a session's appended lines continues from: the
+    /// number of stored messages, the next tool call position, the stored
+    /// message uuids and the API response ids already stored.
+    pub fn get_append_point(
+        &self,
+        session_id: &str,
+    ) -> Result<(usize, i32, Vec<String>, Vec<String>)> {
+        let conn = self.conn.lock().unwrap();
+        let message_count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM messages WHERE session_id = ?1",
+            params![session_id],
+            |row| row.get(0),
+        )?;
+        let tool_seq: i32 = conn.query_row(
+            "SELECT COALESCE(MAX(seq) + 1, 0) FROM tool_calls WHERE session_id = ?1",
+            params![session_id],
+            |row| row.get(0),
+        )?;
+        let mut stmt = conn.prepare(
+            "SELECT uuid FROM messages WHERE session_id = ?1 AND uuid IS NOT NULL",
+        )?;
+        let uuids = stmt
+            .query_map(params![session_id], |row| row.get(0))?
+            .collect::<Result<Vec<String>>>()?;
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT response_id FROM messages
+             WHERE session_id = ?1 AND response_id IS NOT NULL",
+        )?;
+        let response_ids = stmt
+            .query_map(params![session_id], |row| row.get(0))?
+            .collect::<Result<Vec<String>>>()?;
+        Ok((message_count as usize, tool_seq, uuids, response_ids))
+    }
+
+    /// Store an imported session with its messages, tags and star, returning
+    /// whether it was written. An existing session with the same id is
+    /// replaced only when `overwrite` is set.
+    pub fn import_session(
+        &self,
+        session: &Session,
+        messages: &[Message],
+        overwrite: bool,
+    ) -> Result<bool> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        if !overwrite && session_row_exists(&tx, &session.session_id)? {
+            return Ok(false);
+        }
+        upsert_session_row(&tx, session)?;
+        tx.execute(
+            "UPDATE sessions SET starred = ?2 WHERE session_id = ?1",
+            params![session.session_id, session.starred],
+        )?;
+        tx.execute("DELETE FROM session_tags WHERE session_id = ?1", params![session.session_id])?;
+        for tag in &session.tags {
+            tx.execute(
+                "INSERT OR IGNORE INTO session_tags (session_id, tag) VALUES (?1, ?2)",
+                params![session.session_id, tag],
+            )?;
+        }
+        delete_message_rows(&tx, &session.session_id)?;
+        insert_message_rows(&tx, messages)?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// Delete the messages of sessions that ended before `before`, keeping
+    /// their metadata and marking them `content_purged`. Returns the number of
+    /// sessions purged.
+    pub fn purge_old_messages(&self, before: &str) -> Result<usize> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            "CREATE TEMP TABLE purge_ids AS
+             SELECT session_id FROM sessions
+             WHERE julianday(ended_at) < julianday(?1) AND COALESCE(content_purged, 0) = 0",
+            params![before],
+        )?;
+        tx.execute(
+            "DELETE FROM messages WHERE session_id IN (SELECT session_id FROM purge_ids)",
+            [],
+        )?;
+        tx.execute(
+            "DELETE FROM tool_calls WHERE session_id IN (SELECT session_id FROM purge_ids)",
+            [],
+        )?;
+        tx.execute(
+            "DELETE FROM messages_raw WHERE session_id IN (SELECT session_id FROM purge_ids)",
+            [],
+        )?;
+        let purged = tx.execute(
+            "UPDATE sessions SET content_purged = 1
+             WHERE session_id IN (SELECT session_id FROM purge_ids)",
+            [],
+        )?;
+        tx.execute("DROP TABLE purge_ids", [])?;
+
+        tx.commit()?;
+        Ok(purged)
+    }
+
+    /// Merge sessions from another viewer database into this one.
+    ///
+    /// Sessions missing here are copied; on a session_id collision the copy
+    /// with the later `ended_at` wins and replaces this one's messages. Stars
+    /// and tags from either database are kept. The other machine's source
+    /// file state isn't copied, so a copied session is parsed in full at its
+    /// next sync. The other database must have the current schema. Returns
+    /// the number of sessions copied.
+    pub fn merge_database(&self, other_path: &Path) -> Result<usize> {
+        let mut conn = self.write_conn()?;
+        conn.execute(
+            "ATTACH DATABASE ?1 AS other",
+            params![other_path.to_string_lossy()],
+        )?;
+
+        let merged = (|| {
+            let other = Some(DatabaseName::Attached("other"));
+            let version: i64 = conn.pragma_query_value(other, "user_version", |row| row.get(0))?;
+            if version != MIGRATIONS.len() as i64 {
+                return Err(rusqlite::Error::SqliteFailure(
+                    rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISMATCH),
+                    Some(format!(
+                        "cannot merge a database at schema version {version}; version {} is \
+                         required, so open it with this viewer first",
+                        MIGRATIONS.len()
+                    )),
+                ));
+            }
+            let tx = conn.transaction()?;
+            tx.execute(
+                "CREATE TEMP TABLE merge_ids AS
+                 SELECT o.session_id FROM other.sessions o
+                 LEFT JOIN main.sessions m ON m.session_id = o.session_id
+                 WHERE m.session_id IS NULL
+                    OR COALESCE(julianday(o.ended_at), 0) > COALESCE(julianday(m.ended_at), 0)",
+                [],
+            )?;
+            // Deleting through main.messages keeps the FTS index in step via triggers
+            tx.execute(
+                "DELETE FROM main.messages WHERE session_id IN (SELECT session_id FROM merge_ids)",
+                [],
+            )?;
+            tx.execute(
+                "DELETE FROM main.tool_calls
+                 WHERE session_id IN (SELECT session_id FROM merge_ids)",
+                [],
+            )?;
+            tx.execute(
+                "DELETE FROM main.messages_raw
+                 WHERE session_id IN (SELECT session_id FROM merge_ids)",
+                [],
+            )?;
+            let merged = tx.execute(
+                "INSERT INTO main.sessions (session_id, project, machine, first_message,
+                                            started_at, ended_at, message_count, file_size,
+                                            file_hash, agent, git_branch, parent_session_id,
+                                            content_purged, reasoning_effort, cwd,
+                                            file_mtime, model, total_input_tokens,
+                                            total_output_tokens, summary, starred,
+                                            source_path)
+                 SELECT session_id, project, machine, first_message, started_at, ended_at,
+                        message_count, NULL, NULL, agent, git_branch,
+                        parent_session_id, content_purged, reasoning_effort, cwd, NULL,
+                        model, total_input_tokens, total_output_tokens, summary,
+                        COALESCE(starred, 0), NULL
+                 FROM other.sessions
+                 WHERE session_id IN (SELECT session_id FROM merge_ids)
+                 ON CONFLICT(session_id) DO UPDATE SET
+                     project = excluded.project,
+                     machine = excluded.machine,
+                     first_message = excluded.first_message,
+                     started_at = excluded.started_at,
+                     ended_at = excluded.ended_at,
+                     message_count = excluded.message_count,
+                     file_size = excluded.file_size,
+                     file_hash = excluded.file_hash,
+                     agent = excluded.agent,
+                     git_branch = excluded.git_branch,
+                     parent_session_id = excluded.parent_session_id,
+                     content_purged = excluded.content_purged,
+                     reasoning_effort = excluded.reasoning_effort,
+                     cwd = excluded.cwd,
+                     file_mtime = excluded.file_mtime,
+                     model = excluded.model,
+                     total_input_tokens = excluded.total_input_tokens,
+                     total_output_tokens = excluded.total_output_tokens,
+                     summary = excluded.summary,
+                     starred = COALESCE(sessions.starred, 0) OR excluded.starred,
+                     source_path = excluded.source_path,
+                     parsed_offset = NULL",
+                [],
+            )?;
+            tx.execute(
+                "UPDATE main.sessions SET starred = 1
+                 WHERE session_id IN (SELECT session_id FROM other.sessions WHERE starred)",
+                [],
+            )?;
+            tx.execute(
+                "INSERT OR IGNORE INTO main.session_tags (session_id, tag)
+                 SELECT session_id, tag FROM other.session_tags
+                 WHERE session_id IN (SELECT session_id FROM main.sessions)",
+                [],
+            )?;
+            tx.execute(
+                "INSERT INTO main.messages (session_id, msg_id, role, content, timestamp,
+                                            is_sidechain, repeat_count, uuid, parent_uuid,
+                                            response_id, ordinal, is_tool_only)
+                 SELECT session_id, msg_id, role, content, timestamp, is_sidechain,
+                        repeat_count, uuid, parent_uuid, response_id, ordinal, is_tool_only
+                 FROM other.messages
+                 WHERE session_id IN (SELECT session_id FROM merge_ids)
+                 ORDER BY id",
+                [],
+            )?;
+            tx.execute(
+                "INSERT INTO main.tool_calls (session_id, msg_id, seq, tool_name, summary,
+                                              tool_input_json)
+                 SELECT session_id, msg_id, seq, tool_name, summary, tool_input_json
+                 FROM other.tool_calls
+                 WHERE session_id IN (SELECT session_id FROM merge_ids)
+                 ORDER BY id",
+                [],
+            )?;
+            tx.execute(
+                "INSERT INTO main.messages_raw (session_id, msg_id, raw_json)
+                 SELECT session_id, msg_id, raw_json FROM other.messages_raw
+                 WHERE session_id IN (SELECT session_id FROM merge_ids)",
+                [],
+            )?;
+            tx.execute("DROP TABLE merge_ids", [])?;
+            tx.commit()?;
+            Ok(merged)
+        })();
+
+        conn.execute("DETACH DATABASE other", [])?;
+        merged
+    }
+
+    /// Get the original JSON of a message, if it was stored.
+    pub fn get_raw_message(&self, session_id: &str, msg_id: &str) -> Result<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT raw_json FROM messages_raw WHERE session_id = ?1 AND msg_id = ?2",
+            params![session_id, msg_id],
+            |row| row.get(0),
+        )
+        .optional()
+    }
+
+    /// Get file info for incremental sync check: size, hash and, when
+    /// recorded, modification time.
+    pub fn get_session_file_info(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<(i64, String, Option<i64>)>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT file_size, file_hash, file_mtime FROM sessions WHERE session_id = ?1",
+        )?;
+
+        let result = stmt.query_row(params![session_id], |row| {
+            let size: Option<i64> = row.get(0)?;
+            let hash: Option<String> = row.get(1)?;
+            let mtime: Option<i64> = row.get(2)?;
+            Ok(size.zip(hash).map(|(size, hash)| (size, hash, mtime)))
+        });
+
+        match result {
+            Ok(Some(info)) => Ok(Some(info)),
+            Ok(None) => Ok(None),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Record a new modification time for a session's unchanged source file.
+    pub fn set_file_mtime(&self, session_id: &str, mtime: i64) -> Result<()> {
+        let conn = self.write_conn()?;
+        conn.execute(
+            "UPDATE sessions SET file_mtime = ?2 WHERE session_id = ?1",
+            params![session_id, mtime],
+        )?;
+        Ok(())
+    }
+
+    /// Get list of unique projects.
+    pub fn get_projects(&self) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT project FROM sessions ORDER BY project",
+        )?;
+
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    /// Get session, message, and project totals with per-agent and
+    /// per-project breakdowns.
+    pub fn get_stats(&self) -> Result<Stats> {
+        let conn = self.conn.lock().unwrap();
+
+        let (session_count, message_count, project_count, earliest, latest) = conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(message_count), 0), COUNT(DISTINCT project),
+                    MIN(started_at), MAX(started_at)
+             FROM sessions
+             WHERE message_count > 0",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+        )?;
+
+        let grouped = |column: &str| -> Result<Vec<(String, i64)>> {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT COALESCE({column}, ''), COUNT(*)
+                 FROM sessions
+                 WHERE message_count > 0
+                 GROUP BY 1
+                 ORDER BY 2 DESC, 1"
+            ))?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect()
+        };
+
+        Ok(Stats {
+            session_count,
+            message_count,
+            project_count,
+            by_agent: grouped("agent")?,
+            by_project: grouped("project")?,
+            earliest_started_at: earliest,
+            latest_started_at: latest,
+        })
+    }
+
+    /// Export sessions with their messages in session_id order, starting after
+    /// `after_session_id`.
+    ///
+    /// Each batch takes the connection lock only briefly per session, so a
+    /// client can drive a large export without blocking syncs for long.
+    pub fn export_batch(
+        &self,
+        after_session_id: Option<&str>,
+        batch_size: i32,
+    ) -> Result<ExportBatch> {
+        let batch_size = batch_size.clamp(1, MAX_EXPORT_BATCH);
+
+        let mut sessions = {
+            let conn = self.conn.lock().unwrap();
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {SESSION_COLUMNS}
+                 FROM sessions
+                 WHERE ?1 IS NULL OR session_id > ?1
+                 ORDER BY session_id
+                 LIMIT ?2"
+            ))?;
+            // Fetch one extra row to learn whether another batch exists
+            let rows = stmt.query_map(params![after_session_id, batch_size + 1], row_to_session)?;
+            rows.collect::<Result<Vec<_>>>()?
+        };
+
+        let has_more = sessions.len() > batch_size as usize;
+        sessions.truncate(batch_size as usize);
+        let next = if has_more {
+            sessions.last().map(|s| s.session_id.clone())
+        } else {
+            None
+        };
+
+        let sessions = sessions
+            .into_iter()
+            .map(|session| {
+                let messages = self.get_messages_with_tools(&session.session_id, None, None)?;
+                Ok(ExportSession { session, messages })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(ExportBatch { sessions, next })
+    }
+
+    /// Get every session of a project with its messages, oldest first.
+    pub fn export_project(&self, project: &str) -> Result<Vec<ExportSession>> {
+        let sessions = {
+            let conn = self.conn.lock().unwrap();
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {SESSION_COLUMNS}
+                 FROM sessions
+                 WHERE project = ?1
+                 ORDER BY started_at, session_id"
+            ))?;
+            let rows = stmt.query_map(params![project], row_to_session)?;
+            rows.collect::<Result<Vec<_>>>()?
+        };
+
+        sessions
+            .into_iter()
+            .map(|session| {
+                let messages = self.get_messages_with_tools(&session.session_id, None, None)?;
+                Ok(ExportSession { session, messages })
+            })
+            .collect()
+    }
+
+    /// Get the latest user prompt of each project, most recently active first.
+    pub fn get_last_prompts_by_project(&self, limit: i32) -> Result<Vec<LastPrompt>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT project, session_id, msg_id, content, timestamp
+            FROM (
+                SELECT s.project, m.session_id, m.msg_id, m.content, m.timestamp,
+                       ROW_NUMBER() OVER (
+                           PARTITION BY s.project ORDER BY m.timestamp DESC, m.id DESC
+                       ) AS rn
+                FROM messages m
+                JOIN sessions s ON m.session_id = s.session_id
+                WHERE m.role = 'user'
+                  AND COALESCE(m.is_sidechain, 0) = 0
+                  AND COALESCE(s.message_count, 0) > 0
+            )
+            WHERE rn = 1
+            ORDER BY timestamp DESC
+            LIMIT ?1
+            "#,
+        )?;
+        let rows = stmt.query_map(params![limit], |row| {
+            let content: String = row.get(3)?;
+            let content = if content.chars().count() > MAX_LAST_PROMPT {
+                let cut: String = content.chars().take(MAX_LAST_PROMPT).collect();
+                format!("{}...", cut)
+            } else {
+                content
+            };
+            Ok(LastPrompt {
+                project: row.get(0)?,
+                session_id: row.get(1)?,
+                msg_id: row.get(2)?,
+                content,
+                timestamp: row.get(4)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Project daily growth from sessions sampled within `active_window` of `now`.
+    ///
+    /// This is a heuristic: each active session's rate is its size change
+    /// between its oldest and newest retained samples, and the projection
+    /// assumes those sessions keep growing at that rate. Source file bytes
+    /// stand in for database bytes.
+    pub fn estimate_growth(
+        &self,
+        now: chrono::DateTime<chrono::Utc>,
+        active_window: chrono::Duration,
+    ) -> Result<GrowthEstimate> {
+        let samples: Vec<(String, i64, String)> = {
+            let conn = self.conn.lock().unwrap();
+            let mut stmt = conn.prepare(
+                "SELECT session_id, file_size, sampled_at
+                 FROM size_samples
+                 ORDER BY session_id, sampled_at",
+            )?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+            rows.collect::<Result<_>>()?
+        };
+
+        let mut by_session: HashMap<&str, Vec<(i64, chrono::DateTime<chrono::Utc>)>> =
+            HashMap::new();
+        for (session_id, size, sampled_at) in &samples {
+            if let Ok(at) = chrono::DateTime::parse_from_rfc3339(sampled_at) {
+                by_session
+                    .entry(session_id)
+                    .or_default()
+                    .push((*size, at.with_timezone(&chrono::Utc)));
+            }
+        }
+
+        let mut estimate = GrowthEstimate {
+            active_sessions: 0,
+            bytes_per_day: 0.0,
+        };
+        for points in by_session.values() {
+            let (Some(first), Some(last)) = (points.first(), points.last()) else {
+                continue;
+            };
+            if now - last.1 > active_window {
+                continue;
+            }
+            estimate.active_sessions += 1;
+
+            let elapsed = (last.1 - first.1).num_seconds();
+            if elapsed > 0 && last.0 > first.0 {
+                estimate.bytes_per_day += (last.0 - first.0) as f64 * 86_400.0 / elapsed as f64;
+            }
+        }
+        Ok(estimate)
+    }
+
+    /// Record that a session was opened, clearing its unread state.
+    pub fn mark_session_read(&self, session_id: &str, now: &str) -> Result<()> {
+        let conn = self.write_conn()?;
+        conn.execute(
+            "UPDATE sessions SET last_viewed_at = ?2 WHERE session_id = ?1",
+            params![session_id, now],
+        )?;
+        Ok(())
+    }
+
+    /// Star or unstar a session. Returns false if the session doesn't exist.
+    pub fn set_session_starred(&self, session_id: &str, starred: bool) -> Result<bool> {
+        let conn = self.write_conn()?;
+        let updated = conn.execute(
+            "UPDATE sessions SET starred = ?2 WHERE session_id = ?1",
+            params![session_id, starred],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Get sessions never opened whose last activity is before `cutoff`,
+    /// oldest first, as cleanup candidates.
+    pub fn get_stale_unread(&self, cutoff: &str) -> Result<Vec<Session>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SESSION_COLUMNS}
+             FROM sessions
+             WHERE COALESCE(message_count, 0) > 0
+               AND last_viewed_at IS NULL
+               AND julianday(COALESCE(ended_at, started_at)) < julianday(?1)
+             ORDER BY COALESCE(ended_at, started_at) ASC"
+        ))?;
+        let rows = stmt.query_map(params![cutoff], row_to_session)?;
+        rows.collect()
+    }
+
+    /// Tag a session. Tagging twice with the same tag is a no-op.
+    pub fn add_session_tag(&self, session_id: &str, tag: &str) -> Result<()> {
+        let conn = self.write_conn()?;
+        conn.execute(
+            "INSERT OR IGNORE INTO session_tags (session_id, tag) VALUES (?1, ?2)",
+            params![session_id, tag],
+        )?;
+        Ok(())
+    }
+
+    /// Remove a tag from a session, returning whether it was present.
+    pub fn remove_session_tag(&self, session_id: &str, tag: &str) -> Result<bool> {
+        let conn = self.write_conn()?;
+        let removed = conn.execute(
+            "DELETE FROM session_tags WHERE session_id = ?1 AND tag = ?2",
+            params![session_id, tag],
+        )?;
+        Ok(removed > 0)
+    }
+
+    /// Get a session's tags in name order.
+    pub fn get_session_tags(&self, session_id: &str) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare("SELECT tag FROM session_tags WHERE session_id = ?1 ORDER BY tag")?;
+        let rows = stmt.query_map(params![session_id], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    /// Get every tag with the number of sessions carrying it, most used first.
+    pub fn get_tags_with_counts(&self) -> Result<Vec<(String, i64)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT tag, COUNT(*) AS n
+             FROM session_tags
+             GROUP BY tag
+             ORDER BY n DESC, tag ASC",
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    struct TestDb {
+        db: Database,
+        _dir: TempDir,  // Keep tempdir alive
+    }
+
+    fn create_test_db() -> TestDb {
+        let dir = TempDir::new().unwrap();
+        let db_path = dir.path().join("test.db");
+        let db = Database::open_with(&db_path, &DbConfig::default()).unwrap();
+        TestDb { db, _dir: dir }
+    }
+
+    /// Sessions in the default order, optionally only one project's.
+    fn list_sessions(db: &Database, project: Option<&str>, limit: i32) -> Vec<Session> {
+        let filter = SessionFilter {
+            project: project.map(String::from),
+            ..Default::default()
+        };
+        db.get_sessions_filtered(&filter, limit, SessionSort::default()).unwrap()
+    }
+
+    /// Write a session row directly, leaving its messages alone.
+    fn upsert_session(db: &Database, session: &Session) -> Result<()> {
+        let conn = db.write_conn()?;
+        upsert_session_row(&conn, session)
+    }
+
+    /// Add messages in one transaction, leaving the session's others alone.
+    fn insert_messages(db: &Database, messages: &[Message]) -> Result<()> {
+        let mut conn = db.write_conn()?;
+        let tx = conn.transaction()?;
+        insert_message_rows(&tx, messages)?;
+        tx.commit()
+    }
+
+    fn sample_session(id: &str, project: &str, message_count: i32) -> Session {
+        Session {
+            session_id: id.to_string(),
+            project: project.to_string(),
+            machine: "local".to_string(),
+            first_message: Some("Test message".to_string()),
+            summary: None,
+            started_at: Some("2026-01-08T10:00:00Z".to_string()),
+            ended_at: Some("2026-01-08T11:00:00Z".to_string()),
+            message_count,
+            file_size: Some(1000),
+            file_hash: Some("abc123".to_string()),
+            file_mtime: Some(1_700_000_000_000_000_000),
+            source_path: None,
+            agent: "claude".to_string(),
+            git_branch: None,
+            parent_session_id: None,
+            content_purged: false,
+            reasoning_effort: None,
+            cwd: None,
+            model: None,
+            total_input_tokens: None,
+            total_output_tokens: None,
+            parsed_offset: None,
+            starred: false,
+            tags: Vec::new(),
+            is_new_since_last_open: false,
+        }
+    }
+
+    #[test]
+    fn test_session_index() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 3)).unwrap();
+        upsert_session(db, &sample_session("s2", "project2", 1)).unwrap();
+        upsert_session(db, &sample_session("empty", "project1", 0)).unwrap();
+
+        let index = db.get_session_index().unwrap();
+        let sessions = list_sessions(db, None, 100);
+        assert_eq!(index.len(), sessions.len());
+        assert!(index.iter().all(|e| e.session_id != "empty"));
+
+        let entry = serde_json::to_value(&index[0]).unwrap();
+        let mut keys: Vec<&str> = entry.as_object().unwrap().keys().map(|k| k.as_str()).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, ["first_message", "project", "session_id", "started_at"]);
+    }
+
+    #[test]
+    fn test_migrate_unversioned_database() {
+        let dir = TempDir::new().unwrap();
+        let db_path = dir.path().join("old.db");
+        {
+            let conn = Connection::open(&db_path).unwrap();
+            conn.execute_batch(
+                r#"
+                CREATE TABLE sessions (
+                    session_id TEXT PRIMARY KEY,
+                    project TEXT NOT NULL,
+                    machine TEXT DEFAULT 'local',
+                    first_message TEXT,
+                    started_at TEXT,
+                    ended_at TEXT,
+                    message_count INTEGER DEFAULT 0,
+                    file_size INTEGER,
+                    file_hash TEXT,
+                    agent TEXT DEFAULT 'claude'
+                );
+                CREATE TABLE messages (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    session_id TEXT NOT NULL,
+                    msg_id TEXT NOT NULL,
+                    role TEXT NOT NULL,
+                    content TEXT,
+                    timestamp TEXT
+                );
+                INSERT INTO sessions (session_id, project, first_message, started_at, message_count,
+                                      file_size, file_hash)
+                VALUES ('s1', 'legacy', 'Hello', '2025-01-01T00:00:00Z', 1, 100, 'abc');
+                INSERT INTO messages (session_id, msg_id, role, content, timestamp)
+                VALUES ('s1', 'm1', 'user', 'Hello', '2025-01-01T00:00:00Z');
+                INSERT INTO messages (session_id, msg_id, role, content, timestamp)
+                VALUES ('s1', 'm0', 'user', 'Earlier', '2024-12-31T00:00:00Z');
+                "#,
+            )
+            .unwrap();
+            let version: i64 =
+                conn.pragma_query_value(None, "user_version", |row| row.get(0)).unwrap();
+            assert_eq!(version, 0);
+        }
+
+        let db = Database::open_with(&db_path, &DbConfig::default()).unwrap();
+        {
+            let conn = db.conn.lock().unwrap();
+            let version: i64 =
+                conn.pragma_query_value(None, "user_version", |row| row.get(0)).unwrap();
+            assert_eq!(version, MIGRATIONS.len() as i64);
+
+            let columns = |table: &str| -> Vec<String> {
+                conn.prepare(&format!("PRAGMA table_info({})", table))
+                    .unwrap()
+                    .query_map([], |row| row.get(1))
+                    .unwrap()
+                    .collect::<Result<_>>()
+                    .unwrap()
+            };
+            let session_columns = columns("sessions");
+            for column in ["cwd", "model", "total_input_tokens", "source_path"] {
+                assert!(session_columns.iter().any(|c| c == column), "{}", column);
+            }
+            let message_columns = columns("messages");
+            for column in ["is_sidechain", "uuid", "parent_uuid", "response_id", "is_tool_only"] {
+                assert!(message_columns.iter().any(|c| c == column), "{}", column);
+            }
+            let msg_id_index: i64 = conn
+                .query_row(
+                    "SELECT COUNT(*) FROM sqlite_master WHERE name = 'idx_messages_msg_id'",
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(msg_id_index, 1);
+        }
+
+        let sessions = list_sessions(&db, None, 10);
+        let session = &sessions[0];
+        assert_eq!(session.project, "legacy");
+        assert_eq!(session.first_message.as_deref(), Some("Hello"));
+        assert_eq!(session.model, None);
+        // Claude sessions are parsed again to fill in what older syncs missed
+        assert_eq!(db.get_session_file_info("s1").unwrap(), None);
+        // Stored messages are placed in timestamp order
+        let messages = db.get_messages("s1", None, None).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].content, "Earlier");
+        assert_eq!(db.get_messages("s1", Some(1), Some(1)).unwrap()[0].content, "Hello");
+        // Existing titles are indexed
+        let hits = db.search_all("hello", 10, DEFAULT_TITLE_WEIGHT).unwrap();
+        assert!(hits.iter().any(|hit| hit.kind == HitKind::Title && hit.session_id == "s1"));
+
+        // Reopening an up-to-date database is a no-op
+        drop(db);
+        let db = Database::open_with(&db_path, &DbConfig::default()).unwrap();
+        assert_eq!(list_sessions(&db, None, 10)[0].project, "legacy");
+    }
+
+    #[test]
+    fn test_starred_sessions() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        let mut older = sample_session("older", "project1", 1);
+        older.started_at = Some("2026-01-01T10:00:00Z".to_string());
+        upsert_session(db, &older).unwrap();
+        upsert_session(db, &sample_session("newer", "project1", 1)).unwrap();
+
+        assert!(db.set_session_starred("older", true).unwrap());
+        assert!(!db.set_session_starred("missing", true).unwrap());
+
+        // A resync rewrites the session but keeps the star
+        older.message_count = 5;
+        upsert_session(db, &older).unwrap();
+
+        let starred_only = SessionFilter {
+            starred_only: true,
+            ..Default::default()
+        };
+        let starred = db.get_sessions_filtered(&starred_only, 10, SessionSort::default()).unwrap();
+        assert_eq!(starred.len(), 1);
+        assert_eq!(starred[0].session_id, "older");
+        assert!(starred[0].starred);
+        assert_eq!(starred[0].message_count, 5);
+
+        let ids = |sort| -> Vec<String> {
+            db.get_sessions_filtered(&SessionFilter::default(), 10, sort)
+                .unwrap()
+                .into_iter()
+                .map(|s| s.session_id)
+                .collect()
+        };
+        assert_eq!(ids(SessionSort::Newest), ["newer", "older"]);
+        assert_eq!(ids(SessionSort::StarredFirst), ["older", "newer"]);
+
+        db.set_session_starred("older", false).unwrap();
+        db.set_session_starred("newer", true).unwrap();
+        assert_eq!(ids(SessionSort::StarredFirst), ["newer", "older"]);
+    }
+
+    #[test]
+    fn test_filters_zero_message_count() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 0)).unwrap();
+        upsert_session(db, &sample_session("s2", "project1", 5)).unwrap();
+
+        let sessions = list_sessions(db, None, 100);
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].session_id, "s2");
+    }
+
+    #[test]
+    fn test_returns_sessions_with_positive_message_count() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 10)).unwrap();
+        upsert_session(db, &sample_session("s2", "project1", 5)).unwrap();
+        upsert_session(db, &sample_session("s3", "project1", 1)).unwrap();
+
+        let sessions = list_sessions(db, None, 100);
+        assert_eq!(sessions.len(), 3);
+    }
+
+    #[test]
+    fn test_filters_by_project() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 5)).unwrap();
+        upsert_session(db, &sample_session("s2", "project2", 5)).unwrap();
+        upsert_session(db, &sample_session("s3", "project1", 3)).unwrap();
+
+        let sessions = list_sessions(db, Some("project1"), 100);
+        assert_eq!(sessions.len(), 2);
+        assert!(sessions.iter().all(|s| s.project == "project1"));
+    }
+
+    #[test]
+    fn test_respects_limit() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        for i in 0..10 {
+            upsert_session(db, &sample_session(&format!("s{}", i), "project1", 5)).unwrap();
+        }
+
+        let sessions = list_sessions(db, None, 3);
+        assert_eq!(sessions.len(), 3);
+    }
+
+    #[test]
+    fn test_flag_sessions_new_since_last_open() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+
+        assert_eq!(db.record_open("2026-01-08T12:00:00Z").unwrap(), None);
+        let previous = db.record_open("2026-01-10T09:00:00+00:00").unwrap();
+        assert_eq!(previous.as_deref(), Some("2026-01-08T12:00:00Z"));
+        assert_eq!(
+            db.get_meta("last_open_at").unwrap().as_deref(),
+            Some("2026-01-10T09:00:00+00:00")
+        );
+
+        let mut old = sample_session("old", "project1", 5);
+        old.started_at = Some("2026-01-08T10:00:00Z".to_string());
+        let mut newer = sample_session("newer", "project1", 5);
+        newer.started_at = Some("2026-01-09T10:00:00+00:00".to_string());
+        upsert_session(db, &old).unwrap();
+        upsert_session(db, &newer).unwrap();
+
+        let mut sessions = list_sessions(db, None, 10);
+        flag_new_since(&mut sessions, &previous.unwrap());
+        let flagged: Vec<(&str, bool)> = sessions
+            .iter()
+            .map(|s| (s.session_id.as_str(), s.is_new_since_last_open))
+            .collect();
+        assert_eq!(flagged, vec![("newer", true), ("old", false)]);
+    }
+
+    #[test]
+    fn test_get_session_tree() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+
+        upsert_session(db, &sample_session("root", "project1", 5)).unwrap();
+        for (id, started) in [("fork_b", "2026-01-09T10:00:00Z"), ("fork_a", "2026-01-08T12:00:00Z")] {
+            let mut fork = sample_session(id, "project1", 5);
+            fork.parent_session_id = Some("root".to_string());
+            fork.started_at = Some(started.to_string());
+            upsert_session(db, &fork).unwrap();
+        }
+
+        let tree = db.get_session_tree("root").unwrap().unwrap();
+        assert_eq!(tree.session.session_id, "root");
+        let children: Vec<&str> = tree
+            .children
+            .iter()
+            .map(|c| c.session.session_id.as_str())
+            .collect();
+        assert_eq!(children, vec!["fork_a", "fork_b"]);
+        assert!(tree.children.iter().all(|c| c.children.is_empty()));
+
+        assert!(db.get_session_tree("missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_get_session_thread() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+
+        upsert_session(db, &sample_session("first", "project1", 5)).unwrap();
+        for (id, parent) in [("second", "first"), ("third", "second"), ("other", "first")] {
+            let mut session = sample_session(id, "project1", 5);
+            session.parent_session_id = Some(parent.to_string());
+            upsert_session(db, &session).unwrap();
+        }
+        let ids = |session_id: &str| -> Vec<String> {
+            db.get_session_thread(session_id)
+                .unwrap()
+                .into_iter()
+                .map(|s| s.session_id)
+                .collect()
+        };
+
+        assert_eq!(ids("third"), ["first", "second", "third"]);
+        assert_eq!(ids("second"), ["first", "second"]);
+        assert_eq!(ids("other"), ["first", "other"]);
+        assert_eq!(ids("first"), ["first"]);
+        assert!(ids("missing").is_empty());
+
+        // A parent that was never synced ends the chain
+        let mut orphan = sample_session("orphan", "project1", 5);
+        orphan.parent_session_id = Some("unsynced".to_string());
+        upsert_session(db, &orphan).unwrap();
+        assert_eq!(ids("orphan"), ["orphan"]);
+
+        // A cycle stops at the first repeated session
+        let mut looped = sample_session("first", "project1", 5);
+        looped.parent_session_id = Some("third".to_string());
+        upsert_session(db, &looped).unwrap();
+        assert_eq!(ids("third"), ["first", "second", "third"]);
+    }
+
+    #[test]
+    fn test_get_session_tree_breaks_cycles() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+
+        let mut a = sample_session("a", "project1", 5);
+        a.parent_session_id = Some("b".to_string());
+        let mut b = sample_session("b", "project1", 5);
+        b.parent_session_id = Some("a".to_string());
+        upsert_session(db, &a).unwrap();
+        upsert_session(db, &b).unwrap();
+
+        let tree = db.get_session_tree("a").unwrap().unwrap();
+        assert_eq!(tree.children.len(), 1);
+        assert_eq!(tree.children[0].session.session_id, "b");
+        assert!(tree.children[0].children.is_empty());
+    }
+
+    #[test]
+    fn test_get_sessions_by_branch() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+
+        for (id, branch, started) in [
+            ("m1", Some("main"), "2026-01-08T10:00:00Z"),
+            ("f1", Some("feature"), "2026-01-08T11:00:00Z"),
+            ("m2", Some("main"), "2026-01-09T10:00:00Z"),
+            ("n1", None, "2026-01-09T12:00:00Z"),
+        ] {
+            let mut session = sample_session(id, "project1", 5);
+            session.git_branch = branch.map(String::from);
+            session.started_at = Some(started.to_string());
+            upsert_session(db, &session).unwrap();
+        }
+        let mut other = sample_session("o1", "project2", 5);
+        other.git_branch = Some("main".to_string());
+        upsert_session(db, &other).unwrap();
+
+        let groups: Vec<(String, Vec<String>)> = db
+            .get_sessions_by_branch("project1")
+            .unwrap()
+            .into_iter()
+            .map(|(branch, sessions)| {
+                (branch, sessions.into_iter().map(|s| s.session_id).collect())
+            })
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                ("feature".to_string(), vec!["f1".to_string()]),
+                ("main".to_string(), vec!["m2".to_string(), "m1".to_string()]),
+                ("unknown".to_string(), vec!["n1".to_string()]),
+            ]
+        );
+
+        let on_main = SessionFilter {
+            project: Some("project1".to_string()),
+            git_branch: Some("main".to_string()),
+            ..Default::default()
+        };
+        let ids: Vec<String> = db
+            .get_sessions_filtered(&on_main, 100, SessionSort::default())
+            .unwrap()
+            .into_iter()
+            .map(|s| s.session_id)
+            .collect();
+        assert_eq!(ids, ["m2", "m1"]);
+    }
+
+    #[test]
+    fn test_filter_sessions_by_tool_usage() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+
+        for (id, tool) in [("s1", "Read"), ("s2", "Bash"), ("s3", "Edit")] {
+            upsert_session(db, &sample_session(id, "project1", 1)).unwrap();
+            insert_messages(db, &[Message {
+                msg_id: format!("{}-m1", id),
+                session_id: id.to_string(),
+                role: "assistant".to_string(),
+                content: format!("[{}]", tool),
+                timestamp: "2026-01-08T10:00:00Z".to_string(),
+                is_sidechain: false,
+                tool_calls: Some(vec![ToolCall {
+                    session_id: id.to_string(),
+                    msg_id: format!("{}-m1", id),
+                    seq: 0,
+                    tool_name: tool.to_string(),
+                    summary: format!("[{}]", tool),
+                    tool_input_json: None,
+                }]),
+                ..Default::default()
+            }])
+            .unwrap();
+        }
+        upsert_session(db, &sample_session("s4", "project1", 1)).unwrap();
+
+        let filter = SessionFilter {
+            uses_tool: Some("Bash".to_string()),
+            ..Default::default()
+        };
+        let sessions = db
+            .get_sessions_filtered(&filter, 100, SessionSort::default())
+            .unwrap();
+        let ids: Vec<&str> = sessions.iter().map(|s| s.session_id.as_str()).collect();
+        assert_eq!(ids, vec!["s2"]);
+
+        let all = db
+            .get_sessions_filtered(&SessionFilter::default(), 100, SessionSort::default())
+            .unwrap();
+        assert_eq!(all.len(), 4);
+    }
+
+    #[test]
+    fn test_get_stats() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+
+        let empty = db.get_stats().unwrap();
+        assert_eq!(empty.session_count, 0);
+        assert_eq!(empty.earliest_started_at, None);
+
+        let seeds = [
+            ("c1", "app", "claude", 3, "2026-01-05T10:00:00Z"),
+            ("c2", "app", "claude", 2, "2026-01-07T10:00:00Z"),
+            ("x1", "tools", "codex", 4, "2026-01-06T10:00:00Z"),
+            // Empty sessions are left out entirely
+            ("e1", "other", "codex", 0, "2026-01-01T10:00:00Z"),
+        ];
+        for (id, project, agent, count, started) in seeds {
+            let mut session = sample_session(id, project, count);
+            session.agent = agent.to_string();
+            session.started_at = Some(started.to_string());
+            upsert_session(db, &session).unwrap();
+        }
+
+        let stats = db.get_stats().unwrap();
+        assert_eq!(stats.session_count, 3);
+        assert_eq!(stats.message_count, 9);
+        assert_eq!(stats.project_count, 2);
+        assert_eq!(
+            stats.by_agent,
+            vec![("claude".to_string(), 2), ("codex".to_string(), 1)]
+        );
+        assert_eq!(
+            stats.by_project,
+            vec![("app".to_string(), 2), ("tools".to_string(), 1)]
+        );
+        assert_eq!(stats.earliest_started_at.as_deref(), Some("2026-01-05T10:00:00Z"));
+        assert_eq!(stats.latest_started_at.as_deref(), Some("2026-01-07T10:00:00Z"));
+    }
+
+    #[test]
+    fn test_get_sessions_filtered_by_model() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+
+        for (id, model) in [("s1", Some("claude-opus-4-1")), ("s2", Some("gpt-5")), ("s3", None)] {
+            let mut session = sample_session(id, "project1", 1);
+            session.model = model.map(String::from);
+            upsert_session(db, &session).unwrap();
+        }
+
+        let filter = SessionFilter {
+            model: Some("Opus".to_string()),
+            ..Default::default()
+        };
+        let sessions = db
+            .get_sessions_filtered(&filter, 100, SessionSort::default())
+            .unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].session_id, "s1");
+        assert_eq!(sessions[0].model.as_deref(), Some("claude-opus-4-1"));
+    }
+
+    #[test]
+    fn test_sort_by_last_activity() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+
+        // Started first but still active
+        let mut long_running = sample_session("long", "project1", 5);
+        long_running.started_at = Some("2026-01-01T09:00:00Z".to_string());
+        long_running.ended_at = Some("2026-01-08T18:00:00Z".to_string());
+        long_running.total_input_tokens = Some(100);
+        long_running.total_output_tokens = Some(50);
+        upsert_session(db, &long_running).unwrap();
+
+        let mut recent = sample_session("recent", "project1", 12);
+        recent.started_at = Some("2026-01-08T10:00:00Z".to_string());
+        recent.ended_at = Some("2026-01-08T11:00:00Z".to_string());
+        upsert_session(db, &recent).unwrap();
+
+        let mut no_end = sample_session("no_end", "project1", 3);
+        no_end.started_at = Some("2026-01-09T10:00:00Z".to_string());
+        no_end.ended_at = None;
+        no_end.total_input_tokens = Some(1000);
+        upsert_session(db, &no_end).unwrap();
+
+        let ids = |sort| -> Vec<String> {
+            db.get_sessions_filtered(&SessionFilter::default(), 100, sort)
+                .unwrap()
+                .into_iter()
+                .map(|s| s.session_id)
+                .collect()
+        };
+        assert_eq!(ids(SessionSort::Newest), vec!["no_end", "recent", "long"]);
+        assert_eq!(ids(SessionSort::RecentActivity), vec!["long", "recent", "no_end"]);
+        assert_eq!(ids(SessionSort::MostTokens), vec!["no_end", "long", "recent"]);
+        assert_eq!(ids(SessionSort::Oldest), vec!["long", "recent", "no_end"]);
+        assert_eq!(ids(SessionSort::MostMessages), vec!["recent", "long", "no_end"]);
+        assert_eq!(ids(SessionSort::Longest), vec!["long", "recent", "no_end"]);
+
+        let parsed: Vec<SessionSort> =
+            serde_json::from_str(r#"["started_asc", "messages_desc", "duration_desc"]"#).unwrap();
+        assert_eq!(
+            parsed,
+            [SessionSort::Oldest, SessionSort::MostMessages, SessionSort::Longest]
+        );
+        // Anything outside the allowlist is rejected before reaching SQL
+        let injected = r#""started_at; DROP TABLE sessions""#;
+        assert!(serde_json::from_str::<SessionSort>(injected).is_err());
+
+        let sessions = list_sessions(db, None, 100);
+        let long = sessions.iter().find(|s| s.session_id == "long").unwrap();
+        assert_eq!((long.total_input_tokens, long.total_output_tokens), (Some(100), Some(50)));
+    }
+
+    #[test]
+    fn test_get_random_sessions() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        for i in 0..10 {
+            upsert_session(db, &sample_session(&format!("s{}", i), "project1", 5)).unwrap();
+        }
+        upsert_session(db, &sample_session("empty", "project1", 0)).unwrap();
+        upsert_session(db, &sample_session("other", "project2", 5)).unwrap();
+
+        let sessions = db.get_random_sessions(4, None).unwrap();
+        assert_eq!(sessions.len(), 4);
+        assert!(sessions.iter().all(|s| s.message_count > 0));
+
+        let sessions = db.get_random_sessions(100, Some("project1")).unwrap();
+        assert_eq!(sessions.len(), 10);
+        assert!(sessions.iter().all(|s| s.project == "project1" && s.message_count > 0));
+    }
+
+    #[test]
+    fn test_empty_database() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        let sessions = list_sessions(db, None, 100);
+        assert!(sessions.is_empty());
+    }
+
+    #[test]
+    fn test_insert_and_get_messages() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 2)).unwrap();
+
+        let messages = vec![
+            Message {
+                msg_id: "m1".to_string(),
+                session_id: "s1".to_string(),
+                role: "user".to_string(),
+                content: "Hello".to_string(),
+                timestamp: "2026-01-08T10:00:00Z".to_string(),
+                is_sidechain: false,
+                tool_calls: None,
+                ..Default::default()
+            },
+            Message {
+                msg_id: "m2".to_string(),
+                session_id: "s1".to_string(),
+                role: "assistant".to_string(),
+                content: "Hi there".to_string(),
+                timestamp: "2026-01-08T10:01:00Z".to_string(),
+                is_sidechain: false,
+                tool_calls: None,
+                ..Default::default()
+            },
+        ];
+        insert_messages(db, &messages).unwrap();
+
+        let retrieved = db.get_messages("s1", None, None).unwrap();
+        assert_eq!(retrieved.len(), 2);
+        assert_eq!(retrieved[0].content, "Hello");
+        assert_eq!(retrieved[1].content, "Hi there");
+    }
+
+    #[test]
+    fn test_get_messages_paginated() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 5)).unwrap();
+        let messages: Vec<Message> = (0..5)
+            .map(|i| Message {
+                msg_id: format!("m{}", i),
+                session_id: "s1".to_string(),
+                role: "user".to_string(),
+                content: format!("Message {}", i),
+                timestamp: format!("2026-01-08T10:0{}:00Z", i),
+                ..Default::default()
+            })
+            .collect();
+        insert_messages(db, &messages).unwrap();
+        assert_eq!(db.count_messages("s1").unwrap(), 5);
+        assert_eq!(db.count_messages("missing").unwrap(), 0);
+
+        let ids = |offset, limit| -> Vec<String> {
+            db.get_messages("s1", offset, limit)
+                .unwrap()
+                .into_iter()
+                .map(|m| m.msg_id)
+                .collect()
+        };
+        assert_eq!(ids(Some(1), Some(2)), ["m1", "m2"]);
+        assert_eq!(ids(Some(3), None), ["m3", "m4"]);
+        assert_eq!(ids(None, Some(2)), ["m0", "m1"]);
+        assert_eq!(ids(Some(4), Some(10)), ["m4"]);
+        assert!(ids(Some(5), Some(2)).is_empty());
+        assert!(ids(Some(100), None).is_empty());
+        assert!(ids(None, Some(0)).is_empty());
+        assert_eq!(ids(None, None).len(), 5);
+
+        let structured = db.get_messages_with_tools("s1", Some(2), Some(1)).unwrap();
+        assert_eq!(structured.len(), 1);
+        assert_eq!(structured[0].msg_id, "m2");
+    }
+
+    #[test]
+    fn test_get_messages_follows_parent_links() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 4)).unwrap();
+
+        // All four share a timestamp and were stored out of thread order
+        let message = |id: &str, parent: Option<&str>| Message {
+            msg_id: id.to_string(),
+            session_id: "s1".to_string(),
+            role: "user".to_string(),
+            content: id.to_string(),
+            timestamp: "2026-01-08T10:00:00Z".to_string(),
+            uuid: Some(id.to_string()),
+            parent_uuid: parent.map(String::from),
+            ..Default::default()
+        };
+        insert_messages(db, &[
+            message("reply", Some("question")),
+            message("followup", Some("reply")),
+            message("question", None),
+            message("answer", Some("followup")),
+        ])
+        .unwrap();
+
+        let ids = |offset, limit| -> Vec<String> {
+            db.get_messages("s1", offset, limit)
+                .unwrap()
+                .into_iter()
+                .map(|m| m.msg_id)
+                .collect()
+        };
+        assert_eq!(ids(None, None), ["question", "reply", "followup", "answer"]);
+        assert_eq!(ids(Some(1), Some(2)), ["reply", "followup"]);
+
+        // Without links, timestamp ties keep insertion order
+        upsert_session(db, &sample_session("s2", "project1", 2)).unwrap();
+        let unlinked = |id: &str| Message {
+            session_id: "s2".to_string(),
+            ..message(id, None)
+        };
+        insert_messages(db, &[unlinked("first"), unlinked("second")]).unwrap();
+        let ids: Vec<String> = db
+            .get_messages("s2", None, None)
+            .unwrap()
+            .into_iter()
+            .map(|m| m.msg_id)
+            .collect();
+        assert_eq!(ids, ["first", "second"]);
+    }
+
+    #[test]
+    fn test_get_messages_with_tools() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 2)).unwrap();
+
+        let messages = vec![
+            Message {
+                msg_id: "m1".to_string(),
+                session_id: "s1".to_string(),
+                role: "user".to_string(),
+                content: "Run the tests".to_string(),
+                timestamp: "2026-01-08T10:00:00Z".to_string(),
+                is_sidechain: false,
+                tool_calls: None,
+                ..Default::default()
+            },
+            Message {
+                msg_id: "m2".to_string(),
+                session_id: "s1".to_string(),
+                role: "assistant".to_string(),
+                content: "[Bash: Run tests]\n$ cargo test".to_string(),
+                timestamp: "2026-01-08T10:01:00Z".to_string(),
+                is_sidechain: false,
+                tool_calls: Some(vec![ToolCall {
+                    session_id: "s1".to_string(),
+                    msg_id: "m2".to_string(),
+                    seq: 0,
+                    tool_name: "Bash".to_string(),
+                    summary: "[Bash: Run tests]\n$ cargo test".to_string(),
+                    tool_input_json: None,
+                }]),
+                ..Default::default()
+            },
+        ];
+        insert_messages(db, &messages).unwrap();
+
+        let plain = db.get_messages("s1", None, None).unwrap();
+        assert!(plain.iter().all(|m| m.tool_calls.is_none()));
+
+        let structured = db.get_messages_with_tools("s1", None, None).unwrap();
+        assert_eq!(structured.len(), 2);
+        assert!(structured[0].tool_calls.as_ref().unwrap().is_empty());
+        let calls = structured[1].tool_calls.as_ref().unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].tool_name, "Bash");
+        assert!(calls[0].summary.contains("$ cargo test"));
+
+        db.replace_session(&sample_session("s1", "project1", 1), &messages[..1], &[])
+            .unwrap();
+        let structured = db.get_messages_with_tools("s1", None, None).unwrap();
+        assert!(structured[0].tool_calls.as_ref().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_get_tool_call_detail() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 1)).unwrap();
+
+        let input = r#"{"file_path":"/src/lib.rs","old_string":"fn old() {}","new_string":"fn new() {}"}"#;
+        let messages = vec![Message {
+            msg_id: "m1".to_string(),
+            session_id: "s1".to_string(),
+            role: "assistant".to_string(),
+            content: "[Edit: /src/lib.rs]".to_string(),
+            timestamp: "2026-01-08T10:00:00Z".to_string(),
+            is_sidechain: false,
+            tool_calls: Some(vec![ToolCall {
+                session_id: "s1".to_string(),
+                msg_id: "m1".to_string(),
+                seq: 0,
+                tool_name: "Edit".to_string(),
+                summary: "[Edit: /src/lib.rs]".to_string(),
+                tool_input_json: Some(input.to_string()),
+            }]),
+            ..Default::default()
+        }];
+        insert_messages(db, &messages).unwrap();
+
+        let detail = db.get_tool_call_detail("s1", 0).unwrap().unwrap();
+        assert_eq!(detail.tool_name, "Edit");
+        let parsed: serde_json::Value =
+            serde_json::from_str(detail.tool_input_json.as_deref().unwrap()).unwrap();
+        assert_eq!(parsed["old_string"], "fn old() {}");
+        assert_eq!(parsed["new_string"], "fn new() {}");
+
+        assert!(db.get_tool_call_detail("s1", 1).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_get_activity_feed() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 3)).unwrap();
+
+        let calls = [
+            ("m1", "Read", r#"{"file_path":"/src/main.rs"}"#),
+            ("m2", "Edit", r#"{"file_path":"/src/main.rs","old_string":"a","new_string":"b"}"#),
+            ("m3", "Bash", r#"{"command":"cargo test","description":"Run tests"}"#),
+        ];
+        let messages: Vec<Message> = calls
+            .iter()
+            .enumerate()
+            .map(|(i, (msg_id, tool, input))| Message {
+                msg_id: msg_id.to_string(),
+                session_id: "s1".to_string(),
+                role: "assistant".to_string(),
+                content: format!("Prose before [{}]", tool),
+                timestamp: format!("2026-01-08T10:00:{:02}Z", i),
+                is_sidechain: false,
+                tool_calls: Some(vec![ToolCall {
+                    session_id: "s1".to_string(),
+                    msg_id: msg_id.to_string(),
+                    seq: i as i32,
+                    tool_name: tool.to_string(),
+                    summary: format!("[{}]", tool),
+                    tool_input_json: Some(input.to_string()),
+                }]),
+                ..Default::default()
+            })
+            .collect();
+        insert_messages(db, &messages).unwrap();
+
+        let feed = db.get_activity_feed("s1").unwrap();
+        let entries: Vec<(&str, Option<&str>)> = feed
+            .iter()
+            .map(|e| (e.tool_name.as_str(), e.target.as_deref()))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                ("Read", Some("/src/main.rs")),
+                ("Edit", Some("/src/main.rs")),
+                ("Bash", Some("cargo test")),
+            ]
+        );
+        assert_eq!(feed[2].timestamp, "2026-01-08T10:00:02Z");
+        assert!(db.get_activity_feed("missing").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_concurrent_replace_session() {
+        let test_db = create_test_db();
+        let db = Arc::new(test_db.db);
+
+        let handles: Vec<_> = (0..8)
+            .map(|writer| {
+                let db = Arc::clone(&db);
+                std::thread::spawn(move || {
+                    let session = sample_session("s1", "project1", 10);
+                    let messages: Vec<Message> = (0..10)
+                        .map(|i| Message {
+                            msg_id: format!("m{}", i),
+                            session_id: "s1".to_string(),
+                            role: "user".to_string(),
+                            content: format!("writer {} message {}", writer, i),
+                            timestamp: format!("2026-01-08T10:00:{:02}Z", i),
+                            is_sidechain: false,
+                            tool_calls: None,
+                            ..Default::default()
+                        })
+                        .collect();
+                    for _ in 0..20 {
+                        db.replace_session(&session, &messages, &[]).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // Exactly one writer's full message set survives
+        let messages = db.get_messages("s1", None, None).unwrap();
+        assert_eq!(messages.len(), 10);
+        let writer = messages[0].content.split(' ').nth(1).unwrap().to_string();
+        assert!(messages
+            .iter()
+            .all(|m| m.content.starts_with(&format!("writer {} ", writer))));
+        assert_eq!(db.search("message", 100, &SearchOptions::default()).unwrap().len(), 10);
+    }
+
+    #[test]
+    fn test_purge_old_messages() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+
+        let mut old = sample_session("old", "project1", 1);
+        old.ended_at = Some("2025-06-01T10:00:00Z".to_string());
+        upsert_session(db, &old).unwrap();
+        upsert_session(db, &sample_session("recent", "project1", 1)).unwrap();
+
+        let messages: Vec<Message> = ["old", "recent"]
+            .iter()
+            .map(|id| Message {
+                msg_id: format!("{}-m1", id),
+                session_id: id.to_string(),
+                role: "user".to_string(),
+                content: "Secret deployment token".to_string(),
+                timestamp: "2026-01-08T10:00:00Z".to_string(),
+                is_sidechain: false,
+                tool_calls: None,
+                ..Default::default()
+            })
+            .collect();
+        insert_messages(db, &messages).unwrap();
+
+        assert_eq!(db.purge_old_messages("2026-01-01").unwrap(), 1);
+        assert_eq!(db.purge_old_messages("2026-01-01").unwrap(), 0);
+
+        let sessions = list_sessions(db, None, 10);
+        let old = sessions.iter().find(|s| s.session_id == "old").unwrap();
+        assert!(old.content_purged);
+        assert_eq!(old.first_message.as_deref(), Some("Test message"));
+        assert_eq!(old.message_count, 1);
+        assert!(db.get_messages("old", None, None).unwrap().is_empty());
+
+        let recent = sessions.iter().find(|s| s.session_id == "recent").unwrap();
+        assert!(!recent.content_purged);
+        assert_eq!(db.get_messages("recent", None, None).unwrap().len(), 1);
+
+        let results = db.search("deployment", 10, &SearchOptions::default()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].session_id, "recent");
+
+        // Reparsing a purged session updates its metadata but not its messages
+        let mut reparsed = sample_session("old", "project1", 2);
+        reparsed.ended_at = Some("2025-06-01T10:00:00Z".to_string());
+        reparsed.parsed_offset = Some(100);
+        db.replace_session(&reparsed, &messages[..1], &[]).unwrap();
+        assert!(db.append_session(&reparsed, &messages[..1], &[], 100).unwrap());
+        let old = db.get_session("old").unwrap().unwrap();
+        assert!(old.content_purged);
+        assert_eq!(old.message_count, 2);
+        assert!(db.get_messages("old", None, None).unwrap().is_empty());
+        let results = db.search("deployment", 10, &SearchOptions::default()).unwrap();
+        assert_eq!(results.len(), 1);
+    }
+
+    #[test]
+    fn test_purge_old_messages_compares_instants() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+
+        // The cutoff is 2025-12-31T19:00:00Z
+        let ends = [("before", "2025-12-31T18:30:00Z"), ("after", "2025-12-31T20:00:00Z")];
+        for (id, ended_at) in ends {
+            let mut session = sample_session(id, "project1", 1);
+            session.ended_at = Some(ended_at.to_string());
+            upsert_session(db, &session).unwrap();
+        }
+
+        assert_eq!(db.purge_old_messages("2026-01-01T00:00:00+05:00").unwrap(), 1);
+        let purged = |id: &str| db.get_session(id).unwrap().unwrap().content_purged;
+        assert!(purged("before"));
+        assert!(!purged("after"));
+    }
+
+    #[test]
+    fn test_get_response_latencies() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 7)).unwrap();
+
+        let messages: Vec<Message> = [
+            ("m1", "user", "2026-01-08T10:00:00Z"),
+            ("m2", "assistant", "2026-01-08T10:00:05.500Z"),
+            ("m3", "assistant", "2026-01-08T10:00:30Z"),
+            ("m4", "user", "2026-01-08T10:01:00Z"),
+            ("m5", "assistant", "2026-01-08T10:03:00Z"),
+            ("m6", "user", "2026-01-08T10:04:00Z"),
+            ("m7", "assistant", ""),
+        ]
+        .iter()
+        .map(|(id, role, ts)| Message {
+            msg_id: id.to_string(),
+            session_id: "s1".to_string(),
+            role: role.to_string(),
+            content: "text".to_string(),
+            timestamp: ts.to_string(),
+            is_sidechain: false,
+            tool_calls: None,
+            ..Default::default()
+        })
+        .collect();
+        insert_messages(db, &messages).unwrap();
+
+        let gaps: Vec<(String, String, f64)> = db
+            .get_response_latencies("s1")
+            .unwrap()
+            .into_iter()
+            .map(|l| (l.user_msg_id, l.assistant_msg_id, l.seconds))
+            .collect();
+        assert_eq!(
+            gaps,
+            vec![
+                ("m1".to_string(), "m2".to_string(), 5.5),
+                ("m4".to_string(), "m5".to_string(), 120.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_message_role_counts() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 4)).unwrap();
+
+        let messages: Vec<Message> = ["user", "assistant", "assistant", "system"]
+            .iter()
+            .enumerate()
+            .map(|(i, role)| Message {
+                msg_id: format!("m{}", i),
+                session_id: "s1".to_string(),
+                role: role.to_string(),
+                content: format!("Message {}", i),
+                timestamp: format!("2026-01-08T10:0{}:00Z", i),
+                is_sidechain: false,
+                tool_calls: None,
+                ..Default::default()
+            })
+            .collect();
+        insert_messages(db, &messages).unwrap();
+
+        assert_eq!(db.get_message_role_counts("s1").unwrap(), (1, 2, 1));
+        assert_eq!(db.get_message_role_counts("missing").unwrap(), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_get_term_frequencies() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 3)).unwrap();
+        upsert_session(db, &sample_session("s2", "project1", 1)).unwrap();
+        upsert_session(db, &sample_session("s3", "project2", 1)).unwrap();
+
+        let msg = |id: &str, session: &str, content: &str| Message {
+            msg_id: id.to_string(),
+            session_id: session.to_string(),
+            role: "user".to_string(),
+            content: content.to_string(),
+            timestamp: "2026-01-08T10:00:00Z".to_string(),
+            is_sidechain: false,
+            tool_calls: None,
+            ..Default::default()
+        };
+        insert_messages(db, &[
+            msg("m1", "s1", "The kubernetes cluster needs a kubernetes upgrade"),
+            msg("m2", "s1", "Deploy the cluster with helm\n[Bash: Deploy]\n$ helm install kubernetes"),
+            msg("m3", "s1", "Kubernetes is fine now"),
+            msg("m4", "s2", "Write the helm chart"),
+            msg("m5", "s3", "Postgres postgres postgres"),
+        ])
+        .unwrap();
+
+        let terms = db
+            .get_term_frequencies(&TermScope::Session("s1".to_string()), 3)
+            .unwrap();
+        assert_eq!(
+            terms,
+            vec![
+                ("kubernetes".to_string(), 3),
+                ("cluster".to_string(), 2),
+                ("deploy".to_string(), 1),
+            ]
+        );
+
+        let terms = db
+            .get_term_frequencies(&TermScope::Project("project1".to_string()), 10)
+            .unwrap();
+        assert!(terms.contains(&("helm".to_string(), 2)));
+        assert!(!terms.iter().any(|(t, _)| t == "postgres" || t == "the"));
+    }
+
+    #[test]
+    fn test_insert_messages_batch_is_atomic() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 3000)).unwrap();
+
+        let batch = |prefix: &str, n: usize| -> Vec<Message> {
+            (0..n)
+                .map(|i| Message {
+                    msg_id: format!("{}-{}", prefix, i),
+                    session_id: "s1".to_string(),
+                    role: "user".to_string(),
+                    content: format!("batch message {}", i),
+                    ..Default::default()
+                })
+                .collect()
+        };
+
+        insert_messages(db, &batch("ok", 3000)).unwrap();
+        assert_eq!(db.get_messages("s1", None, None).unwrap().len(), 3000);
+        assert_eq!(db.search("batch", 10, &SearchOptions::default()).unwrap().len(), 10);
+
+        // A failure partway through rolls back the whole batch
+        db.conn
+            .lock()
+            .unwrap()
+            .execute_batch(
+                "CREATE TEMP TRIGGER fail_insert BEFORE INSERT ON messages
+                 WHEN NEW.msg_id = 'bad-500'
+                 BEGIN SELECT RAISE(ABORT, 'boom'); END;",
+            )
+            .unwrap();
+        assert!(insert_messages(db, &batch("bad", 1000)).is_err());
+        assert_eq!(db.get_messages("s1", None, None).unwrap().len(), 3000);
+        assert!(db.find_sessions_for_message("bad-0").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_term_over_time() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 6)).unwrap();
+
+        let seeded = [
+            ("2026-02-02T10:00:00Z", "kubernetes upgrade"),
+            ("2026-02-20T10:00:00Z", "kubernetes rollback"),
+            ("2026-03-03T10:00:00Z", "kubernetes pods"),
+            ("2026-03-03T12:30:00.500Z", "more kubernetes"),
+            ("2026-03-04T09:00:00+00:00", "kubernetes again"),
+            ("2026-03-05T09:00:00Z", "lunch plans"),
+            ("", "kubernetes without a time"),
+        ];
+        let messages: Vec<Message> = seeded
+            .iter()
+            .enumerate()
+            .map(|(i, (timestamp, content))| Message {
+                msg_id: format!("m{}", i),
+                session_id: "s1".to_string(),
+                role: "user".to_string(),
+                content: content.to_string(),
+                timestamp: timestamp.to_string(),
+                ..Default::default()
+            })
+            .collect();
+        insert_messages(db, &messages).unwrap();
+
+        let months = db.term_over_time("kubernetes", TimeBucket::Month).unwrap();
+        assert_eq!(months, vec![("2026-02".to_string(), 2), ("2026-03".to_string(), 3)]);
+
+        let days = db.term_over_time("kubernetes", TimeBucket::Day).unwrap();
+        assert_eq!(days.len(), 4);
+        assert_eq!(days[2], ("2026-03-03".to_string(), 2));
+
+        let weeks = db.term_over_time("kubernetes", TimeBucket::Week).unwrap();
+        assert_eq!(weeks.iter().map(|(_, n)| n).sum::<i64>(), 5);
+    }
+
+    #[test]
+    fn test_search_date_range() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 4)).unwrap();
+
+        let days = ["2026-01-05", "2026-01-06", "2026-01-07", "2026-01-08"];
+        let messages: Vec<Message> = days
+            .iter()
+            .enumerate()
+            .map(|(i, day)| Message {
+                msg_id: format!("m{}", i),
+                session_id: "s1".to_string(),
+                role: "user".to_string(),
+                content: "Deploying the release".to_string(),
+                timestamp: format!("{}T10:00:00Z", day),
+                ..Default::default()
+            })
+            .collect();
+        insert_messages(db, &messages).unwrap();
+
+        let search = |after: Option<&str>, before: Option<&str>| {
+            let options = SearchOptions {
+                after: after.map(String::from),
+                before: before.map(String::from),
+                ..Default::default()
+            };
+            let mut ids: Vec<String> = db
+                .search("release", 10, &options)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.msg_id)
+                .collect();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(search(None, None).len(), 4);
+        // Both bounds are inclusive
+        assert_eq!(
+            search(Some("2026-01-06T10:00:00Z"), Some("2026-01-07T10:00:00Z")),
+            vec!["m1", "m2"]
+        );
+        assert_eq!(search(Some("2026-01-08T10:00:00Z"), None), vec!["m3"]);
+        assert_eq!(search(None, Some("2026-01-05T10:00:00Z")), vec!["m0"]);
+        assert!(search(Some("2026-01-09T00:00:00Z"), None).is_empty());
+        // Bounds compare as instants, whatever their offset or precision
+        assert_eq!(search(Some("2026-01-08T11:00:00+01:00"), None), vec!["m3"]);
+        assert_eq!(search(None, Some("2026-01-05T10:00:00.000Z")), vec!["m0"]);
+        assert_eq!(search(Some("2026-01-07T09:30:00-00:30"), None), vec!["m2", "m3"]);
+    }
+
+    #[test]
+    fn test_full_text_search() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 2)).unwrap();
+
+        let messages = vec![
+            Message {
+                msg_id: "m1".to_string(),
+                session_id: "s1".to_string(),
+                role: "user".to_string(),
+                content: "How do I implement authentication?".to_string(),
+                timestamp: "2026-01-08T10:00:00Z".to_string(),
+                is_sidechain: false,
+                tool_calls: None,
+                ..Default::default()
+            },
+            Message {
+                msg_id: "m2".to_string(),
+                session_id: "s1".to_string(),
+                role: "assistant".to_string(),
+                content: "You can use JWT tokens for authentication".to_string(),
+                timestamp: "2026-01-08T10:01:00Z".to_string(),
+                is_sidechain: false,
+                tool_calls: None,
+                ..Default::default()
+            },
+        ];
+        insert_messages(db, &messages).unwrap();
+
+        let results = db.search("authentication", 10, &SearchOptions::default()).unwrap();
+        assert_eq!(results.len(), 2);
+
+        let results = db.search("JWT tokens", 10, &SearchOptions::default()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].role, "assistant");
+    }
+
+    #[test]
+    fn test_get_session_timeline_collapses_tool_runs() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 3)).unwrap();
+
+        let tool_turn = |i: usize, names: &[&str]| {
+            let msg_id = format!("m{}", i);
+            let calls: Vec<ToolCall> = names
+                .iter()
+                .enumerate()
+                .map(|(seq, name)| ToolCall {
+                    session_id: "s1".to_string(),
+                    msg_id: msg_id.clone(),
+                    seq: (i * 10 + seq) as i32,
+                    tool_name: name.to_string(),
+                    summary: format!("[{}: src/lib.rs]", name),
+                    tool_input_json: None,
+                })
+                .collect();
+            let content = calls.iter().map(|c| c.summary.clone()).collect::<Vec<_>>().join("\n");
+            Message {
+                msg_id,
+                session_id: "s1".to_string(),
+                role: "assistant".to_string(),
+                content,
+                is_tool_only: true,
+                tool_calls: Some(calls),
+                ..Default::default()
+            }
+        };
+        let prose = |i: usize, role: &str, content: &str| Message {
+            msg_id: format!("m{}", i),
+            session_id: "s1".to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            ..Default::default()
+        };
+
+        insert_messages(db, &[
+            prose(0, "user", "Refactor the parser"),
+            tool_turn(1, &["Read", "Grep"]),
+            tool_turn(2, &["Read"]),
+            tool_turn(3, &["Edit", "Edit"]),
+            prose(4, "assistant", "Done refactoring."),
+            tool_turn(5, &["Bash"]),
+        ])
+        .unwrap();
+
+        let timeline: Vec<(String, String, usize)> = db
+            .get_session_timeline("s1")
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.msg_id, e.role, e.collapsed_tool_calls))
+            .collect();
+        assert_eq!(
+            timeline,
+            vec![
+                ("m0".to_string(), "user".to_string(), 0),
+                ("m1".to_string(), "tool".to_string(), 5),
+                ("m4".to_string(), "assistant".to_string(), 0),
+                ("m5".to_string(), "tool".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_sessions_for_message() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+
+        let message = |session_id: &str, msg_id: &str| Message {
+            msg_id: msg_id.to_string(),
+            session_id: session_id.to_string(),
+            role: "user".to_string(),
+            content: "hello".to_string(),
+            ..Default::default()
+        };
+        for id in ["s1", "s2", "s3"] {
+            upsert_session(db, &sample_session(id, "project1", 1)).unwrap();
+        }
+        insert_messages(db, &[
+            message("s2", "msg-2026-01-08T10-00-00Z-0"),
+            message("s1", "msg-2026-01-08T10-00-00Z-0"),
+            message("s3", "msg-2026-01-08T11-00-00Z-0"),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            db.find_sessions_for_message("msg-2026-01-08T10-00-00Z-0").unwrap(),
+            vec!["s1", "s2"]
+        );
+        assert_eq!(
+            db.find_sessions_for_message("msg-2026-01-08T11-00-00Z-0").unwrap(),
+            vec!["s3"]
+        );
+        assert!(db.find_sessions_for_message("missing").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_get_normalized_messages() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 6)).unwrap();
+
+        let roles = ["user", "assistant", "developer", "tool", "reasoning", "Human", "mystery"];
+        let messages: Vec<Message> = roles
+            .iter()
+            .enumerate()
+            .map(|(i, role)| Message {
+                msg_id: format!("m{}", i),
+                session_id: "s1".to_string(),
+                role: role.to_string(),
+                content: "text".to_string(),
+                ..Default::default()
+            })
+            .collect();
+        insert_messages(db, &messages).unwrap();
+
+        let normalized = db.get_normalized_messages("s1").unwrap();
+        let pairs: Vec<(&str, &str)> = normalized
+            .iter()
+            .map(|m| (m.message.role.as_str(), m.raw_role.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                ("user", "user"),
+                ("assistant", "assistant"),
+                ("system", "developer"),
+                ("tool", "tool"),
+                ("thinking", "reasoning"),
+                ("user", "Human"),
+                ("system", "mystery"),
+            ]
+        );
+
+        let json = serde_json::to_value(&normalized[2]).unwrap();
+        assert_eq!(json["role"], "system");
+        assert_eq!(json["raw_role"], "developer");
+    }
+
+    #[test]
+    fn test_merge_database() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        let other_path = test_db._dir.path().join("other.db");
+        let other = Database::open_with(&other_path, &DbConfig::default()).unwrap();
+
+        let message = |id: &str, content: &str| Message {
+            msg_id: "m0".to_string(),
+            session_id: id.to_string(),
+            role: "user".to_string(),
+            content: content.to_string(),
+            timestamp: "2026-01-08T10:00:00Z".to_string(),
+            is_sidechain: false,
+            tool_calls: None,
+            ..Default::default()
+        };
+
+        let mine = sample_session("mine", "project1", 1);
+        db.replace_session(&mine, &[message("mine", "local only")], &[]).unwrap();
+        let mut older_here = sample_session("shared-old", "project1", 1);
+        older_here.parsed_offset = Some(500);
+        let raw = [("m0".to_string(), "{}".to_string()), ("m1".to_string(), "{}".to_string())];
+        db.replace_session(&older_here, &[message("shared-old", "stale local")], &raw).unwrap();
+        let mut newer_here = sample_session("shared-new", "project1", 1);
+        newer_here.ended_at = Some("2026-02-01T00:00:00Z".to_string());
+        db.replace_session(&newer_here, &[message("shared-new", "fresh local")], &[]).unwrap();
+
+        let theirs = sample_session("theirs", "project2", 1);
+        other.replace_session(&theirs, &[message("theirs", "remote only")], &[]).unwrap();
+        let mut newer_there = sample_session("shared-old", "project1", 1);
+        newer_there.ended_at = Some("2026-03-01T00:00:00Z".to_string());
+        newer_there.summary = Some("Remote summary".to_string());
+        newer_there.source_path = Some("/remote/shared-old.jsonl".to_string());
+        let raw = [("m0".to_string(), r#"{"remote":true}"#.to_string())];
+        let messages = [message("shared-old", "fresh remote")];
+        other.replace_session(&newer_there, &messages, &raw).unwrap();
+        let older_there = sample_session("shared-new", "project1", 1);
+        other.replace_session(&older_there, &[message("shared-new", "stale remote")], &[]).unwrap();
+        other.set_session_starred("theirs", true).unwrap();
+        other.set_session_starred("shared-new", true).unwrap();
+        other.add_session_tag("theirs", "remote").unwrap();
+        other.add_session_tag("shared-new", "remote").unwrap();
+        db.add_session_tag("shared-new", "local").unwrap();
+        drop(other);
+
+        assert_eq!(db.merge_database(&other_path).unwrap(), 2);
+
+        let content = |id: &str| db.get_messages(id, None, None).unwrap()[0].content.clone();
+        assert_eq!(content("mine"), "local only");
+        assert_eq!(content("theirs"), "remote only");
+        assert_eq!(content("shared-old"), "fresh remote");
+        assert_eq!(content("shared-new"), "fresh local");
+        assert_eq!(db.get_messages("shared-old", None, None).unwrap().len(), 1);
+
+        // Stars carry over, even onto a session whose local copy was kept
+        let starred = |id: &str| db.get_session(id).unwrap().unwrap().starred;
+        assert!(starred("theirs"));
+        assert!(starred("shared-new"));
+        assert!(!starred("shared-old"));
+
+        // Tags from both sides are kept
+        assert_eq!(db.get_session_tags("theirs").unwrap(), vec!["remote"]);
+        assert_eq!(db.get_session_tags("shared-new").unwrap(), vec!["local", "remote"]);
+
+        // A replaced session takes the winner's metadata and raw messages, but
+        // none of the other machine's file state
+        let shared = db.get_session("shared-old").unwrap().unwrap();
+        assert_eq!(shared.summary.as_deref(), Some("Remote summary"));
+        assert_eq!(shared.source_path, None);
+        assert_eq!(shared.parsed_offset, None);
+        assert_eq!(db.get_session_file_info("shared-old").unwrap(), None);
+        assert_eq!(db.get_session_file_info("theirs").unwrap(), None);
+        let raw = |msg_id: &str| db.get_raw_message("shared-old", msg_id).unwrap();
+        assert_eq!(raw("m0").as_deref(), Some(r#"{"remote":true}"#));
+        assert_eq!(raw("m1"), None);
+
+        // FTS follows the merged content
+        let hits = |q: &str| db.search(q, 10, &SearchOptions::default()).unwrap().len();
+        assert_eq!(hits("remote"), 2);
+        assert_eq!(hits("stale"), 0);
+
+        // The attachment is released, so merging again is a no-op
+        assert_eq!(db.merge_database(&other_path).unwrap(), 0);
+
+        // A database at an older schema is refused before anything is read
+        let old_path = test_db._dir.path().join("old.db");
+        let old = Connection::open(&old_path).unwrap();
+        old.execute_batch("CREATE TABLE sessions (session_id TEXT); PRAGMA user_version = 3;")
+            .unwrap();
+        drop(old);
+        let err = db.merge_database(&old_path).unwrap_err().to_string();
+        assert!(err.contains("schema version 3"), "{err}");
+        assert_eq!(db.merge_database(&other_path).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_get_stale_unread() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+
+        let mut recent_unread = sample_session("recent-unread", "project1", 3);
+        recent_unread.ended_at = Some("2026-03-01T10:00:00Z".to_string());
+        upsert_session(db, &recent_unread).unwrap();
+        upsert_session(db, &sample_session("old-unread", "project1", 3)).unwrap();
+        upsert_session(db, &sample_session("old-read", "project1", 3)).unwrap();
+        db.mark_session_read("old-read", "2026-01-09T10:00:00Z").unwrap();
+
+        let stale: Vec<String> = db
+            .get_stale_unread("2026-02-01T00:00:00+00:00")
+            .unwrap()
+            .into_iter()
+            .map(|s| s.session_id)
+            .collect();
+        assert_eq!(stale, vec!["old-unread"]);
+
+        // Re-syncing a session keeps its read state
+        upsert_session(db, &sample_session("old-read", "project1", 4)).unwrap();
+        assert_eq!(db.get_stale_unread("2026-02-01T00:00:00Z").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_db_config_applied_on_open() {
+        let dir = TempDir::new().unwrap();
+        let config = DbConfig {
+            cache_size_kb: 8192,
+            mmap_size_mb: 64,
+        };
+        let db = Database::open_with(&dir.path().join("test.db"), &config).unwrap();
+
+        let cache_size: i64 = db
+            .conn
+            .lock()
+            .unwrap()
+            .pragma_query_value(None, "cache_size", |row| row.get(0))
+            .unwrap();
+        assert_eq!(cache_size, -8192);
+        assert_eq!(db.config().unwrap().cache_size_kb, 8192);
+
+        // Out-of-range values fall back to the defaults
+        let applied = db
+            .apply_config(&DbConfig {
+                cache_size_kb: -5,
+                mmap_size_mb: 64,
+            })
+            .unwrap();
+        assert_eq!(applied.cache_size_kb, DEFAULT_CACHE_SIZE_KB);
+    }
+
+    #[test]
+    fn test_search_all_boosts_titles() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+
+        let mut titled = sample_session("titled", "project1", 1);
+        titled.first_message = Some("Migrate the billing service to gRPC".to_string());
+        upsert_session(db, &titled).unwrap();
+        upsert_session(db, &sample_session("buried", "project1", 1)).unwrap();
+        insert_messages(db, &[Message {
+            msg_id: "m0".to_string(),
+            session_id: "buried".to_string(),
+            role: "assistant".to_string(),
+            content: "grpc ".repeat(3)
+                + &"Unrelated discussion about lunch, weather and the weekend. ".repeat(3),
+            ..Default::default()
+        }])
+        .unwrap();
+
+        let hits = db.search_all("grpc", 10, DEFAULT_TITLE_WEIGHT).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].kind, HitKind::Title);
+        assert_eq!(hits[0].session_id, "titled");
+        assert_eq!(hits[0].msg_id, None);
+        assert!(hits[0].snippet.contains("<mark>gRPC</mark>"));
+        assert_eq!(hits[1].kind, HitKind::Message);
+        assert_eq!(hits[1].msg_id.as_deref(), Some("m0"));
+
+        // With the boost turned down, the denser body match wins
+        let hits = db.search_all("grpc", 10, 0.01).unwrap();
+        assert_eq!(hits[0].kind, HitKind::Message);
+
+        // The title index follows session updates and deletes
+        titled.first_message = Some("Something else".to_string());
+        upsert_session(db, &titled).unwrap();
+        assert!(db.search_all("billing", 10, DEFAULT_TITLE_WEIGHT).unwrap().is_empty());
+        db.delete_session("buried").unwrap();
+        assert!(db.search_all("grpc", 10, DEFAULT_TITLE_WEIGHT).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_search_all_titles_survive_rowid_changes() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+
+        for (id, title) in [("first", "Tune the cache"), ("last", "Fix login")] {
+            let mut session = sample_session(id, "project1", 1);
+            session.first_message = Some(title.to_string());
+            upsert_session(db, &session).unwrap();
+        }
+        // Renumber the implicit rowids, as VACUUM is allowed to
+        db.conn
+            .lock()
+            .unwrap()
+            .execute_batch(
+                "UPDATE sessions SET rowid = 3 WHERE session_id = 'first';
+                 UPDATE sessions SET rowid = 1 WHERE session_id = 'last';
+                 UPDATE sessions SET rowid = 2 WHERE session_id = 'first';",
+            )
+            .unwrap();
+
+        let hits = db.search_all("login", 10, DEFAULT_TITLE_WEIGHT).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].session_id, "last");
+        let hits = db.search_all("cache", 10, DEFAULT_TITLE_WEIGHT).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].session_id, "first");
+    }
+
+    #[test]
+    fn test_rank_sessions() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+
+        let contents = [
+            ("weak", "We briefly mentioned kubernetes once in a long discussion about lunch plans and other unrelated topics"),
+            ("strong", "kubernetes kubernetes deployment"),
+            ("strong", "kubernetes cluster upgrade"),
+            ("strong", "kubernetes pods"),
+            ("none", "nothing relevant"),
+        ];
+        for (id, _) in contents {
+            upsert_session(db, &sample_session(id, "project1", 1)).unwrap();
+        }
+        let messages: Vec<Message> = contents
+            .iter()
+            .enumerate()
+            .map(|(i, (id, content))| Message {
+                msg_id: format!("m{}", i),
+                session_id: id.to_string(),
+                role: "user".to_string(),
+                content: content.to_string(),
+                timestamp: "2026-01-08T10:00:00Z".to_string(),
+                is_sidechain: false,
+                tool_calls: None,
+                ..Default::default()
+            })
+            .collect();
+        insert_messages(db, &messages).unwrap();
+
+        let ranked: Vec<String> = db
+            .rank_sessions("kubernetes", 10)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.session_id)
+            .collect();
+        assert_eq!(ranked, vec!["strong", "weak"]);
+
+        // Raw input with FTS syntax characters doesn't fail
+        assert_eq!(db.rank_sessions("kubernetes (", 10).unwrap().len(), 2);
+        assert!(db.search_all("don't -", 10, 1.0).is_ok());
+        assert!(db.search_merged("\"", 10).unwrap().is_empty());
+        assert!(db.term_over_time("(", TimeBucket::Day).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_search_merged_spans_turn() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 5)).unwrap();
+
+        let messages: Vec<Message> = [
+            ("m0", "user", "How do I deploy?"),
+            ("m1", "assistant", "First build the image."),
+            ("m2", "assistant", "Then push it to the kubernetes cluster."),
+            ("m3", "assistant", "Finally check the <rollout>."),
+            ("m4", "user", "Thanks"),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, (id, role, content))| Message {
+            msg_id: id.to_string(),
+            session_id: "s1".to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            timestamp: format!("2026-01-08T10:00:{:02}Z", i),
+            is_sidechain: false,
+            tool_calls: None,
+            ..Default::default()
+        })
+        .collect();
+        insert_messages(db, &messages).unwrap();
+
+        let results = db.search_merged("kubernetes", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        let result = &results[0];
+        assert_eq!(result.role, "assistant");
+        assert_eq!(result.msg_ids, vec!["m1", "m2", "m3"]);
+        assert_eq!(result.match_msg_id, "m2");
+        assert_eq!(result.timestamp, "2026-01-08T10:00:01Z");
+        assert_eq!(
+            result.content,
+            "First build the image.\n\nThen push it to the <mark>kubernetes</mark> cluster.\n\nFinally check the &lt;rollout&gt;."
+        );
+
+        // Hits in two messages of the same turn collapse into one result
+        let results = db.search_merged("the", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].content.matches("<mark>").count(), 3);
+    }
+
+    #[test]
+    fn test_sentence_snippet() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 1)).unwrap();
 
-    /// Get list of unique projects.
-    pub fn get_projects(&self) -> Result<Vec<String>> {
-        let conn = self.conn.lock().unwrap();
+        let content = "First we built the image. Then the deploy pushed it to the kubernetes \
+                       cluster in staging! Finally we checked the rollout.";
+        insert_messages(db, &[Message {
+            msg_id: "m1".to_string(),
+            session_id: "s1".to_string(),
+            role: "assistant".to_string(),
+            content: content.to_string(),
+            timestamp: "2026-01-08T10:00:00Z".to_string(),
+            is_sidechain: false,
+            tool_calls: None,
+            ..Default::default()
+        }])
+        .unwrap();
 
-        let mut stmt = conn.prepare(
-            "SELECT DISTINCT project FROM sessions ORDER BY project",
-        )?;
+        let page = db
+            .search_page(
+                "Kubernetes",
+                10,
+                None,
+                &SearchOptions {
+                    snippet: SnippetStyle::Sentence,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            page.results[0].snippet,
+            "Then the deploy pushed it to the <mark>kubernetes</mark> cluster in staging!"
+        );
 
-        let rows = stmt.query_map([], |row| row.get(0))?;
-        rows.collect()
+        // Long sentences are cut on both sides around the match
+        let long = format!("{} needle {}", "word ".repeat(200), "word ".repeat(200));
+        let snippet = sentence_snippet(&long, "needle").unwrap();
+        assert!(snippet.starts_with("...") && snippet.ends_with("..."));
+        assert!(snippet.contains("<mark>needle</mark>"));
+        assert!(snippet.len() <= MAX_SENTENCE_SNIPPET + "......<mark></mark>".len());
+
+        assert_eq!(sentence_snippet("no match here", "needle"), None);
+        assert_eq!(
+            sentence_snippet("Use <b>needle</b> & co", "needle").unwrap(),
+            "Use &lt;b&gt;<mark>needle</mark>&lt;/b&gt; &amp; co"
+        );
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::TempDir;
+    #[test]
+    fn test_search_snippets_escape_html() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 1)).unwrap();
+        insert_messages(db, &[Message {
+            msg_id: "m1".to_string(),
+            session_id: "s1".to_string(),
+            role: "user".to_string(),
+            content: "<script>alert(1)</script> needle <mark>x</mark>".to_string(),
+            timestamp: "2026-01-08T10:00:00Z".to_string(),
+            ..Default::default()
+        }])
+        .unwrap();
 
-    struct TestDb {
-        db: Database,
-        _dir: TempDir,  // Keep tempdir alive
+        let expected =
+            "&lt;script&gt;alert(1)&lt;/script&gt; <mark>needle</mark> &lt;mark&gt;x&lt;/mark&gt;";
+        let page = db.search_page("needle", 10, None, &SearchOptions::default()).unwrap();
+        assert_eq!(page.results[0].snippet, expected);
+        assert_eq!(page.results[0].snippets, [expected]);
+        let hits = db.search_all("needle", 10, DEFAULT_TITLE_WEIGHT).unwrap();
+        assert!(hits.iter().all(|hit| hit.snippet.contains("&lt;script&gt;")));
     }
 
-    fn create_test_db() -> TestDb {
-        let dir = TempDir::new().unwrap();
-        let db_path = dir.path().join("test.db");
-        let db = Database::open(&db_path).unwrap();
-        TestDb { db, _dir: dir }
+    #[test]
+    fn test_prepare_fts_query() {
+        assert_eq!(
+            prepare_fts_query(r#"foo "bar baz" -qux"#, false),
+            r#""foo" AND "bar baz" NOT "qux""#
+        );
+        assert_eq!(prepare_fts_query("auth* OR login", false), r#""auth"* OR "login""#);
+        assert_eq!(prepare_fts_query("msg_id:abc", false), r#"msg_id:"abc""#);
+        assert_eq!(prepare_fts_query("don't - (", false), r#""don't""#);
+        assert_eq!(prepare_fts_query("AND -foo bar OR", false), r#""bar""#);
+        assert_eq!(prepare_fts_query(r#"say "hello world"#, false), r#""say" AND "hello world""#);
+        assert_eq!(prepare_fts_query("  '  ", false), "");
+        assert_eq!(
+            prepare_fts_query(r#"parse_cla "exact phrase" -skip"#, true),
+            r#""parse_cla"* AND "exact phrase" NOT "skip"*"#
+        );
     }
 
-    fn sample_session(id: &str, project: &str, message_count: i32) -> Session {
-        Session {
-            session_id: id.to_string(),
-            project: project.to_string(),
-            machine: "local".to_string(),
-            first_message: Some("Test message".to_string()),
-            started_at: Some("2026-01-08T10:00:00Z".to_string()),
-            ended_at: Some("2026-01-08T11:00:00Z".to_string()),
-            message_count,
-            file_size: Some(1000),
-            file_hash: Some("abc123".to_string()),
-            agent: "claude".to_string(),
-        }
+    #[test]
+    fn test_search_prefix_mode() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 1)).unwrap();
+        insert_messages(db, &[Message {
+            msg_id: "m1".to_string(),
+            session_id: "s1".to_string(),
+            role: "user".to_string(),
+            content: "Why does parse_claude_session drop sidechains?".to_string(),
+            timestamp: "2026-01-08T10:00:00Z".to_string(),
+            ..Default::default()
+        }])
+        .unwrap();
+
+        let exact = SearchOptions::default();
+        let prefix = SearchOptions {
+            prefix: true,
+            ..Default::default()
+        };
+        let hits = |query: &str, options: &SearchOptions| {
+            db.search(query, 10, options).unwrap().len()
+        };
+        assert_eq!(hits("parse*", &exact), 1);
+        assert_eq!(hits("parse_claude", &exact), 1);
+        assert_eq!(hits("parse_cla", &exact), 0);
+        assert_eq!(hits("parse_cla", &prefix), 1);
+        assert_eq!(hits("pars sidech", &prefix), 1);
+        assert_eq!(hits("pars", &exact), 0);
     }
 
     #[test]
-    fn test_filters_zero_message_count() {
+    fn test_search_in_session() {
         let test_db = create_test_db();
         let db = &test_db.db;
-        db.upsert_session(&sample_session("s1", "project1", 0)).unwrap();
-        db.upsert_session(&sample_session("s2", "project1", 5)).unwrap();
+        let message = |session_id: &str, msg_id: &str, content: &str| Message {
+            msg_id: msg_id.to_string(),
+            session_id: session_id.to_string(),
+            role: "user".to_string(),
+            content: content.to_string(),
+            timestamp: "2026-01-08T10:00:00Z".to_string(),
+            ..Default::default()
+        };
+        for id in ["s1", "s2"] {
+            upsert_session(db, &sample_session(id, "project1", 2)).unwrap();
+        }
+        insert_messages(db, &[
+            message("s1", "m1", "The migration failed on startup"),
+            message("s1", "m2", "Unrelated chatter"),
+            message("s2", "m3", "Another migration question"),
+        ])
+        .unwrap();
 
-        let sessions = db.get_sessions(None, 100).unwrap();
-        assert_eq!(sessions.len(), 1);
-        assert_eq!(sessions[0].session_id, "s2");
+        let results = db.search_in_session("s1", "migration", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!((results[0].session_id.as_str(), results[0].msg_id.as_str()), ("s1", "m1"));
+        assert_eq!(results[0].snippet, "The <mark>migration</mark> failed on startup");
+
+        assert_eq!(db.search("migration", 10, &SearchOptions::default()).unwrap().len(), 2);
+        assert!(db.search_in_session("missing", "migration", 10).unwrap().is_empty());
     }
 
     #[test]
-    fn test_returns_sessions_with_positive_message_count() {
+    fn test_search_by_role() {
         let test_db = create_test_db();
         let db = &test_db.db;
-        db.upsert_session(&sample_session("s1", "project1", 10)).unwrap();
-        db.upsert_session(&sample_session("s2", "project1", 5)).unwrap();
-        db.upsert_session(&sample_session("s3", "project1", 1)).unwrap();
+        upsert_session(db, &sample_session("s1", "project1", 3)).unwrap();
+        let message = |msg_id: &str, role: &str, content: &str| Message {
+            msg_id: msg_id.to_string(),
+            session_id: "s1".to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            timestamp: "2026-01-08T10:00:00Z".to_string(),
+            ..Default::default()
+        };
+        insert_messages(db, &[
+            message("m1", "user", "Why does the migration fail?"),
+            message("m2", "assistant", "The migration fails because the column exists"),
+            message("m3", "user", "Rerun the migration"),
+            message("m4", "human", "Is the migration done?"),
+            message("m5", "model", "The migration is done"),
+        ])
+        .unwrap();
 
-        let sessions = db.get_sessions(None, 100).unwrap();
-        assert_eq!(sessions.len(), 3);
+        let search_role = |role: Option<&str>, limit: i32| -> Vec<String> {
+            let options = SearchOptions {
+                role: role.map(String::from),
+                ..Default::default()
+            };
+            let mut ids: Vec<String> = db
+                .search("migration", limit, &options)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.msg_id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(search_role(None, 10), ["m1", "m2", "m3", "m4", "m5"]);
+        // Roles match through their aliases
+        assert_eq!(search_role(Some("user"), 10), ["m1", "m3", "m4"]);
+        assert_eq!(search_role(Some("assistant"), 10), ["m2", "m5"]);
+        // The limit applies after the role filter
+        assert_eq!(search_role(Some("assistant"), 1).len(), 1);
+        assert_eq!(search_role(Some("user"), 1).len(), 1);
     }
 
     #[test]
-    fn test_filters_by_project() {
+    fn test_search_returns_multiple_snippets() {
         let test_db = create_test_db();
         let db = &test_db.db;
-        db.upsert_session(&sample_session("s1", "project1", 5)).unwrap();
-        db.upsert_session(&sample_session("s2", "project2", 5)).unwrap();
-        db.upsert_session(&sample_session("s3", "project1", 3)).unwrap();
+        upsert_session(db, &sample_session("s1", "project1", 2)).unwrap();
+        let filler = "lorem ipsum dolor sit amet ".repeat(4);
+        let long = format!("First deadlock here. {filler}Second deadlock. {filler}Third deadlock!");
+        let message = |id: &str, content: &str| Message {
+            msg_id: id.to_string(),
+            session_id: "s1".to_string(),
+            role: "assistant".to_string(),
+            content: content.to_string(),
+            timestamp: "2026-01-08T10:00:00Z".to_string(),
+            ..Default::default()
+        };
+        insert_messages(db, &[
+            message("m1", &long),
+            message("m2", "a deadlock and another deadlock"),
+        ])
+        .unwrap();
 
-        let sessions = db.get_sessions(Some("project1"), 100).unwrap();
-        assert_eq!(sessions.len(), 2);
-        assert!(sessions.iter().all(|s| s.project == "project1"));
+        let results = db.search("deadlock", 10, &SearchOptions::default()).unwrap();
+        let snippets = |id: &str| {
+            results.iter().find(|r| r.msg_id == id).unwrap().snippets.clone()
+        };
+
+        let long_snippets = snippets("m1");
+        assert_eq!(long_snippets.len(), 3);
+        assert!(long_snippets[0].starts_with("First <mark>deadlock</mark> here."));
+        assert!(long_snippets[1].starts_with("...") && long_snippets[1].ends_with("..."));
+        assert!(long_snippets[1].contains("Second <mark>deadlock</mark>."));
+        assert!(long_snippets[2].ends_with("Third <mark>deadlock</mark>!"));
+
+        // Nearby matches share one fragment
+        assert_eq!(
+            snippets("m2"),
+            ["a <mark>deadlock</mark> and another <mark>deadlock</mark>"]
+        );
     }
 
     #[test]
-    fn test_respects_limit() {
+    fn test_search_sanitizes_query() {
         let test_db = create_test_db();
         let db = &test_db.db;
-        for i in 0..10 {
-            db.upsert_session(&sample_session(&format!("s{}", i), "project1", 5)).unwrap();
-        }
+        upsert_session(db, &sample_session("s1", "project1", 3)).unwrap();
+        let message = |id: &str, content: &str| Message {
+            msg_id: id.to_string(),
+            session_id: "s1".to_string(),
+            role: "user".to_string(),
+            content: content.to_string(),
+            timestamp: "2026-01-08T10:00:00Z".to_string(),
+            ..Default::default()
+        };
+        insert_messages(db, &[
+            message("m1", "foo then bar baz"),
+            message("m2", "foo then bar baz and qux"),
+            message("m3", "foo then baz bar"),
+        ])
+        .unwrap();
 
-        let sessions = db.get_sessions(None, 3).unwrap();
-        assert_eq!(sessions.len(), 3);
+        let ids = |query: &str| -> Vec<String> {
+            let mut ids: Vec<String> = db
+                .search(query, 10, &SearchOptions::default())
+                .unwrap()
+                .into_iter()
+                .map(|r| r.msg_id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(ids(r#"foo "bar baz" -qux"#), ["m1"]);
+        assert_eq!(ids(r#"foo "bar baz""#), ["m1", "m2"]);
+        assert_eq!(ids("qux OR baz"), ["m1", "m2", "m3"]);
+        assert_eq!(ids("foo bar"), ["m1", "m2", "m3"]);
+        for query in ["it's", "-", "\"", "(foo", "NEAR(", "foo AND", "'"] {
+            assert!(db.search(query, 10, &SearchOptions::default()).is_ok(), "{}", query);
+        }
     }
 
     #[test]
-    fn test_empty_database() {
+    fn test_search_snippet_column() {
         let test_db = create_test_db();
         let db = &test_db.db;
-        let sessions = db.get_sessions(None, 100).unwrap();
-        assert!(sessions.is_empty());
+        upsert_session(db, &sample_session("s1", "project1", 1)).unwrap();
+        insert_messages(db, &[Message {
+            msg_id: "needle-msg".to_string(),
+            session_id: "s1".to_string(),
+            role: "user".to_string(),
+            content: "Nothing to see here".to_string(),
+            timestamp: "2026-01-08T10:00:00Z".to_string(),
+            is_sidechain: false,
+            tool_calls: None,
+            ..Default::default()
+        }])
+        .unwrap();
+
+        let msg_id_snippet = SearchOptions {
+            snippet_column: 1,
+            ..Default::default()
+        };
+        let page = db.search_page("msg_id:needle", 10, None, &msg_id_snippet).unwrap();
+        assert_eq!(page.results[0].snippet, "<mark>needle</mark>-msg");
+
+        let page = db
+            .search_page("msg_id:needle", 10, None, &SearchOptions::default())
+            .unwrap();
+        assert_eq!(page.results[0].snippet, "Nothing to see here");
+
+        let out_of_range = SearchOptions {
+            snippet_column: FTS_COLUMNS.len(),
+            ..Default::default()
+        };
+        assert!(db.search_page("needle", 10, None, &out_of_range).is_err());
     }
 
     #[test]
-    fn test_insert_and_get_messages() {
+    fn test_search_page_cursor() {
         let test_db = create_test_db();
         let db = &test_db.db;
-        db.upsert_session(&sample_session("s1", "project1", 2)).unwrap();
+        upsert_session(db, &sample_session("s1", "project1", 50)).unwrap();
 
-        let messages = vec![
-            Message {
-                msg_id: "m1".to_string(),
+        // Vary the filler so ranks differ, with repeats to force rank ties
+        let messages: Vec<Message> = (0..50)
+            .map(|i| Message {
+                msg_id: format!("m{}", i),
                 session_id: "s1".to_string(),
                 role: "user".to_string(),
-                content: "Hello".to_string(),
-                timestamp: "2026-01-08T10:00:00Z".to_string(),
-            },
-            Message {
-                msg_id: "m2".to_string(),
-                session_id: "s1".to_string(),
-                role: "assistant".to_string(),
-                content: "Hi there".to_string(),
-                timestamp: "2026-01-08T10:01:00Z".to_string(),
-            },
-        ];
-        db.insert_messages(&messages).unwrap();
+                content: format!("needle {}", "hay ".repeat(i % 7)),
+                timestamp: format!("2026-01-08T10:00:{:02}Z", i),
+                is_sidechain: false,
+                tool_calls: None,
+                ..Default::default()
+            })
+            .collect();
+        insert_messages(db, &messages).unwrap();
 
-        let retrieved = db.get_messages("s1").unwrap();
-        assert_eq!(retrieved.len(), 2);
-        assert_eq!(retrieved[0].content, "Hello");
-        assert_eq!(retrieved[1].content, "Hi there");
+        let mut seen = std::collections::HashSet::new();
+        let mut after: Option<String> = None;
+        let mut pages = 0;
+        loop {
+            let page = db
+                .search_page("needle", 10, after.as_deref(), &SearchOptions::default())
+                .unwrap();
+            assert!(page.results.len() <= 10);
+            for result in &page.results {
+                assert!(seen.insert(result.msg_id.clone()), "duplicate {}", result.msg_id);
+            }
+            pages += 1;
+            match page.next {
+                Some(token) => after = Some(token),
+                None => break,
+            }
+        }
+
+        assert_eq!(pages, 5);
+        assert_eq!(seen.len(), 50);
+
+        // An oversized page is capped, and reports the size it applied
+        let page = db.search_page("needle", 1000, None, &SearchOptions::default()).unwrap();
+        assert_eq!(page.limit, MAX_SEARCH_LIMIT);
+        assert_eq!(page.results.len(), 50);
+        assert!(db
+            .search_page("needle", 10, Some("garbage"), &SearchOptions::default())
+            .is_err());
     }
 
     #[test]
-    fn test_full_text_search() {
+    fn test_verify_search_integrity_detects_and_repairs_drift() {
         let test_db = create_test_db();
         let db = &test_db.db;
-        db.upsert_session(&sample_session("s1", "project1", 2)).unwrap();
+        upsert_session(db, &sample_session("s1", "project1", 2)).unwrap();
 
         let messages = vec![
             Message {
                 msg_id: "m1".to_string(),
                 session_id: "s1".to_string(),
                 role: "user".to_string(),
-                content: "How do I implement authentication?".to_string(),
+                content: "Where is the kubernetes config?".to_string(),
                 timestamp: "2026-01-08T10:00:00Z".to_string(),
+                is_sidechain: false,
+                tool_calls: None,
+                ..Default::default()
             },
             Message {
                 msg_id: "m2".to_string(),
                 session_id: "s1".to_string(),
                 role: "assistant".to_string(),
-                content: "You can use JWT tokens for authentication".to_string(),
+                content: "It lives in the deploy directory".to_string(),
                 timestamp: "2026-01-08T10:01:00Z".to_string(),
+                is_sidechain: false,
+                tool_calls: None,
+                ..Default::default()
             },
         ];
-        db.insert_messages(&messages).unwrap();
+        insert_messages(db, &messages).unwrap();
 
-        let results = db.search("authentication", 10).unwrap();
-        assert_eq!(results.len(), 2);
+        let report = db.verify_search_integrity(false).unwrap();
+        assert!(!report.drift);
+        assert_eq!(report.message_count, 2);
+        assert_eq!(report.indexed_count, 2);
 
-        let results = db.search("JWT tokens", 10).unwrap();
-        assert_eq!(results.len(), 1);
-        assert_eq!(results[0].role, "assistant");
+        // Drop one message from the index behind the triggers' back
+        {
+            let conn = db.conn.lock().unwrap();
+            conn.execute(
+                "INSERT INTO messages_fts(messages_fts, rowid, content, msg_id, session_id)
+                 SELECT 'delete', id, content, msg_id, session_id FROM messages WHERE msg_id = 'm1'",
+                [],
+            )
+            .unwrap();
+        }
+        assert!(db.search("kubernetes", 10, &SearchOptions::default()).unwrap().is_empty());
+
+        let report = db.verify_search_integrity(false).unwrap();
+        assert!(report.drift);
+        assert!(!report.repaired);
+        assert_eq!(report.indexed_count, 1);
+        assert_eq!(report.missing_from_index, 1);
+
+        let report = db.verify_search_integrity(true).unwrap();
+        assert!(report.repaired);
+        assert_eq!(report.indexed_count, 2);
+        assert_eq!(db.search("kubernetes", 10, &SearchOptions::default()).unwrap().len(), 1);
+        assert!(!db.verify_search_integrity(false).unwrap().drift);
+    }
+
+    #[test]
+    fn test_rebuild_fts_after_corruption() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 2)).unwrap();
+        let message = |id: &str, content: &str| Message {
+            msg_id: id.to_string(),
+            session_id: "s1".to_string(),
+            role: "user".to_string(),
+            content: content.to_string(),
+            timestamp: "2026-01-08T10:00:00Z".to_string(),
+            ..Default::default()
+        };
+        insert_messages(db, &[
+            message("m1", "The kubernetes manifest"),
+            message("m2", "A terraform module"),
+        ])
+        .unwrap();
+
+        // Wipe the index's term data, as an interrupted write might
+        {
+            let conn = db.conn.lock().unwrap();
+            conn.execute("DELETE FROM messages_fts_data WHERE id > 10", []).unwrap();
+        }
+        assert!(!matches!(
+            db.search("kubernetes", 10, &SearchOptions::default()).as_deref(),
+            Ok([_])
+        ));
+
+        db.rebuild_fts().unwrap();
+        db.optimize_fts().unwrap();
+        for term in ["kubernetes", "terraform"] {
+            assert_eq!(db.search(term, 10, &SearchOptions::default()).unwrap().len(), 1);
+        }
+        assert!(!db.verify_search_integrity(false).unwrap().drift);
     }
 
     #[test]
-    fn test_delete_session_messages() {
+    fn test_replace_session_clears_messages() {
         let test_db = create_test_db();
         let db = &test_db.db;
-        db.upsert_session(&sample_session("s1", "project1", 1)).unwrap();
+        upsert_session(db, &sample_session("s1", "project1", 1)).unwrap();
 
         let messages = vec![Message {
             msg_id: "m1".to_string(),
@@ -486,26 +5616,101 @@ mod tests {
             role: "user".to_string(),
             content: "Test".to_string(),
             timestamp: "2026-01-08T10:00:00Z".to_string(),
+            is_sidechain: false,
+            tool_calls: None,
+            ..Default::default()
         }];
-        db.insert_messages(&messages).unwrap();
+        insert_messages(db, &messages).unwrap();
+
+        assert_eq!(db.get_messages("s1", None, None).unwrap().len(), 1);
+
+        db.replace_session(&sample_session("s1", "project1", 0), &[], &[]).unwrap();
+        assert_eq!(db.get_messages("s1", None, None).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_delete_session_clears_rows_and_index() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 1)).unwrap();
+        upsert_session(db, &sample_session("s2", "project1", 1)).unwrap();
+        let message = |session_id: &str| Message {
+            msg_id: "m1".to_string(),
+            session_id: session_id.to_string(),
+            role: "user".to_string(),
+            content: "Rotate the leaked credentials".to_string(),
+            timestamp: "2026-01-08T10:00:00Z".to_string(),
+            ..Default::default()
+        };
+        insert_messages(db, &[message("s1"), message("s2")]).unwrap();
+        assert_eq!(db.search("credentials", 10, &SearchOptions::default()).unwrap().len(), 2);
+
+        assert!(db.delete_session("s1").unwrap());
+        assert!(db.get_session("s1").unwrap().is_none());
+        assert!(db.get_messages("s1", None, None).unwrap().is_empty());
+        let results = db.search("credentials", 10, &SearchOptions::default()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].session_id, "s2");
+
+        let report = db.verify_search_integrity(false).unwrap();
+        assert!(!report.drift);
+        assert_eq!(report.indexed_count, 1);
+        let titles: i64 = db
+            .conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM sessions_fts_docsize", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(titles, 1);
+
+        // Deleting again reports that nothing was there
+        assert!(!db.delete_session("s1").unwrap());
+    }
+
+    #[test]
+    fn test_token_outliers_flag_giant_message() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        upsert_session(db, &sample_session("s1", "project1", 6)).unwrap();
+        let sizes = [100, 120, 90, 20_000, 110, 100];
+        let messages: Vec<Message> = sizes
+            .iter()
+            .enumerate()
+            .map(|(i, &size)| Message {
+                msg_id: format!("m{}", i),
+                session_id: "s1".to_string(),
+                role: "assistant".to_string(),
+                content: "x".repeat(size),
+                timestamp: format!("2026-01-08T10:0{}:00Z", i),
+                ..Default::default()
+            })
+            .collect();
+        insert_messages(db, &messages).unwrap();
 
-        assert_eq!(db.get_messages("s1").unwrap().len(), 1);
+        let report = db.get_token_outliers("s1").unwrap();
+        assert_eq!(report.median, 105.0);
+        assert_eq!(report.threshold, 105.0 * OUTLIER_FACTOR);
+        assert_eq!(report.outliers.len(), 1);
+        assert_eq!(report.outliers[0].msg_id, "m3");
+        assert_eq!(report.outliers[0].size, 20_000);
 
-        db.delete_session_messages("s1").unwrap();
-        assert_eq!(db.get_messages("s1").unwrap().len(), 0);
+        let empty = db.get_token_outliers("missing").unwrap();
+        assert_eq!(empty.median, 0.0);
+        assert!(empty.outliers.is_empty());
     }
 
     #[test]
     fn test_get_session_file_info() {
         let test_db = create_test_db();
         let db = &test_db.db;
-        db.upsert_session(&sample_session("s1", "project1", 5)).unwrap();
+        upsert_session(db, &sample_session("s1", "project1", 5)).unwrap();
 
         let info = db.get_session_file_info("s1").unwrap();
         assert!(info.is_some());
-        let (size, hash) = info.unwrap();
+        let (size, hash, mtime) = info.unwrap();
         assert_eq!(size, 1000);
         assert_eq!(hash, "abc123");
+        assert_eq!(mtime, Some(1_700_000_000_000_000_000));
 
         let info = db.get_session_file_info("nonexistent").unwrap();
         assert!(info.is_none());
@@ -515,29 +5720,347 @@ mod tests {
     fn test_get_projects() {
         let test_db = create_test_db();
         let db = &test_db.db;
-        db.upsert_session(&sample_session("s1", "alpha", 5)).unwrap();
-        db.upsert_session(&sample_session("s2", "beta", 5)).unwrap();
-        db.upsert_session(&sample_session("s3", "alpha", 3)).unwrap();
+        upsert_session(db, &sample_session("s1", "alpha", 5)).unwrap();
+        upsert_session(db, &sample_session("s2", "beta", 5)).unwrap();
+        upsert_session(db, &sample_session("s3", "alpha", 3)).unwrap();
 
         let projects = db.get_projects().unwrap();
         assert_eq!(projects, vec!["alpha", "beta"]);
     }
 
+    #[test]
+    fn test_export_project() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        for (id, project, started) in [
+            ("late", "project1", "2026-01-09T10:00:00Z"),
+            ("early", "project1", "2026-01-08T10:00:00Z"),
+            ("other", "project2", "2026-01-08T10:00:00Z"),
+        ] {
+            let mut session = sample_session(id, project, 1);
+            session.started_at = Some(started.to_string());
+            upsert_session(db, &session).unwrap();
+            insert_messages(db, &[Message {
+                msg_id: format!("{}-m1", id),
+                session_id: id.to_string(),
+                role: "user".to_string(),
+                content: format!("Message for {}", id),
+                timestamp: started.to_string(),
+                ..Default::default()
+            }])
+            .unwrap();
+        }
+
+        let exported = db.export_project("project1").unwrap();
+        let ids: Vec<&str> = exported.iter().map(|e| e.session.session_id.as_str()).collect();
+        assert_eq!(ids, ["early", "late"]);
+        assert!(exported.iter().all(|e| e.messages.len() == 1));
+        assert!(db.export_project("missing").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_export_batch_pages() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        for i in 0..7 {
+            let id = format!("s{}", i);
+            upsert_session(db, &sample_session(&id, "project1", 1)).unwrap();
+            insert_messages(db, &[Message {
+                msg_id: format!("{}-m1", id),
+                session_id: id.clone(),
+                role: "user".to_string(),
+                content: format!("Message for {}", id),
+                timestamp: "2026-01-08T10:00:00Z".to_string(),
+                is_sidechain: false,
+                tool_calls: None,
+                ..Default::default()
+            }])
+            .unwrap();
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        let mut after: Option<String> = None;
+        let mut batches = 0;
+        loop {
+            let batch = db.export_batch(after.as_deref(), 3).unwrap();
+            assert!(batch.sessions.len() <= 3);
+            for exported in &batch.sessions {
+                assert!(seen.insert(exported.session.session_id.clone()));
+                assert_eq!(exported.messages.len(), 1);
+                assert_eq!(exported.messages[0].session_id, exported.session.session_id);
+            }
+            batches += 1;
+            match batch.next {
+                Some(next) => after = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(batches, 3);
+        assert_eq!(seen.len(), 7);
+    }
+
+    #[test]
+    fn test_get_last_prompts_by_project() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+
+        let rows = [
+            ("a1", "alpha", "user", "Old alpha prompt", "2026-01-08T10:00:00Z"),
+            ("a2", "alpha", "user", "Latest alpha prompt", "2026-01-09T10:00:00Z"),
+            ("a2", "alpha", "assistant", "Newer reply", "2026-01-09T10:05:00Z"),
+            ("b1", "beta", "user", "Only beta prompt", "2026-01-08T12:00:00Z"),
+        ];
+        for (session_id, project, _, _, _) in rows {
+            upsert_session(db, &sample_session(session_id, project, 2)).unwrap();
+        }
+        // Zero-message sessions are excluded even if their rows linger
+        upsert_session(db, &sample_session("empty", "gamma", 0)).unwrap();
+
+        let messages: Vec<Message> = rows
+            .iter()
+            .chain([("empty", "gamma", "user", "Ignored", "2026-01-10T10:00:00Z")].iter())
+            .enumerate()
+            .map(|(i, (session_id, _, role, content, ts))| Message {
+                msg_id: format!("m{}", i),
+                session_id: session_id.to_string(),
+                role: role.to_string(),
+                content: content.to_string(),
+                timestamp: ts.to_string(),
+                is_sidechain: false,
+                tool_calls: None,
+                ..Default::default()
+            })
+            .collect();
+        insert_messages(db, &messages).unwrap();
+
+        let prompts: Vec<(String, String, String)> = db
+            .get_last_prompts_by_project(10)
+            .unwrap()
+            .into_iter()
+            .map(|p| (p.project, p.session_id, p.content))
+            .collect();
+        assert_eq!(
+            prompts,
+            vec![
+                ("alpha".to_string(), "a2".to_string(), "Latest alpha prompt".to_string()),
+                ("beta".to_string(), "b1".to_string(), "Only beta prompt".to_string()),
+            ]
+        );
+        assert_eq!(db.get_last_prompts_by_project(1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_append_session_checks_stored_offset() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        let message = |id: &str| Message {
+            msg_id: id.to_string(),
+            session_id: "s1".to_string(),
+            role: "user".to_string(),
+            content: format!("Message {}", id),
+            ..Default::default()
+        };
+        let mut session = sample_session("s1", "project1", 1);
+        session.parsed_offset = Some(100);
+        db.replace_session(&session, &[message("m1")], &[]).unwrap();
+
+        session.parsed_offset = Some(200);
+        session.message_count = 2;
+        assert!(db.append_session(&session, &[message("m2")], &[], 100).unwrap());
+
+        // A second append from the same offset lost the race and writes nothing
+        session.parsed_offset = Some(300);
+        session.message_count = 3;
+        assert!(!db.append_session(&session, &[message("m3")], &[], 100).unwrap());
+        let stored = db.get_session("s1").unwrap().unwrap();
+        assert_eq!((stored.parsed_offset, stored.message_count), (Some(200), 2));
+        assert_eq!(db.get_messages("s1", None, None).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_replace_session_stores_raw_and_size_sample() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        let message = Message {
+            msg_id: "m1".to_string(),
+            session_id: "s1".to_string(),
+            role: "user".to_string(),
+            content: "Hello".to_string(),
+            ..Default::default()
+        };
+        let raw = [("m1".to_string(), r#"{"type":"user"}"#.to_string())];
+        db.replace_session(&sample_session("s1", "project1", 1), &[message], &raw)
+            .unwrap();
+
+        assert_eq!(db.get_raw_message("s1", "m1").unwrap().as_deref(), Some(raw[0].1.as_str()));
+        let samples: Vec<i64> = {
+            let conn = db.conn.lock().unwrap();
+            let mut stmt = conn
+                .prepare("SELECT file_size FROM size_samples WHERE session_id = 's1'")
+                .unwrap();
+            let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+            rows.collect::<Result<_>>().unwrap()
+        };
+        assert_eq!(samples, [1000]);
+    }
+
+    #[test]
+    fn test_estimate_growth() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        let sample = |db: &Database, id: &str, size: i64, at: &str| {
+            insert_size_sample(&db.write_conn().unwrap(), id, size, at).unwrap();
+        };
+
+        // Active: grows 1000 bytes per hour; more samples than are retained
+        let samples = [(3, 0), (4, 500), (5, 1000), (6, 2000), (7, 3000), (8, 4000), (9, 5000)];
+        for (hour, size) in samples {
+            let at = format!("2026-01-08T{:02}:00:00Z", hour);
+            sample(db, "active", size, &at);
+        }
+        // Stale: last sampled days ago
+        sample(db, "stale", 1000, "2026-01-01T10:00:00Z");
+        sample(db, "stale", 900_000, "2026-01-01T11:00:00Z");
+
+        let now = chrono::DateTime::parse_from_rfc3339("2026-01-08T12:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let estimate = db.estimate_growth(now, chrono::Duration::hours(24)).unwrap();
+        assert_eq!(estimate.active_sessions, 1);
+        assert!((estimate.bytes_per_day - 24_000.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_get_tags_with_counts() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        for id in ["s1", "s2", "s3"] {
+            upsert_session(db, &sample_session(id, "project1", 5)).unwrap();
+        }
+
+        db.add_session_tag("s1", "bugfix").unwrap();
+        db.add_session_tag("s2", "bugfix").unwrap();
+        db.add_session_tag("s2", "bugfix").unwrap();
+        db.add_session_tag("s3", "bugfix").unwrap();
+        db.add_session_tag("s1", "auth").unwrap();
+        db.add_session_tag("s3", "auth").unwrap();
+        db.add_session_tag("s3", "refactor").unwrap();
+        db.add_session_tag("s1", "refactor").unwrap();
+        assert!(db.remove_session_tag("s1", "refactor").unwrap());
+        assert!(!db.remove_session_tag("s1", "refactor").unwrap());
+
+        assert_eq!(
+            db.get_tags_with_counts().unwrap(),
+            vec![
+                ("bugfix".to_string(), 3),
+                ("auth".to_string(), 2),
+                ("refactor".to_string(), 1),
+            ]
+        );
+        assert_eq!(db.get_session_tags("s3").unwrap(), vec!["auth", "bugfix", "refactor"]);
+
+        db.delete_session("s3").unwrap();
+        assert_eq!(
+            db.get_tags_with_counts().unwrap(),
+            vec![("bugfix".to_string(), 2), ("auth".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn test_get_sessions_filtered_by_tag() {
+        let test_db = create_test_db();
+        let db = &test_db.db;
+        for id in ["s1", "s2"] {
+            upsert_session(db, &sample_session(id, "project1", 5)).unwrap();
+        }
+        db.add_session_tag("s1", "exploration").unwrap();
+        db.add_session_tag("s1", "bugfix").unwrap();
+        db.add_session_tag("s2", "bugfix").unwrap();
+
+        // Tags live outside the sessions row, so a resync keeps them
+        upsert_session(db, &sample_session("s1", "project1", 6)).unwrap();
+
+        let tagged = |tag: &str| -> Vec<(String, Vec<String>)> {
+            let filter = SessionFilter {
+                tag: Some(tag.to_string()),
+                ..Default::default()
+            };
+            let mut sessions: Vec<_> = db
+                .get_sessions_filtered(&filter, 10, SessionSort::default())
+                .unwrap()
+                .into_iter()
+                .map(|s| (s.session_id, s.tags))
+                .collect();
+            sessions.sort();
+            sessions
+        };
+        let s1_tags = || vec!["bugfix".to_string(), "exploration".to_string()];
+        assert_eq!(tagged("exploration"), [("s1".to_string(), s1_tags())]);
+        assert_eq!(
+            tagged("bugfix"),
+            [("s1".to_string(), s1_tags()), ("s2".to_string(), vec!["bugfix".to_string()])]
+        );
+
+        assert_eq!(db.get_session("s1").unwrap().unwrap().tags, s1_tags());
+
+        assert!(db.remove_session_tag("s2", "bugfix").unwrap());
+        assert_eq!(tagged("bugfix"), [("s1".to_string(), s1_tags())]);
+        assert!(db.get_session("s2").unwrap().unwrap().tags.is_empty());
+        assert!(tagged("missing").is_empty());
+    }
+
+    #[test]
+    fn test_read_only_mode() {
+        let dir = TempDir::new().unwrap();
+        let db_path = dir.path().join("shared.db");
+        {
+            let db = Database::open_with(&db_path, &DbConfig::default()).unwrap();
+            upsert_session(&db, &sample_session("s1", "project1", 1)).unwrap();
+            insert_messages(&db, &[Message {
+                msg_id: "m1".to_string(),
+                session_id: "s1".to_string(),
+                role: "user".to_string(),
+                content: "Shared research notes".to_string(),
+                timestamp: "2026-01-08T10:00:00Z".to_string(),
+                is_sidechain: false,
+                tool_calls: None,
+                ..Default::default()
+            }])
+            .unwrap();
+        }
+
+        let db = Database::open_read_only_with(&db_path, &DbConfig::default()).unwrap();
+        assert!(db.is_read_only());
+        assert_eq!(list_sessions(&db, None, 100).len(), 1);
+        assert_eq!(db.get_messages("s1", None, None).unwrap().len(), 1);
+        assert_eq!(db.search("research", 10, &SearchOptions::default()).unwrap().len(), 1);
+
+        let err = db
+            .replace_session(&sample_session("s2", "project1", 1), &[], &[])
+            .unwrap_err();
+        assert!(err.to_string().contains("read-only"));
+        assert!(db.delete_session("s1").is_err());
+        assert!(db.verify_search_integrity(true).is_ok());
+        assert_eq!(db.get_messages("s1", None, None).unwrap().len(), 1);
+    }
+
     #[test]
     fn test_upsert_updates_existing() {
         let test_db = create_test_db();
         let db = &test_db.db;
 
         let mut session = sample_session("s1", "project1", 5);
-        db.upsert_session(&session).unwrap();
+        upsert_session(db, &session).unwrap();
 
         session.message_count = 10;
         session.first_message = Some("Updated message".to_string());
-        db.upsert_session(&session).unwrap();
+        session.summary = Some("Session topic".to_string());
+        upsert_session(db, &session).unwrap();
 
-        let sessions = db.get_sessions(None, 100).unwrap();
+        let sessions = list_sessions(db, None, 100);
         assert_eq!(sessions.len(), 1);
         assert_eq!(sessions[0].message_count, 10);
         assert_eq!(sessions[0].first_message, Some("Updated message".to_string()));
+        assert_eq!(sessions[0].summary.as_deref(), Some("Session topic"));
     }
 }
@@ -0,0 +1,169 @@
+//! Line-level diffs between the messages of two sessions.
+
+use crate::db::Message;
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+
+/// Maximum combined content size compared before the diff is cut short.
+const MAX_DIFF_BYTES: usize = 2 * 1024 * 1024;
+
+/// A single line in a message diff.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiffLine {
+    /// One of `equal`, `insert`, or `delete`.
+    pub tag: String,
+    pub text: String,
+}
+
+/// Diff of the messages at one position in both sessions.
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageDiff {
+    pub index: usize,
+    pub role_a: Option<String>,
+    pub role_b: Option<String>,
+    pub lines: Vec<DiffLine>,
+}
+
+/// Differences between two sessions, aligned by message order.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionDiff {
+    pub session_a: String,
+    pub session_b: String,
+    /// Number of aligned message pairs with identical role and content.
+    pub identical: usize,
+    /// Only the positions that differ.
+    pub messages: Vec<MessageDiff>,
+    /// Set when the size cap stopped the comparison early.
+    pub truncated: bool,
+}
+
+/// Diff two sessions' messages, pairing them up by position.
+pub fn diff_sessions(
+    session_a: &str,
+    a: &[Message],
+    session_b: &str,
+    b: &[Message],
+) -> SessionDiff {
+    let mut result = SessionDiff {
+        session_a: session_a.to_string(),
+        session_b: session_b.to_string(),
+        identical: 0,
+        messages: Vec::new(),
+        truncated: false,
+    };
+    let mut compared = 0;
+
+    for index in 0..a.len().max(b.len()) {
+        let (left, right) = (a.get(index), b.get(index));
+
+        let same = match (left, right) {
+            (Some(l), Some(r)) => l.role == r.role && l.content == r.content,
+            _ => false,
+        };
+        if same {
+            result.identical += 1;
+            continue;
+        }
+
+        compared += left.map_or(0, |m| m.content.len()) + right.map_or(0, |m| m.content.len());
+        if compared > MAX_DIFF_BYTES {
+            result.truncated = true;
+            break;
+        }
+
+        let old = left.map_or("", |m| m.content.as_str());
+        let new = right.map_or("", |m| m.content.as_str());
+        let lines = TextDiff::from_lines(old, new)
+            .iter_all_changes()
+            .map(|change| DiffLine {
+                tag: match change.tag() {
+                    ChangeTag::Equal => "equal",
+                    ChangeTag::Insert => "insert",
+                    ChangeTag::Delete => "delete",
+                }
+                .to_string(),
+                text: change.value().trim_end_matches('\n').to_string(),
+            })
+            .collect();
+
+        result.messages.push(MessageDiff {
+            index,
+            role_a: left.map(|m| m.role.clone()),
+            role_b: right.map(|m| m.role.clone()),
+            lines,
+        });
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(session_id: &str, role: &str, content: &str) -> Message {
+        Message {
+            msg_id: String::new(),
+            session_id: session_id.to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            timestamp: String::new(),
+            is_sidechain: false,
+            tool_calls: None,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_diff_highlights_single_changed_message() {
+        let a = vec![
+            msg("a", "user", "Add a login page"),
+            msg("a", "assistant", "Created login.html\nAdded form validation"),
+            msg("a", "user", "Thanks"),
+        ];
+        let b = vec![
+            msg("b", "user", "Add a login page"),
+            msg("b", "assistant", "Created login.html\nAdded CSRF protection"),
+            msg("b", "user", "Thanks"),
+        ];
+
+        let diff = diff_sessions("a", &a, "b", &b);
+        assert_eq!(diff.identical, 2);
+        assert!(!diff.truncated);
+        assert_eq!(diff.messages.len(), 1);
+
+        let changed = &diff.messages[0];
+        assert_eq!(changed.index, 1);
+        assert_eq!(
+            changed.lines,
+            vec![
+                DiffLine { tag: "equal".to_string(), text: "Created login.html".to_string() },
+                DiffLine { tag: "delete".to_string(), text: "Added form validation".to_string() },
+                DiffLine { tag: "insert".to_string(), text: "Added CSRF protection".to_string() },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_extra_messages() {
+        let a = vec![msg("a", "user", "Hello")];
+        let b = vec![msg("b", "user", "Hello"), msg("b", "assistant", "Hi")];
+
+        let diff = diff_sessions("a", &a, "b", &b);
+        assert_eq!(diff.messages.len(), 1);
+        assert_eq!(diff.messages[0].role_a, None);
+        assert_eq!(diff.messages[0].role_b.as_deref(), Some("assistant"));
+        assert_eq!(diff.messages[0].lines[0].tag, "insert");
+    }
+
+    #[test]
+    fn test_diff_size_cap() {
+        let big = "x".repeat(MAX_DIFF_BYTES);
+        let a = vec![msg("a", "user", &big), msg("a", "user", "tail")];
+        let b = vec![msg("b", "user", "short"), msg("b", "user", "other tail")];
+
+        let diff = diff_sessions("a", &a, "b", &b);
+        assert!(diff.truncated);
+        assert!(diff.messages.is_empty());
+    }
+}
@@ -0,0 +1,502 @@
+//! Export sessions as a self-contained HTML document or as normalized JSON,
+//! and import JSON exports back.
+
+use crate::db::{escape_html, Database, ExportSession, Message, Session};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt::Write;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Version of the JSON session export, bumped when its shape changes incompatibly.
+pub const JSON_SCHEMA_VERSION: u64 = 1;
+
+/// Why a JSON session export couldn't be read.
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error("missing schema_version")]
+    MissingVersion,
+    #[error("unsupported schema_version {0}, expected {JSON_SCHEMA_VERSION}")]
+    UnsupportedVersion(u64),
+    #[error("invalid session export: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("database error: {0}")]
+    Db(#[from] rusqlite::Error),
+}
+
+/// Outcome of importing exported sessions.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ImportStats {
+    pub imported: usize,
+    /// Sessions left alone because one with the same id already existed.
+    pub skipped: usize,
+}
+
+/// The portable part of an exported session: its metadata, tags and star.
+/// State tied to the exporting machine's copy of the source file, such as its
+/// path, size, hash and how far it was parsed, is left out.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PortableSession {
+    pub session_id: String,
+    pub project: String,
+    pub machine: String,
+    pub agent: String,
+    pub first_message: Option<String>,
+    #[serde(default)]
+    pub summary: Option<String>,
+    pub started_at: Option<String>,
+    pub ended_at: Option<String>,
+    pub message_count: i32,
+    #[serde(default)]
+    pub git_branch: Option<String>,
+    #[serde(default)]
+    pub parent_session_id: Option<String>,
+    #[serde(default)]
+    pub content_purged: bool,
+    #[serde(default)]
+    pub reasoning_effort: Option<String>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub total_input_tokens: Option<i64>,
+    #[serde(default)]
+    pub total_output_tokens: Option<i64>,
+    #[serde(default)]
+    pub starred: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl From<&Session> for PortableSession {
+    fn from(session: &Session) -> Self {
+        PortableSession {
+            session_id: session.session_id.clone(),
+            project: session.project.clone(),
+            machine: session.machine.clone(),
+            agent: session.agent.clone(),
+            first_message: session.first_message.clone(),
+            summary: session.summary.clone(),
+            started_at: session.started_at.clone(),
+            ended_at: session.ended_at.clone(),
+            message_count: session.message_count,
+            git_branch: session.git_branch.clone(),
+            parent_session_id: session.parent_session_id.clone(),
+            content_purged: session.content_purged,
+            reasoning_effort: session.reasoning_effort.clone(),
+            cwd: session.cwd.clone(),
+            model: session.model.clone(),
+            total_input_tokens: session.total_input_tokens,
+            total_output_tokens: session.total_output_tokens,
+            starred: session.starred,
+            tags: session.tags.clone(),
+        }
+    }
+}
+
+impl From<PortableSession> for Session {
+    fn from(session: PortableSession) -> Self {
+        Session {
+            session_id: session.session_id,
+            project: session.project,
+            machine: session.machine,
+            first_message: session.first_message,
+            summary: session.summary,
+            started_at: session.started_at,
+            ended_at: session.ended_at,
+            message_count: session.message_count,
+            file_size: None,
+            file_hash: None,
+            file_mtime: None,
+            source_path: None,
+            agent: session.agent,
+            git_branch: session.git_branch,
+            parent_session_id: session.parent_session_id,
+            content_purged: session.content_purged,
+            reasoning_effort: session.reasoning_effort,
+            cwd: session.cwd,
+            model: session.model,
+            total_input_tokens: session.total_input_tokens,
+            total_output_tokens: session.total_output_tokens,
+            parsed_offset: None,
+            starred: session.starred,
+            tags: session.tags,
+            is_new_since_last_open: false,
+        }
+    }
+}
+
+/// A session and its messages as read from a JSON export.
+#[derive(Deserialize)]
+struct SessionJson {
+    session: PortableSession,
+    messages: Vec<Message>,
+}
+
+/// Inlined so the document stays readable without any other files.
+const EXPORT_CSS: &str = "
+body { font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', sans-serif;
+       max-width: 960px; margin: 0 auto; padding: 24px; color: #1f2328; background: #fff; }
+nav ol { padding-left: 24px; }
+nav li { margin: 4px 0; }
+.meta { color: #656d76; font-size: 13px; }
+details.session { border: 1px solid #d0d7de; border-radius: 6px; margin: 16px 0; padding: 0 16px; }
+details.session > summary { cursor: pointer; padding: 12px 0; font-weight: 600; }
+.message { border-left: 3px solid #d0d7de; margin: 12px 0; padding: 4px 12px; }
+.message.user { border-color: #0969da; }
+.message.assistant { border-color: #8250df; }
+.message.tool { border-color: #9a6700; }
+.role { font-size: 12px; font-weight: 600; text-transform: uppercase; color: #656d76; }
+.content { white-space: pre-wrap; word-wrap: break-word; margin: 4px 0 0; font-family: inherit; }
+";
+
+/// Title shown for a session: its summary, else its first message, else its id.
+fn session_title(session: &Session) -> &str {
+    session
+        .summary
+        .as_deref()
+        .or(session.first_message.as_deref())
+        .unwrap_or(&session.session_id)
+}
+
+/// Export a session and its messages as normalized JSON, independent of the
+/// agent's own log format:
+/// `{"schema_version": 1, "session": {...}, "messages": [...]}`.
+///
+/// Only the session's `PortableSession` fields are written.
+pub fn session_to_json(session: &Session, messages: &[Message]) -> Value {
+    json!({
+        "schema_version": JSON_SCHEMA_VERSION,
+        "session": PortableSession::from(session),
+        "messages": messages,
+    })
+}
+
+/// Export a stored session with its messages, or None if there is no such
+/// session.
+pub fn export_session(db: &Database, session_id: &str) -> rusqlite::Result<Option<Value>> {
+    let Some(session) = db.get_session(session_id)? else {
+        return Ok(None);
+    };
+    let messages = db.get_messages_with_tools(session_id, None, None)?;
+    Ok(Some(session_to_json(&session, &messages)))
+}
+
+/// Read back a session exported by `session_to_json`. Fields of the source
+/// file left out of the export are None.
+pub fn session_from_json(value: Value) -> Result<ExportSession, ImportError> {
+    match value.get("schema_version").map(Value::as_u64) {
+        None | Some(None) => return Err(ImportError::MissingVersion),
+        Some(Some(JSON_SCHEMA_VERSION)) => {}
+        Some(Some(version)) => return Err(ImportError::UnsupportedVersion(version)),
+    }
+    let export: SessionJson = serde_json::from_value(value)?;
+    Ok(ExportSession {
+        session: export.session.into(),
+        messages: export.messages,
+    })
+}
+
+/// Import a JSON export file holding one exported session or an array of them.
+///
+/// Every export is validated before anything is written. A session whose id
+/// already exists is replaced when `overwrite` is set and skipped otherwise.
+/// Messages are inserted like synced ones, so they are searchable at once,
+/// and the session's tags and star are restored. Imported sessions keep no
+/// source file, so syncs never prune or reparse them.
+pub fn import_sessions_file(
+    db: &Database,
+    path: &Path,
+    overwrite: bool,
+) -> Result<ImportStats, ImportError> {
+    let value: Value = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    let exports = match value {
+        Value::Array(items) => items
+            .into_iter()
+            .map(session_from_json)
+            .collect::<Result<Vec<_>, _>>()?,
+        value => vec![session_from_json(value)?],
+    };
+
+    let mut stats = ImportStats::default();
+    for mut export in exports {
+        // Messages always belong to the session they were exported with
+        let session_id = &export.session.session_id;
+        for message in &mut export.messages {
+            message.session_id.clone_from(session_id);
+            for call in message.tool_calls.iter_mut().flatten() {
+                call.session_id.clone_from(session_id);
+            }
+        }
+        if db.import_session(&export.session, &export.messages, overwrite)? {
+            stats.imported += 1;
+        } else {
+            stats.skipped += 1;
+        }
+    }
+    Ok(stats)
+}
+
+/// Render a project's sessions as one HTML document: a linked table of
+/// contents followed by each session as a collapsible section.
+///
+/// All session text is escaped, so nothing in a transcript can inject markup.
+pub fn project_to_html(project: &str, sessions: &[ExportSession]) -> String {
+    let project = escape_html(project);
+    let mut html = String::new();
+
+    // Writing to a String can't fail
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{project} sessions</title>\n<style>{EXPORT_CSS}</style>\n</head>\n<body>\n\
+         <h1>{project}</h1>\n<p class=\"meta\">{} sessions</p>\n<nav>\n<ol>\n",
+        sessions.len()
+    );
+    for (i, export) in sessions.iter().enumerate() {
+        let _ = writeln!(
+            html,
+            "<li><a href=\"#session-{i}\">{}</a> <span class=\"meta\">{}</span></li>",
+            escape_html(session_title(&export.session)),
+            escape_html(export.session.started_at.as_deref().unwrap_or("")),
+        );
+    }
+    html.push_str("</ol>\n</nav>\n");
+
+    for (i, export) in sessions.iter().enumerate() {
+        let session = &export.session;
+        let _ = write!(
+            html,
+            "<details class=\"session\" id=\"session-{i}\">\n<summary>{}</summary>\n\
+             <p class=\"meta\">{} &middot; {} &middot; {} messages</p>\n",
+            escape_html(session_title(session)),
+            escape_html(&session.session_id),
+            escape_html(session.started_at.as_deref().unwrap_or("")),
+            export.messages.len(),
+        );
+        for (j, message) in export.messages.iter().enumerate() {
+            let role = escape_html(&message.role);
+            let _ = write!(
+                html,
+                "<div class=\"message {role}\" id=\"session-{i}-message-{j}\">\n\
+                 <div class=\"role\"><a href=\"#session-{i}-message-{j}\">{role}</a> {}</div>\n\
+                 <pre class=\"content\">{}</pre>\n</div>\n",
+                escape_html(&message.timestamp),
+                escape_html(&message.content),
+            );
+        }
+        html.push_str("</details>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{Database, DbConfig, ToolCall};
+
+    fn export(id: &str, first_message: &str, contents: &[(&str, &str)]) -> ExportSession {
+        let session: Session = serde_json::from_value(serde_json::json!({
+            "session_id": id,
+            "project": "my-app",
+            "machine": "local",
+            "first_message": first_message,
+            "started_at": "2026-01-08T10:00:00Z",
+            "ended_at": "2026-01-08T11:00:00Z",
+            "message_count": contents.len(),
+            "file_size": null,
+            "file_hash": null,
+            "agent": "claude",
+        }))
+        .unwrap();
+        let messages = contents
+            .iter()
+            .map(|(role, content)| Message {
+                session_id: id.to_string(),
+                role: role.to_string(),
+                content: content.to_string(),
+                timestamp: "2026-01-08T10:00:00Z".to_string(),
+                ..Default::default()
+            })
+            .collect();
+        ExportSession { session, messages }
+    }
+
+    #[test]
+    fn test_project_to_html_structure() {
+        let sessions = vec![
+            export("s1", "Add login", &[("user", "Add login"), ("assistant", "Done")]),
+            export("s2", "Fix tests", &[("user", "Fix tests")]),
+        ];
+        let html = project_to_html("my-app", &sessions);
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>my-app sessions</title>"));
+        assert!(html.contains("<style>"));
+        assert!(html.contains("<a href=\"#session-0\">Add login</a>"));
+        assert!(html.contains("<a href=\"#session-1\">Fix tests</a>"));
+        assert!(html.contains("<details class=\"session\" id=\"session-0\">"));
+        assert!(html.contains("<details class=\"session\" id=\"session-1\">"));
+        assert_eq!(html.matches("<details").count(), 2);
+        assert_eq!(html.matches("<div class=\"message ").count(), 3);
+        assert!(html.contains("id=\"session-0-message-1\""));
+        assert!(html.contains("<pre class=\"content\">Done</pre>"));
+        assert!(html.trim_end().ends_with("</html>"));
+    }
+
+    #[test]
+    fn test_project_to_html_escapes_session_text() {
+        let sessions = vec![export(
+            "s1",
+            "<img src=x onerror=alert(1)>",
+            &[("user", "<script>alert('x')</script> & \"quotes\"")],
+        )];
+        let html = project_to_html("<b>proj</b>", &sessions);
+
+        assert!(!html.contains("<script>"));
+        assert!(!html.contains("<img"));
+        assert!(!html.contains("<b>"));
+        assert!(html.contains(
+            "&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt; &amp; &quot;quotes&quot;"
+        ));
+        assert!(html.contains("&lt;img src=x onerror=alert(1)&gt;"));
+        assert!(html.contains("<h1>&lt;b&gt;proj&lt;/b&gt;</h1>"));
+    }
+
+    #[test]
+    fn test_session_json_round_trip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let mut original = export("s1", "Run the tests", &[("user", "Run the tests")]);
+        original.session.model = Some("claude-opus".to_string());
+        original.session.git_branch = Some("main".to_string());
+        original.session.file_size = Some(1024);
+        original.session.source_path = Some("/home/me/.claude/projects/-app/s1.jsonl".into());
+        original.session.parsed_offset = Some(1024);
+        original.messages[0].msg_id = "m1".to_string();
+        original.messages.push(Message {
+            msg_id: "m2".to_string(),
+            session_id: "s1".to_string(),
+            role: "assistant".to_string(),
+            content: "[Bash: Run tests]\n$ cargo test".to_string(),
+            timestamp: "2026-01-08T10:00:05Z".to_string(),
+            uuid: Some("a1".to_string()),
+            parent_uuid: Some("u1".to_string()),
+            tool_calls: Some(vec![ToolCall {
+                session_id: "s1".to_string(),
+                msg_id: "m2".to_string(),
+                seq: 0,
+                tool_name: "Bash".to_string(),
+                summary: "[Bash: Run tests]\n$ cargo test".to_string(),
+                tool_input_json: None,
+            }]),
+            ..Default::default()
+        });
+        db.replace_session(&original.session, &original.messages, &[]).unwrap();
+        db.set_session_starred("s1", true).unwrap();
+        db.add_session_tag("s1", "release").unwrap();
+        db.add_session_tag("s1", "ci").unwrap();
+
+        let exported = export_session(&db, "s1").unwrap().unwrap();
+        assert_eq!(exported["schema_version"], JSON_SCHEMA_VERSION);
+        assert_eq!(exported["session"]["session_id"], "s1");
+        assert_eq!(exported["session"]["starred"], true);
+        assert_eq!(exported["session"]["tags"], json!(["ci", "release"]));
+        assert_eq!(exported["messages"].as_array().unwrap().len(), 2);
+        // The exporting machine's sync state stays behind
+        for field in ["file_size", "file_hash", "file_mtime", "source_path", "parsed_offset"] {
+            assert!(exported["session"].get(field).is_none(), "{} exported", field);
+        }
+
+        // Through a file and into a cleared database
+        let path = tmp.path().join("s1.json");
+        std::fs::write(&path, serde_json::to_string_pretty(&exported).unwrap()).unwrap();
+        assert!(db.delete_session("s1").unwrap());
+        let stats = import_sessions_file(&db, &path, false).unwrap();
+        assert_eq!(stats, ImportStats { imported: 1, skipped: 0 });
+
+        assert_eq!(export_session(&db, "s1").unwrap().unwrap(), exported);
+        let imported = db.get_session("s1").unwrap().unwrap();
+        assert!(imported.starred);
+        assert_eq!((imported.source_path, imported.parsed_offset), (None, None));
+        assert_eq!(export_session(&db, "missing").unwrap(), None);
+    }
+
+    #[test]
+    fn test_import_sessions_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let path = tmp.path().join("sessions.json");
+        let exports: Vec<Value> = [
+            export("s1", "Fix the parser", &[("user", "The tokenizer drops unicode")]),
+            export("s2", "Speed up sync", &[("user", "Sync walks every tokenizer file")]),
+        ]
+        .iter()
+        .map(|e| session_to_json(&e.session, &e.messages))
+        .collect();
+        std::fs::write(&path, serde_json::to_string(&exports).unwrap()).unwrap();
+
+        let stats = import_sessions_file(&db, &path, false).unwrap();
+        assert_eq!(stats, ImportStats { imported: 2, skipped: 0 });
+        assert!(db.get_session("s1").unwrap().is_some());
+        assert!(db.get_session("s2").unwrap().is_some());
+        let mut hits: Vec<String> = db
+            .search("tokenizer", 10, &Default::default())
+            .unwrap()
+            .into_iter()
+            .map(|r| r.session_id)
+            .collect();
+        hits.sort();
+        assert_eq!(hits, ["s1", "s2"]);
+
+        // Existing sessions are skipped unless overwriting
+        let edited = export("s1", "Fix the parser", &[("user", "Rewrite the lexer")]);
+        std::fs::write(&path, session_to_json(&edited.session, &edited.messages).to_string())
+            .unwrap();
+        let stats = import_sessions_file(&db, &path, false).unwrap();
+        assert_eq!(stats, ImportStats { imported: 0, skipped: 1 });
+        assert_eq!(db.search("lexer", 10, &Default::default()).unwrap().len(), 0);
+
+        let stats = import_sessions_file(&db, &path, true).unwrap();
+        assert_eq!(stats, ImportStats { imported: 1, skipped: 0 });
+        assert_eq!(db.search("lexer", 10, &Default::default()).unwrap().len(), 1);
+        let hits = db.search("tokenizer", 10, &Default::default()).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].session_id, "s2");
+
+        // One bad export rejects the whole file
+        let mut bad = exports.clone();
+        bad[1]["schema_version"] = json!(99);
+        std::fs::write(&path, serde_json::to_string(&bad).unwrap()).unwrap();
+        assert!(matches!(
+            import_sessions_file(&db, &path, true),
+            Err(ImportError::UnsupportedVersion(99))
+        ));
+        assert_eq!(db.search("lexer", 10, &Default::default()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_session_from_json_checks_schema_version() {
+        let session = export("s1", "Hi", &[("user", "Hi")]);
+        let mut value = session_to_json(&session.session, &session.messages);
+        assert!(session_from_json(value.clone()).is_ok());
+
+        value["schema_version"] = json!(JSON_SCHEMA_VERSION + 1);
+        assert!(matches!(
+            session_from_json(value.clone()),
+            Err(ImportError::UnsupportedVersion(2))
+        ));
+        value.as_object_mut().unwrap().remove("schema_version");
+        assert!(matches!(session_from_json(value), Err(ImportError::MissingVersion)));
+        assert!(matches!(
+            session_from_json(json!({"schema_version": 1, "session": {}})),
+            Err(ImportError::Json(_))
+        ));
+    }
+}
@@ -1,44 +1,175 @@
 // Prevents additional console window on Windows in release
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod cli;
 mod commands;
 mod db;
+mod diff;
+mod export;
 mod parser;
 mod sync;
+mod watch;
 
 use commands::AppState;
-use db::Database;
-use std::sync::Arc;
+use db::{Database, DbConfig};
+use std::sync::{Arc, Mutex};
+use tauri::{Emitter, Manager};
 
 fn main() {
     // Initialize data directory
     let data_dir = sync::data_dir();
     std::fs::create_dir_all(&data_dir).expect("Failed to create data directory");
 
+    let args = cli::parse_args(std::env::args().skip(1));
+
     // Open database (use separate file from Python version to avoid schema conflicts)
-    let db_path = data_dir.join("sessions-tauri.db");
-    let db = Database::open(&db_path).expect("Failed to open database");
+    let db_path = args
+        .db_path
+        .unwrap_or_else(|| data_dir.join("sessions-tauri.db"));
+    let defaults = DbConfig::default();
+    let db_config = DbConfig {
+        cache_size_kb: args.cache_size_kb.unwrap_or(defaults.cache_size_kb),
+        mmap_size_mb: args.mmap_size_mb.unwrap_or(defaults.mmap_size_mb),
+    };
+    let db = if args.read_only {
+        Database::open_read_only_with(&db_path, &db_config)
+    } else {
+        Database::open_with(&db_path, &db_config)
+    }
+    .expect("Failed to open database");
+    match db.config() {
+        Ok(applied) => println!(
+            "SQLite cache_size={} KiB, mmap_size={} MiB",
+            applied.cache_size_kb, applied.mmap_size_mb
+        ),
+        Err(e) => eprintln!("Failed to read database settings: {}", e),
+    }
 
     // Initial sync
-    println!("Running initial sync...");
-    let stats = sync::sync_all(&db, "local");
-    println!(
-        "Synced {} sessions ({} new, {} unchanged)",
-        stats.total_sessions, stats.synced, stats.skipped
-    );
+    if db.is_read_only() {
+        println!("Opened {} read-only, skipping sync", db_path.display());
+    } else {
+        println!("Running initial sync...");
+        let stats = sync::sync_all(&db, "local");
+        println!(
+            "Synced {} sessions ({} new, {} updated, {} unchanged)",
+            stats.total_sessions, stats.new, stats.updated, stats.unchanged
+        );
+    }
+
+    // Remember the previous launch so sessions started since then can be flagged
+    let last_open_at = if db.is_read_only() {
+        db.get_meta("last_open_at")
+    } else {
+        db.record_open(&chrono::Utc::now().to_rfc3339())
+    }
+    .unwrap_or_else(|e| {
+        eprintln!("Failed to read last open time: {}", e);
+        None
+    });
 
-    let state = AppState { db: Arc::new(db) };
+    let launch_target = args.launch_target;
+
+    let state = AppState {
+        db: Arc::new(db),
+        launch_target: launch_target.clone(),
+        last_open_at,
+        max_limit: args.max_limit.unwrap_or(commands::DEFAULT_MAX_LIMIT),
+        session_tails: Mutex::default(),
+        sync_running: Arc::default(),
+        sync_cancel: Arc::default(),
+    };
+    let watch_db = Arc::clone(&state.db);
 
     tauri::Builder::default()
         .manage(state)
+        .setup(move |app| {
+            // The frontend also pulls this via get_launch_target in case it
+            // wasn't listening yet when the event fired
+            if let Some(target) = launch_target {
+                app.emit("launch-target", target)?;
+            }
+
+            // Pick up sessions as they are written now that the initial sync is done
+            if !watch_db.is_read_only() {
+                let handle = app.handle().clone();
+                let on_update = move |session_id: &str| {
+                    if let Err(e) = handle.emit("session-updated", session_id) {
+                        eprintln!("Failed to emit session-updated: {}", e);
+                    }
+                };
+                match watch::start(watch_db, "local", on_update) {
+                    Ok(watcher) => {
+                        app.manage(Mutex::new(watcher));
+                    }
+                    Err(e) => eprintln!("Failed to watch session directories: {}", e),
+                }
+            }
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             commands::get_sessions,
+            commands::get_session,
+            commands::get_session_index,
+            commands::get_sessions_by_branch,
+            commands::get_session_tree,
+            commands::get_thread,
+            commands::get_random_sessions,
             commands::get_messages,
+            commands::count_messages,
+            commands::get_stats,
+            commands::watch_session,
+            commands::unwatch_session,
+            commands::get_normalized_messages,
+            commands::find_session_for_message,
+            commands::get_message_role_counts,
+            commands::get_response_latencies,
+            commands::get_tool_call_detail,
+            commands::get_session_timeline,
+            commands::get_activity_feed,
+            commands::get_term_frequencies,
+            commands::term_over_time,
+            commands::diff_sessions,
             commands::search,
+            commands::search_merged,
+            commands::search_all,
+            commands::search_in_session,
+            commands::rank_sessions,
+            commands::search_page,
+            commands::verify_search_integrity,
             commands::get_projects,
+            commands::get_last_prompts_by_project,
+            commands::export_batch,
+            commands::export_project,
+            commands::export_session_json,
+            commands::import_sessions,
+            commands::add_session_tag,
+            commands::remove_session_tag,
+            commands::get_session_tags,
+            commands::get_tags_with_counts,
+            commands::set_starred,
+            commands::open_source_file,
+            commands::rebuild_index,
+            commands::merge_database,
+            commands::mark_session_read,
+            commands::delete_session,
+            commands::get_raw_message,
+            commands::get_token_outliers,
+            commands::get_stale_unread,
             commands::trigger_sync,
+            commands::cancel_sync,
+            commands::get_parse_metrics,
+            commands::estimate_growth,
+            commands::reproject_all,
+            commands::get_project_paths,
+            commands::merge_projects,
             commands::check_session_update,
             commands::sync_session,
+            commands::import_session_file,
+            commands::purge_old_messages,
+            commands::get_launch_target,
+            commands::get_db_config,
+            commands::tune_database,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
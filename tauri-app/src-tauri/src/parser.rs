@@ -1,16 +1,96 @@
 //! Parse Claude Code and Codex JSONL session files.
 
-use crate::db::{Message, Session};
+use crate::db::{Message, Session, ToolCall};
 use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
 use std::path::Path;
 
 /// Parsed session result.
 pub struct ParsedSession {
     pub metadata: Session,
     pub messages: Vec<Message>,
+    /// Original JSON entry of each message as (msg_id, json); only
+    /// collected when `ParseOptions::store_raw` is set.
+    pub raw_messages: Vec<(String, String)>,
+}
+
+/// Whether a path names a session log, plain (`.jsonl`) or gzipped (`.jsonl.gz`).
+pub fn is_session_file(path: &Path) -> bool {
+    session_file_stem(path).is_some()
+}
+
+/// File name of a session log without its `.jsonl` or `.jsonl.gz` extension.
+pub fn session_file_stem(path: &Path) -> Option<&str> {
+    let name = path.file_name()?.to_str()?;
+    name.strip_suffix(".jsonl.gz").or_else(|| name.strip_suffix(".jsonl"))
+}
+
+/// Open a session log for reading line by line, decompressing `.gz` files.
+pub fn open_session_file(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let file = File::open(path)?;
+    if path.extension().is_some_and(|e| e == "gz") {
+        Ok(Box::new(BufReader::new(GzDecoder::new(file))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+/// JSONL entries read through one reused line buffer, skipping blank and
+/// unparseable lines.
+struct JsonlEntries<R> {
+    reader: R,
+    buf: Vec<u8>,
+    /// Bytes read up to the end of the last complete line or entry; an
+    /// unfinished line still being written is not counted.
+    consumed: u64,
+}
+
+impl<R: BufRead> JsonlEntries<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: Vec::new(),
+            consumed: 0,
+        }
+    }
+
+    /// Read the next entry, returned with its trimmed source line. Lines
+    /// that aren't valid UTF-8 are skipped; read errors end the file.
+    fn next_entry(&mut self) -> Option<(Value, &str)> {
+        loop {
+            self.buf.clear();
+            let read = match self.reader.read_until(b'\n', &mut self.buf) {
+                Ok(0) | Err(_) => return None,
+                Ok(n) => n as u64,
+            };
+            let complete = self.buf.ends_with(b"\n");
+            if complete {
+                self.consumed += read;
+            }
+            let Ok(text) = std::str::from_utf8(&self.buf) else {
+                continue;
+            };
+            let start = text.len() - text.trim_start().len();
+            let end = text.trim_end().len();
+            if end <= start {
+                continue;
+            }
+            if let Ok(entry) = serde_json::from_slice(&self.buf[start..end]) {
+                if !complete {
+                    self.consumed += read;
+                }
+                let line = std::str::from_utf8(&self.buf[start..end]).ok()?;
+                return Some((entry, line));
+            }
+        }
+    }
 }
 
 /// Parse a timestamp string to ISO format.
@@ -27,22 +107,49 @@ fn parse_timestamp(ts: &str) -> Option<DateTime<Utc>> {
         })
 }
 
-/// Create a message ID from timestamp and index.
-/// Always includes index to ensure uniqueness even with same timestamps.
-fn make_msg_id(ts: &str, index: usize) -> String {
+/// Create a message ID from timestamp, index and content.
+/// The index keeps IDs unique within a session even with same timestamps,
+/// and the content hash ties each ID to its message, so re-syncing an
+/// unchanged file reproduces the same IDs.
+fn make_msg_id(ts: &str, index: usize, content: &str) -> String {
+    let hash = blake3::hash(content.as_bytes()).to_hex();
+    let hash = &hash[..MSG_ID_HASH_LEN];
     if ts.is_empty() {
-        format!("msg-{}", index)
+        format!("msg-{}-{}", index, hash)
     } else {
-        format!("msg-{}-{}", ts.replace([':', '.'], "-"), index)
+        format!("msg-{}-{}-{}", ts.replace([':', '.'], "-"), index, hash)
+    }
+}
+
+/// Hex digits of the content hash kept in a message ID.
+const MSG_ID_HASH_LEN: usize = 12;
+
+/// Shorten a message to a single-line session preview.
+fn preview_text(content: &str) -> String {
+    let truncated: String = content.chars().take(300).collect();
+    let mut summary = truncated.replace('\n', " ");
+    if content.len() > 300 {
+        summary.push_str("...");
     }
+    summary
+}
+
+/// Preview for a session with no user message, taken from its first assistant reply.
+fn assistant_preview(content: &str) -> String {
+    format!("[assistant] {}", preview_text(content))
 }
 
 /// Extract text content from Claude message content (string or array of blocks).
-fn extract_text_content(content: &Value, include_tools: bool) -> String {
+///
+/// Text and thinking blocks lead, in block order, followed by a blank line
+/// and then the tool markers in block order, so interleaved turns read as
+/// prose first and tool calls after.
+fn extract_text_content(content: &Value, include_tools: bool, verbosity: ToolVerbosity) -> String {
     match content {
         Value::String(s) => s.clone(),
         Value::Array(blocks) => {
             let mut texts = Vec::new();
+            let mut tools = Vec::new();
             for block in blocks {
                 if let Value::Object(obj) = block {
                     let block_type = obj.get("type").and_then(|v| v.as_str()).unwrap_or("");
@@ -57,27 +164,149 @@ fn extract_text_content(content: &Value, include_tools: bool) -> String {
                                 texts.push(format!("[Thinking]\n{}", thinking));
                             }
                         }
+                        "image" => texts.push(format_image(obj)),
                         "tool_use" if include_tools => {
-                            texts.push(format_tool_use(obj));
+                            tools.push(format_tool_use(obj, verbosity));
                         }
                         _ => {}
                     }
                 }
             }
-            texts.join("\n")
+            match (texts.is_empty(), tools.is_empty()) {
+                (_, true) => texts.join("\n"),
+                (true, false) => tools.join("\n"),
+                (false, false) => format!("{}\n\n{}", texts.join("\n"), tools.join("\n")),
+            }
+        }
+        _ => String::new(),
+    }
+}
+
+/// Placeholder for an image block giving its media type and decoded size,
+/// so the image data itself is never stored.
+fn format_image(block: &serde_json::Map<String, Value>) -> String {
+    let source = block.get("source");
+    let media_type = source.and_then(|s| s.get("media_type")).and_then(|v| v.as_str());
+    let data = source.and_then(|s| s.get("data")).and_then(|v| v.as_str());
+    match (media_type, data) {
+        (Some(media_type), Some(data)) => {
+            format!("[Image: {}, {} bytes]", media_type, base64_decoded_len(data))
         }
+        (Some(media_type), None) => format!("[Image: {}]", media_type),
+        (None, Some(data)) => format!("[Image: {} bytes]", base64_decoded_len(data)),
+        (None, None) => "[Image]".to_string(),
+    }
+}
+
+/// Number of bytes a base64 string decodes to.
+fn base64_decoded_len(data: &str) -> usize {
+    let data = data.trim_end();
+    let padding = data.bytes().rev().take_while(|&b| b == b'=').count().min(2);
+    (data.len() / 4 * 3).saturating_sub(padding)
+}
+
+/// The prose of Claude message content: the string itself, or its text
+/// blocks, without thinking or tool markers.
+fn prose_text(content: &Value) -> String {
+    match content {
+        Value::String(s) => s.clone(),
+        Value::Array(blocks) => blocks
+            .iter()
+            .filter(|block| block.get("type").and_then(|v| v.as_str()) == Some("text"))
+            .filter_map(|block| block.get("text").and_then(|v| v.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
         _ => String::new(),
     }
 }
 
+/// Maximum stored size of a tool's raw input JSON.
+const MAX_TOOL_INPUT_BYTES: usize = 64 * 1024;
+
+/// Maximum length of any single string value kept in an oversized tool input.
+const MAX_TOOL_INPUT_STRING: usize = 4 * 1024;
+
+/// Extract (tool name, formatted summary, raw input JSON) from the tool_use blocks of a message.
+fn extract_tool_calls(content: &Value) -> Vec<(String, String, Option<String>)> {
+    let Some(blocks) = content.as_array() else {
+        return Vec::new();
+    };
+
+    blocks
+        .iter()
+        .filter_map(|block| block.as_object())
+        .filter(|obj| obj.get("type").and_then(|v| v.as_str()) == Some("tool_use"))
+        .map(|obj| {
+            let name = obj.get("name").and_then(|v| v.as_str()).unwrap_or("unknown");
+            let input = obj.get("input").map(cap_tool_input);
+            (name.to_string(), format_tool_use(obj, ToolVerbosity::Normal), input)
+        })
+        .collect()
+}
+
+/// Serialize a tool input, truncating long string values if it exceeds the size cap.
+fn cap_tool_input(input: &Value) -> String {
+    fn truncate_strings(value: &mut Value) {
+        match value {
+            Value::String(s) if s.len() > MAX_TOOL_INPUT_STRING => {
+                let cut: String = s.chars().take(MAX_TOOL_INPUT_STRING).collect();
+                *s = format!("{}... [truncated]", cut);
+            }
+            Value::Array(items) => items.iter_mut().for_each(truncate_strings),
+            Value::Object(map) => map.values_mut().for_each(truncate_strings),
+            _ => {}
+        }
+    }
+
+    let json = input.to_string();
+    if json.len() <= MAX_TOOL_INPUT_BYTES {
+        return json;
+    }
+
+    let mut capped = input.clone();
+    truncate_strings(&mut capped);
+    let json = capped.to_string();
+    if json.len() <= MAX_TOOL_INPUT_BYTES {
+        json
+    } else {
+        r#"{"truncated":true}"#.to_string()
+    }
+}
+
+/// How much of each tool's input to render in message content.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolVerbosity {
+    /// A single header line per tool, e.g. `[Bash: description]`.
+    Minimal,
+    /// Header plus the key input, e.g. the Bash command or todo items.
+    #[default]
+    Normal,
+    /// Header plus the complete input JSON.
+    Full,
+}
+
 /// Format a tool_use block for display.
-fn format_tool_use(block: &serde_json::Map<String, Value>) -> String {
+fn format_tool_use(block: &serde_json::Map<String, Value>, verbosity: ToolVerbosity) -> String {
     let tool_name = block
         .get("name")
         .and_then(|v| v.as_str())
         .unwrap_or("unknown");
     let input = block.get("input").cloned().unwrap_or(Value::Object(Default::default()));
 
+    let normal = format_tool_summary(tool_name, &input);
+    match verbosity {
+        ToolVerbosity::Minimal => normal.lines().next().unwrap_or_default().to_string(),
+        ToolVerbosity::Normal => normal,
+        ToolVerbosity::Full => {
+            let header = normal.lines().next().unwrap_or_default();
+            format!("{}\n{}", header, cap_tool_input(&input))
+        }
+    }
+}
+
+/// Render a tool's header and key input.
+fn format_tool_summary(tool_name: &str, input: &Value) -> String {
     match tool_name {
         "Read" => {
             let path = input.get("file_path").and_then(|v| v.as_str()).unwrap_or("unknown");
@@ -91,6 +320,23 @@ fn format_tool_use(block: &serde_json::Map<String, Value>) -> String {
             let path = input.get("file_path").and_then(|v| v.as_str()).unwrap_or("unknown");
             format!("[Write: {}]", path)
         }
+        "MultiEdit" => {
+            let path = input.get("file_path").and_then(|v| v.as_str()).unwrap_or("unknown");
+            let count = input.get("edits").and_then(|v| v.as_array()).map_or(0, |e| e.len());
+            let noun = if count == 1 { "edit" } else { "edits" };
+            format!("[MultiEdit: {} ({} {})]", path, count, noun)
+        }
+        "WebFetch" => {
+            let url = input.get("url").and_then(|v| v.as_str()).unwrap_or("unknown");
+            match input.get("prompt").and_then(|v| v.as_str()) {
+                Some(prompt) => format!("[WebFetch: {} — \"{}\"]", url, prompt),
+                None => format!("[WebFetch: {}]", url),
+            }
+        }
+        "WebSearch" => {
+            let query = input.get("query").and_then(|v| v.as_str()).unwrap_or("");
+            format!("[WebSearch: {}]", query)
+        }
         "Bash" => {
             let cmd = input.get("command").and_then(|v| v.as_str()).unwrap_or("");
             let desc = input.get("description").and_then(|v| v.as_str());
@@ -135,34 +381,253 @@ fn format_tool_use(block: &serde_json::Map<String, Value>) -> String {
     }
 }
 
-/// Parse a Claude Code session file.
-pub fn parse_claude_session(path: &Path, project: &str, machine: &str) -> Option<ParsedSession> {
-    let session_id = path.file_stem()?.to_str()?.to_string();
+/// Which messages count toward a session's `message_count`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageCountPolicy {
+    /// User and assistant messages that carry prose. Turns made up only of
+    /// thinking or tool calls are still stored but not counted.
+    #[default]
+    Prose,
+    /// Every stored message.
+    All,
+}
+
+impl MessageCountPolicy {
+    /// Whether a stored message with this raw content counts.
+    fn counts(self, content: &Value) -> bool {
+        match self {
+            MessageCountPolicy::All => true,
+            MessageCountPolicy::Prose => has_prose(content),
+        }
+    }
+}
+
+/// `usage` fields counted as input tokens: fresh input plus cache writes and reads.
+const INPUT_TOKEN_FIELDS: [&str; 3] =
+    ["input_tokens", "cache_creation_input_tokens", "cache_read_input_tokens"];
+
+/// Add an assistant turn's `usage` to the running totals. A total stays
+/// None until some entry reports a field for it.
+fn add_usage(usage: &Value, input: &mut Option<i64>, output: &mut Option<i64>) {
+    let field = |name: &str| usage.get(name).and_then(|v| v.as_i64());
+    let inputs: Vec<i64> = INPUT_TOKEN_FIELDS.iter().filter_map(|name| field(name)).collect();
+    if !inputs.is_empty() {
+        *input = Some(input.unwrap_or(0) + inputs.iter().sum::<i64>());
+    }
+    if let Some(n) = field("output_tokens") {
+        *output = Some(output.unwrap_or(0) + n);
+    }
+}
+
+/// Model names seen in a session, to pick the one it mostly ran on.
+#[derive(Default)]
+struct ModelTally {
+    /// Model name to (uses, position of its last use).
+    seen: HashMap<String, (usize, usize)>,
+    uses: usize,
+}
+
+impl ModelTally {
+    fn record(&mut self, model: Option<&str>) {
+        // Claude marks locally generated turns (e.g. API errors) "<synthetic>"
+        let Some(model) = model.filter(|m| !m.is_empty() && *m != "<synthetic>") else {
+            return;
+        };
+        self.uses += 1;
+        let entry = self.seen.entry(model.to_string()).or_default();
+        *entry = (entry.0 + 1, self.uses);
+    }
+
+    /// The most used model; a tie goes to the one used last.
+    fn dominant(self) -> Option<String> {
+        self.seen
+            .into_iter()
+            .max_by_key(|(_, counts)| *counts)
+            .map(|(model, _)| model)
+    }
+}
+
+/// Follow `parent` up past entries that produced no message (metadata, tool
+/// results, folded repeats) to the nearest ancestor that did.
+fn nearest_message_parent(
+    parent: Option<&str>,
+    links: &HashMap<String, Option<String>>,
+    emitted: &HashSet<String>,
+) -> Option<String> {
+    let mut current = parent?;
+    // Bounded so a malformed cycle can't loop forever
+    for _ in 0..=links.len() {
+        if emitted.contains(current) {
+            return Some(current.to_string());
+        }
+        current = links.get(current)?.as_deref()?;
+    }
+    None
+}
+
+/// Whether message content contains non-empty text outside thinking and tool blocks.
+fn has_prose(content: &Value) -> bool {
+    match content {
+        Value::String(s) => !s.trim().is_empty(),
+        Value::Array(blocks) => blocks.iter().any(|block| {
+            block.get("type").and_then(|v| v.as_str()) == Some("text")
+                && block
+                    .get("text")
+                    .and_then(|v| v.as_str())
+                    .is_some_and(|t| !t.trim().is_empty())
+        }),
+        _ => false,
+    }
+}
+
+/// Count a message as a repeat of the previous one when it has the same
+/// role, thread and content. Returns whether it was folded.
+fn fold_repeat(messages: &mut [Message], role: &str, content: &str, is_sidechain: bool) -> bool {
+    match messages.last_mut() {
+        Some(last)
+            if last.role == role && last.is_sidechain == is_sidechain && last.content == content =>
+        {
+            last.repeat_count += 1;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Options controlling how session files are parsed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParseOptions {
+    /// Keep sidechain (subagent) entries, tagged with `is_sidechain`.
+    pub include_sidechains: bool,
+    /// How much tool input to render into message content.
+    pub tool_verbosity: ToolVerbosity,
+    /// Which messages count toward `message_count`.
+    pub count_policy: MessageCountPolicy,
+    /// Fold consecutive identical messages (e.g. a prompt resent after a
+    /// failed request) into the first, counting the copies in `repeat_count`.
+    pub collapse_repeats: bool,
+    /// Keep each message's original JSON entry, for debugging extraction.
+    pub store_raw: bool,
+    /// Keep Codex function calls and their outputs as messages.
+    pub include_tools: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            include_sidechains: true,
+            tool_verbosity: ToolVerbosity::default(),
+            count_policy: MessageCountPolicy::default(),
+            collapse_repeats: false,
+            store_raw: false,
+            include_tools: true,
+        }
+    }
+}
+
+/// Parse a Claude Code session file with the given options.
+pub fn parse_claude_session_with(
+    path: &Path,
+    project: &str,
+    machine: &str,
+    options: &ParseOptions,
+) -> Option<ParsedSession> {
+    let session_id = session_file_stem(path)?.to_string();
 
     // Skip agent files
     if session_id.starts_with("agent-") {
         return None;
     }
 
-    let file = File::open(path).ok()?;
+    let reader = open_session_file(path).ok()?;
+    let mut parsed = parse_claude_entries(reader, session_id, project, machine, options, None)?;
+    // Offsets into a decompressed stream can't be resumed from
+    if path.extension().is_some_and(|e| e == "gz") {
+        parsed.metadata.parsed_offset = None;
+    }
+    Some(parsed)
+}
+
+/// Where a parse of lines appended to a Claude session file picks up.
+#[derive(Debug, Clone, Default)]
+pub struct ClaudeResume {
+    /// Byte offset the previous parse stopped at.
+    pub offset: u64,
+    /// Messages already stored, so new message ids continue the sequence.
+    pub message_index: usize,
+    /// Next tool call position.
+    pub tool_seq: i32,
+    /// uuids of the stored messages, for parent links and replay detection.
+    pub uuids: Vec<String>,
+    /// API response ids of the stored messages, so a response split across
+    /// the offset doesn't count its usage twice.
+    pub response_ids: Vec<String>,
+}
+
+/// Parse only the lines appended to a plain Claude session file since
+/// `resume.offset`. The metadata describes just those lines.
+///
+/// Returns None if the file can't be read, or if an appended message's
+/// parent can't be traced to a stored message, in which case only a full
+/// parse links it correctly.
+pub fn parse_claude_session_from(
+    path: &Path,
+    project: &str,
+    machine: &str,
+    options: &ParseOptions,
+    resume: &ClaudeResume,
+) -> Option<ParsedSession> {
+    let session_id = session_file_stem(path)?.to_string();
+    let mut file = File::open(path).ok()?;
+    file.seek(SeekFrom::Start(resume.offset)).ok()?;
     let reader = BufReader::new(file);
+    parse_claude_entries(reader, session_id, project, machine, options, Some(resume))
+}
 
+/// Parse Claude entries from `reader`, continuing from `resume` if given.
+fn parse_claude_entries(
+    reader: impl BufRead,
+    session_id: String,
+    project: &str,
+    machine: &str,
+    options: &ParseOptions,
+    resume: Option<&ClaudeResume>,
+) -> Option<ParsedSession> {
     let mut messages = Vec::new();
+    let mut raw_messages = Vec::new();
     let mut first_message: Option<String> = None;
+    let mut summary: Option<String> = None;
     let mut started_at: Option<DateTime<Utc>> = None;
     let mut ended_at: Option<DateTime<Utc>> = None;
+    let mut git_branch: Option<String> = None;
+    let mut parent_session_id: Option<String> = None;
+    let mut reasoning_effort: Option<String> = None;
+    let mut cwd: Option<String> = None;
+    let mut first_assistant: Option<String> = None;
+    let mut total_input_tokens: Option<i64> = None;
+    let mut total_output_tokens: Option<i64> = None;
+    let mut usage_ids: HashSet<String> = HashSet::new();
+    let mut models = ModelTally::default();
+    // Every entry's uuid to its parentUuid, and the uuids that became messages
+    let mut links: HashMap<String, Option<String>> = HashMap::new();
+    let mut emitted: HashSet<String> = HashSet::new();
+    let mut tool_seq = 0;
+    let mut message_count = 0;
+    let mut base_index = 0;
+    if let Some(resume) = resume {
+        for uuid in &resume.uuids {
+            links.insert(uuid.clone(), None);
+            emitted.insert(uuid.clone());
+        }
+        usage_ids.extend(resume.response_ids.iter().cloned());
+        tool_seq = resume.tool_seq;
+        base_index = resume.message_index;
+    }
 
-    for line in reader.lines() {
-        let line = match line {
-            Ok(l) if !l.trim().is_empty() => l,
-            _ => continue,
-        };
-
-        let entry: Value = match serde_json::from_str(&line) {
-            Ok(v) => v,
-            Err(_) => continue,
-        };
-
+    let mut entries = JsonlEntries::new(reader);
+    while let Some((entry, line)) = entries.next_entry() {
         // Extract timestamp
         let ts_str = entry
             .get("timestamp")
@@ -177,70 +642,248 @@ pub fn parse_claude_session(path: &Path, project: &str, machine: &str) -> Option
             ended_at = Some(ts);
         }
 
+        // The branch can change mid-session; keep the latest one recorded
+        if let Some(branch) = entry
+            .get("gitBranch")
+            .and_then(|v| v.as_str())
+            .filter(|b| !b.is_empty())
+        {
+            git_branch = Some(branch.to_string());
+        }
+
+        if cwd.is_none() {
+            cwd = entry
+                .get("cwd")
+                .and_then(|v| v.as_str())
+                .filter(|c| !c.is_empty())
+                .map(String::from);
+        }
+
+        if reasoning_effort.is_none() {
+            reasoning_effort = entry
+                .get("thinkingMetadata")
+                .and_then(|m| m.get("level"))
+                .and_then(|v| v.as_str())
+                .map(String::from);
+        }
+
+        // Summaries are written as the conversation moves on; the latest is most current
+        if entry.get("type").and_then(|v| v.as_str()) == Some("summary") {
+            if let Some(text) = entry
+                .get("summary")
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+            {
+                summary = Some(text.to_string());
+            }
+            continue;
+        }
+
+        // A forked session replays its parent's history under the parent's id
+        if parent_session_id.is_none() {
+            parent_session_id = entry
+                .get("sessionId")
+                .and_then(|v| v.as_str())
+                .filter(|id| !id.is_empty() && *id != session_id)
+                .map(String::from);
+        }
+
+        let uuid = entry.get("uuid").and_then(|v| v.as_str()).map(String::from);
+        let parent = entry.get("parentUuid").and_then(|v| v.as_str());
+        if let Some(uuid) = &uuid {
+            // Resumed and compacted sessions replay earlier entries; keep the first
+            if links.contains_key(uuid) {
+                continue;
+            }
+            links.insert(uuid.clone(), parent.map(String::from));
+        }
+
+        // Meta entries (e.g. injected command output) aren't part of the transcript
+        if entry.get("isMeta").and_then(|v| v.as_bool()) == Some(true) {
+            continue;
+        }
+
+        // Usage is spend whether or not the turn is shown. A response split
+        // across several entries repeats its usage and model under one id.
+        if entry.get("type").and_then(|v| v.as_str()) == Some("assistant") {
+            let msg_data = entry.get("message").unwrap_or(&Value::Null);
+            let first_seen = match msg_data.get("id").and_then(|v| v.as_str()) {
+                Some(id) => usage_ids.insert(id.to_string()),
+                None => true,
+            };
+            if first_seen {
+                if let Some(usage) = msg_data.get("usage") {
+                    add_usage(usage, &mut total_input_tokens, &mut total_output_tokens);
+                }
+                models.record(msg_data.get("model").and_then(|v| v.as_str()));
+            }
+        }
+
+        let is_sidechain = entry.get("isSidechain").and_then(|v| v.as_bool()) == Some(true);
+        if is_sidechain && !options.include_sidechains {
+            continue;
+        }
+
         let entry_type = entry.get("type").and_then(|v| v.as_str()).unwrap_or("");
 
         match entry_type {
             "user" => {
                 let msg_data = entry.get("message").unwrap_or(&Value::Null);
                 let content_val = msg_data.get("content").unwrap_or(&Value::Null);
-                let content = extract_text_content(content_val, true);
+                let content = extract_text_content(content_val, true, options.tool_verbosity);
 
                 if !content.trim().is_empty() {
-                    if first_message.is_none() {
-                        let truncated: String = content.chars().take(300).collect();
-                        let mut summary = truncated.replace('\n', " ");
-                        if content.len() > 300 {
-                            summary.push_str("...");
-                        }
-                        first_message = Some(summary);
+                    if options.collapse_repeats
+                        && fold_repeat(&mut messages, "user", &content, is_sidechain)
+                    {
+                        continue;
+                    }
+                    // Titles come from prose, never from a tool marker
+                    if first_message.is_none() && !is_sidechain && has_prose(content_val) {
+                        first_message = Some(preview_text(&prose_text(content_val)));
                     }
 
+                    let parent_uuid = nearest_message_parent(parent, &links, &emitted);
+                    if resume.is_some() && parent.is_some() && parent_uuid.is_none() {
+                        return None;
+                    }
+                    if options.count_policy.counts(content_val) {
+                        message_count += 1;
+                    }
+                    let msg_id = make_msg_id(ts_str, base_index + messages.len(), &content);
+                    if options.store_raw {
+                        raw_messages.push((msg_id.clone(), line.to_string()));
+                    }
                     messages.push(Message {
-                        msg_id: make_msg_id(ts_str, messages.len()),
+                        msg_id,
                         session_id: session_id.clone(),
                         role: "user".to_string(),
                         content,
                         timestamp: ts_str.to_string(),
+                        is_sidechain,
+                        repeat_count: 0,
+                        uuid: uuid.clone(),
+                        parent_uuid,
+                        response_id: None,
+                        is_tool_only: false,
+                        tool_calls: None,
                     });
+                    emitted.extend(uuid);
                 }
             }
             "assistant" => {
                 let msg_data = entry.get("message").unwrap_or(&Value::Null);
                 let content_val = msg_data.get("content").unwrap_or(&Value::Null);
-                let content = extract_text_content(content_val, true);
+                let content = extract_text_content(content_val, true, options.tool_verbosity);
 
                 if !content.trim().is_empty() {
+                    if options.collapse_repeats
+                        && fold_repeat(&mut messages, "assistant", &content, is_sidechain)
+                    {
+                        continue;
+                    }
+                    if first_assistant.is_none() && !is_sidechain && has_prose(content_val) {
+                        first_assistant = Some(assistant_preview(&prose_text(content_val)));
+                    }
+                    let parent_uuid = nearest_message_parent(parent, &links, &emitted);
+                    if resume.is_some() && parent.is_some() && parent_uuid.is_none() {
+                        return None;
+                    }
+                    let msg_id = make_msg_id(ts_str, base_index + messages.len(), &content);
+                    let tool_calls: Vec<ToolCall> = extract_tool_calls(content_val)
+                        .into_iter()
+                        .map(|(tool_name, summary, tool_input_json)| {
+                            tool_seq += 1;
+                            ToolCall {
+                                session_id: session_id.clone(),
+                                msg_id: msg_id.clone(),
+                                seq: tool_seq - 1,
+                                tool_name,
+                                summary,
+                                tool_input_json,
+                            }
+                        })
+                        .collect();
+
+                    if options.count_policy.counts(content_val) {
+                        message_count += 1;
+                    }
+                    if options.store_raw {
+                        raw_messages.push((msg_id.clone(), line.to_string()));
+                    }
                     messages.push(Message {
-                        msg_id: make_msg_id(ts_str, messages.len()),
+                        msg_id,
                         session_id: session_id.clone(),
                         role: "assistant".to_string(),
                         content,
                         timestamp: ts_str.to_string(),
+                        is_sidechain,
+                        repeat_count: 0,
+                        uuid: uuid.clone(),
+                        parent_uuid,
+                        response_id: msg_data.get("id").and_then(|v| v.as_str()).map(String::from),
+                        is_tool_only: !tool_calls.is_empty() && !has_prose(content_val),
+                        tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
                     });
+                    emitted.extend(uuid);
                 }
             }
             _ => {}
         }
     }
 
+    let first_message = first_message.or(first_assistant);
+
+    // The recorded cwd names the project unambiguously; the encoded
+    // directory name passed in is only a fallback
+    let project = cwd
+        .as_deref()
+        .and_then(project_from_cwd)
+        .unwrap_or_else(|| project.to_string());
+
     let metadata = Session {
         session_id,
-        project: project.to_string(),
+        project,
         machine: machine.to_string(),
         first_message,
+        summary,
         started_at: started_at.map(|dt| dt.to_rfc3339()),
         ended_at: ended_at.map(|dt| dt.to_rfc3339()),
-        message_count: messages.len() as i32,
+        message_count,
         file_size: None,
         file_hash: None,
+        file_mtime: None,
+        source_path: None,
         agent: "claude".to_string(),
+        git_branch,
+        parent_session_id,
+        content_purged: false,
+        reasoning_effort,
+        cwd,
+        model: models.dominant(),
+        total_input_tokens,
+        total_output_tokens,
+        parsed_offset: Some((resume.map_or(0, |r| r.offset) + entries.consumed) as i64),
+        starred: false,
+        tags: Vec::new(),
+        is_new_since_last_open: false,
     };
 
-    Some(ParsedSession { metadata, messages })
+    Some(ParsedSession {
+        metadata,
+        messages,
+        raw_messages,
+    })
+}
+
+/// Name a Claude project after the last component of its working directory.
+pub(crate) fn project_from_cwd(cwd: &str) -> Option<String> {
+    Path::new(cwd).file_name().map(|n| n.to_string_lossy().into_owned())
 }
 
-/// Extract project name from Codex cwd path.
-fn extract_codex_project(cwd: &str) -> String {
+/// Extract project name from a Codex or Gemini cwd path.
+pub(crate) fn extract_codex_project(cwd: &str) -> String {
     if cwd.is_empty() {
         return "unknown".to_string();
     }
@@ -251,10 +894,105 @@ fn extract_codex_project(cwd: &str) -> String {
         .to_string()
 }
 
-/// Parse a Codex session file.
-pub fn parse_codex_session(path: &Path, machine: &str, include_exec: bool) -> Option<ParsedSession> {
-    let file = File::open(path).ok()?;
-    let reader = BufReader::new(file);
+/// Extract a Codex project name that includes the cwd's parent directory,
+/// for telling apart projects whose final components match.
+pub(crate) fn extract_codex_project_with_parent(cwd: &str) -> String {
+    let project = extract_codex_project(cwd);
+    match Path::new(cwd).parent().and_then(|p| p.file_name()).and_then(|n| n.to_str()) {
+        Some(parent) => format!("{}/{}", parent, project),
+        None => project,
+    }
+}
+
+/// Markers that open a system-injected context block in Codex user turns.
+const CODEX_INJECTED_MARKERS: &[&str] = &[
+    "# AGENTS.md",
+    "<environment_context>",
+    "<INSTRUCTIONS>",
+    "<user_instructions>",
+];
+
+/// Whether a Codex user message is an injected context block rather than a prompt.
+fn is_codex_injected_context(content: &str) -> bool {
+    let trimmed = content.trim_start();
+    CODEX_INJECTED_MARKERS.iter().any(|m| trimmed.starts_with(m))
+}
+
+/// Hash message content for in-session de-duplication.
+fn content_hash(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Maximum length of a Codex tool output kept in message content, in chars.
+const MAX_CODEX_TOOL_OUTPUT: usize = 4 * 1024;
+
+/// Render a Codex `function_call` as an assistant message carrying its
+/// tool call, e.g. `[Tool: shell]` followed by the call's arguments.
+fn codex_function_call(payload: &Value, ts: &str, index: usize, seq: i32) -> Message {
+    let name = payload.get("name").and_then(|v| v.as_str()).unwrap_or("unknown");
+    let arguments = payload.get("arguments").and_then(|v| v.as_str()).unwrap_or("");
+    let content = if arguments.is_empty() {
+        format!("[Tool: {}]", name)
+    } else {
+        format!("[Tool: {}]\n{}", name, arguments)
+    };
+    let tool_input_json = serde_json::from_str::<Value>(arguments)
+        .ok()
+        .map(|input| cap_tool_input(&input));
+    let msg_id = make_msg_id(ts, index, &content);
+
+    Message {
+        tool_calls: Some(vec![ToolCall {
+            session_id: String::new(),
+            msg_id: msg_id.clone(),
+            seq,
+            tool_name: name.to_string(),
+            summary: content.clone(),
+            tool_input_json,
+        }]),
+        msg_id,
+        role: "assistant".to_string(),
+        content,
+        timestamp: ts.to_string(),
+        is_tool_only: true,
+        ..Default::default()
+    }
+}
+
+/// Text of a Codex `function_call_output`, truncated. Shell outputs wrap the
+/// text in a JSON object alongside exit metadata.
+fn codex_function_output(payload: &Value) -> Option<String> {
+    let output = match payload.get("output")? {
+        Value::String(output) => serde_json::from_str::<Value>(output)
+            .ok()
+            .and_then(|v| v.get("output").and_then(|o| o.as_str()).map(String::from))
+            .unwrap_or_else(|| output.clone()),
+        Value::Object(obj) => obj.get("output").and_then(|o| o.as_str())?.to_string(),
+        _ => return None,
+    };
+    if output.trim().is_empty() {
+        return None;
+    }
+    if output.chars().count() > MAX_CODEX_TOOL_OUTPUT {
+        let cut: String = output.chars().take(MAX_CODEX_TOOL_OUTPUT).collect();
+        Some(format!("{}... [truncated]", cut))
+    } else {
+        Some(output)
+    }
+}
+
+/// Parse a Codex session file. `function_call` items become assistant
+/// messages and their outputs `tool` messages when `options.include_tools`
+/// is set.
+pub fn parse_codex_session_with(
+    path: &Path,
+    machine: &str,
+    include_exec: bool,
+    options: &ParseOptions,
+) -> Option<ParsedSession> {
+    let reader = open_session_file(path).ok()?;
 
     let mut messages = Vec::new();
     let mut first_message: Option<String> = None;
@@ -262,18 +1000,18 @@ pub fn parse_codex_session(path: &Path, machine: &str, include_exec: bool) -> Op
     let mut ended_at: Option<DateTime<Utc>> = None;
     let mut session_id: Option<String> = None;
     let mut project = "unknown".to_string();
-
-    for line in reader.lines() {
-        let line = match line {
-            Ok(l) if !l.trim().is_empty() => l,
-            _ => continue,
-        };
-
-        let entry: Value = match serde_json::from_str(&line) {
-            Ok(v) => v,
-            Err(_) => continue,
-        };
-
+    let mut git_branch: Option<String> = None;
+    let mut reasoning_effort: Option<String> = None;
+    let mut cwd: Option<String> = None;
+    let mut first_assistant: Option<String> = None;
+    let mut parent_session_id: Option<String> = None;
+    let mut seen_injected: HashSet<u64> = HashSet::new();
+    let mut models = ModelTally::default();
+    let mut tool_seq = 0;
+    let mut message_count = 0;
+
+    let mut entries = JsonlEntries::new(reader);
+    while let Some((entry, _)) = entries.next_entry() {
         let entry_type = entry.get("type").and_then(|v| v.as_str()).unwrap_or("");
         let payload = entry.get("payload").unwrap_or(&Value::Null);
         let ts_str = entry.get("timestamp").and_then(|v| v.as_str()).unwrap_or("");
@@ -288,8 +1026,25 @@ pub fn parse_codex_session(path: &Path, machine: &str, include_exec: bool) -> Op
         match entry_type {
             "session_meta" => {
                 session_id = payload.get("id").and_then(|v| v.as_str()).map(String::from);
-                let cwd = payload.get("cwd").and_then(|v| v.as_str()).unwrap_or("");
-                project = extract_codex_project(cwd);
+                let session_cwd = payload.get("cwd").and_then(|v| v.as_str()).unwrap_or("");
+                project = extract_codex_project(session_cwd);
+                cwd = Some(session_cwd).filter(|c| !c.is_empty()).map(String::from);
+                git_branch = payload
+                    .get("git")
+                    .and_then(|g| g.get("branch"))
+                    .and_then(|v| v.as_str())
+                    .map(String::from);
+                // A forked or resumed rollout names the conversation it continues
+                parent_session_id = payload
+                    .get("forked_from_id")
+                    .and_then(|v| v.as_str())
+                    .filter(|id| !id.is_empty())
+                    .map(|id| format!("codex:{}", id));
+                reasoning_effort = payload
+                    .get("reasoning_effort")
+                    .and_then(|v| v.as_str())
+                    .map(String::from);
+                models.record(payload.get("model").and_then(|v| v.as_str()));
 
                 // Check originator - skip codex_exec unless explicitly included
                 let originator = payload.get("originator").and_then(|v| v.as_str()).unwrap_or("");
@@ -297,60 +1052,104 @@ pub fn parse_codex_session(path: &Path, machine: &str, include_exec: bool) -> Op
                     return None;
                 }
             }
+            "turn_context" => {
+                models.record(payload.get("model").and_then(|v| v.as_str()));
+                // Later turns may change the effort; keep the session's initial setting
+                if reasoning_effort.is_none() {
+                    reasoning_effort = payload
+                        .get("effort")
+                        .and_then(|v| v.as_str())
+                        .map(String::from);
+                }
+            }
             "response_item" => {
+                match payload.get("type").and_then(|v| v.as_str()) {
+                    Some("function_call") if options.include_tools => {
+                        messages.push(codex_function_call(
+                            payload,
+                            ts_str,
+                            messages.len(),
+                            tool_seq,
+                        ));
+                        tool_seq += 1;
+                        continue;
+                    }
+                    Some("function_call_output") if options.include_tools => {
+                        if let Some(content) = codex_function_output(payload) {
+                            messages.push(Message {
+                                msg_id: make_msg_id(ts_str, messages.len(), &content),
+                                role: "tool".to_string(),
+                                content,
+                                timestamp: ts_str.to_string(),
+                                ..Default::default()
+                            });
+                        }
+                        continue;
+                    }
+                    _ => {}
+                }
+
                 let role = payload.get("role").and_then(|v| v.as_str()).unwrap_or("");
                 if role != "user" && role != "assistant" {
                     continue;
                 }
 
-                let content_blocks = payload.get("content").and_then(|v| v.as_array());
-                let mut texts = Vec::new();
-
-                if let Some(blocks) = content_blocks {
-                    for block in blocks {
-                        if let Some(obj) = block.as_object() {
-                            let block_type = obj.get("type").and_then(|v| v.as_str()).unwrap_or("");
-                            if matches!(block_type, "input_text" | "output_text" | "text") {
-                                if let Some(text) = obj.get("text").and_then(|v| v.as_str()) {
-                                    if !text.is_empty() {
-                                        texts.push(text.to_string());
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+                let texts: Vec<&str> = match payload.get("content") {
+                    // Some payloads carry the text directly instead of a block list
+                    Some(Value::String(text)) => vec![text.as_str()],
+                    Some(Value::Array(blocks)) => blocks
+                        .iter()
+                        .filter_map(|block| block.as_object())
+                        .filter(|obj| {
+                            matches!(
+                                obj.get("type").and_then(|v| v.as_str()),
+                                Some("input_text" | "output_text" | "text")
+                            )
+                        })
+                        .filter_map(|obj| obj.get("text").and_then(|v| v.as_str()))
+                        .filter(|text| !text.is_empty())
+                        .collect(),
+                    _ => Vec::new(),
+                };
 
                 let content = texts.join("\n");
                 if content.trim().is_empty() {
                     continue;
                 }
 
-                // Skip system/instruction messages
-                if role == "user"
-                    && (content.starts_with("# AGENTS.md")
-                        || content.starts_with("<environment_context>")
-                        || content.starts_with("<INSTRUCTIONS>"))
-                {
+                // Skip system/instruction messages, and keep only the first copy of
+                // context blocks that Codex re-injects with a leading preamble
+                if role == "user" {
+                    if is_codex_injected_context(&content) {
+                        continue;
+                    }
+                    if CODEX_INJECTED_MARKERS.iter().any(|m| content.contains(m))
+                        && !seen_injected.insert(content_hash(&content))
+                    {
+                        continue;
+                    }
+                }
+
+                if options.collapse_repeats && fold_repeat(&mut messages, role, &content, false) {
                     continue;
                 }
 
-                // Capture first user message
+                // Capture first user message, or the first reply as a fallback
                 if role == "user" && first_message.is_none() {
-                    let truncated: String = content.chars().take(300).collect();
-                    let mut summary = truncated.replace('\n', " ");
-                    if content.len() > 300 {
-                        summary.push_str("...");
-                    }
-                    first_message = Some(summary);
+                    first_message = Some(preview_text(&content));
+                }
+                if role == "assistant" && first_assistant.is_none() {
+                    first_assistant = Some(assistant_preview(&content));
                 }
 
+                message_count += 1;
                 messages.push(Message {
-                    msg_id: make_msg_id(ts_str, messages.len()),
+                    msg_id: make_msg_id(ts_str, messages.len(), &content),
                     session_id: String::new(), // Will be set below
                     role: role.to_string(),
                     content,
                     timestamp: ts_str.to_string(),
+                    ..Default::default()
                 });
             }
             _ => {}
@@ -359,7 +1158,9 @@ pub fn parse_codex_session(path: &Path, machine: &str, include_exec: bool) -> Op
 
     // Fallback session_id from filename
     let final_session_id = session_id
-        .unwrap_or_else(|| path.file_stem().unwrap_or_default().to_string_lossy().to_string());
+        .unwrap_or_else(|| session_file_stem(path).unwrap_or_default().to_string());
+
+    let first_message = first_message.or(first_assistant);
 
     // Prefix with "codex:" to avoid collision
     let prefixed_id = format!("codex:{}", final_session_id);
@@ -367,6 +1168,9 @@ pub fn parse_codex_session(path: &Path, machine: &str, include_exec: bool) -> Op
     // Update message session_ids
     for msg in &mut messages {
         msg.session_id = prefixed_id.clone();
+        for call in msg.tool_calls.iter_mut().flatten() {
+            call.session_id = prefixed_id.clone();
+        }
     }
 
     let metadata = Session {
@@ -374,20 +1178,189 @@ pub fn parse_codex_session(path: &Path, machine: &str, include_exec: bool) -> Op
         project,
         machine: machine.to_string(),
         first_message,
+        summary: None,
         started_at: started_at.map(|dt| dt.to_rfc3339()),
         ended_at: ended_at.map(|dt| dt.to_rfc3339()),
-        message_count: messages.len() as i32,
+        // Only messages from text blocks are prose; tool calls and outputs aren't counted
+        message_count,
         file_size: None,
         file_hash: None,
+        file_mtime: None,
+        source_path: None,
         agent: "codex".to_string(),
+        git_branch,
+        parent_session_id,
+        content_purged: false,
+        reasoning_effort,
+        cwd,
+        model: models.dominant(),
+        total_input_tokens: None,
+        total_output_tokens: None,
+        parsed_offset: None,
+        starred: false,
+        tags: Vec::new(),
+        is_new_since_last_open: false,
+    };
+
+    Some(ParsedSession {
+        metadata,
+        messages,
+        raw_messages: Vec::new(),
+    })
+}
+
+/// Parse a Gemini CLI `logs.json`. The log is a JSON array holding every
+/// session recorded for one project, so one session is returned per
+/// `sessionId`, in order of first appearance. Each session's `file_size` and
+/// `file_hash` cover only its own entries, so it can be told apart from
+/// changes to the others.
+pub fn parse_gemini_sessions(path: &Path, machine: &str) -> Option<Vec<ParsedSession>> {
+    let file = File::open(path).ok()?;
+    let entries: Vec<Value> = serde_json::from_reader(BufReader::new(file)).ok()?;
+
+    let mut order: Vec<&str> = Vec::new();
+    let mut by_session: HashMap<&str, Vec<&Value>> = HashMap::new();
+    for entry in &entries {
+        let Some(id) = entry.get("sessionId").and_then(|v| v.as_str()).filter(|id| !id.is_empty())
+        else {
+            continue;
+        };
+        by_session
+            .entry(id)
+            .or_insert_with(|| {
+                order.push(id);
+                Vec::new()
+            })
+            .push(entry);
+    }
+
+    Some(
+        order
+            .into_iter()
+            .map(|id| parse_gemini_entries(id, &by_session[id], machine))
+            .collect(),
+    )
+}
+
+/// Build one Gemini session from its log entries.
+fn parse_gemini_entries(id: &str, entries: &[&Value], machine: &str) -> ParsedSession {
+    // Prefix with "gemini:" to avoid collision
+    let session_id = format!("gemini:{}", id);
+    let mut messages = Vec::new();
+    let mut first_message: Option<String> = None;
+    let mut first_assistant: Option<String> = None;
+    let mut started_at: Option<DateTime<Utc>> = None;
+    let mut ended_at: Option<DateTime<Utc>> = None;
+    let mut cwd: Option<String> = None;
+    let mut models = ModelTally::default();
+
+    for entry in entries {
+        let ts_str = entry.get("timestamp").and_then(|v| v.as_str()).unwrap_or("");
+        if let Some(ts) = parse_timestamp(ts_str) {
+            started_at = Some(started_at.map_or(ts, |s| s.min(ts)));
+            ended_at = Some(ended_at.map_or(ts, |e| e.max(ts)));
+        }
+        if cwd.is_none() {
+            cwd = entry
+                .get("cwd")
+                .and_then(|v| v.as_str())
+                .filter(|c| !c.is_empty())
+                .map(String::from);
+        }
+
+        let role = match entry.get("type").and_then(|v| v.as_str()) {
+            Some("user") => "user",
+            Some("model") | Some("gemini") => "assistant",
+            _ => continue,
+        };
+        if role == "assistant" {
+            models.record(entry.get("model").and_then(|v| v.as_str()));
+        }
+        let content = entry
+            .get("message")
+            .or_else(|| entry.get("content"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("");
+        if content.trim().is_empty() {
+            continue;
+        }
+
+        if role == "user" && first_message.is_none() {
+            first_message = Some(preview_text(content));
+        }
+        if role == "assistant" && first_assistant.is_none() {
+            first_assistant = Some(assistant_preview(content));
+        }
+        messages.push(Message {
+            msg_id: make_msg_id(ts_str, messages.len(), content),
+            session_id: session_id.clone(),
+            role: role.to_string(),
+            content: content.to_string(),
+            timestamp: ts_str.to_string(),
+            ..Default::default()
+        });
+    }
+
+    let mut hasher = blake3::Hasher::new();
+    let mut size = 0;
+    for entry in entries {
+        let json = entry.to_string();
+        size += json.len();
+        hasher.update(json.as_bytes());
+    }
+
+    let metadata = Session {
+        session_id,
+        project: extract_codex_project(cwd.as_deref().unwrap_or("")),
+        machine: machine.to_string(),
+        first_message: first_message.or(first_assistant),
+        summary: None,
+        started_at: started_at.map(|dt| dt.to_rfc3339()),
+        ended_at: ended_at.map(|dt| dt.to_rfc3339()),
+        // Gemini log entries are plain text, so all of them are prose
+        message_count: messages.len() as i32,
+        file_size: Some(size as i64),
+        file_hash: Some(hasher.finalize().to_hex().to_string()),
+        file_mtime: None,
+        source_path: None,
+        agent: "gemini".to_string(),
+        git_branch: None,
+        parent_session_id: None,
+        content_purged: false,
+        reasoning_effort: None,
+        cwd,
+        model: models.dominant(),
+        total_input_tokens: None,
+        total_output_tokens: None,
+        parsed_offset: None,
+        starred: false,
+        tags: Vec::new(),
+        is_new_since_last_open: false,
     };
 
-    Some(ParsedSession { metadata, messages })
+    ParsedSession {
+        metadata,
+        messages,
+        raw_messages: Vec::new(),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    /// Parse a Claude session file with default options.
+    fn parse_claude_default(path: &Path, project: &str, machine: &str) -> Option<ParsedSession> {
+        parse_claude_session_with(path, project, machine, &ParseOptions::default())
+    }
+
+    fn parse_codex_session(
+        path: &Path,
+        machine: &str,
+        include_exec: bool,
+    ) -> Option<ParsedSession> {
+        parse_codex_session_with(path, machine, include_exec, &ParseOptions::default())
+    }
     use std::fs;
     use tempfile::tempdir;
 
@@ -401,7 +1374,7 @@ mod tests {
 
         fs::write(&session_file, content).unwrap();
 
-        let result = parse_claude_session(&session_file, "test-project", "local");
+        let result = parse_claude_default(&session_file, "test-project", "local");
         assert!(result.is_some());
 
         let parsed = result.unwrap();
@@ -423,7 +1396,7 @@ mod tests {
         let content = r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Hello"}}"#;
         fs::write(&session_file, content).unwrap();
 
-        let result = parse_claude_session(&session_file, "test-project", "local");
+        let result = parse_claude_default(&session_file, "test-project", "local");
         assert!(result.is_none());
     }
 
@@ -435,7 +1408,7 @@ mod tests {
         let content = r#"{"type":"assistant","timestamp":"2026-01-08T10:00:00Z","message":{"content":[{"type":"text","text":"Let me read that file."},{"type":"tool_use","name":"Read","input":{"file_path":"/path/to/file.txt"}}]}}"#;
         fs::write(&session_file, content).unwrap();
 
-        let result = parse_claude_session(&session_file, "test-project", "local");
+        let result = parse_claude_default(&session_file, "test-project", "local");
         assert!(result.is_some());
 
         let parsed = result.unwrap();
@@ -445,9 +1418,564 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_codex_session_basic() {
+    fn test_interleaved_blocks_put_prose_first() {
         let tmp = tempdir().unwrap();
-        let session_file = tmp.path().join("rollout-2026-01-08-abc123.jsonl");
+        let session_file = tmp.path().join("test-session.jsonl");
+
+        let content = r#"{"type":"assistant","timestamp":"2026-01-08T10:00:00Z","message":{"content":[{"type":"tool_use","name":"Read","input":{"file_path":"/src/lib.rs"}},{"type":"text","text":"Found the bug."},{"type":"tool_use","name":"Bash","input":{"command":"cargo test"}},{"type":"text","text":"Tests pass."}]}}"#;
+        fs::write(&session_file, content).unwrap();
+
+        let parsed = parse_claude_default(&session_file, "test-project", "local").unwrap();
+        assert_eq!(
+            parsed.messages[0].content,
+            "Found the bug.\nTests pass.\n\n[Read: /src/lib.rs]\n[Bash]\n$ cargo test"
+        );
+        assert_eq!(
+            parsed.metadata.first_message.as_deref(),
+            Some("[assistant] Found the bug. Tests pass.")
+        );
+
+        // A tool-only turn never becomes the title
+        let content = r#"{"type":"assistant","timestamp":"2026-01-08T10:00:00Z","message":{"content":[{"type":"tool_use","name":"Read","input":{"file_path":"/src/lib.rs"}}]}}
+{"type":"assistant","timestamp":"2026-01-08T10:01:00Z","message":{"content":[{"type":"text","text":"Summary"}]}}"#;
+        fs::write(&session_file, content).unwrap();
+        let parsed = parse_claude_default(&session_file, "test-project", "local").unwrap();
+        assert_eq!(parsed.messages[0].content, "[Read: /src/lib.rs]");
+        assert_eq!(parsed.metadata.first_message.as_deref(), Some("[assistant] Summary"));
+    }
+
+    #[test]
+    fn test_parse_claude_session_extracts_tool_calls() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("test-session.jsonl");
+
+        let content = r#"{"type":"assistant","timestamp":"2026-01-08T10:00:00Z","message":{"content":[{"type":"text","text":"Running tests."},{"type":"tool_use","name":"Bash","input":{"command":"cargo test","description":"Run tests"}}]}}
+{"type":"assistant","timestamp":"2026-01-08T10:01:00Z","message":{"content":[{"type":"tool_use","name":"Read","input":{"file_path":"/src/main.rs"}}]}}
+{"type":"assistant","timestamp":"2026-01-08T10:02:00Z","message":{"content":[{"type":"text","text":"All done."}]}}"#;
+        fs::write(&session_file, content).unwrap();
+
+        let parsed = parse_claude_default(&session_file, "test-project", "local").unwrap();
+        assert_eq!(parsed.messages.len(), 3);
+
+        let calls = parsed.messages[0].tool_calls.as_ref().unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].tool_name, "Bash");
+        assert_eq!(calls[0].seq, 0);
+        assert_eq!(calls[0].msg_id, parsed.messages[0].msg_id);
+        assert_eq!(calls[0].summary, "[Bash: Run tests]\n$ cargo test");
+
+        let calls = parsed.messages[1].tool_calls.as_ref().unwrap();
+        assert_eq!(calls[0].tool_name, "Read");
+        assert_eq!(calls[0].seq, 1);
+
+        assert!(parsed.messages[2].tool_calls.is_none());
+        let tool_only: Vec<bool> = parsed.messages.iter().map(|m| m.is_tool_only).collect();
+        assert_eq!(tool_only, [false, true, false]);
+    }
+
+    #[test]
+    fn test_tool_input_json_is_kept_and_capped() {
+        let small = serde_json::json!({"file_path": "/a.rs", "old_string": "x", "new_string": "y"});
+        let calls = extract_tool_calls(&serde_json::json!([
+            {"type": "tool_use", "name": "Edit", "input": small}
+        ]));
+        assert_eq!(calls[0].2.as_deref(), Some(small.to_string().as_str()));
+
+        let huge = serde_json::json!({"file_path": "/a.rs", "content": "z".repeat(200_000)});
+        let capped = cap_tool_input(&huge);
+        assert!(capped.len() <= MAX_TOOL_INPUT_BYTES);
+        let parsed: Value = serde_json::from_str(&capped).unwrap();
+        assert_eq!(parsed["file_path"], "/a.rs");
+        assert!(parsed["content"].as_str().unwrap().ends_with("[truncated]"));
+    }
+
+    #[test]
+    fn test_parse_claude_session_detects_fork_parent() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("child-session.jsonl");
+
+        let content = r#"{"type":"user","sessionId":"parent-session","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Original prompt"}}
+{"type":"user","sessionId":"child-session","timestamp":"2026-01-08T10:05:00Z","message":{"content":"Forked prompt"}}"#;
+        fs::write(&session_file, content).unwrap();
+
+        let parsed = parse_claude_default(&session_file, "test-project", "local").unwrap();
+        assert_eq!(parsed.metadata.parent_session_id.as_deref(), Some("parent-session"));
+    }
+
+    #[test]
+    fn test_parse_claude_session_totals_token_usage() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("test-session.jsonl");
+
+        // The second response is split across two entries repeating its usage;
+        // the third entry reports no usage at all
+        let content = r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Hello"}}
+{"type":"assistant","timestamp":"2026-01-08T10:00:05Z","message":{"id":"msg_1","content":[{"type":"text","text":"Hi"}],"usage":{"input_tokens":10,"cache_read_input_tokens":200,"output_tokens":5}}}
+{"type":"assistant","timestamp":"2026-01-08T10:01:00Z","message":{"id":"msg_2","content":[{"type":"text","text":"Reading"}],"usage":{"input_tokens":3,"cache_creation_input_tokens":40,"output_tokens":7}}}
+{"type":"assistant","timestamp":"2026-01-08T10:01:01Z","message":{"id":"msg_2","content":[{"type":"tool_use","name":"Read","input":{"file_path":"/a.rs"}}],"usage":{"input_tokens":3,"cache_creation_input_tokens":40,"output_tokens":7}}}
+{"type":"assistant","timestamp":"2026-01-08T10:02:00Z","message":{"content":[{"type":"text","text":"Done"}]}}"#;
+        fs::write(&session_file, content).unwrap();
+
+        let parsed = parse_claude_default(&session_file, "test-project", "local").unwrap();
+        assert_eq!(parsed.metadata.total_input_tokens, Some(10 + 200 + 3 + 40));
+        assert_eq!(parsed.metadata.total_output_tokens, Some(5 + 7));
+
+        // Partial usage only fills the totals it reports
+        fs::write(
+            &session_file,
+            r#"{"type":"assistant","message":{"content":"Hi","usage":{"output_tokens":4}}}"#,
+        )
+        .unwrap();
+        let parsed = parse_claude_default(&session_file, "test-project", "local").unwrap();
+        assert_eq!(parsed.metadata.total_input_tokens, None);
+        assert_eq!(parsed.metadata.total_output_tokens, Some(4));
+    }
+
+    #[test]
+    fn test_parse_claude_session_project_from_cwd() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("test-session.jsonl");
+
+        let content = r#"{"type":"summary","summary":"No cwd here"}
+{"type":"user","cwd":"/Users/me/Projects/parent/my-app","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Hello"}}"#;
+        fs::write(&session_file, content).unwrap();
+
+        let parsed = parse_claude_default(&session_file, "parent-my-app", "local").unwrap();
+        assert_eq!(parsed.metadata.project, "my-app");
+        assert_eq!(parsed.metadata.cwd.as_deref(), Some("/Users/me/Projects/parent/my-app"));
+
+        // Without a cwd the caller's project name is kept
+        fs::write(&session_file, r#"{"type":"user","message":{"content":"Hello"}}"#).unwrap();
+        let parsed = parse_claude_default(&session_file, "parent-my-app", "local").unwrap();
+        assert_eq!(parsed.metadata.project, "parent-my-app");
+        assert_eq!(parsed.metadata.cwd, None);
+    }
+
+    #[test]
+    fn test_parse_claude_session_summary() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("test-session.jsonl");
+
+        let content = r#"{"type":"summary","summary":"Fix flaky sync test","leafUuid":"u1"}
+{"type":"user","uuid":"u1","timestamp":"2026-01-08T10:00:00Z","message":{"content":"The sync test fails sometimes"}}
+{"type":"summary","summary":"  Fix flaky sync test in CI  ","leafUuid":"u2"}
+{"type":"summary","summary":""}"#;
+        fs::write(&session_file, content).unwrap();
+
+        let parsed = parse_claude_default(&session_file, "test-project", "local").unwrap();
+        assert_eq!(parsed.metadata.summary.as_deref(), Some("Fix flaky sync test in CI"));
+        assert_eq!(
+            parsed.metadata.first_message.as_deref(),
+            Some("The sync test fails sometimes")
+        );
+        assert_eq!(parsed.messages.len(), 1);
+
+        // Without a summary entry there is nothing to capture
+        fs::write(
+            &session_file,
+            r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Hi"}}"#,
+        )
+        .unwrap();
+        let parsed = parse_claude_default(&session_file, "test-project", "local").unwrap();
+        assert_eq!(parsed.metadata.summary, None);
+    }
+
+    #[test]
+    fn test_assistant_only_session_preview() {
+        let tmp = tempdir().unwrap();
+
+        let claude_file = tmp.path().join("test-session.jsonl");
+        let content = r#"{"type":"assistant","timestamp":"2026-01-08T10:00:00Z","message":{"content":[{"type":"text","text":"Nightly report:\nall checks passed"}]}}"#;
+        fs::write(&claude_file, content).unwrap();
+        let parsed = parse_claude_default(&claude_file, "test-project", "local").unwrap();
+        assert_eq!(
+            parsed.metadata.first_message.as_deref(),
+            Some("[assistant] Nightly report: all checks passed")
+        );
+
+        let codex_file = tmp.path().join("rollout-2026-01-08-abc123.jsonl");
+        let content = r#"{"type":"session_meta","timestamp":"2026-01-08T10:00:00Z","payload":{"id":"abc123","cwd":"/home/user/myproject"}}
+{"type":"response_item","timestamp":"2026-01-08T10:01:00Z","payload":{"role":"assistant","content":[{"type":"output_text","text":"Automated summary"}]}}"#;
+        fs::write(&codex_file, content).unwrap();
+        let parsed = parse_codex_session(&codex_file, "local", false).unwrap();
+        assert_eq!(parsed.metadata.first_message.as_deref(), Some("[assistant] Automated summary"));
+
+        // A later user message still takes precedence
+        let content = r#"{"type":"assistant","message":{"content":[{"type":"text","text":"Ready"}]}}
+{"type":"user","message":{"content":"Real question"}}"#;
+        fs::write(&claude_file, content).unwrap();
+        let parsed = parse_claude_default(&claude_file, "test-project", "local").unwrap();
+        assert_eq!(parsed.metadata.first_message.as_deref(), Some("Real question"));
+    }
+
+    #[test]
+    fn test_tool_verbosity_levels() {
+        let content: Value = serde_json::from_str(
+            r#"[{"type":"tool_use","name":"Bash","input":{"command":"cargo test --workspace","description":"Run tests","timeout":60000}}]"#,
+        )
+        .unwrap();
+
+        let minimal = extract_text_content(&content, true, ToolVerbosity::Minimal);
+        let normal = extract_text_content(&content, true, ToolVerbosity::Normal);
+        let full = extract_text_content(&content, true, ToolVerbosity::Full);
+
+        assert_eq!(minimal, "[Bash: Run tests]");
+        assert_eq!(normal, "[Bash: Run tests]\n$ cargo test --workspace");
+        assert!(full.starts_with("[Bash: Run tests]\n"));
+        assert!(full.contains("\"timeout\":60000"));
+        assert!(minimal.len() < normal.len() && normal.len() < full.len());
+    }
+
+    #[test]
+    fn test_format_web_and_multi_edit_tools() {
+        let multi_edit = serde_json::json!({
+            "file_path": "src/foo.rs",
+            "edits": [
+                {"old_string": "a", "new_string": "b"},
+                {"old_string": "c", "new_string": "d"},
+                {"old_string": "e", "new_string": "f"}
+            ]
+        });
+        assert_eq!(
+            format_tool_summary("MultiEdit", &multi_edit),
+            "[MultiEdit: src/foo.rs (3 edits)]"
+        );
+
+        let single = serde_json::json!({"file_path": "a.rs", "edits": [{"old_string": "a"}]});
+        assert_eq!(format_tool_summary("MultiEdit", &single), "[MultiEdit: a.rs (1 edit)]");
+
+        let fetch = serde_json::json!({"url": "https://docs.rs/rusqlite", "prompt": "summarize"});
+        assert_eq!(
+            format_tool_summary("WebFetch", &fetch),
+            "[WebFetch: https://docs.rs/rusqlite — \"summarize\"]"
+        );
+        let fetch = serde_json::json!({"url": "https://example.com"});
+        assert_eq!(format_tool_summary("WebFetch", &fetch), "[WebFetch: https://example.com]");
+
+        let search = serde_json::json!({"query": "sqlite fts5 bm25", "allowed_domains": []});
+        assert_eq!(format_tool_summary("WebSearch", &search), "[WebSearch: sqlite fts5 bm25]");
+    }
+
+    #[test]
+    fn test_parse_claude_session_image_placeholder() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("test-session.jsonl");
+
+        // "iVBORw0KGgo=" decodes to the 8-byte PNG signature
+        let content = r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":[{"type":"text","text":"What is wrong here?"},{"type":"image","source":{"type":"base64","media_type":"image/png","data":"iVBORw0KGgo="}}]}}
+{"type":"user","timestamp":"2026-01-08T10:01:00Z","message":{"content":[{"type":"image","source":{"type":"url","url":"https://example.com/a.png"}}]}}"#;
+        fs::write(&session_file, content).unwrap();
+
+        let parsed = parse_claude_default(&session_file, "test-project", "local").unwrap();
+        assert_eq!(parsed.messages.len(), 2);
+        assert_eq!(
+            parsed.messages[0].content,
+            "What is wrong here?\n[Image: image/png, 8 bytes]"
+        );
+        assert!(!parsed.messages[0].content.contains("iVBOR"));
+        assert_eq!(parsed.messages[1].content, "[Image]");
+        assert_eq!(parsed.metadata.first_message.as_deref(), Some("What is wrong here?"));
+    }
+
+    #[test]
+    fn test_parse_claude_session_git_branch() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("test-session.jsonl");
+
+        let content = r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":"No branch yet"}}
+{"type":"user","gitBranch":"main","timestamp":"2026-01-08T10:01:00Z","message":{"content":"On main"}}
+{"type":"user","gitBranch":"feature/login","timestamp":"2026-01-08T10:02:00Z","message":{"content":"Switched"}}
+{"type":"user","gitBranch":"","timestamp":"2026-01-08T10:03:00Z","message":{"content":"Empty branch"}}
+{"type":"assistant","timestamp":"2026-01-08T10:04:00Z","message":{"content":"No branch field"}}"#;
+        fs::write(&session_file, content).unwrap();
+
+        let parsed = parse_claude_default(&session_file, "test-project", "local").unwrap();
+        assert_eq!(parsed.metadata.git_branch.as_deref(), Some("feature/login"));
+
+        let content = r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Hello"}}"#;
+        fs::write(&session_file, content).unwrap();
+        let parsed = parse_claude_default(&session_file, "test-project", "local").unwrap();
+        assert_eq!(parsed.metadata.git_branch, None);
+    }
+
+    #[test]
+    fn test_parse_claude_session_skips_meta_entries() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("test-session.jsonl");
+
+        let content = r#"{"type":"user","isMeta":true,"timestamp":"2026-01-08T10:00:00Z","message":{"content":"<local-command-stdout>ok</local-command-stdout>"}}
+{"type":"user","gitBranch":"main","timestamp":"2026-01-08T10:01:00Z","message":{"content":"Real question"}}"#;
+        fs::write(&session_file, content).unwrap();
+
+        let parsed = parse_claude_default(&session_file, "test-project", "local").unwrap();
+        assert_eq!(parsed.metadata.git_branch.as_deref(), Some("main"));
+        assert_eq!(parsed.messages.len(), 1);
+        assert_eq!(parsed.messages[0].content, "Real question");
+        assert_eq!(parsed.metadata.first_message.as_deref(), Some("Real question"));
+    }
+
+    #[test]
+    fn test_jsonl_entries_match_line_by_line_parsing() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("large-session.jsonl");
+
+        let mut content = Vec::new();
+        for i in 0..20_000 {
+            let entry = if i % 2 == 0 {
+                format!(
+                    r#"{{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{{"content":"Question {} {}"}}}}"#,
+                    i,
+                    "x".repeat(i % 300)
+                )
+            } else {
+                format!(
+                    r#"{{"type":"assistant","timestamp":"2026-01-08T10:00:01Z","message":{{"content":[{{"type":"text","text":"Answer {}"}}]}}}}"#,
+                    i
+                )
+            };
+            content.extend_from_slice(entry.as_bytes());
+            // Mix in CRLF endings, blank and whitespace-only lines, truncated
+            // JSON and invalid UTF-8, all of which must be skipped
+            content.extend_from_slice(match i % 7 {
+                0 => b"\r\n".as_slice(),
+                1 => b"\n\n   \n",
+                2 => b"\n{\"type\":\"user\",\"message\n",
+                3 => b"\n\xff\xfe\n",
+                _ => b"\n",
+            });
+        }
+        fs::write(&session_file, &content).unwrap();
+
+        // The line-at-a-time loop the parsers used before
+        let mut expected: Vec<Value> = Vec::new();
+        for line in BufReader::new(File::open(&session_file).unwrap()).lines() {
+            let line = match line {
+                Ok(l) if !l.trim().is_empty() => l,
+                _ => continue,
+            };
+            if let Ok(entry) = serde_json::from_str(&line) {
+                expected.push(entry);
+            }
+        }
+        let mut entries = JsonlEntries::new(open_session_file(&session_file).unwrap());
+        let mut actual = Vec::new();
+        while let Some((entry, line)) = entries.next_entry() {
+            assert_eq!(serde_json::from_str::<Value>(line).unwrap(), entry);
+            actual.push(entry);
+        }
+        assert_eq!(actual.len(), 20_000);
+        assert_eq!(expected.len(), 20_000);
+        assert!(actual == expected);
+
+        let options = ParseOptions {
+            store_raw: true,
+            ..Default::default()
+        };
+        let parsed =
+            parse_claude_session_with(&session_file, "test-project", "local", &options).unwrap();
+        assert_eq!(parsed.messages.len(), 20_000);
+        assert_eq!(parsed.messages[19_999].content, "Answer 19999");
+        assert_eq!(parsed.raw_messages.len(), 20_000);
+        assert!(parsed
+            .raw_messages
+            .iter()
+            .all(|(_, raw)| raw.starts_with('{') && raw.ends_with('}')));
+    }
+
+    #[test]
+    fn test_parse_gzipped_claude_session() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let tmp = tempdir().unwrap();
+        let content = r#"{"type":"user","cwd":"/Users/me/app","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Hello"}}
+{"type":"assistant","timestamp":"2026-01-08T10:01:00Z","message":{"content":[{"type":"text","text":"Hi there"}]}}"#;
+        let plain_file = tmp.path().join("plain").join("test-session.jsonl");
+        fs::create_dir_all(plain_file.parent().unwrap()).unwrap();
+        fs::write(&plain_file, content).unwrap();
+
+        let gz_file = tmp.path().join("test-session.jsonl.gz");
+        let file = fs::File::create(&gz_file).unwrap();
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        encoder.write_all(content.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+        assert!(is_session_file(&gz_file));
+
+        let mut plain = parse_claude_default(&plain_file, "test-project", "local").unwrap();
+        let gzipped = parse_claude_default(&gz_file, "test-project", "local").unwrap();
+        assert_eq!(gzipped.metadata.session_id, "test-session");
+        // Only a plain file can be resumed from an offset
+        assert_eq!(plain.metadata.parsed_offset, Some(content.len() as i64));
+        assert_eq!(gzipped.metadata.parsed_offset, None);
+        plain.metadata.parsed_offset = None;
+        assert_eq!(
+            serde_json::to_value(&gzipped.metadata).unwrap(),
+            serde_json::to_value(&plain.metadata).unwrap()
+        );
+        assert_eq!(
+            serde_json::to_value(&gzipped.messages).unwrap(),
+            serde_json::to_value(&plain.messages).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_claude_session_parent_links() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("test-session.jsonl");
+
+        // The answer's parent is a skipped meta entry; its link resolves
+        // through to the question
+        let content = r#"{"type":"user","uuid":"u1","parentUuid":null,"timestamp":"2026-01-08T10:00:00Z","message":{"content":"Question"}}
+{"type":"user","isMeta":true,"uuid":"meta","parentUuid":"u1","timestamp":"2026-01-08T10:00:00Z","message":{"content":"injected"}}
+{"type":"assistant","uuid":"a1","parentUuid":"meta","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Answer"}}"#;
+        fs::write(&session_file, content).unwrap();
+
+        let parsed = parse_claude_default(&session_file, "test-project", "local").unwrap();
+        let links: Vec<(Option<&str>, Option<&str>)> = parsed
+            .messages
+            .iter()
+            .map(|m| (m.uuid.as_deref(), m.parent_uuid.as_deref()))
+            .collect();
+        assert_eq!(links, vec![(Some("u1"), None), (Some("a1"), Some("u1"))]);
+    }
+
+    #[test]
+    fn test_parse_claude_session_dedupes_replayed_uuids() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("test-session.jsonl");
+
+        // A resumed session replays u1 and a1 before continuing; entries
+        // without a uuid are kept as before
+        let content = r#"{"type":"user","uuid":"u1","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Question"}}
+{"type":"assistant","uuid":"a1","parentUuid":"u1","timestamp":"2026-01-08T10:00:05Z","message":{"content":"Answer"}}
+{"type":"user","uuid":"u1","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Question"}}
+{"type":"assistant","uuid":"a1","parentUuid":"u1","timestamp":"2026-01-08T10:00:05Z","message":{"content":"Answer (replayed)"}}
+{"type":"user","uuid":"u2","parentUuid":"a1","timestamp":"2026-01-08T11:00:00Z","message":{"content":"Follow-up"}}
+{"type":"user","timestamp":"2026-01-08T11:00:01Z","message":{"content":"No uuid"}}
+{"type":"user","timestamp":"2026-01-08T11:00:02Z","message":{"content":"No uuid"}}"#;
+        fs::write(&session_file, content).unwrap();
+
+        let parsed = parse_claude_default(&session_file, "test-project", "local").unwrap();
+        let contents: Vec<&str> = parsed.messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["Question", "Answer", "Follow-up", "No uuid", "No uuid"]);
+        assert_eq!(parsed.metadata.message_count, 5);
+    }
+
+    #[test]
+    fn test_parse_claude_session_sidechain_entries() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("test-session.jsonl");
+
+        let content = r#"{"type":"user","isSidechain":true,"timestamp":"2026-01-08T10:00:00Z","message":{"content":"Subagent prompt"}}
+{"type":"assistant","isSidechain":true,"timestamp":"2026-01-08T10:01:00Z","message":{"content":[{"type":"text","text":"Subagent reply"}]}}
+{"type":"user","timestamp":"2026-01-08T10:02:00Z","message":{"content":"Main prompt"}}"#;
+        fs::write(&session_file, content).unwrap();
+
+        let parsed = parse_claude_default(&session_file, "test-project", "local").unwrap();
+        assert_eq!(parsed.messages.len(), 3);
+        assert!(parsed.messages[0].is_sidechain);
+        assert!(parsed.messages[1].is_sidechain);
+        assert!(!parsed.messages[2].is_sidechain);
+        assert_eq!(parsed.metadata.first_message.as_deref(), Some("Main prompt"));
+
+        let options = ParseOptions {
+            include_sidechains: false,
+            ..Default::default()
+        };
+        let parsed =
+            parse_claude_session_with(&session_file, "test-project", "local", &options).unwrap();
+        assert_eq!(parsed.messages.len(), 1);
+        assert_eq!(parsed.messages[0].content, "Main prompt");
+        assert_eq!(parsed.metadata.message_count, 1);
+    }
+
+    #[test]
+    fn test_parse_claude_session_message_count_policy() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("test-session.jsonl");
+
+        let content = r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Fix the bug"}}
+{"type":"assistant","timestamp":"2026-01-08T10:01:00Z","message":{"content":[{"type":"thinking","thinking":"Let me look"}]}}
+{"type":"assistant","timestamp":"2026-01-08T10:02:00Z","message":{"content":[{"type":"tool_use","name":"Read","input":{"file_path":"/src/main.rs"}}]}}
+{"type":"assistant","timestamp":"2026-01-08T10:03:00Z","message":{"content":[{"type":"thinking","thinking":"Found it"},{"type":"text","text":"Fixed."}]}}"#;
+        fs::write(&session_file, content).unwrap();
+
+        let parsed = parse_claude_default(&session_file, "test-project", "local").unwrap();
+        assert_eq!(parsed.messages.len(), 4);
+        assert_eq!(parsed.metadata.message_count, 2);
+
+        let options = ParseOptions {
+            count_policy: MessageCountPolicy::All,
+            ..Default::default()
+        };
+        let parsed =
+            parse_claude_session_with(&session_file, "test-project", "local", &options).unwrap();
+        assert_eq!(parsed.metadata.message_count, 4);
+    }
+
+    #[test]
+    fn test_parse_claude_session_collapse_repeats() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("test-session.jsonl");
+
+        let content = r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Fix the bug"}}
+{"type":"user","timestamp":"2026-01-08T10:00:30Z","message":{"content":"Fix the bug"}}
+{"type":"assistant","timestamp":"2026-01-08T10:01:00Z","message":{"content":"Fixed."}}
+{"type":"user","timestamp":"2026-01-08T10:02:00Z","message":{"content":"Fix the bug"}}"#;
+        fs::write(&session_file, content).unwrap();
+
+        // Off by default: every entry is kept
+        let parsed = parse_claude_default(&session_file, "test-project", "local").unwrap();
+        assert_eq!(parsed.messages.len(), 4);
+        assert!(parsed.messages.iter().all(|m| m.repeat_count == 0));
+
+        let options = ParseOptions {
+            collapse_repeats: true,
+            ..Default::default()
+        };
+        let parsed =
+            parse_claude_session_with(&session_file, "test-project", "local", &options).unwrap();
+        assert_eq!(parsed.messages.len(), 3);
+        assert_eq!(parsed.messages[0].timestamp, "2026-01-08T10:00:00Z");
+        assert_eq!(parsed.messages[0].repeat_count, 1);
+        // Only consecutive copies fold
+        assert_eq!(parsed.messages[2].content, "Fix the bug");
+        assert_eq!(parsed.messages[2].repeat_count, 0);
+        assert_eq!(parsed.metadata.message_count, 3);
+    }
+
+    #[test]
+    fn test_parse_claude_session_store_raw() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("test-session.jsonl");
+
+        let user = r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Hi"}}"#;
+        let reply = r#"{"type":"assistant","timestamp":"2026-01-08T10:01:00Z","message":{"content":[{"type":"text","text":"Hello"}]}}"#;
+        fs::write(&session_file, format!("{}\n{}\n", user, reply)).unwrap();
+
+        let parsed = parse_claude_default(&session_file, "test-project", "local").unwrap();
+        assert!(parsed.raw_messages.is_empty());
+
+        let options = ParseOptions {
+            store_raw: true,
+            ..Default::default()
+        };
+        let parsed =
+            parse_claude_session_with(&session_file, "test-project", "local", &options).unwrap();
+        assert_eq!(
+            parsed.raw_messages,
+            vec![
+                (parsed.messages[0].msg_id.clone(), user.to_string()),
+                (parsed.messages[1].msg_id.clone(), reply.to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_codex_session_basic() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("rollout-2026-01-08-abc123.jsonl");
 
         let content = r#"{"type":"session_meta","timestamp":"2026-01-08T10:00:00Z","payload":{"id":"abc123","cwd":"/home/user/myproject","originator":"codex_cli_rs"}}
 {"type":"response_item","timestamp":"2026-01-08T10:01:00Z","payload":{"role":"user","content":[{"type":"input_text","text":"Hello Codex"}]}}
@@ -462,6 +1990,214 @@ mod tests {
         assert_eq!(parsed.metadata.session_id, "codex:abc123");
         assert_eq!(parsed.metadata.project, "myproject");
         assert_eq!(parsed.metadata.agent, "codex");
+        assert_eq!(parsed.metadata.parent_session_id, None);
+        assert_eq!(parsed.messages.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_codex_session_collapse_repeats() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("rollout-2026-01-08-abc123.jsonl");
+
+        let content = r#"{"type":"session_meta","timestamp":"2026-01-08T10:00:00Z","payload":{"id":"abc123","cwd":"/home/user/myproject"}}
+{"type":"response_item","timestamp":"2026-01-08T10:01:00Z","payload":{"role":"user","content":[{"type":"input_text","text":"Fix the bug"}]}}
+{"type":"response_item","timestamp":"2026-01-08T10:01:30Z","payload":{"role":"user","content":[{"type":"input_text","text":"Fix the bug"}]}}
+{"type":"response_item","timestamp":"2026-01-08T10:02:00Z","payload":{"role":"assistant","content":[{"type":"output_text","text":"Fixed."}]}}"#;
+        fs::write(&session_file, content).unwrap();
+
+        let parsed = parse_codex_session(&session_file, "local", false).unwrap();
+        assert_eq!(parsed.messages.len(), 3);
+
+        let options = ParseOptions {
+            collapse_repeats: true,
+            ..Default::default()
+        };
+        let parsed = parse_codex_session_with(&session_file, "local", false, &options).unwrap();
+        assert_eq!(parsed.messages.len(), 2);
+        assert_eq!(parsed.messages[0].repeat_count, 1);
+        assert_eq!(parsed.messages[1].repeat_count, 0);
+        assert_eq!(parsed.metadata.message_count, 2);
+    }
+
+    #[test]
+    fn test_parse_codex_session_forked_from() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("rollout-2026-01-08-def456.jsonl");
+
+        let content = r#"{"type":"session_meta","timestamp":"2026-01-08T10:00:00Z","payload":{"id":"def456","forked_from_id":"abc123","cwd":"/home/user/myproject"}}
+{"type":"response_item","timestamp":"2026-01-08T10:01:00Z","payload":{"role":"user","content":[{"type":"input_text","text":"Carry on"}]}}"#;
+        fs::write(&session_file, content).unwrap();
+
+        let parsed = parse_codex_session(&session_file, "local", false).unwrap();
+        assert_eq!(parsed.metadata.session_id, "codex:def456");
+        assert_eq!(parsed.metadata.parent_session_id.as_deref(), Some("codex:abc123"));
+    }
+
+    #[test]
+    fn test_parse_codex_session_reasoning_effort() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("rollout-2026-01-08-abc123.jsonl");
+
+        let content = r#"{"type":"session_meta","timestamp":"2026-01-08T10:00:00Z","payload":{"id":"abc123","cwd":"/home/user/myproject","reasoning_effort":"high"}}
+{"type":"response_item","timestamp":"2026-01-08T10:01:00Z","payload":{"role":"user","content":[{"type":"input_text","text":"Hello Codex"}]}}"#;
+        fs::write(&session_file, content).unwrap();
+        let parsed = parse_codex_session(&session_file, "local", false).unwrap();
+        assert_eq!(parsed.metadata.reasoning_effort.as_deref(), Some("high"));
+
+        // Falls back to the first turn_context, and stays NULL when absent
+        let content = r#"{"type":"session_meta","timestamp":"2026-01-08T10:00:00Z","payload":{"id":"abc123","cwd":"/home/user/myproject"}}
+{"type":"turn_context","timestamp":"2026-01-08T10:00:30Z","payload":{"effort":"medium"}}
+{"type":"turn_context","timestamp":"2026-01-08T10:05:00Z","payload":{"effort":"low"}}"#;
+        fs::write(&session_file, content).unwrap();
+        let parsed = parse_codex_session(&session_file, "local", false).unwrap();
+        assert_eq!(parsed.metadata.reasoning_effort.as_deref(), Some("medium"));
+
+        let content = r#"{"type":"session_meta","timestamp":"2026-01-08T10:00:00Z","payload":{"id":"abc123","cwd":"/home/user/myproject"}}"#;
+        fs::write(&session_file, content).unwrap();
+        let parsed = parse_codex_session(&session_file, "local", false).unwrap();
+        assert_eq!(parsed.metadata.reasoning_effort, None);
+    }
+
+    #[test]
+    fn test_parse_session_model_picks_most_used() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("test-session.jsonl");
+
+        // Opus answers twice (once split across two entries), Sonnet once
+        // last; the synthetic error turn is ignored
+        let content = r#"{"type":"assistant","timestamp":"2026-01-08T10:00:00Z","message":{"id":"msg_1","model":"claude-opus-4-1","content":"One"}}
+{"type":"assistant","timestamp":"2026-01-08T10:01:00Z","message":{"id":"msg_2","model":"claude-opus-4-1","content":"Two"}}
+{"type":"assistant","timestamp":"2026-01-08T10:01:01Z","message":{"id":"msg_2","model":"claude-opus-4-1","content":"Two more"}}
+{"type":"assistant","timestamp":"2026-01-08T10:02:00Z","message":{"id":"msg_3","model":"claude-sonnet-4-5","content":"Three"}}
+{"type":"assistant","timestamp":"2026-01-08T10:03:00Z","message":{"model":"<synthetic>","content":"API Error"}}"#;
+        fs::write(&session_file, content).unwrap();
+        let parsed = parse_claude_default(&session_file, "test-project", "local").unwrap();
+        assert_eq!(parsed.metadata.model.as_deref(), Some("claude-opus-4-1"));
+
+        // A tie goes to the model used last
+        let content = r#"{"type":"assistant","message":{"id":"msg_1","model":"claude-opus-4-1","content":"One"}}
+{"type":"assistant","message":{"id":"msg_2","model":"claude-sonnet-4-5","content":"Two"}}"#;
+        fs::write(&session_file, content).unwrap();
+        let parsed = parse_claude_default(&session_file, "test-project", "local").unwrap();
+        assert_eq!(parsed.metadata.model.as_deref(), Some("claude-sonnet-4-5"));
+
+        let codex_file = tmp.path().join("rollout-2026-01-08-abc123.jsonl");
+        let content = r#"{"type":"session_meta","timestamp":"2026-01-08T10:00:00Z","payload":{"id":"abc123","cwd":"/home/user/myproject","model":"gpt-5"}}
+{"type":"turn_context","timestamp":"2026-01-08T10:00:30Z","payload":{"model":"gpt-5-codex"}}
+{"type":"turn_context","timestamp":"2026-01-08T10:05:00Z","payload":{"model":"gpt-5-codex"}}"#;
+        fs::write(&codex_file, content).unwrap();
+        let parsed = parse_codex_session(&codex_file, "local", false).unwrap();
+        assert_eq!(parsed.metadata.model.as_deref(), Some("gpt-5-codex"));
+    }
+
+    #[test]
+    fn test_parse_gemini_sessions() {
+        let tmp = tempdir().unwrap();
+        let log = tmp.path().join("logs.json");
+        fs::write(
+            &log,
+            r#"[
+  {"sessionId": "5f1c", "messageId": 0, "type": "user", "message": "Explain the build script", "timestamp": "2026-01-08T10:00:00.000Z", "cwd": "/home/me/code/webapp"},
+  {"sessionId": "5f1c", "messageId": 1, "type": "model", "model": "gemini-2.5-pro", "message": "It compiles the assets first.", "timestamp": "2026-01-08T10:00:05.000Z"},
+  {"sessionId": "9a2e", "messageId": 0, "type": "user", "message": "/help", "timestamp": "2026-01-09T09:00:00.000Z"},
+  {"sessionId": "5f1c", "messageId": 2, "type": "user", "message": "", "timestamp": "2026-01-08T10:01:00.000Z"},
+  {"sessionId": "5f1c", "messageId": 3, "type": "tool", "message": "ignored", "timestamp": "2026-01-08T10:02:00.000Z"}
+]"#,
+        )
+        .unwrap();
+
+        let sessions = parse_gemini_sessions(&log, "local").unwrap();
+        assert_eq!(sessions.len(), 2);
+
+        let first = &sessions[0];
+        assert_eq!(first.metadata.session_id, "gemini:5f1c");
+        assert_eq!(first.metadata.agent, "gemini");
+        assert_eq!(first.metadata.project, "webapp");
+        assert_eq!(first.metadata.cwd.as_deref(), Some("/home/me/code/webapp"));
+        assert_eq!(first.metadata.model.as_deref(), Some("gemini-2.5-pro"));
+        assert_eq!(first.metadata.message_count, 2);
+        assert_eq!(first.metadata.first_message.as_deref(), Some("Explain the build script"));
+        assert_eq!(first.metadata.ended_at.as_deref(), Some("2026-01-08T10:02:00+00:00"));
+        let roles: Vec<&str> = first.messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["user", "assistant"]);
+        assert!(first.messages.iter().all(|m| m.session_id == "gemini:5f1c"));
+
+        let second = &sessions[1];
+        assert_eq!(second.metadata.session_id, "gemini:9a2e");
+        assert_eq!(second.metadata.project, "unknown");
+        assert_eq!(second.messages.len(), 1);
+
+        fs::write(&log, "not json").unwrap();
+        assert!(parse_gemini_sessions(&log, "local").is_none());
+    }
+
+    #[test]
+    fn test_parse_codex_session_string_content() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("rollout-2026-01-08-abc123.jsonl");
+
+        let content = r#"{"type":"session_meta","timestamp":"2026-01-08T10:00:00Z","payload":{"id":"abc123","cwd":"/home/user/myproject"}}
+{"type":"response_item","timestamp":"2026-01-08T10:01:00Z","payload":{"role":"user","content":"Plain string prompt"}}
+{"type":"response_item","timestamp":"2026-01-08T10:02:00Z","payload":{"role":"assistant","content":[{"type":"output_text","text":"Block reply"}]}}"#;
+        fs::write(&session_file, content).unwrap();
+
+        let parsed = parse_codex_session(&session_file, "local", false).unwrap();
+        assert_eq!(parsed.messages.len(), 2);
+        assert_eq!(parsed.messages[0].content, "Plain string prompt");
+        assert_eq!(parsed.metadata.first_message.as_deref(), Some("Plain string prompt"));
+        assert_eq!(parsed.messages[1].content, "Block reply");
+    }
+
+    #[test]
+    fn test_parse_codex_session_function_calls() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("rollout-2026-01-08-abc123.jsonl");
+
+        let content = r#"{"type":"session_meta","timestamp":"2026-01-08T10:00:00Z","payload":{"id":"abc123","cwd":"/home/user/myproject"}}
+{"type":"response_item","timestamp":"2026-01-08T10:01:00Z","payload":{"role":"user","content":[{"type":"input_text","text":"List the files"}]}}
+{"type":"response_item","timestamp":"2026-01-08T10:01:05Z","payload":{"type":"function_call","name":"shell","arguments":"{\"command\":[\"ls\"]}","call_id":"call_1"}}
+{"type":"response_item","timestamp":"2026-01-08T10:01:06Z","payload":{"type":"function_call_output","call_id":"call_1","output":"{\"output\":\"Cargo.toml\\nsrc\\n\",\"metadata\":{\"exit_code\":0}}"}}
+{"type":"response_item","timestamp":"2026-01-08T10:01:10Z","payload":{"role":"assistant","content":[{"type":"output_text","text":"Two entries."}]}}"#;
+        fs::write(&session_file, content).unwrap();
+
+        let parsed = parse_codex_session(&session_file, "local", false).unwrap();
+        let messages: Vec<(&str, &str)> = parsed
+            .messages
+            .iter()
+            .map(|m| (m.role.as_str(), m.content.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                ("user", "List the files"),
+                ("assistant", "[Tool: shell]\n{\"command\":[\"ls\"]}"),
+                ("tool", "Cargo.toml\nsrc\n"),
+                ("assistant", "Two entries."),
+            ]
+        );
+        assert_eq!(parsed.metadata.message_count, 2);
+        assert_eq!(parsed.metadata.first_message.as_deref(), Some("List the files"));
+
+        let calls = parsed.messages[1].tool_calls.as_ref().unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].tool_name, "shell");
+        assert_eq!(calls[0].session_id, "codex:abc123");
+        assert_eq!(calls[0].msg_id, parsed.messages[1].msg_id);
+        assert_eq!(calls[0].tool_input_json.as_deref(), Some(r#"{"command":["ls"]}"#));
+
+        let long_output = format!(
+            r#"{{"type":"response_item","payload":{{"type":"function_call_output","call_id":"c","output":"{}"}}}}"#,
+            "y".repeat(MAX_CODEX_TOOL_OUTPUT + 10)
+        );
+        fs::write(&session_file, format!("{}\n{}", content, long_output)).unwrap();
+        let parsed = parse_codex_session(&session_file, "local", false).unwrap();
+        assert!(parsed.messages[4].content.ends_with("... [truncated]"));
+
+        let options = ParseOptions {
+            include_tools: false,
+            ..Default::default()
+        };
+        let parsed = parse_codex_session_with(&session_file, "local", false, &options).unwrap();
         assert_eq!(parsed.messages.len(), 2);
     }
 
@@ -542,6 +2278,30 @@ mod tests {
         assert_eq!(parsed.messages[0].content, "Hello actual message");
     }
 
+    #[test]
+    fn test_parse_codex_session_dedups_injected_context() {
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("test.jsonl");
+
+        let meta = r#"{"type":"session_meta","payload":{"id":"test-id","cwd":"/test"}}"#;
+        // Leading whitespace slipped past the old starts_with check
+        let padded = r#"{"type":"response_item","payload":{"role":"user","content":[{"type":"input_text","text":"\n  <environment_context>cwd=/test</environment_context>"}]}}"#;
+        let prefixed = r#"{"type":"response_item","payload":{"role":"user","content":[{"type":"input_text","text":"Context follows\n<environment_context>cwd=/test</environment_context>"}]}}"#;
+        let prompt = r#"{"type":"response_item","payload":{"role":"user","content":[{"type":"input_text","text":"Fix the bug"}]}}"#;
+        let content = [meta, padded, prefixed, prompt, padded, prefixed, prefixed].join("\n");
+        fs::write(&session_file, content).unwrap();
+
+        let parsed = parse_codex_session(&session_file, "local", false).unwrap();
+        let contents: Vec<&str> = parsed.messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(
+            contents,
+            vec![
+                "Context follows\n<environment_context>cwd=/test</environment_context>",
+                "Fix the bug",
+            ]
+        );
+    }
+
     #[test]
     fn test_extract_codex_project() {
         assert_eq!(extract_codex_project("/home/user/projects/myapp"), "myapp");
@@ -551,24 +2311,48 @@ mod tests {
 
     #[test]
     fn test_make_msg_id() {
-        // With timestamp, includes index for uniqueness
-        assert_eq!(make_msg_id("2026-01-08T10:00:00.123Z", 0), "msg-2026-01-08T10-00-00-123Z-0");
-        assert_eq!(make_msg_id("2026-01-08T10:00:00.123Z", 1), "msg-2026-01-08T10-00-00-123Z-1");
-        // Without timestamp, uses only index
-        assert_eq!(make_msg_id("", 5), "msg-5");
+        let hash = &blake3::hash(b"Hello").to_hex()[..MSG_ID_HASH_LEN];
+        // With timestamp, includes index and content hash
+        assert_eq!(
+            make_msg_id("2026-01-08T10:00:00.123Z", 0, "Hello"),
+            format!("msg-2026-01-08T10-00-00-123Z-0-{}", hash)
+        );
+        // Without timestamp, uses only index and hash
+        assert_eq!(make_msg_id("", 5, "Hello"), format!("msg-5-{}", hash));
     }
 
     #[test]
     fn test_make_msg_id_same_timestamp_uniqueness() {
-        // Same timestamp with different indices should produce different IDs
+        // Same timestamp with different indices or content should produce different IDs
         let ts = "2026-01-08T10:00:00.000Z";
-        let id1 = make_msg_id(ts, 0);
-        let id2 = make_msg_id(ts, 1);
-        let id3 = make_msg_id(ts, 2);
+        let id1 = make_msg_id(ts, 0, "Same");
+        let id2 = make_msg_id(ts, 1, "Same");
+        let id3 = make_msg_id(ts, 1, "Different");
 
         assert_ne!(id1, id2);
         assert_ne!(id2, id3);
         assert_ne!(id1, id3);
+        assert_eq!(make_msg_id(ts, 1, "Same"), id2);
+
+        // A burst of messages sharing a timestamp gets distinct ids that a
+        // re-sync of the same file reproduces
+        let tmp = tempdir().unwrap();
+        let session_file = tmp.path().join("test-session.jsonl");
+        let content = r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Run it"}}
+{"type":"assistant","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Running"}}
+{"type":"assistant","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Done"}}"#;
+        fs::write(&session_file, content).unwrap();
+        let ids = || -> Vec<String> {
+            parse_claude_default(&session_file, "test-project", "local")
+                .unwrap()
+                .messages
+                .into_iter()
+                .map(|m| m.msg_id)
+                .collect()
+        };
+        let first = ids();
+        assert_eq!(first.iter().collect::<HashSet<_>>().len(), 3);
+        assert_eq!(ids(), first);
     }
 
     #[test]
@@ -583,7 +2367,7 @@ mod tests {
         );
         fs::write(&session_file, content).unwrap();
 
-        let result = parse_claude_session(&session_file, "test", "local");
+        let result = parse_claude_default(&session_file, "test", "local");
         assert!(result.is_some());
 
         let parsed = result.unwrap();
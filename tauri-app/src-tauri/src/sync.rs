@@ -1,41 +1,213 @@
 //! Sync sessions from Claude Code and Codex directories.
 
-use crate::db::Database;
-use crate::parser::{parse_claude_session, parse_codex_session};
+use crate::db::{Database, Session};
+use crate::parser::{
+    extract_codex_project, extract_codex_project_with_parent, is_session_file,
+    open_session_file, parse_claude_session_from, parse_claude_session_with,
+    parse_codex_session_with, parse_gemini_sessions, project_from_cwd, session_file_stem,
+    ClaudeResume, ParseOptions,
+};
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::io::Read;
+use std::io::{BufRead, Read};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Parse throughput counters, accumulated over the process lifetime.
+static FILES_PARSED: AtomicU64 = AtomicU64::new(0);
+static BYTES_PARSED: AtomicU64 = AtomicU64::new(0);
+static MESSAGES_PARSED: AtomicU64 = AtomicU64::new(0);
+static PARSE_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// Snapshot of parse throughput since the process started.
+#[derive(Debug, Clone, Copy, Default, serde::Serialize)]
+pub struct ParseMetrics {
+    pub files_parsed: u64,
+    pub bytes_parsed: u64,
+    pub messages_parsed: u64,
+    /// Cumulative time spent in the parsers, in milliseconds.
+    pub parse_time_ms: u64,
+}
+
+/// Read the current parse throughput counters.
+pub fn parse_metrics() -> ParseMetrics {
+    ParseMetrics {
+        files_parsed: FILES_PARSED.load(Ordering::Relaxed),
+        bytes_parsed: BYTES_PARSED.load(Ordering::Relaxed),
+        messages_parsed: MESSAGES_PARSED.load(Ordering::Relaxed),
+        parse_time_ms: PARSE_NANOS.load(Ordering::Relaxed) / 1_000_000,
+    }
+}
 
-/// Compute MD5 hash of a file.
+fn record_parse(bytes: i64, messages: usize, elapsed: Duration) {
+    FILES_PARSED.fetch_add(1, Ordering::Relaxed);
+    BYTES_PARSED.fetch_add(bytes.max(0) as u64, Ordering::Relaxed);
+    MESSAGES_PARSED.fetch_add(messages as u64, Ordering::Relaxed);
+    PARSE_NANOS.fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+}
+
+/// Compute the BLAKE3 hash of a file.
 pub fn compute_file_hash(path: &Path) -> Option<String> {
-    let mut file = fs::File::open(path).ok()?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer).ok()?;
-    Some(format!("{:x}", md5::compute(&buffer)))
-}
-
-/// Get the Claude projects directory.
-pub fn claude_projects_dir() -> PathBuf {
-    std::env::var("CLAUDE_PROJECTS_DIR")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| {
-            dirs::home_dir()
-                .unwrap_or_default()
-                .join(".claude")
-                .join("projects")
-        })
+    hash_file(path).ok()
+}
+
+/// Hash a file in chunks without buffering it whole.
+fn hash_file(path: &Path) -> std::io::Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    std::io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// Hash a file unless `hash` already holds its hash, keeping the result there
+/// so one sync reads the file at most once.
+fn cached_hash<'a>(path: &Path, hash: &'a mut Option<String>) -> std::io::Result<&'a str> {
+    match hash {
+        Some(hash) => Ok(hash),
+        None => Ok(hash.insert(hash_file(path)?)),
+    }
+}
+
+/// Hash a file along with its first `prefix_len` bytes, in one read.
+fn hash_file_with_prefix(path: &Path, prefix_len: u64) -> std::io::Result<(String, String)> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    std::io::copy(&mut (&mut file).take(prefix_len), &mut hasher)?;
+    let prefix = hasher.finalize().to_hex().to_string();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok((prefix, hasher.finalize().to_hex().to_string()))
+}
+
+/// A file's modification time in nanoseconds since the Unix epoch.
+pub(crate) fn file_mtime(meta: &fs::Metadata) -> Option<i64> {
+    let since_epoch = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    i64::try_from(since_epoch.as_nanos()).ok()
+}
+
+/// Whether a session's source file matches what was stored at its last
+/// sync. Matching size and mtime are trusted without reading the file;
+/// otherwise a same-size file is hashed, and if only its mtime moved the
+/// new mtime is stored so the next check takes the fast path. A hash the
+/// check computes is left in `hash` for the sync to reuse.
+fn file_unchanged(
+    db: &Database,
+    session_id: &str,
+    path: &Path,
+    size: i64,
+    mtime: Option<i64>,
+    hash: &mut Option<String>,
+) -> Result<bool, SyncError> {
+    let Ok(Some((stored_size, stored_hash, stored_mtime))) = db.get_session_file_info(session_id)
+    else {
+        return Ok(false);
+    };
+    if stored_size != size {
+        return Ok(false);
+    }
+    if mtime.is_some() && mtime == stored_mtime {
+        return Ok(true);
+    }
+    if cached_hash(path, hash)? != stored_hash {
+        return Ok(false);
+    }
+    if let Some(mtime) = mtime {
+        db.set_file_mtime(session_id, mtime)?;
+    }
+    Ok(true)
+}
+
+/// Waits between attempts to open a session file the agent may hold locked.
+const OPEN_RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_millis(100),
+    Duration::from_millis(300),
+    Duration::from_millis(600),
+];
+
+/// Whether an open failed because another process holds the file locked:
+/// a sharing or lock violation on Windows, or a would-block elsewhere.
+fn is_lock_violation(e: &std::io::Error) -> bool {
+    // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+    if cfg!(windows) && matches!(e.raw_os_error(), Some(32 | 33)) {
+        return true;
+    }
+    e.kind() == std::io::ErrorKind::WouldBlock
 }
 
-/// Get the Codex sessions directory.
-pub fn codex_sessions_dir() -> PathBuf {
-    std::env::var("CODEX_SESSIONS_DIR")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| {
-            dirs::home_dir()
-                .unwrap_or_default()
-                .join(".codex")
-                .join("sessions")
+/// Run `open`, retrying after each delay while the file is locked. Any other
+/// error is returned at once.
+fn retry_open<T>(
+    mut open: impl FnMut() -> std::io::Result<T>,
+    delays: &[Duration],
+) -> std::io::Result<T> {
+    let mut delays = delays.iter();
+    loop {
+        match open() {
+            Err(e) if is_lock_violation(&e) => match delays.next() {
+                Some(delay) => std::thread::sleep(*delay),
+                None => return Err(e),
+            },
+            result => return result,
+        }
+    }
+}
+
+/// Check a session file can be opened, waiting out a short-lived lock held by
+/// the writing agent (common on Windows).
+fn ensure_readable(path: &Path) -> Result<(), SyncError> {
+    match retry_open(|| fs::File::open(path), &OPEN_RETRY_DELAYS) {
+        Ok(_) => Ok(()),
+        Err(e) if is_lock_violation(&e) => Err(SyncError::Locked(e)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Get the Claude projects directories.
+pub fn claude_projects_dirs() -> Vec<PathBuf> {
+    session_roots("CLAUDE_PROJECTS_DIR", || {
+        dirs::home_dir()
+            .unwrap_or_default()
+            .join(".claude")
+            .join("projects")
+    })
+}
+
+/// Get the Codex sessions directories.
+pub fn codex_sessions_dirs() -> Vec<PathBuf> {
+    session_roots("CODEX_SESSIONS_DIR", || {
+        dirs::home_dir()
+            .unwrap_or_default()
+            .join(".codex")
+            .join("sessions")
+    })
+}
+
+/// Get the Gemini CLI temp directories, which hold a log per project.
+pub fn gemini_tmp_dirs() -> Vec<PathBuf> {
+    session_roots("GEMINI_TMP_DIR", || {
+        dirs::home_dir()
+            .unwrap_or_default()
+            .join(".gemini")
+            .join("tmp")
+    })
+}
+
+/// Read a list of session roots from an environment variable, separated like
+/// `PATH` (`:`, or `;` on Windows), falling back to the default location.
+fn session_roots(var: &str, default: impl FnOnce() -> PathBuf) -> Vec<PathBuf> {
+    let roots: Vec<PathBuf> = std::env::var_os(var)
+        .map(|list| {
+            std::env::split_paths(&list)
+                .filter(|path| !path.as_os_str().is_empty())
+                .collect()
         })
+        .unwrap_or_default();
+    if roots.is_empty() {
+        vec![default()]
+    } else {
+        roots
+    }
 }
 
 /// Get the data directory for our app.
@@ -63,15 +235,14 @@ fn get_project_name(dir_name: &str) -> String {
     name.replace('-', "_")
 }
 
-/// Find all Claude project directories.
-pub fn find_claude_projects() -> Vec<PathBuf> {
-    let dir = claude_projects_dir();
+/// Find all Claude project directories under a projects root.
+pub fn find_claude_projects(dir: &Path) -> Vec<PathBuf> {
     if !dir.exists() {
         return Vec::new();
     }
 
     let mut projects = Vec::new();
-    if let Ok(entries) = fs::read_dir(&dir) {
+    if let Ok(entries) = fs::read_dir(dir) {
         for entry in entries.flatten() {
             let path = entry.path();
             if path.is_dir() {
@@ -84,9 +255,79 @@ pub fn find_claude_projects() -> Vec<PathBuf> {
     projects
 }
 
-/// Find all Codex session files.
-pub fn find_codex_sessions() -> Vec<PathBuf> {
-    let dir = codex_sessions_dir();
+/// Find the session files of every Claude project under the given roots.
+pub fn find_claude_sessions(roots: &[PathBuf]) -> Vec<PathBuf> {
+    let files = roots
+        .iter()
+        .flat_map(|root| find_claude_projects(root))
+        .flat_map(|dir| fs::read_dir(dir).into_iter().flatten().flatten())
+        .map(|entry| entry.path())
+        .filter(|path| is_session_file(path))
+        .map(|path| (session_file_stem(&path).unwrap_or_default().to_string(), path));
+    newest_per_session(files)
+}
+
+/// Find all Codex session files under the given roots.
+fn find_codex_sessions_in(roots: &[PathBuf]) -> Vec<PathBuf> {
+    let files = roots.iter().flat_map(|root| find_codex_sessions(root)).map(|path| {
+        // Files without a rollout uuid are keyed by path so none are dropped
+        let key = session_file_stem(&path)
+            .and_then(codex_file_uuid)
+            .unwrap_or_else(|| path.to_string_lossy().into_owned());
+        (key, path)
+    });
+    newest_per_session(files)
+}
+
+/// Keep one file per session key, found under any root: the most recently
+/// modified copy, with a tie going to the later one. Returned sorted.
+fn newest_per_session(files: impl Iterator<Item = (String, PathBuf)>) -> Vec<PathBuf> {
+    let mut newest: HashMap<String, (Option<SystemTime>, PathBuf)> = HashMap::new();
+    for (key, path) in files {
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+        match newest.get(&key) {
+            Some((kept, _)) if *kept > modified => {}
+            _ => {
+                newest.insert(key, (modified, path));
+            }
+        }
+    }
+    let mut paths: Vec<PathBuf> = newest.into_values().map(|(_, path)| path).collect();
+    paths.sort();
+    paths
+}
+
+/// Find the Gemini CLI logs (`<project hash>/logs.json`) under a temp root.
+pub fn find_gemini_sessions(dir: &Path) -> Vec<PathBuf> {
+    let mut logs: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path().join("logs.json"))
+        .filter(|path| path.is_file())
+        .collect();
+    logs.sort();
+    logs
+}
+
+/// Read the ids (without the `gemini:` prefix) of the sessions a Gemini log
+/// records. An unreadable log records none.
+fn gemini_log_session_ids(path: &Path) -> HashSet<String> {
+    let Ok(file) = fs::File::open(path) else {
+        return HashSet::new();
+    };
+    let entries: Vec<serde_json::Value> =
+        serde_json::from_reader(std::io::BufReader::new(file)).unwrap_or_default();
+    entries
+        .iter()
+        .filter_map(|entry| entry.get("sessionId")?.as_str())
+        .filter(|id| !id.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Find all Codex session files under a sessions root.
+pub fn find_codex_sessions(dir: &Path) -> Vec<PathBuf> {
     if !dir.exists() {
         return Vec::new();
     }
@@ -94,7 +335,7 @@ pub fn find_codex_sessions() -> Vec<PathBuf> {
     let mut sessions = Vec::new();
 
     // Navigate year/month/day structure
-    if let Ok(years) = fs::read_dir(&dir) {
+    if let Ok(years) = fs::read_dir(dir) {
         for year in years.flatten() {
             let year_path = year.path();
             if !year_path.is_dir() || !year.file_name().to_string_lossy().chars().all(|c| c.is_ascii_digit()) {
@@ -118,7 +359,7 @@ pub fn find_codex_sessions() -> Vec<PathBuf> {
                             if let Ok(files) = fs::read_dir(&day_path) {
                                 for file in files.flatten() {
                                     let file_path = file.path();
-                                    if file_path.extension().map_or(false, |e| e == "jsonl") {
+                                    if is_session_file(&file_path) {
                                         sessions.push(file_path);
                                     }
                                 }
@@ -135,13 +376,57 @@ pub fn find_codex_sessions() -> Vec<PathBuf> {
 }
 
 /// Sync result for a single session.
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize)]
 #[allow(dead_code)]
 pub struct SyncResult {
     pub session_id: String,
     pub project: String,
     pub skipped: bool,
+    /// Whether the session was not in the database before this sync.
+    pub is_new: bool,
     pub messages: usize,
+    /// Project the session belonged to before its source file moved to a
+    /// directory naming a different project.
+    pub renamed_from: Option<String>,
+}
+
+/// Sessions moved from one project to another because their directory was
+/// renamed; `from` can be merged into `to` with `merge_projects`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ProjectRename {
+    pub from: String,
+    pub to: String,
+}
+
+/// Reason a session file was not synced.
+#[derive(Debug, thiserror::Error)]
+pub enum SyncError {
+    #[error("agent-file")]
+    AgentFile,
+    #[error("exec")]
+    ExecSession,
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to parse session file")]
+    Parse,
+    #[error("not in a known session directory")]
+    UnknownLocation,
+    #[error("unknown agent: {0}")]
+    UnknownAgent(String),
+    #[error("file is locked, will retry on next sync: {0}")]
+    Locked(std::io::Error),
+    #[error("database error: {0}")]
+    Db(#[from] rusqlite::Error),
+}
+
+impl SyncError {
+    /// Whether the file was skipped deliberately rather than failing.
+    pub fn is_rule(&self) -> bool {
+        matches!(
+            self,
+            SyncError::AgentFile | SyncError::ExecSession | SyncError::UnknownLocation
+        )
+    }
 }
 
 /// Sync a Claude session file.
@@ -151,209 +436,1002 @@ pub fn sync_claude_session(
     project_name: &str,
     machine: &str,
     force: bool,
-) -> Option<SyncResult> {
-    let session_id = path.file_stem()?.to_str()?;
+    options: &ParseOptions,
+) -> Result<SyncResult, SyncError> {
+    let session_id = session_file_stem(path).ok_or(SyncError::Parse)?;
 
     // Skip agent files
     if session_id.starts_with("agent-") {
-        return None;
+        return Err(SyncError::AgentFile);
     }
+    let _guard = db.lock_session(session_id);
+
+    ensure_readable(path)?;
 
-    let source_size = fs::metadata(path).ok()?.len() as i64;
+    let meta = fs::metadata(path)?;
+    let source_size = meta.len() as i64;
+    let source_mtime = file_mtime(&meta);
+
+    // A different stored path means the file moved, e.g. its repo was renamed
+    let source_path = path.to_string_lossy().into_owned();
+    let stored = db.get_session_source(session_id)?;
+    let moved_from = match &stored {
+        Some((project, Some(stored_path))) if *stored_path != source_path => Some(project.clone()),
+        _ => None,
+    };
 
     // Check if file has changed
-    if !force {
-        if let Ok(Some((stored_size, stored_hash))) = db.get_session_file_info(session_id) {
-            if stored_size == source_size {
-                let source_hash = compute_file_hash(path)?;
-                if source_hash == stored_hash {
-                    return Some(SyncResult {
-                        session_id: session_id.to_string(),
-                        project: project_name.to_string(),
-                        skipped: true,
-                        messages: 0,
-                    });
-                }
+    let mut source_hash = None;
+    if !force && file_unchanged(db, session_id, path, source_size, source_mtime, &mut source_hash)?
+    {
+        let mut result = SyncResult {
+            session_id: session_id.to_string(),
+            project: project_name.to_string(),
+            skipped: true,
+            is_new: false,
+            messages: 0,
+            renamed_from: None,
+        };
+        if let Some((project, stored_path)) = stored {
+            if stored_path.as_deref() != Some(source_path.as_str()) {
+                let project = match &moved_from {
+                    Some(_) => {
+                        let cwd = first_claude_cwd(path).ok().flatten();
+                        relocated_project(cwd.as_deref(), project_name)
+                    }
+                    None => project,
+                };
+                db.move_session(session_id, &source_path, &project)?;
+                result.renamed_from = moved_from.filter(|old| *old != project);
+                result.project = project;
             }
         }
+        return Ok(result);
     }
 
-    let source_hash = compute_file_hash(path)?;
+    // A file that only grew can be synced by parsing just its new lines
+    if !force && moved_from.is_none() {
+        let appended =
+            append_claude_session(db, path, machine, source_size, source_mtime, options)?;
+        if let Some(result) = appended {
+            return Ok(result);
+        }
+    }
+
+    let source_hash = cached_hash(path, &mut source_hash)?.to_string();
 
     // Parse the session
-    let mut parsed = parse_claude_session(path, project_name, machine)?;
+    let started = Instant::now();
+    let mut parsed = parse_claude_session_with(path, project_name, machine, options)
+        .ok_or(SyncError::Parse)?;
+    record_parse(source_size, parsed.messages.len(), started.elapsed());
     parsed.metadata.file_size = Some(source_size);
     parsed.metadata.file_hash = Some(source_hash);
+    parsed.metadata.file_mtime = source_mtime;
+    parsed.metadata.source_path = Some(source_path);
+    let mut renamed_from = None;
+    if let Some(old_project) = moved_from {
+        parsed.metadata.project = relocated_project(parsed.metadata.cwd.as_deref(), project_name);
+        renamed_from = Some(old_project).filter(|old| *old != parsed.metadata.project);
+    }
 
     // Update database
-    db.upsert_session(&parsed.metadata).ok()?;
-    db.delete_session_messages(&parsed.metadata.session_id).ok()?;
-    if !parsed.messages.is_empty() {
-        db.insert_messages(&parsed.messages).ok()?;
-    }
+    let is_new = db.replace_session(&parsed.metadata, &parsed.messages, &parsed.raw_messages)?;
 
-    Some(SyncResult {
+    Ok(SyncResult {
         session_id: parsed.metadata.session_id,
-        project: project_name.to_string(),
+        project: parsed.metadata.project,
         skipped: false,
+        is_new,
         messages: parsed.messages.len(),
+        renamed_from,
     })
 }
 
+/// Sync a Claude session file that grew since its last sync by parsing only
+/// the appended lines and adding their messages to the stored ones.
+///
+/// Returns None when the file needs a full parse instead: it is new,
+/// compressed or shrank, the previously synced bytes changed (the file was
+/// rewritten), repeats are being folded into earlier messages, or another
+/// sync stored a different offset in the meantime.
+fn append_claude_session(
+    db: &Database,
+    path: &Path,
+    machine: &str,
+    size: i64,
+    mtime: Option<i64>,
+    options: &ParseOptions,
+) -> Result<Option<SyncResult>, SyncError> {
+    if options.collapse_repeats {
+        return Ok(None);
+    }
+    let session_id = session_file_stem(path).ok_or(SyncError::Parse)?;
+    let Some(stored) = db.get_session(session_id)? else {
+        return Ok(None);
+    };
+    let (Some(stored_size), Some(stored_hash), Some(offset)) =
+        (stored.file_size, stored.file_hash.as_deref(), stored.parsed_offset)
+    else {
+        return Ok(None);
+    };
+    if stored.content_purged || size <= stored_size || offset > stored_size {
+        return Ok(None);
+    }
+    let (prefix_hash, source_hash) = hash_file_with_prefix(path, stored_size as u64)?;
+    if prefix_hash != stored_hash {
+        return Ok(None);
+    }
+
+    let (message_index, tool_seq, uuids, response_ids) = db.get_append_point(session_id)?;
+    let resume = ClaudeResume {
+        offset: offset as u64,
+        message_index,
+        tool_seq,
+        uuids,
+        response_ids,
+    };
+    let started = Instant::now();
+    let Some(appended) = parse_claude_session_from(path, &stored.project, machine, options, &resume)
+    else {
+        return Ok(None);
+    };
+    record_parse(size - offset, appended.messages.len(), started.elapsed());
+
+    let mut session = merge_appended(stored, appended.metadata);
+    session.file_size = Some(size);
+    session.file_hash = Some(source_hash);
+    session.file_mtime = mtime;
+    if !db.append_session(&session, &appended.messages, &appended.raw_messages, offset)? {
+        return Ok(None);
+    }
+
+    Ok(Some(SyncResult {
+        session_id: session.session_id,
+        project: session.project,
+        skipped: false,
+        is_new: false,
+        messages: appended.messages.len(),
+        renamed_from: None,
+    }))
+}
+
+/// Fold the metadata parsed from a session's appended lines into its stored
+/// metadata. Values fixed at the start of a session keep their stored value;
+/// ones that move as it runs take the appended value.
+fn merge_appended(stored: Session, appended: Session) -> Session {
+    let sum = |a: Option<i64>, b: Option<i64>| match (a, b) {
+        (Some(a), Some(b)) => Some(a + b),
+        (a, b) => a.or(b),
+    };
+    Session {
+        first_message: stored.first_message.or(appended.first_message),
+        summary: appended.summary.or(stored.summary),
+        started_at: stored.started_at.or(appended.started_at),
+        ended_at: appended.ended_at.or(stored.ended_at),
+        message_count: stored.message_count + appended.message_count,
+        git_branch: appended.git_branch.or(stored.git_branch),
+        parent_session_id: stored.parent_session_id.or(appended.parent_session_id),
+        reasoning_effort: stored.reasoning_effort.or(appended.reasoning_effort),
+        cwd: stored.cwd.or(appended.cwd),
+        model: stored.model.or(appended.model),
+        total_input_tokens: sum(stored.total_input_tokens, appended.total_input_tokens),
+        total_output_tokens: sum(stored.total_output_tokens, appended.total_output_tokens),
+        parsed_offset: appended.parsed_offset,
+        ..stored
+    }
+}
+
+/// Project for a Claude session whose file moved. Its recorded cwd still
+/// names the project unless that directory is gone, in which case the repo
+/// was renamed and the file's new directory names it.
+fn relocated_project(cwd: Option<&str>, dir_project: &str) -> String {
+    cwd.filter(|cwd| Path::new(cwd).is_dir())
+        .and_then(project_from_cwd)
+        .unwrap_or_else(|| dir_project.to_string())
+}
+
 /// Sync a Codex session file.
 pub fn sync_codex_session(
     db: &Database,
     path: &Path,
     machine: &str,
     force: bool,
-) -> Option<SyncResult> {
-    let source_size = fs::metadata(path).ok()?.len() as i64;
+    options: &ParseOptions,
+) -> Result<SyncResult, SyncError> {
+    ensure_readable(path)?;
+    let parse = |source_size: i64| {
+        let started = Instant::now();
+        let parsed = parse_codex_session_with(path, machine, false, options)
+            .ok_or(SyncError::ExecSession)?;
+        record_parse(source_size, parsed.messages.len(), started.elapsed());
+        Ok::<_, SyncError>(parsed)
+    };
+    let meta = fs::metadata(path)?;
+    let mut source_size = meta.len() as i64;
+    let mut source_mtime = file_mtime(&meta);
 
     // Parse first to get session_id (and skip non-interactive)
-    let mut parsed = parse_codex_session(path, machine, false)?;
-
+    let mut parsed = parse(source_size)?;
+    let _guard = db.lock_session(&parsed.metadata.session_id);
+
+    // The first parse ran unlocked; if the file changed since, parse it again
+    // under the lock so this copy is no older than what another sync stored
+    let meta = fs::metadata(path)?;
+    if (meta.len() as i64, file_mtime(&meta)) != (source_size, source_mtime) {
+        source_size = meta.len() as i64;
+        source_mtime = file_mtime(&meta);
+        let reparsed = parse(source_size)?;
+        if reparsed.metadata.session_id != parsed.metadata.session_id {
+            return Err(SyncError::Parse);
+        }
+        parsed = reparsed;
+    }
     let session_id = &parsed.metadata.session_id;
 
     // Check if file has changed
-    if !force {
-        if let Ok(Some((stored_size, stored_hash))) = db.get_session_file_info(session_id) {
-            if stored_size == source_size {
-                let source_hash = compute_file_hash(path)?;
-                if source_hash == stored_hash {
-                    return Some(SyncResult {
-                        session_id: session_id.clone(),
-                        project: parsed.metadata.project.clone(),
-                        skipped: true,
-                        messages: 0,
-                    });
-                }
+    let mut source_hash = None;
+    if !force && file_unchanged(db, session_id, path, source_size, source_mtime, &mut source_hash)?
+    {
+        // Sessions synced before their source path was kept get it now
+        let source_path = path.to_string_lossy();
+        if let Some((project, stored_path)) = db.get_session_source(session_id)? {
+            if stored_path.as_deref() != Some(source_path.as_ref()) {
+                db.move_session(session_id, &source_path, &project)?;
             }
         }
+        return Ok(SyncResult {
+            session_id: session_id.clone(),
+            project: parsed.metadata.project.clone(),
+            skipped: true,
+            is_new: false,
+            messages: 0,
+            renamed_from: None,
+        });
     }
 
-    let source_hash = compute_file_hash(path)?;
+    let source_hash = cached_hash(path, &mut source_hash)?.to_string();
     parsed.metadata.file_size = Some(source_size);
     parsed.metadata.file_hash = Some(source_hash);
+    parsed.metadata.file_mtime = source_mtime;
+    parsed.metadata.source_path = Some(path.to_string_lossy().into_owned());
 
     // Update database
-    db.upsert_session(&parsed.metadata).ok()?;
-    db.delete_session_messages(&parsed.metadata.session_id).ok()?;
-    if !parsed.messages.is_empty() {
-        db.insert_messages(&parsed.messages).ok()?;
-    }
+    let is_new = db.replace_session(&parsed.metadata, &parsed.messages, &[])?;
 
-    Some(SyncResult {
+    Ok(SyncResult {
         session_id: parsed.metadata.session_id,
         project: parsed.metadata.project,
         skipped: false,
+        is_new,
         messages: parsed.messages.len(),
+        renamed_from: None,
     })
 }
 
-/// Sync all Claude sessions from a project directory.
-pub fn sync_claude_project(
+/// Sync every session in a Gemini CLI log. All of a project's sessions share
+/// one log, so each is stored with the size and hash of its own entries and
+/// only sessions whose entries changed are rewritten.
+pub fn sync_gemini_log(
     db: &Database,
-    project_dir: &Path,
+    path: &Path,
     machine: &str,
-) -> (usize, usize, usize) {
-    let project_name = get_project_name(&project_dir.file_name().unwrap_or_default().to_string_lossy());
+) -> Result<Vec<SyncResult>, SyncError> {
+    ensure_readable(path)?;
+    let meta = fs::metadata(path)?;
+    let source_size = meta.len() as i64;
+    let source_mtime = file_mtime(&meta);
+    let source_path = path.to_string_lossy().into_owned();
+    let skipped = |session_id: String, project: String| SyncResult {
+        session_id,
+        project,
+        skipped: true,
+        is_new: false,
+        messages: 0,
+        renamed_from: None,
+    };
+
+    // Sessions synced from the log record its mtime, so a log untouched
+    // since then needn't be parsed
+    let stored = db.get_sessions_by_source(&source_path)?;
+    if source_mtime.is_some()
+        && !stored.is_empty()
+        && stored.iter().all(|session| session.file_mtime == source_mtime)
+    {
+        return Ok(stored.into_iter().map(|s| skipped(s.session_id, s.project)).collect());
+    }
 
-    let mut total = 0;
-    let mut synced = 0;
-    let mut skipped = 0;
+    let started = Instant::now();
+    let sessions = parse_gemini_sessions(path, machine).ok_or(SyncError::Parse)?;
+    let parsed_messages = sessions.iter().map(|s| s.messages.len()).sum();
+    record_parse(source_size, parsed_messages, started.elapsed());
+
+    let mut results = Vec::new();
+    for mut parsed in sessions {
+        let session_id = parsed.metadata.session_id.clone();
+        let _guard = db.lock_session(&session_id);
+        // A session moved in from another log is rewritten to record its new source
+        let same_source = db.get_session_source(&session_id)?.and_then(|(_, path)| path)
+            == Some(source_path.clone());
+        let unchanged = db.get_session_file_info(&session_id)?.is_some_and(|(size, hash, _)| {
+            parsed.metadata.file_size == Some(size) && parsed.metadata.file_hash == Some(hash)
+        });
+        if same_source && unchanged {
+            if let Some(mtime) = source_mtime {
+                db.set_file_mtime(&session_id, mtime)?;
+            }
+            results.push(skipped(session_id, parsed.metadata.project));
+            continue;
+        }
 
-    if let Ok(entries) = fs::read_dir(project_dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.extension().map_or(false, |e| e == "jsonl") {
-                if let Some(result) = sync_claude_session(db, &path, &project_name, machine, false) {
-                    total += 1;
-                    if result.skipped {
-                        skipped += 1;
-                    } else {
-                        synced += 1;
-                    }
-                }
+        parsed.metadata.file_mtime = source_mtime;
+        parsed.metadata.source_path = Some(source_path.clone());
+        let is_new = db.replace_session(&parsed.metadata, &parsed.messages, &[])?;
+        results.push(SyncResult {
+            session_id,
+            project: parsed.metadata.project,
+            skipped: false,
+            is_new,
+            messages: parsed.messages.len(),
+            renamed_from: None,
+        });
+    }
+    Ok(results)
+}
+
+/// Sync a Claude session file, naming its project after the containing directory.
+fn sync_claude_file(
+    db: &Database,
+    path: &Path,
+    machine: &str,
+    options: &ParseOptions,
+) -> Result<SyncResult, SyncError> {
+    let project_dir = path.parent().ok_or(SyncError::UnknownLocation)?;
+    let project_name =
+        get_project_name(&project_dir.file_name().unwrap_or_default().to_string_lossy());
+    sync_claude_session(db, path, &project_name, machine, false, options)
+}
+
+/// Meta key holding the parser settings saved by `save_parse_options`.
+const PARSE_OPTIONS_KEY: &str = "parse_options";
+
+/// Get the saved parser settings, or the defaults if none were saved.
+///
+/// Every sync path parses with these, so a file synced by the watcher or an
+/// import is stored the same way as by a full sync.
+pub fn saved_parse_options(db: &Database) -> Result<ParseOptions, SyncError> {
+    Ok(db
+        .get_meta(PARSE_OPTIONS_KEY)?
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default())
+}
+
+/// Save the parser settings applied by later syncs.
+pub fn save_parse_options(db: &Database, options: &ParseOptions) -> Result<(), SyncError> {
+    let json = serde_json::to_string(options).expect("parse options serialize to JSON");
+    db.set_meta(PARSE_OPTIONS_KEY, &json)?;
+    Ok(())
+}
+
+/// Sync a single session file, routing to the Claude or Codex parser by location.
+///
+/// Claude files take their project from the containing directory; Codex files
+/// derive it from the recorded cwd, and a name shared with another cwd is
+/// disambiguated by the next full sync. Files are parsed with the saved
+/// settings.
+pub fn sync_path(db: &Database, path: &Path, machine: &str) -> Result<SyncResult, SyncError> {
+    let roots = SyncRoots {
+        claude: &claude_projects_dirs(),
+        codex: &codex_sessions_dirs(),
+        ..Default::default()
+    };
+    sync_path_in(db, path, machine, &roots)
+}
+
+pub(crate) fn sync_path_in(
+    db: &Database,
+    path: &Path,
+    machine: &str,
+    roots: &SyncRoots,
+) -> Result<SyncResult, SyncError> {
+    let options = saved_parse_options(db)?;
+    if roots.codex.iter().any(|dir| path.starts_with(dir)) {
+        return sync_codex_session(db, path, machine, false, &options);
+    }
+
+    if let Some(claude_dir) = roots.claude.iter().find(|dir| path.starts_with(dir)) {
+        let project_dir = path.parent().ok_or(SyncError::UnknownLocation)?;
+        if project_dir.parent() != Some(claude_dir.as_path()) {
+            return Err(SyncError::UnknownLocation);
+        }
+        return sync_claude_file(db, path, machine, &options);
+    }
+
+    Err(SyncError::UnknownLocation)
+}
+
+/// Import a session file from any location.
+///
+/// The parser is picked from `agent_hint` ("claude" or "codex") or, when
+/// absent, detected from the first entry. Codex files take their project from
+/// the recorded cwd; Claude files use the first `cwd` they record and fall
+/// back to the containing directory name. The file is parsed with the saved
+/// settings.
+pub fn import_session_file(
+    db: &Database,
+    path: &Path,
+    agent_hint: Option<&str>,
+    machine: &str,
+) -> Result<SyncResult, SyncError> {
+    let path = fs::canonicalize(path)?;
+    if !path.is_file() {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "not a file").into());
+    }
+
+    let agent = match agent_hint {
+        Some(hint) => hint.to_ascii_lowercase(),
+        None => detect_agent(&path)?.to_string(),
+    };
+
+    let options = saved_parse_options(db)?;
+    match agent.as_str() {
+        "codex" => sync_codex_session(db, &path, machine, false, &options),
+        "claude" => {
+            // The parser prefers the recorded cwd over this directory-based name
+            let project_name = get_project_name(
+                &path
+                    .parent()
+                    .and_then(|p| p.file_name())
+                    .unwrap_or_default()
+                    .to_string_lossy(),
+            );
+            sync_claude_session(db, &path, &project_name, machine, false, &options)
+        }
+        _ => Err(SyncError::UnknownAgent(agent)),
+    }
+}
+
+/// Guess which agent wrote a session file from its first entry.
+fn detect_agent(path: &Path) -> Result<&'static str, SyncError> {
+    let reader = open_session_file(path)?;
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: serde_json::Value = serde_json::from_str(&line).map_err(|_| SyncError::Parse)?;
+        let is_codex = entry.get("payload").is_some()
+            || entry.get("type").and_then(|v| v.as_str()) == Some("session_meta");
+        return Ok(if is_codex { "codex" } else { "claude" });
+    }
+    Err(SyncError::Parse)
+}
+
+/// Find the first working directory recorded in a Claude session file.
+fn first_claude_cwd(path: &Path) -> std::io::Result<Option<String>> {
+    let reader = open_session_file(path)?;
+    for line in reader.lines() {
+        let line = line?;
+        let Ok(entry) = serde_json::from_str::<serde_json::Value>(&line) else {
+            continue;
+        };
+        if let Some(cwd) = entry.get("cwd").and_then(|v| v.as_str()) {
+            if !cwd.is_empty() {
+                return Ok(Some(cwd.to_string()));
             }
         }
     }
+    Ok(None)
+}
 
-    (total, synced, skipped)
+/// Options controlling a full sync.
+#[derive(Debug, Default, Clone)]
+pub struct SyncOptions {
+    /// Collect a per-file decision log in the returned stats.
+    pub verbose: bool,
+    /// Remove this machine's sessions whose source files are gone.
+    pub prune: bool,
+    /// Parser settings applied to files that are (re)parsed in this pass.
+    pub parse: ParseOptions,
+    /// When set, workers stop between files and the sync returns partial stats.
+    pub cancel: Option<Arc<AtomicBool>>,
 }
 
-/// Sync all sessions (Claude + Codex).
+impl SyncOptions {
+    fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|c| c.load(Ordering::Relaxed))
+    }
+}
+
+/// Sync all sessions (Claude + Codex) with the saved parser settings.
 pub fn sync_all(db: &Database, machine: &str) -> SyncStats {
-    let mut stats = SyncStats::default();
-
-    // Sync Claude projects
-    for project_dir in find_claude_projects() {
-        let (total, synced, skipped) = sync_claude_project(db, &project_dir, machine);
-        stats.total_sessions += total;
-        stats.synced += synced;
-        stats.skipped += skipped;
-    }
-
-    // Sync Codex sessions
-    for session_path in find_codex_sessions() {
-        if let Some(result) = sync_codex_session(db, &session_path, machine, false) {
-            stats.total_sessions += 1;
-            if result.skipped {
-                stats.skipped += 1;
-            } else {
-                stats.synced += 1;
-            }
+    let parse = saved_parse_options(db).unwrap_or_else(|e| {
+        eprintln!("Failed to read parse options: {}", e);
+        ParseOptions::default()
+    });
+    sync_all_with(db, machine, &SyncOptions { parse, ..Default::default() })
+}
+
+/// Sync all sessions (Claude + Codex) with the given options.
+pub fn sync_all_with(db: &Database, machine: &str, options: &SyncOptions) -> SyncStats {
+    let roots = SyncRoots {
+        claude: &claude_projects_dirs(),
+        codex: &codex_sessions_dirs(),
+        gemini: &gemini_tmp_dirs(),
+    };
+    sync_roots(db, machine, options, &roots)
+}
+
+/// Directories a full sync scans, per agent.
+#[derive(Default)]
+pub(crate) struct SyncRoots<'a> {
+    pub(crate) claude: &'a [PathBuf],
+    pub(crate) codex: &'a [PathBuf],
+    pub(crate) gemini: &'a [PathBuf],
+}
+
+/// An independent unit of sync work.
+enum SyncJob {
+    ClaudeSession(PathBuf),
+    CodexSession(PathBuf),
+    GeminiLog(PathBuf),
+}
+
+fn sync_roots(db: &Database, machine: &str, options: &SyncOptions, roots: &SyncRoots) -> SyncStats {
+    let workers = thread::available_parallelism().map_or(1, |n| n.get());
+    sync_roots_with_workers(db, machine, options, roots, workers)
+}
+
+/// Sync every Claude, Codex and Gemini session file, spreading them over
+/// `workers` threads. A session found under several roots is synced from its
+/// newest copy.
+fn sync_roots_with_workers(
+    db: &Database,
+    machine: &str,
+    options: &SyncOptions,
+    roots: &SyncRoots,
+    workers: usize,
+) -> SyncStats {
+    let new_stats = || SyncStats {
+        log: options.verbose.then(Vec::new),
+        ..Default::default()
+    };
+
+    let gemini_logs = roots.gemini.iter().flat_map(|dir| find_gemini_sessions(dir));
+    let jobs: Vec<SyncJob> = find_claude_sessions(roots.claude)
+        .into_iter()
+        .map(SyncJob::ClaudeSession)
+        .chain(find_codex_sessions_in(roots.codex).into_iter().map(SyncJob::CodexSession))
+        .chain(gemini_logs.map(SyncJob::GeminiLog))
+        .collect();
+    let next = AtomicUsize::new(0);
+
+    let mut stats = new_stats();
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers.clamp(1, jobs.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut local = new_stats();
+                    while !options.is_cancelled() {
+                        let Some(job) = jobs.get(next.fetch_add(1, Ordering::Relaxed)) else {
+                            break;
+                        };
+                        match job {
+                            SyncJob::ClaudeSession(path) => {
+                                let result = sync_claude_file(db, path, machine, &options.parse);
+                                local.record(path, &result);
+                            }
+                            SyncJob::CodexSession(path) => {
+                                let result =
+                                    sync_codex_session(db, path, machine, false, &options.parse);
+                                local.record(path, &result);
+                            }
+                            SyncJob::GeminiLog(path) => match sync_gemini_log(db, path, machine) {
+                                Ok(results) => {
+                                    for result in results {
+                                        local.record(path, &Ok(result));
+                                    }
+                                }
+                                Err(e) => local.record(path, &Err(e)),
+                            },
+                        }
+                    }
+                    local
+                })
+            })
+            .collect();
+        for handle in handles {
+            stats.merge(handle.join().expect("sync worker panicked"));
+        }
+    });
+    if let Some(log) = &mut stats.log {
+        log.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    stats.cancelled = options.is_cancelled();
+
+    // A failed, locked or unvisited file may still exist on disk, so only
+    // prune after a clean, complete pass
+    if options.prune && stats.failed == 0 && stats.deferred == 0 && !stats.cancelled {
+        match prune_deleted_sessions(db, machine, &stats.seen, roots) {
+            Ok(deleted) => stats.deleted = deleted,
+            Err(e) => eprintln!("Failed to prune deleted sessions: {}", e),
         }
     }
+    if let Err(e) = disambiguate_codex_projects(db) {
+        eprintln!("Failed to disambiguate Codex projects: {}", e);
+    }
 
     stats
 }
 
+/// Delete this machine's sessions, with their messages and tool calls, whose
+/// ids are not in `seen` (the sessions found on disk during a full sync).
+///
+/// Only sessions last synced from a file under one of the scanned roots are
+/// pruned; imported and merged sessions have no such file to go missing.
+pub(crate) fn prune_deleted_sessions(
+    db: &Database,
+    machine: &str,
+    seen: &HashSet<String>,
+    roots: &SyncRoots,
+) -> rusqlite::Result<usize> {
+    let scanned = roots.claude.iter().chain(roots.codex).chain(roots.gemini);
+    let mut deleted = 0;
+    for session_id in db.get_session_ids(machine)? {
+        if seen.contains(&session_id) {
+            continue;
+        }
+        let Some((_, Some(source_path))) = db.get_session_source(&session_id)? else {
+            continue;
+        };
+        if scanned.clone().any(|dir| Path::new(&source_path).starts_with(dir))
+            && db.delete_session(&session_id)?
+        {
+            deleted += 1;
+        }
+    }
+    Ok(deleted)
+}
+
+/// What sync decided to do with a single file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum SyncDecision {
+    Synced,
+    SkippedUnchanged,
+    SkippedRule,
+    /// The file was locked; it will be picked up on the next sync.
+    Deferred,
+    Failed,
+}
+
+/// A single entry in the verbose sync log.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SyncLogEntry {
+    pub path: String,
+    pub decision: SyncDecision,
+    pub reason: Option<String>,
+}
+
 /// Statistics from a sync operation.
 #[derive(Debug, Default, serde::Serialize)]
 pub struct SyncStats {
     pub total_sessions: usize,
+    /// Sessions written to the database (`new + updated`).
     pub synced: usize,
-    pub skipped: usize,
+    pub new: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub deleted: usize,
+    /// Files left for the next sync because they were locked.
+    pub deferred: usize,
+    pub failed: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log: Option<Vec<SyncLogEntry>>,
+    /// Projects whose sessions moved to a renamed directory.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub renamed_projects: Vec<ProjectRename>,
+    /// The sync was cancelled before every file was visited.
+    pub cancelled: bool,
+    /// Session IDs present on disk during this sync.
+    #[serde(skip)]
+    seen: HashSet<String>,
+}
+
+impl SyncStats {
+    /// Fold another worker's stats into these.
+    fn merge(&mut self, other: SyncStats) {
+        self.total_sessions += other.total_sessions;
+        self.synced += other.synced;
+        self.new += other.new;
+        self.updated += other.updated;
+        self.unchanged += other.unchanged;
+        self.deleted += other.deleted;
+        self.deferred += other.deferred;
+        self.failed += other.failed;
+        if let (Some(log), Some(other_log)) = (&mut self.log, other.log) {
+            log.extend(other_log);
+        }
+        for rename in other.renamed_projects {
+            self.add_rename(rename);
+        }
+        self.seen.extend(other.seen);
+    }
+
+    fn add_rename(&mut self, rename: ProjectRename) {
+        if !self.renamed_projects.contains(&rename) {
+            self.renamed_projects.push(rename);
+        }
+    }
+
+    /// Count a single file's outcome, logging the decision if verbose.
+    pub fn record(&mut self, path: &Path, result: &Result<SyncResult, SyncError>) {
+        if let Ok(r) = result {
+            self.total_sessions += 1;
+            self.seen.insert(r.session_id.clone());
+            if let Some(from) = &r.renamed_from {
+                self.add_rename(ProjectRename {
+                    from: from.clone(),
+                    to: r.project.clone(),
+                });
+            }
+        }
+
+        let (decision, reason) = match result {
+            Ok(r) if r.skipped => {
+                self.unchanged += 1;
+                (SyncDecision::SkippedUnchanged, None)
+            }
+            Ok(r) => {
+                self.synced += 1;
+                if r.is_new {
+                    self.new += 1;
+                } else {
+                    self.updated += 1;
+                }
+                (SyncDecision::Synced, None)
+            }
+            Err(e) if e.is_rule() => (SyncDecision::SkippedRule, Some(e.to_string())),
+            Err(e @ SyncError::Locked(_)) => {
+                self.deferred += 1;
+                (SyncDecision::Deferred, Some(e.to_string()))
+            }
+            Err(e) => {
+                self.failed += 1;
+                (SyncDecision::Failed, Some(e.to_string()))
+            }
+        };
+
+        if let Some(log) = &mut self.log {
+            log.push(SyncLogEntry {
+                path: path.to_string_lossy().to_string(),
+                decision,
+                reason,
+            });
+        }
+    }
+}
+
+/// Re-derive every session's project from its source file, as a fresh sync
+/// would, and return how many sessions changed project.
+///
+/// Claude projects come from the containing directory name and Codex
+/// projects from the recorded cwd. Sessions whose source file is gone keep
+/// their current project.
+pub fn reproject_all(db: &Database) -> rusqlite::Result<usize> {
+    reproject_in(db, &claude_projects_dirs(), &codex_sessions_dirs())
+}
+
+fn reproject_in(
+    db: &Database,
+    claude_dirs: &[PathBuf],
+    codex_dirs: &[PathBuf],
+) -> rusqlite::Result<usize> {
+    let roots = SyncRoots {
+        claude: claude_dirs,
+        codex: codex_dirs,
+        ..Default::default()
+    };
+    let mut projects = Vec::new();
+    let mut codex_cwds = Vec::new();
+    for session_id in db.get_all_session_ids()? {
+        let Some(path) = find_source_file_in(&session_id, &roots) else {
+            continue;
+        };
+        if session_id.starts_with("codex:") {
+            if let Some(cwd) = codex_cwd(&path) {
+                codex_cwds.push((session_id, cwd));
+            }
+            continue;
+        }
+        let project = first_claude_cwd(&path)
+            .ok()
+            .flatten()
+            .and_then(|cwd| project_from_cwd(&cwd))
+            .or_else(|| {
+                path.parent()
+                    .and_then(|dir| dir.file_name())
+                    .map(|name| get_project_name(&name.to_string_lossy()))
+            });
+        if let Some(project) = project {
+            projects.push((session_id, project));
+        }
+    }
+    projects.extend(codex_projects(&codex_cwds));
+    db.update_projects(&projects)
+}
+
+/// Name Codex sessions' projects after the final component of their cwd,
+/// adding the parent directory where different cwds share that component so
+/// `client/app` and `server/app` stay apart.
+fn codex_projects(cwds: &[(String, String)]) -> Vec<(String, String)> {
+    let mut cwds_by_project: HashMap<String, HashSet<&str>> = HashMap::new();
+    for (_, cwd) in cwds {
+        cwds_by_project.entry(extract_codex_project(cwd)).or_default().insert(cwd);
+    }
+    cwds.iter()
+        .map(|(session_id, cwd)| {
+            let project = extract_codex_project(cwd);
+            if cwds_by_project[&project].len() > 1 {
+                (session_id.clone(), extract_codex_project_with_parent(cwd))
+            } else {
+                (session_id.clone(), project)
+            }
+        })
+        .collect()
+}
+
+/// Rename Codex projects from the stored cwds so sessions from different
+/// directories never share a project name. Returns how many sessions changed.
+fn disambiguate_codex_projects(db: &Database) -> rusqlite::Result<usize> {
+    db.update_projects(&codex_projects(&db.get_session_cwds("codex")?))
+}
+
+/// Find the directories holding a project's session files.
+pub fn get_project_paths(db: &Database, project: &str) -> rusqlite::Result<Vec<PathBuf>> {
+    project_paths_in(db, project, &claude_projects_dirs(), &codex_sessions_dirs())
+}
+
+/// Collect the parent directories of the project's synced sessions, plus
+/// any Claude project directory whose name maps to the project. Only
+/// directories that resolve inside one of the session roots are returned.
+fn project_paths_in(
+    db: &Database,
+    project: &str,
+    claude_dirs: &[PathBuf],
+    codex_dirs: &[PathBuf],
+) -> rusqlite::Result<Vec<PathBuf>> {
+    let session_roots = SyncRoots {
+        claude: claude_dirs,
+        codex: codex_dirs,
+        ..Default::default()
+    };
+    let mut dirs: Vec<PathBuf> = db
+        .get_project_session_ids(project)?
+        .iter()
+        .filter_map(|id| find_source_file_in(id, &session_roots))
+        .filter_map(|path| path.parent().map(Path::to_path_buf))
+        .collect();
+    dirs.extend(claude_dirs.iter().flat_map(|root| find_claude_projects(root)).filter(|dir| {
+        dir.file_name()
+            .is_some_and(|name| get_project_name(&name.to_string_lossy()) == project)
+    }));
+
+    let roots: Vec<PathBuf> = claude_dirs
+        .iter()
+        .chain(codex_dirs)
+        .filter_map(|root| root.canonicalize().ok())
+        .collect();
+    let mut paths: Vec<PathBuf> = dirs
+        .into_iter()
+        .filter_map(|dir| dir.canonicalize().ok())
+        .filter(|dir| roots.iter().any(|root| dir.starts_with(root)))
+        .collect();
+    paths.sort();
+    paths.dedup();
+    Ok(paths)
+}
+
+/// Read the cwd recorded in a Codex file's session_meta entry.
+fn codex_cwd(path: &Path) -> Option<String> {
+    let reader = open_session_file(path).ok()?;
+    reader
+        .lines()
+        .map_while(|line| line.ok())
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(&line).ok())
+        .find(|entry| entry.get("type").and_then(|v| v.as_str()) == Some("session_meta"))
+        .and_then(|entry| {
+            entry
+                .get("payload")?
+                .get("cwd")?
+                .as_str()
+                .map(String::from)
+        })
 }
 
 /// Find the source file for a session ID.
 pub fn find_source_file(session_id: &str) -> Option<PathBuf> {
+    let roots = SyncRoots {
+        claude: &claude_projects_dirs(),
+        codex: &codex_sessions_dirs(),
+        gemini: &gemini_tmp_dirs(),
+    };
+    find_source_file_in(session_id, &roots)
+}
+
+/// Find a session's source file under any of the roots, preferring the newest
+/// copy as a full sync does. A Gemini session's source is the project log
+/// that records it.
+pub(crate) fn find_source_file_in(session_id: &str, roots: &SyncRoots) -> Option<PathBuf> {
     if session_id.is_empty() {
         return None;
     }
 
-    // Handle Codex sessions
-    if let Some(codex_id) = session_id.strip_prefix("codex:") {
-        return find_codex_source_file(codex_id);
-    }
+    let candidates: Vec<PathBuf> = if let Some(codex_id) = session_id.strip_prefix("codex:") {
+        roots.codex.iter().filter_map(|dir| find_codex_source_file(codex_id, dir)).collect()
+    } else if let Some(gemini_id) = session_id.strip_prefix("gemini:") {
+        roots
+            .gemini
+            .iter()
+            .flat_map(|dir| find_gemini_sessions(dir))
+            .filter(|log| gemini_log_session_ids(log).contains(gemini_id))
+            .collect()
+    } else {
+        roots.claude.iter().filter_map(|dir| find_claude_source_file(session_id, dir)).collect()
+    };
+    newest_per_session(candidates.into_iter().map(|path| (String::new(), path))).pop()
+}
 
-    // Claude sessions
-    find_claude_source_file(session_id)
+/// Find a session's source file for handing to another program, resolved
+/// through symlinks and confirmed to lie inside a session root.
+pub fn find_verified_source_file(session_id: &str) -> Option<PathBuf> {
+    let roots = SyncRoots {
+        claude: &claude_projects_dirs(),
+        codex: &codex_sessions_dirs(),
+        gemini: &gemini_tmp_dirs(),
+    };
+    find_verified_source_file_in(session_id, &roots)
+}
+
+pub(crate) fn find_verified_source_file_in(session_id: &str, roots: &SyncRoots) -> Option<PathBuf> {
+    let path = find_source_file_in(session_id, roots)?.canonicalize().ok()?;
+    let agent_roots = if session_id.starts_with("codex:") {
+        roots.codex
+    } else if session_id.starts_with("gemini:") {
+        roots.gemini
+    } else {
+        roots.claude
+    };
+    agent_roots
+        .iter()
+        .filter_map(|root| root.canonicalize().ok())
+        .any(|root| path.starts_with(root))
+        .then_some(path)
 }
 
 /// Find a Claude session source file.
-fn find_claude_source_file(session_id: &str) -> Option<PathBuf> {
+fn find_claude_source_file(session_id: &str, projects_dir: &Path) -> Option<PathBuf> {
     // Validate session_id
     if !session_id.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
         return None;
     }
 
-    let projects_dir = claude_projects_dir();
     if !projects_dir.exists() {
         return None;
     }
 
-    for entry in fs::read_dir(&projects_dir).ok()?.flatten() {
+    for entry in fs::read_dir(projects_dir).ok()?.flatten() {
         let project_dir = entry.path();
         if !project_dir.is_dir() {
             continue;
         }
 
-        let candidate = project_dir.join(format!("{}.jsonl", session_id));
-        if candidate.exists() {
-            // Verify path doesn't escape project dir
-            if candidate.canonicalize().ok()?.starts_with(project_dir.canonicalize().ok()?) {
-                return Some(candidate);
+        for extension in ["jsonl", "jsonl.gz"] {
+            let candidate = project_dir.join(format!("{}.{}", session_id, extension));
+            if candidate.exists() {
+                // Verify path doesn't escape project dir
+                if candidate.canonicalize().ok()?.starts_with(project_dir.canonicalize().ok()?) {
+                    return Some(candidate);
+                }
             }
         }
     }
@@ -362,19 +1440,56 @@ fn find_claude_source_file(session_id: &str) -> Option<PathBuf> {
 }
 
 /// Find a Codex session source file by UUID.
-fn find_codex_source_file(session_id: &str) -> Option<PathBuf> {
+/// Extract the session UUID from a Codex rollout file stem.
+fn codex_file_uuid(stem: &str) -> Option<String> {
+    if !stem.starts_with("rollout-") {
+        return None;
+    }
+    // Extract UUID using rsplit
+    let parts: Vec<&str> = stem.rsplit('-').take(5).collect();
+    (parts.len() == 5).then(|| {
+        format!("{}-{}-{}-{}-{}", parts[4], parts[3], parts[2], parts[1], parts[0])
+    })
+}
+
+/// Work out which sessions a file under a session root holds without reading
+/// it, e.g. after the file was deleted. Claude and Codex files hold one
+/// session named by the path; a Gemini log holds every session last synced
+/// from it.
+pub(crate) fn session_ids_for_path(
+    db: &Database,
+    path: &Path,
+    roots: &SyncRoots,
+) -> rusqlite::Result<Vec<String>> {
+    if roots.gemini.iter().any(|dir| path.starts_with(dir)) {
+        let sessions = db.get_sessions_by_source(&path.to_string_lossy())?;
+        return Ok(sessions.into_iter().map(|s| s.session_id).collect());
+    }
+    let Some(stem) = session_file_stem(path) else {
+        return Ok(Vec::new());
+    };
+    if roots.codex.iter().any(|dir| path.starts_with(dir)) {
+        let session_id = codex_file_uuid(stem).map(|uuid| format!("codex:{}", uuid));
+        return Ok(session_id.into_iter().collect());
+    }
+    if roots.claude.iter().any(|dir| path.starts_with(dir)) && !stem.starts_with("agent-") {
+        return Ok(vec![stem.to_string()]);
+    }
+    Ok(Vec::new())
+}
+
+fn find_codex_source_file(session_id: &str, sessions_dir: &Path) -> Option<PathBuf> {
     // Validate session_id
     if !session_id.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
         return None;
     }
 
-    let sessions_dir = codex_sessions_dir();
     if !sessions_dir.exists() {
         return None;
     }
 
     // Search through year/month/day structure
-    for year in fs::read_dir(&sessions_dir).ok()?.flatten() {
+    for year in fs::read_dir(sessions_dir).ok()?.flatten() {
         let year_path = year.path();
         if !year_path.is_dir() {
             continue;
@@ -394,21 +1509,9 @@ fn find_codex_source_file(session_id: &str) -> Option<PathBuf> {
 
                 for file in fs::read_dir(&day_path).ok()?.flatten() {
                     let file_path = file.path();
-                    if file_path.extension().map_or(false, |e| e == "jsonl") {
-                        let stem = file_path.file_stem()?.to_string_lossy();
-                        if stem.starts_with("rollout-") {
-                            // Extract UUID using rsplit
-                            let parts: Vec<&str> = stem.rsplit('-').take(5).collect();
-                            if parts.len() == 5 {
-                                let file_uuid = format!(
-                                    "{}-{}-{}-{}-{}",
-                                    parts[4], parts[3], parts[2], parts[1], parts[0]
-                                );
-                                if file_uuid == session_id {
-                                    return Some(file_path);
-                                }
-                            }
-                        }
+                    let uuid = session_file_stem(&file_path).and_then(codex_file_uuid);
+                    if uuid.as_deref() == Some(session_id) {
+                        return Some(file_path);
                     }
                 }
             }
@@ -421,9 +1524,29 @@ fn find_codex_source_file(session_id: &str) -> Option<PathBuf> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::{DbConfig, SessionFilter, SessionSort};
     use std::fs;
     use tempfile::tempdir;
 
+    /// Run a full sync over just Claude and Codex roots.
+    fn sync_dirs(
+        db: &Database,
+        machine: &str,
+        options: &SyncOptions,
+        claude_dirs: &[PathBuf],
+        codex_dirs: &[PathBuf],
+    ) -> SyncStats {
+        sync_roots(db, machine, options, &roots_of(claude_dirs, codex_dirs))
+    }
+
+    fn roots_of<'a>(claude_dirs: &'a [PathBuf], codex_dirs: &'a [PathBuf]) -> SyncRoots<'a> {
+        SyncRoots {
+            claude: claude_dirs,
+            codex: codex_dirs,
+            ..Default::default()
+        }
+    }
+
     // Helper to validate session ID characters (mirrors the validation in find_*_source_file)
     fn is_valid_session_id(id: &str) -> bool {
         !id.is_empty() && id.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
@@ -517,8 +1640,1268 @@ mod tests {
         fs::write(&file_path, "Hello, World!").unwrap();
 
         let hash = compute_file_hash(&file_path).unwrap();
-        // MD5 of "Hello, World!" is 65a8e27d8879283831b664bd8b7f0ad4
-        assert_eq!(hash, "65a8e27d8879283831b664bd8b7f0ad4");
+        assert_eq!(hash, blake3::hash(b"Hello, World!").to_hex().to_string());
+
+        // Files larger than the copy buffer hash the same as in one piece
+        let content = "0123456789abcdef".repeat(64 * 1024);
+        fs::write(&file_path, &content).unwrap();
+        let hash = compute_file_hash(&file_path).unwrap();
+        assert_eq!(hash, blake3::hash(content.as_bytes()).to_hex().to_string());
+
+        fs::write(&file_path, "").unwrap();
+        assert_eq!(
+            compute_file_hash(&file_path).unwrap(),
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+        );
+    }
+
+    #[test]
+    fn test_store_raw_messages() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let path = tmp.path().join("session-1.jsonl");
+        let line = r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Hi"}}"#;
+        fs::write(&path, line).unwrap();
+
+        sync_claude_session(&db, &path, "proj", "local", true, &Default::default()).unwrap();
+        let msg_id = db.get_messages("session-1", None, None).unwrap()[0].msg_id.clone();
+        assert_eq!(db.get_raw_message("session-1", &msg_id).unwrap(), None);
+
+        let options = ParseOptions {
+            store_raw: true,
+            ..Default::default()
+        };
+        sync_claude_session(&db, &path, "proj", "local", true, &options).unwrap();
+        assert_eq!(
+            db.get_raw_message("session-1", &msg_id).unwrap().as_deref(),
+            Some(line)
+        );
+
+        // A later sync without the option drops the stale copy
+        sync_claude_session(&db, &path, "proj", "local", true, &Default::default()).unwrap();
+        assert_eq!(db.get_raw_message("session-1", &msg_id).unwrap(), None);
+    }
+
+    #[test]
+    fn test_size_and_mtime_fast_path() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let path = tmp.path().join("session-1.jsonl");
+        let write = |text: &str, mtime: std::time::SystemTime| {
+            fs::write(
+                &path,
+                format!(
+                    r#"{{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{{"content":"{}"}}}}"#,
+                    text
+                ),
+            )
+            .unwrap();
+            fs::File::options().write(true).open(&path).unwrap().set_modified(mtime).unwrap();
+        };
+        let sync = || sync_claude_session(&db, &path, "proj", "local", false, &Default::default());
+        let t0 = UNIX_EPOCH + Duration::from_secs(1_767_000_000);
+
+        write("Hello", t0);
+        assert!(!sync().unwrap().skipped);
+        let (_, _, mtime) = db.get_session_file_info("session-1").unwrap().unwrap();
+        assert_eq!(mtime, Some(1_767_000_000_000_000_000));
+
+        // Same size and mtime: trusted without hashing, even if bytes differ
+        write("Jello", t0);
+        assert!(sync().unwrap().skipped);
+
+        // A new mtime falls back to the hash, which catches the edit
+        let t1 = t0 + Duration::from_secs(60);
+        write("Jello", t1);
+        assert!(!sync().unwrap().skipped);
+
+        // Touched but unchanged: skipped, and the new mtime is remembered
+        let t2 = t1 + Duration::from_secs(60);
+        write("Jello", t2);
+        assert!(sync().unwrap().skipped);
+        let (_, _, mtime) = db.get_session_file_info("session-1").unwrap().unwrap();
+        assert_eq!(mtime, Some(1_767_000_120_000_000_000));
+    }
+
+    #[test]
+    fn test_unrecognised_hash_is_reparsed() {
+        let tmp = tempdir().unwrap();
+        let db_path = tmp.path().join("test.db");
+        let db = Database::open_with(&db_path, &DbConfig::default()).unwrap();
+        let path = tmp.path().join("session-1.jsonl");
+        fs::write(
+            &path,
+            r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Hello"}}"#,
+        )
+        .unwrap();
+        let sync = || sync_claude_session(&db, &path, "proj", "local", false, &Default::default());
+        assert!(!sync().unwrap().skipped);
+        let stored = db.get_session_file_info("session-1").unwrap().unwrap();
+
+        // An MD5 hash from an older version, with an mtime that no longer matches
+        rusqlite::Connection::open(&db_path)
+            .unwrap()
+            .execute("UPDATE sessions SET file_hash = ?1, file_mtime = 1", ["0".repeat(32)])
+            .unwrap();
+        assert!(!sync().unwrap().skipped);
+        assert_eq!(db.get_session_file_info("session-1").unwrap().unwrap(), stored);
+        assert!(sync().unwrap().skipped);
+    }
+
+    #[test]
+    fn test_appended_lines_are_parsed_incrementally() {
+        use std::io::Write;
+
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let path = tmp.path().join("session-1.jsonl");
+        let lines = [
+            r#"{"type":"user","uuid":"u1","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Run the tests"}}"#,
+            r#"{"type":"assistant","uuid":"a1","parentUuid":"u1","timestamp":"2026-01-08T10:00:05Z","message":{"id":"r1","content":[{"type":"text","text":"Running them"},{"type":"tool_use","name":"Bash","input":{"command":"cargo test"}}],"usage":{"input_tokens":10,"output_tokens":5}}}"#,
+            r#"{"type":"user","uuid":"u2","parentUuid":"a1","gitBranch":"fix","timestamp":"2026-01-08T10:01:00Z","message":{"content":"Now fix the failure"}}"#,
+            r#"{"type":"assistant","uuid":"a2","parentUuid":"u2","timestamp":"2026-01-08T10:01:30Z","message":{"id":"r2","content":[{"type":"text","text":"Fixed"},{"type":"tool_use","name":"Edit","input":{"file_path":"src/lib.rs"}}],"usage":{"input_tokens":20,"output_tokens":8}}}"#,
+        ];
+        let sync = |db: &Database, force: bool| {
+            sync_claude_session(db, &path, "proj", "local", force, &Default::default()).unwrap()
+        };
+
+        fs::write(&path, format!("{}\n{}\n", lines[0], lines[1])).unwrap();
+        assert_eq!(sync(&db, false).messages, 2);
+
+        // A line still being written is left for the next sync
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        let (head, tail) = lines[2].split_at(20);
+        file.write_all(head.as_bytes()).unwrap();
+        let result = sync(&db, false);
+        assert!(!result.skipped);
+        assert_eq!(result.messages, 0);
+
+        // Only the appended lines are parsed
+        file.write_all(format!("{}\n{}\n", tail, lines[3]).as_bytes()).unwrap();
+        assert_eq!(sync(&db, false).messages, 2);
+        assert!(sync(&db, false).skipped);
+
+        // The result matches a full parse of the final file
+        let full = Database::open_with(&tmp.path().join("full.db"), &DbConfig::default()).unwrap();
+        assert_eq!(sync(&full, true).messages, 4);
+        let stored = |db: &Database| {
+            (
+                serde_json::to_value(db.get_session("session-1").unwrap().unwrap()).unwrap(),
+                serde_json::to_value(db.get_messages_with_tools("session-1", None, None).unwrap())
+                    .unwrap(),
+            )
+        };
+        assert_eq!(stored(&db), stored(&full));
+        let session = db.get_session("session-1").unwrap().unwrap();
+        assert_eq!(session.parsed_offset, Some(fs::metadata(&path).unwrap().len() as i64));
+        assert_eq!(session.git_branch.as_deref(), Some("fix"));
+        assert_eq!((session.total_input_tokens, session.total_output_tokens), (Some(30), Some(13)));
+        let edit = db.get_tool_call_detail("session-1", 1).unwrap().unwrap();
+        assert_eq!(edit.tool_name, "Edit");
+    }
+
+    #[test]
+    fn test_response_split_across_offset_counts_usage_once() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let path = tmp.path().join("session-1.jsonl");
+        let lines = [
+            r#"{"type":"user","uuid":"u1","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Run the tests"}}"#,
+            r#"{"type":"assistant","uuid":"a1","parentUuid":"u1","timestamp":"2026-01-08T10:00:05Z","message":{"id":"r1","content":[{"type":"text","text":"Running them"}],"usage":{"input_tokens":10,"output_tokens":5}}}"#,
+            r#"{"type":"assistant","uuid":"a2","parentUuid":"a1","timestamp":"2026-01-08T10:00:06Z","message":{"id":"r1","content":[{"type":"tool_use","name":"Bash","input":{"command":"cargo test"}}],"usage":{"input_tokens":10,"output_tokens":5}}}"#,
+        ];
+        let sync = |db: &Database, force: bool| {
+            sync_claude_session(db, &path, "proj", "local", force, &Default::default()).unwrap()
+        };
+
+        fs::write(&path, format!("{}\n{}\n", lines[0], lines[1])).unwrap();
+        sync(&db, false);
+        fs::write(&path, format!("{}\n{}\n{}\n", lines[0], lines[1], lines[2])).unwrap();
+        assert_eq!(sync(&db, false).messages, 1);
+
+        let session = db.get_session("session-1").unwrap().unwrap();
+        assert_eq!((session.total_input_tokens, session.total_output_tokens), (Some(10), Some(5)));
+        let full = Database::open_with(&tmp.path().join("full.db"), &DbConfig::default()).unwrap();
+        sync(&full, true);
+        let full_session = full.get_session("session-1").unwrap().unwrap();
+        assert_eq!(full_session.total_input_tokens, session.total_input_tokens);
+    }
+
+    #[test]
+    fn test_rewritten_file_is_fully_reparsed() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let path = tmp.path().join("session-1.jsonl");
+        let line = |uuid: &str, text: &str| {
+            format!(
+                r#"{{"type":"user","uuid":"{}","timestamp":"2026-01-08T10:00:00Z","message":{{"content":"{}"}}}}"#,
+                uuid, text
+            ) + "\n"
+        };
+        let sync = || {
+            sync_claude_session(&db, &path, "proj", "local", false, &Default::default()).unwrap()
+        };
+
+        fs::write(&path, line("u1", "Original question") + &line("u2", "Original follow-up"))
+            .unwrap();
+        assert_eq!(sync().messages, 2);
+
+        // Longer, but the bytes synced before changed
+        let rewritten = line("n1", "Edited question") + &line("n2", "New follow-up");
+        fs::write(&path, rewritten + &line("n3", "Another one")).unwrap();
+        assert_eq!(sync().messages, 3);
+
+        let contents: Vec<String> = db
+            .get_messages("session-1", None, None)
+            .unwrap()
+            .into_iter()
+            .map(|m| m.content)
+            .collect();
+        assert_eq!(contents, ["Edited question", "New follow-up", "Another one"]);
+        assert_eq!(db.get_session("session-1").unwrap().unwrap().message_count, 3);
+    }
+
+    #[test]
+    fn test_verbose_sync_log_decisions() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+
+        let project_dir = tmp.path().join("-Users-me-code-myapp");
+        fs::create_dir(&project_dir).unwrap();
+        fs::write(
+            project_dir.join("session-1.jsonl"),
+            r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Hello"}}"#,
+        )
+        .unwrap();
+        fs::write(
+            project_dir.join("agent-1.jsonl"),
+            r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Hi"}}"#,
+        )
+        .unwrap();
+        // A directory with a .jsonl name cannot be read as a file
+        fs::create_dir(project_dir.join("broken.jsonl")).unwrap();
+
+        let codex_file = tmp.path().join("exec.jsonl");
+        fs::write(
+            &codex_file,
+            r#"{"type":"session_meta","payload":{"id":"exec-id","cwd":"/test","originator":"codex_exec"}}"#,
+        )
+        .unwrap();
+
+        let mut stats = SyncStats {
+            log: Some(Vec::new()),
+            ..Default::default()
+        };
+        let roots = [project_dir.parent().unwrap().to_path_buf()];
+        for _ in 0..2 {
+            for path in find_claude_sessions(&roots) {
+                let result = sync_claude_file(&db, &path, "local", &ParseOptions::default());
+                stats.record(&path, &result);
+            }
+        }
+        let result = sync_codex_session(&db, &codex_file, "local", false, &Default::default());
+        stats.record(&codex_file, &result);
+
+        let log = stats.log.unwrap();
+        let decision_for = |name: &str| -> Vec<SyncDecision> {
+            log.iter()
+                .filter(|e| e.path.ends_with(name))
+                .map(|e| e.decision)
+                .collect()
+        };
+
+        assert_eq!(
+            decision_for("session-1.jsonl"),
+            vec![SyncDecision::Synced, SyncDecision::SkippedUnchanged]
+        );
+        assert_eq!(decision_for("agent-1.jsonl")[0], SyncDecision::SkippedRule);
+        assert_eq!(decision_for("broken.jsonl")[0], SyncDecision::Failed);
+        assert_eq!(decision_for("exec.jsonl"), vec![SyncDecision::SkippedRule]);
+
+        let exec_entry = log.iter().find(|e| e.path.ends_with("exec.jsonl")).unwrap();
+        assert_eq!(exec_entry.reason.as_deref(), Some("exec"));
+        assert_eq!(stats.total_sessions, 2);
+        assert_eq!(stats.synced, 1);
+        assert_eq!(stats.unchanged, 1);
+        assert_eq!(stats.failed, 2);
+    }
+
+    #[test]
+    fn test_sync_path_routes_claude_file() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let claude_dir = tmp.path().join("claude");
+        let codex_dir = tmp.path().join("codex");
+
+        let project_dir = claude_dir.join("-Users-me-code-myapp");
+        fs::create_dir_all(&project_dir).unwrap();
+        let path = project_dir.join("session-1.jsonl");
+        fs::write(
+            &path,
+            r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Hello"}}"#,
+        )
+        .unwrap();
+
+        let (claude_dirs, codex_dirs) = ([claude_dir.clone()], [codex_dir.clone()]);
+        let roots = roots_of(&claude_dirs, &codex_dirs);
+        let result = sync_path_in(&db, &path, "local", &roots).unwrap();
+        assert_eq!(result.session_id, "session-1");
+        assert_eq!(result.project, "myapp");
+        assert!(!result.skipped);
+
+        let result = sync_path_in(&db, &path, "local", &roots).unwrap();
+        assert!(result.skipped);
+    }
+
+    #[test]
+    fn test_sync_path_applies_saved_parse_options() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let claude_dir = tmp.path().join("claude");
+        let project_dir = claude_dir.join("-Users-me-code-myapp");
+        fs::create_dir_all(&project_dir).unwrap();
+        let path = project_dir.join("session-1.jsonl");
+        fs::write(
+            &path,
+            r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Hello"}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(saved_parse_options(&db).unwrap(), ParseOptions::default());
+        let options = ParseOptions {
+            store_raw: true,
+            ..Default::default()
+        };
+        save_parse_options(&db, &options).unwrap();
+        assert_eq!(saved_parse_options(&db).unwrap(), options);
+
+        sync_path_in(&db, &path, "local", &roots_of(&[claude_dir], &[])).unwrap();
+        let messages = db.get_messages("session-1", None, None).unwrap();
+        assert!(db.get_raw_message("session-1", &messages[0].msg_id).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_sync_path_routes_codex_file() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let claude_dir = tmp.path().join("claude");
+        let codex_dir = tmp.path().join("codex");
+
+        let day_dir = codex_dir.join("2026").join("01").join("08");
+        fs::create_dir_all(&day_dir).unwrap();
+        let path = day_dir.join("rollout-2026-01-08T10-00-00-abc123.jsonl");
+        fs::write(
+            &path,
+            r#"{"type":"session_meta","timestamp":"2026-01-08T10:00:00Z","payload":{"id":"abc123","cwd":"/home/user/webapp"}}
+{"type":"response_item","timestamp":"2026-01-08T10:01:00Z","payload":{"role":"user","content":[{"type":"input_text","text":"Hello Codex"}]}}"#,
+        )
+        .unwrap();
+
+        let (claude_dirs, codex_dirs) = ([claude_dir.clone()], [codex_dir.clone()]);
+        let roots = roots_of(&claude_dirs, &codex_dirs);
+        let result = sync_path_in(&db, &path, "local", &roots).unwrap();
+        assert_eq!(result.session_id, "codex:abc123");
+        assert_eq!(result.project, "webapp");
+    }
+
+    #[test]
+    fn test_sync_updates_parse_metrics() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let claude_dir = tmp.path().join("claude");
+        let project_dir = claude_dir.join("-Users-me-code-myapp");
+        fs::create_dir_all(&project_dir).unwrap();
+        let content = r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Hello"}}
+{"type":"assistant","timestamp":"2026-01-08T10:01:00Z","message":{"content":[{"type":"text","text":"Hi"}]}}"#;
+        fs::write(project_dir.join("session-1.jsonl"), content).unwrap();
+
+        // Counters are process-wide and other tests sync concurrently, so
+        // only lower bounds on the deltas are reliable.
+        let before = parse_metrics();
+        let (claude_dirs, codex_dirs) = ([claude_dir.clone()], [tmp.path().join("codex")]);
+        sync_dirs(&db, "local", &SyncOptions::default(), &claude_dirs, &codex_dirs);
+        let after = parse_metrics();
+
+        assert!(after.files_parsed > before.files_parsed);
+        assert!(after.bytes_parsed >= before.bytes_parsed + content.len() as u64);
+        assert!(after.messages_parsed >= before.messages_parsed + 2);
+        assert!(after.parse_time_ms >= before.parse_time_ms);
+    }
+
+    #[test]
+    fn test_project_paths_map_back_to_directories() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let claude_dir = tmp.path().join("claude");
+        let codex_dir = tmp.path().join("codex");
+        let project_dir = claude_dir.join("-Users-me-code-myapp");
+        let other_dir = claude_dir.join("-Users-me-code-other");
+        fs::create_dir_all(&project_dir).unwrap();
+        fs::create_dir_all(&other_dir).unwrap();
+        fs::write(
+            project_dir.join("session-1.jsonl"),
+            r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Hello"}}"#,
+        )
+        .unwrap();
+        let (claude_dirs, codex_dirs) = ([claude_dir.clone()], [codex_dir.clone()]);
+        sync_dirs(&db, "local", &SyncOptions::default(), &claude_dirs, &codex_dirs);
+
+        let paths = project_paths_in(&db, "myapp", &claude_dirs, &codex_dirs).unwrap();
+        assert_eq!(paths, vec![project_dir.canonicalize().unwrap()]);
+
+        // Not yet synced, but the directory name maps to the project
+        let paths = project_paths_in(&db, "other", &claude_dirs, &codex_dirs).unwrap();
+        assert_eq!(paths, vec![other_dir.canonicalize().unwrap()]);
+
+        assert!(project_paths_in(&db, "missing", &claude_dirs, &codex_dirs)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_renamed_directory_moves_sessions() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let claude_dir = tmp.path().join("claude");
+        let codex_dir = tmp.path().join("codex");
+        let repo = tmp.path().join("code").join("oldname");
+        fs::create_dir_all(&repo).unwrap();
+        let old_dir = claude_dir.join("-tmp-code-oldname");
+        fs::create_dir_all(&old_dir).unwrap();
+        let write_session = |dir: &Path, name: &str, cwd: &Path| {
+            fs::write(
+                dir.join(format!("{}.jsonl", name)),
+                format!(
+                    r#"{{"type":"user","timestamp":"2026-01-08T10:00:00Z","cwd":"{}","message":{{"content":"Hi"}}}}"#,
+                    cwd.display()
+                ),
+            )
+            .unwrap();
+        };
+        write_session(&old_dir, "s1", &repo);
+        write_session(&old_dir, "s2", &repo);
+
+        let (claude_dirs, codex_dirs) = ([claude_dir.clone()], [codex_dir.clone()]);
+        let stats = sync_dirs(&db, "local", &SyncOptions::default(), &claude_dirs, &codex_dirs);
+        assert_eq!(stats.new, 2);
+        assert!(stats.renamed_projects.is_empty());
+        let project_of = |id: &str| db.get_session_source(id).unwrap().unwrap().0;
+        assert_eq!(project_of("s1"), "oldname");
+
+        // Rename the repo, and its session directory along with it
+        let new_repo = tmp.path().join("code").join("newname");
+        fs::rename(&repo, &new_repo).unwrap();
+        let new_dir = claude_dir.join("-tmp-code-newname");
+        fs::rename(&old_dir, &new_dir).unwrap();
+        write_session(&new_dir, "s3", &new_repo);
+
+        let stats = sync_dirs(&db, "local", &SyncOptions::default(), &claude_dirs, &codex_dirs);
+        assert_eq!((stats.new, stats.unchanged), (1, 2));
+        assert_eq!(
+            stats.renamed_projects,
+            vec![ProjectRename {
+                from: "oldname".to_string(),
+                to: "newname".to_string(),
+            }]
+        );
+        for id in ["s1", "s2", "s3"] {
+            assert_eq!(project_of(id), "newname");
+        }
+        let (_, path) = db.get_session_source("s1").unwrap().unwrap();
+        assert_eq!(path.unwrap(), new_dir.join("s1.jsonl").to_string_lossy());
+
+        // Settled: nothing moves on the next pass
+        let stats = sync_dirs(&db, "local", &SyncOptions::default(), &claude_dirs, &codex_dirs);
+        assert!(stats.renamed_projects.is_empty());
+
+        // Sessions left under the old name can be folded in
+        db.update_projects(&[("s2".to_string(), "oldname".to_string())]).unwrap();
+        assert_eq!(db.merge_projects("oldname", "newname").unwrap(), 1);
+        assert_eq!(project_of("s2"), "newname");
+    }
+
+    #[test]
+    fn test_reproject_corrects_stale_names() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let claude_dir = tmp.path().join("claude");
+        let codex_dir = tmp.path().join("codex");
+
+        let day_dir = codex_dir.join("2026").join("01").join("08");
+        fs::create_dir_all(&day_dir).unwrap();
+        let uuid = "0193a1b2-c3d4-7e5f-8a9b-0c1d2e3f4a5b";
+        fs::write(
+            day_dir.join(format!("rollout-2026-01-08T10-00-00-{}.jsonl", uuid)),
+            format!(
+                r#"{{"type":"session_meta","timestamp":"2026-01-08T10:00:00Z","payload":{{"id":"{}","cwd":"/home/user/webapp"}}}}
+{{"type":"response_item","timestamp":"2026-01-08T10:01:00Z","payload":{{"role":"user","content":[{{"type":"input_text","text":"Hello"}}]}}}}"#,
+                uuid
+            ),
+        )
+        .unwrap();
+        let project_dir = claude_dir.join("-Users-me-code-myapp");
+        fs::create_dir_all(&project_dir).unwrap();
+        fs::write(
+            project_dir.join("session-1.jsonl"),
+            r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Hello"}}"#,
+        )
+        .unwrap();
+
+        let (claude_dirs, codex_dirs) = ([claude_dir.clone()], [codex_dir.clone()]);
+        let stats = sync_dirs(&db, "local", &SyncOptions::default(), &claude_dirs, &codex_dirs);
+        assert_eq!(stats.failed, 0);
+
+        // Simulate names stored by an older, buggier derivation
+        let codex_id = format!("codex:{}", uuid);
+        db.update_projects(&[(codex_id.clone(), "home_user_webapp".to_string())])
+            .unwrap();
+
+        assert_eq!(reproject_in(&db, &claude_dirs, &codex_dirs).unwrap(), 1);
+        let sessions = db
+            .get_sessions_filtered(&SessionFilter::default(), 10, SessionSort::default())
+            .unwrap();
+        let codex = sessions.iter().find(|s| s.session_id == codex_id).unwrap();
+        assert_eq!(codex.project, "webapp");
+        let claude = sessions.iter().find(|s| s.session_id == "session-1").unwrap();
+        assert_eq!(claude.project, "myapp");
+
+        assert_eq!(reproject_in(&db, &claude_dirs, &codex_dirs).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_codex_projects_sharing_a_name_include_parent() {
+        use std::io::Write;
+
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let codex_dir = tmp.path().join("codex");
+        let day_dir = codex_dir.join("2026").join("01").join("08");
+        fs::create_dir_all(&day_dir).unwrap();
+        let write_session = |uuid: &str, cwd: &str| {
+            let path = day_dir.join(format!("rollout-2026-01-08T10-00-00-{}.jsonl", uuid));
+            fs::write(
+                &path,
+                format!(
+                    r#"{{"type":"session_meta","timestamp":"2026-01-08T10:00:00Z","payload":{{"id":"{}","cwd":"{}"}}}}
+{{"type":"response_item","timestamp":"2026-01-08T10:01:00Z","payload":{{"role":"user","content":[{{"type":"input_text","text":"Hello"}}]}}}}"#,
+                    uuid, cwd
+                ),
+            )
+            .unwrap();
+            path
+        };
+        let client = "0193a1b2-c3d4-7e5f-8a9b-00000000000a";
+        let server = "0193a1b2-c3d4-7e5f-8a9b-00000000000b";
+        let tool = "0193a1b2-c3d4-7e5f-8a9b-00000000000c";
+        write_session(client, "/home/me/client/app");
+        write_session(tool, "/home/me/tool");
+
+        let (claude_dirs, codex_dirs) = ([tmp.path().join("claude")], [codex_dir.clone()]);
+        let roots = roots_of(&claude_dirs, &codex_dirs);
+        sync_dirs(&db, "local", &SyncOptions::default(), &claude_dirs, &codex_dirs);
+        let project = |uuid: &str| {
+            db.get_session_source(&format!("codex:{}", uuid)).unwrap().unwrap().0
+        };
+        assert_eq!(project(client), "app");
+
+        // A second `app` arriving by single-file sync is only told apart by
+        // the next full sync, which renames both
+        let server_path = write_session(server, "/home/me/server/app");
+        sync_path_in(&db, &server_path, "local", &roots).unwrap();
+        assert_eq!(project(client), "app");
+        assert_eq!(project(server), "app");
+        sync_dirs(&db, "local", &SyncOptions::default(), &claude_dirs, &codex_dirs);
+        assert_eq!(project(client), "client/app");
+        assert_eq!(project(server), "server/app");
+        assert_eq!(project(tool), "tool");
+
+        // Re-parsing a file resets its name until the end of the full sync
+        let client_path = day_dir.join(format!("rollout-2026-01-08T10-00-00-{}.jsonl", client));
+        let mut file = fs::OpenOptions::new().append(true).open(client_path).unwrap();
+        writeln!(
+            file,
+            r#"
+{{"type":"response_item","timestamp":"2026-01-08T10:02:00Z","payload":{{"role":"user","content":[{{"type":"input_text","text":"Again"}}]}}}}"#
+        )
+        .unwrap();
+        let stats = sync_dirs(&db, "local", &SyncOptions::default(), &claude_dirs, &codex_dirs);
+        assert_eq!((stats.failed, stats.synced), (0, 1));
+        assert_eq!(project(client), "client/app");
+        assert_eq!(project(server), "server/app");
+
+        let sessions = db
+            .get_sessions_filtered(&SessionFilter::default(), 10, SessionSort::default())
+            .unwrap();
+        let server_session = sessions.iter().find(|s| s.session_id.ends_with(server)).unwrap();
+        assert_eq!(server_session.cwd.as_deref(), Some("/home/me/server/app"));
+        assert_eq!(reproject_in(&db, &claude_dirs, &codex_dirs).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_retry_open_succeeds_after_lock_clears() {
+        use std::io::{Error, ErrorKind};
+
+        let delays = [Duration::ZERO; 3];
+        let mut attempts = 0;
+        let result = retry_open(
+            || {
+                attempts += 1;
+                if attempts < 3 {
+                    Err(Error::new(ErrorKind::WouldBlock, "sharing violation"))
+                } else {
+                    Ok("opened")
+                }
+            },
+            &delays,
+        );
+        assert_eq!(result.unwrap(), "opened");
+        assert_eq!(attempts, 3);
+
+        // Gives up after the last delay
+        let mut attempts = 0;
+        let result: std::io::Result<()> = retry_open(
+            || {
+                attempts += 1;
+                Err(Error::new(ErrorKind::WouldBlock, "sharing violation"))
+            },
+            &delays,
+        );
+        assert!(result.is_err());
+        assert_eq!(attempts, 4);
+
+        // Errors other than a lock aren't worth waiting for
+        for kind in [ErrorKind::NotFound, ErrorKind::PermissionDenied] {
+            let mut attempts = 0;
+            let result: std::io::Result<()> = retry_open(
+                || {
+                    attempts += 1;
+                    Err(Error::new(kind, "not a lock"))
+                },
+                &delays,
+            );
+            assert!(result.is_err());
+            assert_eq!(attempts, 1);
+        }
+    }
+
+    #[test]
+    fn test_locked_file_is_deferred() {
+        let mut stats = SyncStats::default();
+        let locked = Err(SyncError::Locked(std::io::Error::new(
+            std::io::ErrorKind::WouldBlock,
+            "sharing violation",
+        )));
+        stats.record(Path::new("session.jsonl"), &locked);
+        assert_eq!(stats.deferred, 1);
+        assert_eq!(stats.failed, 0);
+        assert_eq!(stats.total_sessions, 0);
+    }
+
+    #[test]
+    fn test_import_session_file_outside_roots() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let downloads = tmp.path().join("downloads");
+        fs::create_dir_all(&downloads).unwrap();
+
+        let claude_path = downloads.join("claude-session.jsonl");
+        fs::write(
+            &claude_path,
+            r#"{"type":"user","cwd":"/home/me/code/myapp","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Hello"}}"#,
+        )
+        .unwrap();
+        let result = import_session_file(&db, &claude_path, None, "local").unwrap();
+        assert_eq!(result.session_id, "claude-session");
+        assert_eq!(result.project, "myapp");
+        assert!(result.is_new);
+
+        let codex_path = downloads.join("rollout-abc123.jsonl");
+        fs::write(
+            &codex_path,
+            r#"{"type":"session_meta","timestamp":"2026-01-08T10:00:00Z","payload":{"id":"abc123","cwd":"/home/user/webapp"}}
+{"type":"response_item","timestamp":"2026-01-08T10:01:00Z","payload":{"role":"user","content":[{"type":"input_text","text":"Hello Codex"}]}}"#,
+        )
+        .unwrap();
+        let result = import_session_file(&db, &codex_path, None, "laptop").unwrap();
+        assert_eq!(result.session_id, "codex:abc123");
+        assert_eq!(result.project, "webapp");
+        assert_eq!(db.get_session_ids("laptop").unwrap(), vec!["codex:abc123"]);
+
+        let result = import_session_file(&db, &claude_path, Some("Claude"), "local").unwrap();
+        assert!(result.skipped);
+        assert!(matches!(
+            import_session_file(&db, &codex_path, Some("gemini"), "local"),
+            Err(SyncError::UnknownAgent(_))
+        ));
+        assert!(matches!(
+            import_session_file(&db, &downloads.join("missing.jsonl"), None, "local"),
+            Err(SyncError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_session_ids_for_path() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let gemini_dirs = [tmp.path().join("gemini")];
+        let log = gemini_dirs[0].join("3b8f0c").join("logs.json");
+        fs::create_dir_all(log.parent().unwrap()).unwrap();
+        fs::write(
+            &log,
+            r#"[{"sessionId":"s1","type":"user","message":"Hello","timestamp":"2026-01-08T10:00:00Z"},
+{"sessionId":"s2","type":"user","message":"Again","timestamp":"2026-01-09T10:00:00Z"}]"#,
+        )
+        .unwrap();
+        sync_gemini_log(&db, &log, "local").unwrap();
+        fs::remove_file(&log).unwrap();
+
+        let claude_dirs = [PathBuf::from("/home/me/.claude/projects")];
+        let codex_dirs = [PathBuf::from("/home/me/.codex/sessions")];
+        let roots = SyncRoots {
+            claude: &claude_dirs,
+            codex: &codex_dirs,
+            gemini: &gemini_dirs,
+        };
+        let ids = |p: &Path| session_ids_for_path(&db, p, &roots).unwrap();
+        let id = |p: &str| ids(Path::new(p)).pop();
+
+        assert_eq!(id("/home/me/.claude/projects/-app/abc-123.jsonl").as_deref(), Some("abc-123"));
+        let gzipped = id("/home/me/.claude/projects/-app/abc-123.jsonl.gz");
+        assert_eq!(gzipped.as_deref(), Some("abc-123"));
+        assert_eq!(id("/home/me/.claude/projects/-app/agent-1.jsonl"), None);
+        let rollout = "rollout-2026-01-08T06-48-54-019b9da7-1f41-7af2-80d9-6e293902fea8.jsonl";
+        assert_eq!(
+            id(&format!("/home/me/.codex/sessions/2026/01/08/{}", rollout)).as_deref(),
+            Some("codex:019b9da7-1f41-7af2-80d9-6e293902fea8")
+        );
+        assert_eq!(id("/home/me/.codex/sessions/2026/01/08/notes.jsonl"), None);
+        assert_eq!(id("/tmp/elsewhere.jsonl"), None);
+
+        // A Gemini log holds every session last synced from it, even once deleted
+        assert_eq!(ids(&log), vec!["gemini:s1", "gemini:s2"]);
+        assert!(ids(&gemini_dirs[0].join("other").join("logs.json")).is_empty());
+    }
+
+    #[test]
+    fn test_sync_path_rejects_unknown_location() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let path = tmp.path().join("elsewhere.jsonl");
+        fs::write(&path, "").unwrap();
+
+        let (claude_dirs, codex_dirs) = ([tmp.path().join("claude")], [tmp.path().join("codex")]);
+        let roots = roots_of(&claude_dirs, &codex_dirs);
+        let result = sync_path_in(&db, &path, "local", &roots);
+        assert!(matches!(result, Err(SyncError::UnknownLocation)));
+    }
+
+    #[test]
+    fn test_sync_reports_new_updated_unchanged_deleted() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let claude_dir = tmp.path().join("claude");
+        let codex_dir = tmp.path().join("codex");
+        let project_dir = claude_dir.join("-Users-me-code-myapp");
+        fs::create_dir_all(&project_dir).unwrap();
+
+        let write_session = |name: &str, text: &str| {
+            fs::write(
+                project_dir.join(format!("{}.jsonl", name)),
+                format!(
+                    r#"{{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{{"content":"{}"}}}}"#,
+                    text
+                ),
+            )
+            .unwrap();
+        };
+        write_session("kept", "Unchanged session");
+        write_session("edited", "Original text");
+        write_session("removed", "Soon to be deleted");
+
+        let options = SyncOptions {
+            prune: true,
+            ..Default::default()
+        };
+        let (claude_dirs, codex_dirs) = ([claude_dir.clone()], [codex_dir.clone()]);
+        let stats = sync_dirs(&db, "local", &options, &claude_dirs, &codex_dirs);
+        assert_eq!((stats.new, stats.updated, stats.unchanged, stats.deleted), (3, 0, 0, 0));
+
+        write_session("edited", "Edited text that is longer");
+        write_session("added", "Brand new session");
+        fs::remove_file(project_dir.join("removed.jsonl")).unwrap();
+
+        let stats = sync_dirs(&db, "local", &options, &claude_dirs, &codex_dirs);
+        assert_eq!(stats.new, 1);
+        assert_eq!(stats.updated, 1);
+        assert_eq!(stats.unchanged, 1);
+        assert_eq!(stats.deleted, 1);
+        assert_eq!(stats.synced, 2);
+        assert_eq!(stats.total_sessions, 3);
+
+        let ids: HashSet<String> = db.get_session_ids("local").unwrap().into_iter().collect();
+        assert!(!ids.contains("removed"));
+        assert!(db.get_messages("removed", None, None).unwrap().is_empty());
+
+        // Pruning is opt-in
+        fs::remove_file(project_dir.join("added.jsonl")).unwrap();
+        let stats = sync_dirs(&db, "local", &SyncOptions::default(), &claude_dirs, &codex_dirs);
+        assert_eq!(stats.deleted, 0);
+        assert!(db.get_session("added").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_prune_deleted_sessions() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let claude_dir = tmp.path().join("claude");
+        let project_dir = claude_dir.join("-Users-me-code-myapp");
+        fs::create_dir_all(&project_dir).unwrap();
+        for name in ["kept", "gone"] {
+            fs::write(
+                project_dir.join(format!("{}.jsonl", name)),
+                r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Hi"}}"#,
+            )
+            .unwrap();
+        }
+        let day_dir = tmp.path().join("codex").join("2026").join("01").join("08");
+        fs::create_dir_all(&day_dir).unwrap();
+        let rollout = day_dir.join("rollout-2026-01-08T10-00-00-abc123.jsonl");
+        fs::write(
+            &rollout,
+            r#"{"type":"session_meta","timestamp":"2026-01-08T10:00:00Z","payload":{"id":"abc123","cwd":"/home/user/webapp"}}
+{"type":"response_item","timestamp":"2026-01-08T10:01:00Z","payload":{"role":"user","content":[{"type":"input_text","text":"Hello Codex"}]}}"#,
+        )
+        .unwrap();
+        let (claude_dirs, codex_dirs) = ([claude_dir.clone()], [tmp.path().join("codex")]);
+        let stats = sync_dirs(
+            &db,
+            "local",
+            &SyncOptions::default(),
+            &claude_dirs,
+            &codex_dirs,
+        );
+        assert_eq!(stats.new, 3);
+
+        // A session imported from outside the roots has no file there to lose
+        let downloads = tmp.path().join("downloads");
+        fs::create_dir_all(&downloads).unwrap();
+        let imported = downloads.join("imported.jsonl");
+        fs::copy(project_dir.join("kept.jsonl"), &imported).unwrap();
+        import_session_file(&db, &imported, Some("claude"), "local").unwrap();
+
+        fs::remove_file(project_dir.join("gone.jsonl")).unwrap();
+        fs::remove_file(&rollout).unwrap();
+        let seen: HashSet<String> = ["kept".to_string()].into_iter().collect();
+        let roots = roots_of(&claude_dirs, &codex_dirs);
+        assert_eq!(prune_deleted_sessions(&db, "local", &seen, &roots).unwrap(), 2);
+
+        assert!(db.get_session("gone").unwrap().is_none());
+        assert!(db.get_session("codex:abc123").unwrap().is_none());
+        assert!(db.get_messages("gone", None, None).unwrap().is_empty());
+        assert!(db.get_session("kept").unwrap().is_some());
+        assert_eq!(db.get_messages("kept", None, None).unwrap().len(), 1);
+        assert!(db.get_session("imported").unwrap().is_some());
+
+        // Sessions synced from another machine are left alone
+        assert_eq!(prune_deleted_sessions(&db, "laptop", &HashSet::new(), &roots).unwrap(), 0);
+        assert!(db.get_session("kept").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_pruning_sync_keeps_json_imported_sessions() {
+        let tmp = tempdir().unwrap();
+        let claude_dir = tmp.path().join("claude");
+        let project_dir = claude_dir.join("-Users-me-code-myapp");
+        fs::create_dir_all(&project_dir).unwrap();
+        let path = project_dir.join("moved.jsonl");
+        fs::write(
+            &path,
+            r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Hi"}}"#,
+        )
+        .unwrap();
+        let (claude_dirs, codex_dirs) = ([claude_dir.clone()], [tmp.path().join("codex")]);
+
+        // Export from one machine...
+        let old = Database::open_with(&tmp.path().join("old.db"), &DbConfig::default()).unwrap();
+        sync_dirs(&old, "local", &SyncOptions::default(), &claude_dirs, &codex_dirs);
+        let session = old.get_session("moved").unwrap().unwrap();
+        let messages = old.get_messages_with_tools("moved", None, None).unwrap();
+        let export = tmp.path().join("export.json");
+        let json = crate::export::session_to_json(&session, &messages);
+        fs::write(&export, json.to_string()).unwrap();
+
+        // ...and import on one with the same roots but without the file
+        fs::remove_file(&path).unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        crate::export::import_sessions_file(&db, &export, false).unwrap();
+        let imported = db.get_session("moved").unwrap().unwrap();
+        assert_eq!(imported.source_path, None);
+        assert_eq!((imported.file_size, imported.parsed_offset), (None, None));
+
+        let options = SyncOptions {
+            prune: true,
+            ..Default::default()
+        };
+        let stats = sync_dirs(&db, "local", &options, &claude_dirs, &codex_dirs);
+        assert_eq!(stats.deleted, 0);
+        assert_eq!(db.get_messages("moved", None, None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_session_roots_from_env() {
+        let default = || PathBuf::from("/default");
+        let var = "AGENT_SESSION_VIEWER_TEST_ROOTS";
+        std::env::remove_var(var);
+        assert_eq!(session_roots(var, default), vec![PathBuf::from("/default")]);
+
+        let list = std::env::join_paths(["/sync/claude", "/home/me/.claude/projects"]).unwrap();
+        std::env::set_var(var, &list);
+        assert_eq!(
+            session_roots(var, default),
+            vec![PathBuf::from("/sync/claude"), PathBuf::from("/home/me/.claude/projects")]
+        );
+        std::env::remove_var(var);
+    }
+
+    #[test]
+    fn test_sync_roots_scans_every_root() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let claude_dirs = [tmp.path().join("claude-a"), tmp.path().join("claude-b")];
+        let codex_dirs = [tmp.path().join("codex-a"), tmp.path().join("codex-b")];
+        let write = |path: &Path, text: &str, age_secs: u64| {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(
+                path,
+                format!(
+                    r#"{{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{{"content":"{}"}}}}"#,
+                    text
+                ),
+            )
+            .unwrap();
+            let mtime = SystemTime::now() - Duration::from_secs(age_secs);
+            fs::File::options().write(true).open(path).unwrap().set_modified(mtime).unwrap();
+        };
+
+        write(&claude_dirs[0].join("-Users-me-app").join("only-a.jsonl"), "From A", 60);
+        write(&claude_dirs[1].join("-Users-me-app").join("only-b.jsonl"), "From B", 60);
+        // The same session under both roots; the newer copy wins
+        let newer = claude_dirs[0].join("-Users-me-app").join("shared.jsonl");
+        write(&newer, "Newer copy", 10);
+        write(&claude_dirs[1].join("-Users-me-app").join("shared.jsonl"), "Older copy", 600);
+
+        for (dir, id) in codex_dirs.iter().zip(["aaaa", "bbbb"]) {
+            let path = dir.join("2026").join("01").join("08").join(format!(
+                "rollout-2026-01-08T10-00-00-{}-1111-2222-3333-444444444444.jsonl",
+                id
+            ));
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(
+                &path,
+                r#"{"type":"session_meta","timestamp":"2026-01-08T10:00:00Z","payload":{"cwd":"/home/me/tool"}}
+{"type":"response_item","timestamp":"2026-01-08T10:01:00Z","payload":{"role":"user","content":[{"type":"input_text","text":"Codex question"}]}}"#,
+            )
+            .unwrap();
+        }
+
+        let stats = sync_dirs(&db, "local", &SyncOptions::default(), &claude_dirs, &codex_dirs);
+        assert_eq!(stats.new, 5);
+        for id in ["only-a", "only-b", "shared"] {
+            assert!(db.get_session(id).unwrap().is_some(), "{} not synced", id);
+        }
+        let shared = db.get_messages("shared", None, None).unwrap();
+        assert_eq!(shared[0].content, "Newer copy");
+        let (_, source) = db.get_session_source("shared").unwrap().unwrap();
+        assert_eq!(source.as_deref(), Some(newer.to_string_lossy().as_ref()));
+        let roots = roots_of(&claude_dirs, &codex_dirs);
+        assert_eq!(find_source_file_in("shared", &roots), Some(newer));
+    }
+
+    #[test]
+    fn test_sync_roots_includes_gemini_logs() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let gemini_dir = tmp.path().join("gemini");
+        let log = gemini_dir.join("3b8f0c").join("logs.json");
+        fs::create_dir_all(log.parent().unwrap()).unwrap();
+        fs::write(
+            &log,
+            r#"[{"sessionId":"s1","type":"user","message":"Hello","timestamp":"2026-01-08T10:00:00Z"},
+{"sessionId":"s2","type":"user","message":"Again","timestamp":"2026-01-09T10:00:00Z"}]"#,
+        )
+        .unwrap();
+        // Stray files and directories without a log are ignored
+        fs::write(gemini_dir.join("notes.txt"), "").unwrap();
+        fs::create_dir_all(gemini_dir.join("empty")).unwrap();
+        assert_eq!(find_gemini_sessions(&gemini_dir), vec![log.clone()]);
+
+        let roots = SyncRoots {
+            gemini: &[gemini_dir],
+            ..Default::default()
+        };
+        let options = SyncOptions {
+            prune: true,
+            ..Default::default()
+        };
+        let stats = sync_roots(&db, "local", &options, &roots);
+        assert_eq!((stats.new, stats.failed), (2, 0));
+        assert!(db.get_session("gemini:s1").unwrap().is_some());
+        assert!(db.get_session("gemini:s2").unwrap().is_some());
+        assert_eq!(find_source_file_in("gemini:s2", &roots), Some(log.clone()));
+        assert_eq!(find_source_file_in("gemini:s3", &roots), None);
+
+        // Unchanged on a second pass, and not pruned
+        let stats = sync_roots(&db, "local", &options, &roots);
+        assert_eq!((stats.unchanged, stats.deleted), (2, 0));
+        assert!(db.get_session("gemini:s1").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_sync_gemini_log_rewrites_only_changed_sessions() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let log = tmp.path().join("gemini").join("3b8f0c").join("logs.json");
+        fs::create_dir_all(log.parent().unwrap()).unwrap();
+        let write_log = |entries: &[&str], secs: u64| {
+            fs::write(&log, format!("[{}]", entries.join(",\n"))).unwrap();
+            let mtime = UNIX_EPOCH + Duration::from_secs(secs);
+            fs::File::options().write(true).open(&log).unwrap().set_modified(mtime).unwrap();
+        };
+        let changed = || -> Vec<String> {
+            let results = sync_gemini_log(&db, &log, "local").unwrap();
+            results.into_iter().filter(|r| !r.skipped).map(|r| r.session_id).collect()
+        };
+        let first = r#"{"sessionId":"s1","type":"user","message":"Hello","timestamp":"2026-01-08T10:00:00Z"}"#;
+        let second = r#"{"sessionId":"s2","type":"user","message":"Again","timestamp":"2026-01-09T10:00:00Z"}"#;
+        let reply = r#"{"sessionId":"s2","type":"model","message":"Reply","timestamp":"2026-01-09T10:01:00Z"}"#;
+
+        write_log(&[first, second], 1_767_000_000);
+        assert_eq!(changed(), vec!["gemini:s1", "gemini:s2"]);
+        assert!(changed().is_empty());
+
+        // A touched log whose entries are the same rewrites nothing
+        write_log(&[first, second], 1_767_000_100);
+        assert!(changed().is_empty());
+
+        // Only the session with new entries is rewritten
+        write_log(&[first, second, reply], 1_767_000_200);
+        assert_eq!(changed(), vec!["gemini:s2"]);
+        assert_eq!(db.get_messages("gemini:s1", None, None).unwrap().len(), 1);
+        assert_eq!(db.get_messages("gemini:s2", None, None).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_parallel_sync_matches_sequential() {
+        let tmp = tempdir().unwrap();
+        let claude_dir = tmp.path().join("claude");
+        let codex_dir = tmp.path().join("codex");
+        let codex_day = codex_dir.join("2026").join("01").join("08");
+        fs::create_dir_all(&codex_day).unwrap();
+
+        for p in 0..40 {
+            let project_dir = claude_dir.join(format!("-Users-me-project{}", p));
+            fs::create_dir_all(&project_dir).unwrap();
+            for s in 0..5 {
+                fs::write(
+                    project_dir.join(format!("p{}-s{}.jsonl", p, s)),
+                    format!(
+                        r#"{{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{{"content":"Question {} in project {}"}}}}
+{{"type":"assistant","timestamp":"2026-01-08T10:01:00Z","message":{{"content":[{{"type":"text","text":"Answer"}}]}}}}"#,
+                        s, p
+                    ),
+                )
+                .unwrap();
+            }
+            // A rule-skipped file in every project
+            fs::write(project_dir.join(format!("agent-{}.jsonl", p)), "{}").unwrap();
+        }
+        for c in 0..20 {
+            fs::write(
+                codex_day.join(format!("rollout-2026-01-08-c{}.jsonl", c)),
+                format!(
+                    r#"{{"type":"session_meta","timestamp":"2026-01-08T10:00:00Z","payload":{{"id":"c{}","cwd":"/home/user/webapp"}}}}
+{{"type":"response_item","timestamp":"2026-01-08T10:01:00Z","payload":{{"role":"user","content":[{{"type":"input_text","text":"Codex question"}}]}}}}"#,
+                    c
+                ),
+            )
+            .unwrap();
+        }
+
+        let options = SyncOptions {
+            verbose: true,
+            ..Default::default()
+        };
+        let (claude_dirs, codex_dirs) = ([claude_dir.clone()], [codex_dir.clone()]);
+        let roots = SyncRoots {
+            claude: &claude_dirs,
+            codex: &codex_dirs,
+            ..Default::default()
+        };
+        let run = |workers: usize| {
+            let path = tmp.path().join(format!("workers-{}.db", workers));
+            let db = Database::open_with(&path, &DbConfig::default()).unwrap();
+            let stats = sync_roots_with_workers(
+                &db, "local", &options, &roots, workers,
+            );
+            let mut sessions: Vec<(String, String, i32)> = db
+                .get_sessions_filtered(&SessionFilter::default(), 1000, SessionSort::default())
+                .unwrap()
+                .into_iter()
+                .map(|s| (s.session_id, s.project, s.message_count))
+                .collect();
+            sessions.sort();
+            (stats, sessions)
+        };
+
+        let (sequential, seq_sessions) = run(1);
+        let (parallel, par_sessions) = run(8);
+
+        assert_eq!(sequential.new, 220);
+        assert_eq!(seq_sessions.len(), 220);
+        assert_eq!(par_sessions, seq_sessions);
+        assert_eq!(
+            (parallel.total_sessions, parallel.new, parallel.failed, parallel.unchanged),
+            (sequential.total_sessions, sequential.new, sequential.failed, sequential.unchanged)
+        );
+        assert_eq!(parallel.seen, sequential.seen);
+
+        let log_paths = |stats: &SyncStats| -> Vec<String> {
+            stats.log.as_ref().unwrap().iter().map(|e| e.path.clone()).collect()
+        };
+        assert_eq!(log_paths(&parallel), log_paths(&sequential));
+        assert_eq!(log_paths(&parallel).len(), 260);
+    }
+
+    #[test]
+    fn test_cancel_stops_sync_between_files() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let claude_dir = tmp.path().join("claude");
+        let project_dir = claude_dir.join("-Users-me-project");
+        fs::create_dir_all(&project_dir).unwrap();
+        let claude_dirs = [claude_dir];
+        let roots = SyncRoots {
+            claude: &claude_dirs,
+            ..Default::default()
+        };
+
+        // A session whose file is gone must survive a cancelled pruning sync
+        let gone = project_dir.join("gone.jsonl");
+        fs::write(
+            &gone,
+            r#"{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{"content":"Hi"}}"#,
+        )
+        .unwrap();
+        sync_roots(&db, "local", &SyncOptions::default(), &roots);
+        fs::remove_file(&gone).unwrap();
+
+        for s in 0..200 {
+            fs::write(
+                project_dir.join(format!("s{}.jsonl", s)),
+                format!(
+                    r#"{{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{{"content":"Question {}"}}}}"#,
+                    s
+                ),
+            )
+            .unwrap();
+        }
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let options = SyncOptions {
+            prune: true,
+            cancel: Some(Arc::clone(&cancel)),
+            ..Default::default()
+        };
+        let stats = thread::scope(|scope| {
+            scope.spawn(|| {
+                while db.get_stats().unwrap().session_count < 4 {
+                    thread::yield_now();
+                }
+                cancel.store(true, Ordering::Relaxed);
+            });
+            sync_roots_with_workers(&db, "local", &options, &roots, 1)
+        });
+
+        assert!(stats.cancelled);
+        assert!(stats.new >= 3, "synced {} files", stats.new);
+        assert!(stats.new < 200, "sync was not cancelled early");
+        assert_eq!(stats.deleted, 0);
+        assert!(db.get_session("gone").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_codex_sync_waiting_on_the_lock_stores_the_newest_copy() {
+        use std::io::Write;
+
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let day_dir = tmp.path().join("codex").join("2026").join("01").join("08");
+        fs::create_dir_all(&day_dir).unwrap();
+        let path = day_dir.join("rollout-2026-01-08T10-00-00-abc123.jsonl");
+        let question = |text: &str| {
+            format!(
+                r#"
+{{"type":"response_item","timestamp":"2026-01-08T10:01:00Z","payload":{{"role":"user","content":[{{"type":"input_text","text":"{}"}}]}}}}"#,
+                text
+            )
+        };
+        fs::write(
+            &path,
+            r#"{"type":"session_meta","timestamp":"2026-01-08T10:00:00Z","payload":{"id":"abc123","cwd":"/home/user/webapp"}}"#
+                .to_string()
+                + &question("First"),
+        )
+        .unwrap();
+
+        // The sync parses the file, then waits for the session while it grows
+        thread::scope(|scope| {
+            let guard = db.lock_session("codex:abc123");
+            let sync =
+                scope.spawn(|| sync_codex_session(&db, &path, "local", false, &Default::default()));
+            thread::sleep(Duration::from_millis(300));
+            let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+            write!(file, "{}", question("Second")).unwrap();
+            drop(file);
+            drop(guard);
+            sync.join().unwrap().unwrap();
+        });
+
+        let (size, _, _) = db.get_session_file_info("codex:abc123").unwrap().unwrap();
+        assert_eq!(size, fs::metadata(&path).unwrap().len() as i64);
+        assert_eq!(db.get_messages("codex:abc123", None, None).unwrap().len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_verified_source_file() {
+        let tmp = tempdir().unwrap();
+        let (claude_dirs, codex_dirs) = ([tmp.path().join("claude")], [tmp.path().join("codex")]);
+        let roots = roots_of(&claude_dirs, &codex_dirs);
+        let day = codex_dirs[0].join("2026").join("01").join("08");
+        let project = claude_dirs[0].join("-Users-me-app");
+        fs::create_dir_all(&day).unwrap();
+        fs::create_dir_all(&project).unwrap();
+        fs::write(project.join("inside.jsonl"), "{}").unwrap();
+
+        let outside = tmp.path().join("outside.jsonl");
+        fs::write(&outside, "{}").unwrap();
+        let uuid = "019b9da7-1f41-7af2-80d9-6e293902fea8";
+        std::os::unix::fs::symlink(
+            &outside,
+            day.join(format!("rollout-2026-01-08T10-00-00-{}.jsonl", uuid)),
+        )
+        .unwrap();
+
+        assert_eq!(
+            find_verified_source_file_in("inside", &roots),
+            Some(project.join("inside.jsonl").canonicalize().unwrap())
+        );
+        // Found by name, but the link resolves outside every session root
+        let codex_id = format!("codex:{}", uuid);
+        assert!(find_source_file_in(&codex_id, &roots).is_some());
+        assert_eq!(find_verified_source_file_in(&codex_id, &roots), None);
+        assert_eq!(find_verified_source_file_in("missing", &roots), None);
+        assert_eq!(find_verified_source_file_in("../outside", &roots), None);
     }
 
     #[test]
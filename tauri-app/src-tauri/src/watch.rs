@@ -0,0 +1,633 @@
+//! Watch the session directories and sync files as they change.
+
+use crate::db::{Database, Message};
+use crate::parser::{
+    is_session_file, parse_claude_session_from, parse_claude_session_with,
+    parse_codex_session_with, parse_gemini_sessions, ClaudeResume, ParseOptions, ParsedSession,
+};
+use crate::sync::{self, claude_projects_dirs, codex_sessions_dirs, gemini_tmp_dirs, SyncRoots};
+use notify_debouncer_mini::notify::{self, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long a file must be quiet before it is synced.
+const DEBOUNCE_DELAY: Duration = Duration::from_millis(500);
+
+/// A running watcher; watching stops when it is dropped.
+pub struct SessionWatcher {
+    _debouncer: Debouncer<RecommendedWatcher>,
+}
+
+/// Watch the Claude, Codex and Gemini session directories, syncing files as
+/// they are created, modified or deleted and calling `on_update` with the id
+/// of each session that changed.
+///
+/// Each sync locks the database only for its own statements, so the watcher
+/// interleaves with `trigger_sync` rather than blocking on it.
+pub fn start<F>(db: Arc<Database>, machine: &str, on_update: F) -> notify::Result<SessionWatcher>
+where
+    F: Fn(&str) + Send + 'static,
+{
+    let roots = SyncRoots {
+        claude: &claude_projects_dirs(),
+        codex: &codex_sessions_dirs(),
+        gemini: &gemini_tmp_dirs(),
+    };
+    start_in(db, machine, &roots, on_update)
+}
+
+fn start_in<F>(
+    db: Arc<Database>,
+    machine: &str,
+    roots: &SyncRoots,
+    on_update: F,
+) -> notify::Result<SessionWatcher>
+where
+    F: Fn(&str) + Send + 'static,
+{
+    let machine = machine.to_string();
+    let dirs = OwnedRoots::from(roots);
+    let mut debouncer = new_debouncer(DEBOUNCE_DELAY, move |result: DebounceEventResult| {
+        match result {
+            Ok(events) => {
+                let paths: Vec<PathBuf> = events.into_iter().map(|e| e.path).collect();
+                for session_id in sync_changed(&db, &paths, &machine, &dirs.roots()) {
+                    on_update(&session_id);
+                }
+            }
+            Err(e) => eprintln!("Watch error: {}", e),
+        }
+    })?;
+
+    watch_roots(debouncer.watcher(), roots)?;
+    Ok(SessionWatcher {
+        _debouncer: debouncer,
+    })
+}
+
+/// Session roots owned by a watcher's event handler.
+struct OwnedRoots {
+    claude: Vec<PathBuf>,
+    codex: Vec<PathBuf>,
+    gemini: Vec<PathBuf>,
+}
+
+impl OwnedRoots {
+    fn from(roots: &SyncRoots) -> Self {
+        OwnedRoots {
+            claude: roots.claude.to_vec(),
+            codex: roots.codex.to_vec(),
+            gemini: roots.gemini.to_vec(),
+        }
+    }
+
+    fn roots(&self) -> SyncRoots<'_> {
+        SyncRoots {
+            claude: &self.claude,
+            codex: &self.codex,
+            gemini: &self.gemini,
+        }
+    }
+}
+
+/// Watch every existing root recursively.
+fn watch_roots(watcher: &mut dyn notify::Watcher, roots: &SyncRoots) -> notify::Result<()> {
+    for dir in roots.claude.iter().chain(roots.codex).chain(roots.gemini) {
+        if dir.is_dir() {
+            watcher.watch(dir, RecursiveMode::Recursive)?;
+        }
+    }
+    Ok(())
+}
+
+/// Messages appended to a watched session's file.
+#[derive(Debug, Clone, Serialize)]
+pub struct AppendedMessages {
+    pub session_id: String,
+    pub messages: Vec<Message>,
+}
+
+/// A live subscription to one session's file; it ends when dropped.
+pub struct SessionTail {
+    _debouncer: Debouncer<RecommendedWatcher>,
+}
+
+/// Follow a session's source file, calling `on_append` with the messages
+/// added by each change, parsed with `options`. Messages already in the file
+/// are not reported, and a session whose file doesn't exist yet is picked up
+/// once it appears.
+pub fn tail_session<F>(
+    db: &Database,
+    session_id: &str,
+    machine: &str,
+    options: ParseOptions,
+    on_append: F,
+) -> notify::Result<SessionTail>
+where
+    F: Fn(AppendedMessages) + Send + 'static,
+{
+    let roots = SyncRoots {
+        claude: &claude_projects_dirs(),
+        codex: &codex_sessions_dirs(),
+        gemini: &gemini_tmp_dirs(),
+    };
+    tail_session_in(db, session_id, machine, options, &roots, on_append)
+}
+
+fn tail_session_in<F>(
+    db: &Database,
+    session_id: &str,
+    machine: &str,
+    options: ParseOptions,
+    roots: &SyncRoots,
+    on_append: F,
+) -> notify::Result<SessionTail>
+where
+    F: Fn(AppendedMessages) + Send + 'static,
+{
+    let session_id = session_id.to_string();
+    let machine = machine.to_string();
+    let source = sync::find_source_file_in(&session_id, roots);
+    let mut position = match &source {
+        Some(path) => tail_position(db, &session_id, path, &machine, &options),
+        None => TailPosition::start(&session_id),
+    };
+    let mut watched = source.clone();
+    let dirs = OwnedRoots::from(roots);
+
+    let mut debouncer = new_debouncer(DEBOUNCE_DELAY, move |result: DebounceEventResult| {
+        let events = match result {
+            Ok(events) => events,
+            Err(e) => return eprintln!("Watch error: {}", e),
+        };
+        // Until the file appears, look for it after each batch of changes
+        let Some(target) = watched
+            .clone()
+            .or_else(|| sync::find_source_file_in(&session_id, &dirs.roots()))
+        else {
+            return;
+        };
+        let Some(path) = events.into_iter().map(|e| e.path).find(|path| *path == target) else {
+            return;
+        };
+        watched = Some(path.clone());
+        let messages = position.advance(&path, &machine, &options);
+        if !messages.is_empty() {
+            on_append(AppendedMessages {
+                session_id: session_id.clone(),
+                messages,
+            });
+        }
+    })?;
+
+    // An existing file is watched through its directory, which keeps working
+    // when the file is replaced; one that doesn't exist yet could appear
+    // anywhere under the roots
+    match source.as_deref().and_then(Path::parent) {
+        Some(dir) => debouncer.watcher().watch(dir, RecursiveMode::NonRecursive)?,
+        None => watch_roots(debouncer.watcher(), roots)?,
+    }
+    Ok(SessionTail {
+        _debouncer: debouncer,
+    })
+}
+
+/// How far a tailed session's file has been read.
+enum TailPosition {
+    /// Claude files are parsed from where the last parse stopped.
+    Claude(ClaudeResume),
+    /// Codex files are parsed in full; messages past this count are new.
+    Codex(usize),
+    /// Gemini logs are parsed in full, keeping this session's messages past
+    /// `known`.
+    Gemini { session_id: String, known: usize },
+}
+
+impl TailPosition {
+    /// The position before any of a session's file has been read.
+    fn start(session_id: &str) -> Self {
+        if session_id.starts_with("codex:") {
+            TailPosition::Codex(0)
+        } else if session_id.starts_with("gemini:") {
+            TailPosition::Gemini {
+                session_id: session_id.to_string(),
+                known: 0,
+            }
+        } else {
+            TailPosition::Claude(ClaudeResume::default())
+        }
+    }
+}
+
+/// Find where tailing a session's existing file starts: where the last sync
+/// stopped if it read the whole file, or else the end of a full parse.
+fn tail_position(
+    db: &Database,
+    session_id: &str,
+    path: &Path,
+    machine: &str,
+    options: &ParseOptions,
+) -> TailPosition {
+    let mut position = TailPosition::start(session_id);
+    if let TailPosition::Claude(resume) = &mut position {
+        if let Some(stored) = stored_resume(db, session_id, path) {
+            *resume = stored;
+            return position;
+        }
+    }
+    position.advance(path, machine, options);
+    position
+}
+
+/// Where the stored copy of a Claude session stopped reading its file, if
+/// that copy is of the file at its current size.
+fn stored_resume(db: &Database, session_id: &str, path: &Path) -> Option<ClaudeResume> {
+    let size = path.metadata().ok()?.len() as i64;
+    let stored = db.get_session(session_id).ok()??;
+    let offset = stored.parsed_offset.filter(|_| stored.file_size == Some(size))?;
+    let (message_index, tool_seq, uuids, response_ids) = db.get_append_point(session_id).ok()?;
+    Some(ClaudeResume {
+        offset: offset as u64,
+        message_index,
+        tool_seq,
+        uuids,
+        response_ids,
+    })
+}
+
+impl TailPosition {
+    /// Parse what was added to the file since the last call, returning the
+    /// new messages. A file that was rewritten rather than appended to is
+    /// parsed again in full, reporting messages past the previous count.
+    fn advance(&mut self, path: &Path, machine: &str, options: &ParseOptions) -> Vec<Message> {
+        match self {
+            TailPosition::Codex(known) => {
+                let Some(parsed) = parse_codex_session_with(path, machine, false, options) else {
+                    return Vec::new();
+                };
+                let appended = parsed.messages.get(*known..).unwrap_or_default().to_vec();
+                *known = parsed.messages.len();
+                appended
+            }
+            TailPosition::Gemini { session_id, known } => {
+                let Some(parsed) = parse_gemini_sessions(path, machine)
+                    .and_then(|sessions| {
+                        sessions.into_iter().find(|s| s.metadata.session_id == *session_id)
+                    })
+                else {
+                    return Vec::new();
+                };
+                let appended = parsed.messages.get(*known..).unwrap_or_default().to_vec();
+                *known = parsed.messages.len();
+                appended
+            }
+            TailPosition::Claude(resume) => {
+                let len = path.metadata().map_or(0, |m| m.len());
+                let appended = (len >= resume.offset)
+                    .then(|| parse_claude_session_from(path, "", machine, options, resume))
+                    .flatten();
+                if let Some(appended) = appended.filter(|p| p.metadata.parsed_offset.is_some()) {
+                    advance_resume(resume, &appended);
+                    return appended.messages;
+                }
+                let Some(parsed) = parse_claude_session_with(path, "", machine, options) else {
+                    return Vec::new();
+                };
+                let known = resume.message_index;
+                *resume = ClaudeResume::default();
+                advance_resume(resume, &parsed);
+                parsed.messages.get(known..).unwrap_or_default().to_vec()
+            }
+        }
+    }
+}
+
+/// Move `resume` past the messages of a parse that continued from it.
+fn advance_resume(resume: &mut ClaudeResume, parsed: &ParsedSession) {
+    if let Some(offset) = parsed.metadata.parsed_offset {
+        resume.offset = offset as u64;
+    }
+    resume.message_index += parsed.messages.len();
+    for message in &parsed.messages {
+        resume.tool_seq += message.tool_calls.as_ref().map_or(0, |calls| calls.len() as i32);
+        resume.uuids.extend(message.uuid.clone());
+        resume.response_ids.extend(message.response_id.clone());
+    }
+}
+
+/// Sync each changed session file or Gemini log, or drop its sessions if the
+/// file is gone and no other root holds a copy, returning the ids of sessions
+/// whose stored data changed. A session whose file is gone but has a copy
+/// elsewhere is synced from its newest copy instead.
+fn sync_changed(db: &Database, paths: &[PathBuf], machine: &str, roots: &SyncRoots) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut updated = Vec::new();
+
+    for path in paths {
+        if !(is_session_file(path) || is_gemini_log(path, roots)) || !seen.insert(path) {
+            continue;
+        }
+        if path.exists() {
+            updated.extend(sync_source(db, path, machine, roots));
+            continue;
+        }
+
+        let session_ids = match sync::session_ids_for_path(db, path, roots) {
+            Ok(ids) => ids,
+            Err(e) => {
+                eprintln!("Failed to look up sessions from {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        for session_id in session_ids {
+            match sync::find_source_file_in(&session_id, roots) {
+                Some(copy) => updated.extend(sync_source(db, &copy, machine, roots)),
+                None => match db.delete_session(&session_id) {
+                    Ok(true) => updated.push(session_id),
+                    Ok(false) => {}
+                    Err(e) => eprintln!("Failed to remove session {}: {}", session_id, e),
+                },
+            }
+        }
+    }
+
+    updated
+}
+
+/// Whether `path` is a Gemini project log under one of the roots.
+fn is_gemini_log(path: &Path, roots: &SyncRoots) -> bool {
+    path.file_name().is_some_and(|name| name == "logs.json")
+        && roots.gemini.iter().any(|dir| path.starts_with(dir))
+}
+
+/// Sync one existing file, returning the ids of sessions whose stored data
+/// changed.
+fn sync_source(db: &Database, path: &Path, machine: &str, roots: &SyncRoots) -> Vec<String> {
+    let results = if is_gemini_log(path, roots) {
+        sync::sync_gemini_log(db, path, machine)
+    } else {
+        sync::sync_path_in(db, path, machine, roots).map(|result| vec![result])
+    };
+    match results {
+        Ok(results) => {
+            results.into_iter().filter(|r| !r.skipped).map(|r| r.session_id).collect()
+        }
+        Err(e) if e.is_rule() => Vec::new(),
+        Err(e) => {
+            eprintln!("Failed to sync {}: {}", path.display(), e);
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::DbConfig;
+    use std::fs;
+    use std::io::Write;
+    use std::sync::mpsc;
+    use tempfile::tempdir;
+
+    fn write_claude_session(path: &Path, text: &str) {
+        fs::write(
+            path,
+            format!(
+                r#"{{"type":"user","timestamp":"2026-01-08T10:00:00Z","message":{{"content":"{}"}}}}"#,
+                text
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_sync_changed_handles_create_modify_delete() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let claude_dir = tmp.path().join("claude");
+        let codex_dir = tmp.path().join("codex");
+        let project_dir = claude_dir.join("-Users-me-app");
+        fs::create_dir_all(&project_dir).unwrap();
+
+        let session = project_dir.join("live-session.jsonl");
+        let notes = project_dir.join("notes.txt");
+        write_claude_session(&session, "First prompt");
+        fs::write(&notes, "not a session").unwrap();
+
+        let (claude_dirs, codex_dirs) = ([claude_dir.clone()], [codex_dir.clone()]);
+        let roots = SyncRoots {
+            claude: &claude_dirs,
+            codex: &codex_dirs,
+            ..Default::default()
+        };
+        let changed = |paths: &[PathBuf]| sync_changed(&db, paths, "local", &roots);
+
+        // Duplicate events for one file sync it once; other files are ignored
+        let paths = vec![session.clone(), session.clone(), notes.clone()];
+        assert_eq!(changed(&paths), vec!["live-session"]);
+        assert_eq!(db.get_messages("live-session", None, None).unwrap().len(), 1);
+
+        // An event for an unchanged file reports nothing
+        let single = [session.clone()];
+        assert!(changed(&single).is_empty());
+
+        write_claude_session(&session, "Rewritten prompt");
+        assert_eq!(changed(&single), vec!["live-session"]);
+        assert_eq!(
+            db.get_messages("live-session", None, None).unwrap()[0].content,
+            "Rewritten prompt"
+        );
+
+        fs::remove_file(&session).unwrap();
+        assert_eq!(changed(&single), vec!["live-session"]);
+        assert!(db.get_session("live-session").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_sync_changed_keeps_session_with_copy_in_other_root() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let claude_dirs = [tmp.path().join("claude"), tmp.path().join("claude-backup")];
+        let session = claude_dirs[0].join("-Users-me-app").join("live-session.jsonl");
+        let copy = claude_dirs[1].join("-Users-me-app").join("live-session.jsonl");
+        for path in [&session, &copy] {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+        }
+        write_claude_session(&session, "First prompt");
+        write_claude_session(&copy, "Backed up prompt");
+
+        let roots = SyncRoots {
+            claude: &claude_dirs,
+            ..Default::default()
+        };
+        let changed =
+            |path: &PathBuf| sync_changed(&db, std::slice::from_ref(path), "local", &roots);
+        assert_eq!(changed(&session), vec!["live-session"]);
+
+        // The session stays, synced from the remaining copy
+        fs::remove_file(&session).unwrap();
+        assert_eq!(changed(&session), vec!["live-session"]);
+        assert_eq!(
+            db.get_messages("live-session", None, None).unwrap()[0].content,
+            "Backed up prompt"
+        );
+
+        // Once no copy is left, it is removed
+        fs::remove_file(&copy).unwrap();
+        assert_eq!(changed(&copy), vec!["live-session"]);
+        assert!(db.get_session("live-session").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_sync_changed_handles_gemini_logs() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let gemini_dirs = [tmp.path().join("gemini")];
+        let log = gemini_dirs[0].join("3b8f0c").join("logs.json");
+        fs::create_dir_all(log.parent().unwrap()).unwrap();
+        let roots = SyncRoots {
+            gemini: &gemini_dirs,
+            ..Default::default()
+        };
+        let changed = || sync_changed(&db, std::slice::from_ref(&log), "local", &roots);
+        let first = r#"{"sessionId":"s1","type":"user","message":"Hello","timestamp":"2026-01-08T10:00:00Z"}"#;
+        let second = r#"{"sessionId":"s2","type":"user","message":"Again","timestamp":"2026-01-09T10:00:00Z"}"#;
+
+        fs::write(&log, format!("[{}]", first)).unwrap();
+        assert_eq!(changed(), vec!["gemini:s1"]);
+
+        // Only the session added to the log is reported
+        fs::write(&log, format!("[{},{}]", first, second)).unwrap();
+        assert_eq!(changed(), vec!["gemini:s2"]);
+
+        // Deleting the log drops every session synced from it
+        fs::remove_file(&log).unwrap();
+        assert_eq!(changed(), vec!["gemini:s1", "gemini:s2"]);
+        assert!(db.get_session("gemini:s1").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_watcher_reports_new_session() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let db = Arc::new(db);
+        let claude_dir = tmp.path().join("claude");
+        let project_dir = claude_dir.join("-Users-me-app");
+        fs::create_dir_all(&project_dir).unwrap();
+
+        let claude_dirs = [claude_dir];
+        let roots = SyncRoots {
+            claude: &claude_dirs,
+            ..Default::default()
+        };
+        let (tx, rx) = mpsc::channel();
+        let _watcher =
+            start_in(Arc::clone(&db), "local", &roots, move |id| tx.send(id.to_string()).unwrap())
+                .unwrap();
+
+        write_claude_session(&project_dir.join("watched.jsonl"), "Hello");
+        let id = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(id, "watched");
+        assert!(db.get_session("watched").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_tail_session_reports_appended_messages() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let claude_dir = tmp.path().join("claude");
+        let project_dir = claude_dir.join("-Users-me-app");
+        fs::create_dir_all(&project_dir).unwrap();
+        let path = project_dir.join("tailed.jsonl");
+
+        let claude_dirs = [claude_dir];
+        let roots = SyncRoots {
+            claude: &claude_dirs,
+            ..Default::default()
+        };
+        let (tx, rx) = mpsc::channel();
+        // The file doesn't exist yet when watching starts
+        let _tail = tail_session_in(
+            &db,
+            "tailed",
+            "local",
+            ParseOptions::default(),
+            &roots,
+            move |appended| tx.send(appended).unwrap(),
+        )
+        .unwrap();
+
+        let line = |ts: &str, text: &str| {
+            format!(
+                r#"{{"type":"user","timestamp":"{}","message":{{"content":"{}"}}}}"#,
+                ts, text
+            )
+        };
+        fs::write(&path, line("2026-01-08T10:00:00Z", "First") + "\n").unwrap();
+        let appended = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(appended.session_id, "tailed");
+        assert_eq!(appended.messages.len(), 1);
+        assert_eq!(appended.messages[0].content, "First");
+
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        writeln!(file, "{}", line("2026-01-08T10:01:00Z", "Second")).unwrap();
+        drop(file);
+        let appended = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(appended.messages.len(), 1);
+        assert_eq!(appended.messages[0].content, "Second");
+    }
+
+    #[test]
+    fn test_tail_session_resumes_from_stored_offset() {
+        let tmp = tempdir().unwrap();
+        let db = Database::open_with(&tmp.path().join("test.db"), &DbConfig::default()).unwrap();
+        let claude_dir = tmp.path().join("claude");
+        let project_dir = claude_dir.join("-Users-me-app");
+        fs::create_dir_all(&project_dir).unwrap();
+        let path = project_dir.join("tailed.jsonl");
+        let line = |ts: &str, uuid: &str, parent: &str, text: &str| {
+            format!(
+                r#"{{"type":"user","uuid":"{}","parentUuid":{},"timestamp":"{}","message":{{"content":"{}"}}}}"#,
+                uuid, parent, ts, text
+            )
+        };
+        fs::write(&path, line("2026-01-08T10:00:00Z", "u1", "null", "First") + "\n").unwrap();
+        let claude_dirs = [claude_dir];
+        let roots = SyncRoots {
+            claude: &claude_dirs,
+            ..Default::default()
+        };
+        sync::sync_path_in(&db, &path, "laptop", &roots).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let _tail = tail_session_in(
+            &db,
+            "tailed",
+            "laptop",
+            ParseOptions::default(),
+            &roots,
+            move |appended| tx.send(appended).unwrap(),
+        )
+        .unwrap();
+
+        // Other files in the session's directory are not followed
+        write_claude_session(&project_dir.join("other.jsonl"), "Elsewhere");
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        writeln!(file, "{}", line("2026-01-08T10:01:00Z", "u2", r#""u1""#, "Second")).unwrap();
+        drop(file);
+        let appended = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(appended.session_id, "tailed");
+        assert_eq!(appended.messages.len(), 1);
+        assert_eq!(appended.messages[0].parent_uuid.as_deref(), Some("u1"));
+
+        // The appended message has the id a sync gives it
+        sync::sync_path_in(&db, &path, "laptop", &roots).unwrap();
+        let stored = db.get_messages("tailed", None, None).unwrap();
+        assert_eq!(stored[1].msg_id, appended.messages[0].msg_id);
+        assert!(rx.recv_timeout(Duration::from_millis(1500)).is_err());
+    }
+}
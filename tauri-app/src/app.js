@@ -41,7 +41,7 @@ const syncStatusEl = document.getElementById('sync-status');
 
 // API calls via Tauri invoke
 async function fetchSessions() {
-    return await invoke('get_sessions', { limit: 1000 });
+    return (await invoke('get_sessions', { limit: 1000 })).items;
 }
 
 async function fetchProjects() {
@@ -49,26 +49,46 @@ async function fetchProjects() {
 }
 
 async function fetchMessages(sessionId) {
-    return await invoke('get_messages', { sessionId });
+    invoke('mark_session_read', { sessionId }).catch(e => console.error('Mark read failed:', e));
+    return invoke('get_messages', { sessionId });
 }
 
 async function searchMessages(query) {
-    return await invoke('search', { query, limit: 50 });
+    return (await invoke('search', { query, limit: 50 })).items;
 }
 
+// Sync runs in the background; clicking the button again cancels it
+let syncing = false;
+
 async function triggerSync() {
-    syncBtn.disabled = true;
-    syncBtn.textContent = '↻ Syncing...';
+    if (syncing) {
+        await invoke('cancel_sync');
+        syncBtn.disabled = true;
+        syncBtn.textContent = '↻ Cancelling...';
+        return;
+    }
+    syncing = true;
+    syncBtn.textContent = '✕ Cancel sync';
     try {
-        const stats = await invoke('trigger_sync');
-        syncStatusEl.textContent = `Synced ${stats.synced} sessions`;
-        await loadSessions();
-    } finally {
-        syncBtn.disabled = false;
+        await invoke('trigger_sync');
+    } catch (e) {
+        syncing = false;
         syncBtn.textContent = '↻ Sync';
+        syncStatusEl.textContent = String(e);
     }
 }
 
+window.__TAURI__.event.listen('sync-complete', async (event) => {
+    const stats = event.payload;
+    syncing = false;
+    syncBtn.disabled = false;
+    syncBtn.textContent = '↻ Sync';
+    syncStatusEl.textContent = stats.cancelled
+        ? `Sync cancelled after ${stats.synced} sessions`
+        : `Synced ${stats.synced} sessions`;
+    await loadSessions();
+});
+
 // Watch for session updates
 function startWatching(sessionId) {
     if (watchInterval) {
@@ -81,8 +101,8 @@ function startWatching(sessionId) {
             const updated = await invoke('check_session_update', { sessionId });
             if (updated && currentSession && currentSession.session_id === sessionId) {
                 await invoke('sync_session', { sessionId });
-                const messages = await fetchMessages(sessionId);
-                currentSessionData = { session: currentSession, messages };
+                const { items: messages, limit, capped } = await fetchMessages(sessionId);
+                currentSessionData = { session: currentSession, messages, limit, capped };
                 renderSession(currentSessionData);
             }
         } catch (e) {
@@ -131,7 +151,7 @@ function renderSessionList() {
         <li class="session-item ${currentSession?.session_id === s.session_id ? 'active' : ''}"
             data-id="${s.session_id}">
             <div class="session-project">${escapeHtml(s.project || '')}</div>
-            <div class="session-title">${escapeHtml(s.first_message || 'No message')}</div>
+            <div class="session-title">${escapeHtml(s.summary || s.first_message || 'No message')}</div>
             <div class="session-meta">
                 <span class="agent-name ${s.agent || 'claude'}">${formatAgentName(s.agent)}</span>
                 <span class="meta-sep">·</span>
@@ -444,8 +464,11 @@ function handleMinimapClick(e) {
 }
 
 function renderSession(data) {
-    const { session, messages } = data;
+    const { session, messages, limit, capped } = data;
     allMessages = [...messages];
+    if (capped) {
+        statusText.textContent = `Showing the first ${limit} messages`;
+    }
     if (sortNewestFirst) {
         allMessages.reverse();
     }
@@ -574,7 +597,7 @@ function renderSearchResults(query, results) {
                         <span class="badge">${escapeHtml(r.project)}</span>
                         <span>${escapeHtml(r.role)}</span>
                     </div>
-                    <div>${safeSnippet(r.snippet) || escapeHtml(r.content.substring(0, 200))}</div>
+                    <div>${(r.snippets?.length ? r.snippets : [r.snippet]).join(' ') || escapeHtml(r.content.substring(0, 200))}</div>
                 </div>
             `).join('')}
         </div>
@@ -654,8 +677,8 @@ async function loadSession(id, scrollToMsg = null) {
     }
 
     currentSession = session;
-    const messages = await fetchMessages(id);
-    currentSessionData = { session, messages };
+    const { items: messages, limit, capped } = await fetchMessages(id);
+    currentSessionData = { session, messages, limit, capped };
     renderSession(currentSessionData);
     renderSessionList();
 
@@ -702,17 +725,6 @@ function escapeHtml(text) {
         .replace(/"/g, '&quot;');
 }
 
-// Safely render FTS snippet, only allowing <mark> tags
-function safeSnippet(snippet) {
-    if (!snippet) return '';
-    // Escape everything first
-    let safe = escapeHtml(snippet);
-    // Then restore only <mark> and </mark> tags
-    safe = safe.replace(/&lt;mark&gt;/g, '<mark>');
-    safe = safe.replace(/&lt;\/mark&gt;/g, '</mark>');
-    return safe;
-}
-
 function formatContent(text) {
     if (!text) return '';
     let html = escapeHtml(text);
@@ -736,7 +748,7 @@ function formatTime(ts) {
 }
 
 function formatAgentName(agent) {
-    return { claude: 'Claude', codex: 'Codex' }[agent] || agent || 'Claude';
+    return { claude: 'Claude', codex: 'Codex', gemini: 'Gemini' }[agent] || agent || 'Claude';
 }
 
 function openShortcutsModal() { shortcutsModal.classList.add('visible'); }
@@ -823,10 +835,34 @@ document.addEventListener('keydown', (e) => {
     }
 });
 
+// Refresh the list when the backend syncs a changed session file
+let sessionUpdateTimeout = null;
+window.__TAURI__.event.listen('session-updated', () => {
+    clearTimeout(sessionUpdateTimeout);
+    sessionUpdateTimeout = setTimeout(() => {
+        loadSessions().catch(e => console.error('Refresh failed:', e));
+    }, 300);
+});
+
 // Initialize
 (async () => {
     await loadSessions();
-    if (sessions.length > 0) {
+    const target = await invoke('get_launch_target');
+    // The list holds only the most recent sessions; look older targets up directly
+    if (target && target.session_id && !sessions.find(s => s.session_id === target.session_id)) {
+        const session = await invoke('get_session', { sessionId: target.session_id });
+        if (session) {
+            allSessions.push(session);
+            filterSessions();
+        }
+    }
+    if (target && target.session_id && sessions.find(s => s.session_id === target.session_id)) {
+        await loadSession(target.session_id);
+    } else if (sessions.length > 0) {
         await loadSession(sessions[0].session_id);
     }
+    if (target && target.search) {
+        searchInput.value = target.search;
+        await doSearch(target.search);
+    }
 })();